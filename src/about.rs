@@ -0,0 +1,88 @@
+//! The About dialog: version, graphics, monitors and audio, with a button to
+//! copy it all for a bug report.
+
+use crate::diagnostics::System;
+use crate::i18n::{t, tf};
+use crate::platform;
+
+pub struct About {
+    system: System,
+    /// Set once the text is on the clipboard, to say so on the button.
+    copied: bool,
+}
+
+impl About {
+    pub fn new(system: System) -> Self {
+        Self {
+            system,
+            copied: false,
+        }
+    }
+
+    /// Returns `true` when the user dismissed the dialog.
+    pub fn ui(&mut self, ctx: &egui::Context) -> bool {
+        let mut close = false;
+        let system = &self.system;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(t("app.name"));
+            ui.label(tf(
+                "about.version",
+                &[("version", &system.version), ("hash", &system.git_hash)],
+            ));
+            ui.add_space(8.0);
+            egui::Grid::new("about").num_columns(2).show(ui, |ui| {
+                ui.label(t("about.os"));
+                ui.label(&system.os);
+                ui.end_row();
+                match &system.graphics {
+                    Some(graphics) => {
+                        ui.label(t("about.adapter"));
+                        ui.label(format!("{} ({})", graphics.adapter, graphics.backend));
+                        ui.end_row();
+                        ui.label(t("about.driver"));
+                        ui.label(&graphics.driver);
+                        ui.end_row();
+                        ui.label(t("about.surface"));
+                        ui.label(format!(
+                            "{}, {}",
+                            graphics.surface_format, graphics.present_mode
+                        ));
+                        ui.end_row();
+                    }
+                    None => {
+                        ui.label(t("about.adapter"));
+                        ui.label(t("about.no_overlay"));
+                        ui.end_row();
+                    }
+                }
+                for (i, monitor) in system.monitors.iter().enumerate() {
+                    ui.label(tf("about.monitor", &[("index", &(i + 1))]));
+                    ui.label(format!(
+                        "{} at ({}, {}), ×{}",
+                        monitor.name, monitor.x, monitor.y, monitor.scale_factor
+                    ));
+                    ui.end_row();
+                }
+                ui.label(t("about.audio"));
+                ui.label(format!("{:?}", system.audio));
+                ui.end_row();
+            });
+            ui.add_space(8.0);
+            ui.horizontal(|ui| {
+                let copy = if self.copied {
+                    t("about.copied")
+                } else {
+                    t("about.copy")
+                };
+                if ui.button(copy).clicked() {
+                    match platform::set_clipboard_text(&system.text()) {
+                        Ok(()) => self.copied = true,
+                        Err(e) => log::warn!("failed to copy the about text: {e}"),
+                    }
+                }
+                close = ui.button(t("dialog.close")).clicked();
+            });
+        });
+        close
+    }
+}
@@ -0,0 +1,270 @@
+//! One place to turn a ping from any producer (clicks, IPC, restored
+//! markers, demos) into an [`Animation`], with the clamping they'd otherwise
+//! each repeat.
+
+use crate::layout::MonitorRect;
+use crate::ping_source::PingSource;
+use crate::protocol::PingKind;
+use crate::{config, Animation, FRAME_COUNT, FRAME_TIME};
+use device_query::mouse_state::MousePosition;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// A ping ready to be queued, with what the producer does alongside it.
+pub struct Built {
+    pub animation: Animation,
+    /// Whether the ping is heard, denials and restored markers aren't.
+    pub sound: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct AnimationBuilder {
+    position: MousePosition,
+    kind: PingKind,
+    persistent: bool,
+    source: PingSource,
+    zone: Option<Arc<str>>,
+    note: Option<Arc<str>>,
+    sequence: Option<(u32, u8)>,
+    frame_time: Duration,
+    frame: u8,
+    denied: bool,
+    silent: bool,
+    toward: Option<MousePosition>,
+}
+
+impl AnimationBuilder {
+    /// A ping at `position` in desktop pixels.
+    pub fn at(position: MousePosition) -> Self {
+        Self {
+            position,
+            kind: PingKind::Missing,
+            persistent: false,
+            source: PingSource::Local,
+            zone: None,
+            note: None,
+            sequence: None,
+            frame_time: FRAME_TIME,
+            frame: 0,
+            denied: false,
+            silent: false,
+            toward: None,
+        }
+    }
+
+    /// A ping at `x`, `y` across `monitor`, 0 to 1 each, clamped onto it.
+    pub fn normalized(monitor: &MonitorRect, x: f32, y: f32) -> Self {
+        let (x, y) = (x.clamp(0.0, 1.0), y.clamp(0.0, 1.0));
+        // NOTE: 1.0 would land on the next monitor's first pixel
+        let right = monitor.x + monitor.width as i32 - 1;
+        let bottom = monitor.y + monitor.height as i32 - 1;
+        Self::at((
+            (monitor.x + (x * monitor.width as f32) as i32).min(right),
+            (monitor.y + (y * monitor.height as f32) as i32).min(bottom),
+        ))
+    }
+
+    pub fn kind(mut self, kind: PingKind) -> Self {
+        self.kind = kind;
+        self
+    }
+
+    pub fn persistent(mut self, persistent: bool) -> Self {
+        self.persistent = persistent;
+        self
+    }
+
+    pub fn source(mut self, source: PingSource) -> Self {
+        self.source = source;
+        self
+    }
+
+    /// Name of the zone the ping landed in, drawn under it.
+    pub fn zone(mut self, zone: Option<Arc<str>>) -> Self {
+        self.zone = zone;
+        self
+    }
+
+    /// Note typed on a persistent marker, drawn under it.
+    pub fn note(mut self, note: Option<Arc<str>>) -> Self {
+        self.note = note;
+        self
+    }
+
+    /// Place in a run of quick pings, with the frames its badge outlasts the artwork by.
+    pub fn sequence(mut self, sequence: Option<u32>, linger: u8) -> Self {
+        self.sequence = sequence.map(|sequence| (sequence, linger));
+        self
+    }
+
+    /// How long the ping shows, clamped to [`config::DURATION_RANGE_MS`].
+    pub fn duration(mut self, duration: Duration) -> Self {
+        let range = config::DURATION_RANGE_MS;
+        let ms = (duration.as_millis() as u32).clamp(*range.start(), *range.end());
+        self.frame_time = Duration::from_millis(ms as u64) / FRAME_COUNT as u32;
+        self
+    }
+
+    /// Starts partway through, e.g. a restored marker already fully shown.
+    pub fn start_frame(mut self, frame: u8) -> Self {
+        self.frame = frame.min(FRAME_COUNT - 1);
+        self
+    }
+
+    /// Draws an arrow from the ping toward `toward`, in desktop pixels.
+    pub fn toward(mut self, toward: Option<MousePosition>) -> Self {
+        self.toward = toward;
+        self
+    }
+
+    /// Shows the grey "no entry" sign of a rejected ping instead.
+    pub fn denied(mut self) -> Self {
+        self.denied = true;
+        self
+    }
+
+    /// Plays no sound, e.g. for markers restored at startup.
+    pub fn silent(mut self) -> Self {
+        self.silent = true;
+        self
+    }
+
+    pub fn build(self) -> Built {
+        self.build_at(Instant::now())
+    }
+
+    /// [`AnimationBuilder::build`] with the ping fired at `now`.
+    pub fn build_at(self, now: Instant) -> Built {
+        let mut animation = Animation::new(self.position, self.persistent && !self.denied, now);
+        animation.kind = self.kind;
+        animation.source = self.source;
+        animation.frame_time = self.frame_time;
+        animation.seek(self.frame, now);
+        animation.denied = self.denied;
+        // NOTE: a denial is just the sign, no badges or labels
+        if !self.denied {
+            animation.zone = self.zone;
+            animation.toward = self.toward;
+            if animation.persistent {
+                animation.note = self.note;
+            }
+            if let Some((sequence, linger)) = self.sequence {
+                animation.sequence = Some(sequence);
+                animation.linger = linger;
+            }
+        }
+        Built {
+            animation,
+            sound: !self.denied && !self.silent,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MONITOR: MonitorRect = MonitorRect {
+        x: -1920,
+        y: 100,
+        width: 1920,
+        height: 1080,
+    };
+
+    fn note() -> Option<Arc<str>> {
+        Some("here".into())
+    }
+
+    #[test]
+    fn normalized_positions_land_on_the_monitor() {
+        let at = |x, y| AnimationBuilder::normalized(&MONITOR, x, y).position;
+        assert_eq!(at(0.0, 0.0), (-1920, 100));
+        assert_eq!(at(0.5, 0.5), (-960, 640));
+        // NOTE: 1.0 is the last pixel, not the first of whatever is to the right
+        assert_eq!(at(1.0, 1.0), (-1, 1179));
+        assert_eq!(at(-3.0, 7.0), (-1920, 1179));
+        assert_eq!(at(f32::NAN, 0.25), (-1920, 370));
+    }
+
+    #[test]
+    fn durations_are_clamped_and_spread_over_the_frames() {
+        let frame_time = |duration| {
+            AnimationBuilder::at((0, 0))
+                .duration(duration)
+                .build()
+                .animation
+                .frame_time
+        };
+        assert_eq!(
+            frame_time(Duration::from_millis(3000)),
+            Duration::from_millis(50)
+        );
+        assert_eq!(
+            frame_time(Duration::ZERO),
+            Duration::from_millis(100) / FRAME_COUNT as u32
+        );
+        assert_eq!(
+            frame_time(Duration::from_secs(3600)),
+            Duration::from_millis(10_000) / FRAME_COUNT as u32
+        );
+    }
+
+    #[test]
+    fn start_frames_past_the_end_stop_at_the_last() {
+        let now = Instant::now();
+        let animation = AnimationBuilder::at((0, 0))
+            .start_frame(u8::MAX)
+            .build_at(now)
+            .animation;
+        assert_eq!(animation.frame, FRAME_COUNT - 1);
+        assert_eq!(animation.frame_at(now), FRAME_COUNT - 1);
+    }
+
+    #[test]
+    fn notes_only_stick_to_markers() {
+        let ping = AnimationBuilder::at((0, 0)).note(note()).build();
+        assert_eq!(ping.animation.note, None);
+        let marker = AnimationBuilder::at((0, 0))
+            .persistent(true)
+            .note(note())
+            .build();
+        assert_eq!(marker.animation.note, note());
+        assert!(marker.animation.persistent);
+    }
+
+    #[test]
+    fn denied_pings_are_a_bare_silent_sign() {
+        let built = AnimationBuilder::at((0, 0))
+            .persistent(true)
+            .zone(Some("Minimap".into()))
+            .note(note())
+            .sequence(Some(3), 20)
+            .toward(Some((50, 50)))
+            .denied()
+            .build();
+        let animation = built.animation;
+        assert!(animation.denied && !built.sound);
+        // NOTE: a denied marker would otherwise stay on screen for good
+        assert!(!animation.persistent);
+        assert_eq!(animation.zone, None);
+        assert_eq!(animation.note, None);
+        assert_eq!(animation.sequence, None);
+        assert_eq!(animation.linger, 0);
+        assert_eq!(animation.toward, None);
+    }
+
+    #[test]
+    fn sequences_carry_their_linger() {
+        let animation = AnimationBuilder::at((0, 0))
+            .sequence(Some(2), 15)
+            .build()
+            .animation;
+        assert_eq!((animation.sequence, animation.linger), (Some(2), 15));
+    }
+
+    #[test]
+    fn only_silent_or_denied_pings_go_unheard() {
+        assert!(AnimationBuilder::at((0, 0)).build().sound);
+        assert!(!AnimationBuilder::at((0, 0)).silent().build().sound);
+    }
+}
@@ -0,0 +1,160 @@
+//! Ordering of animation frame files.
+//!
+//! Frames are ordered by the first number in their file name, so
+//! `frame_2.png` plays before `frame_10.png` and gaps in the numbering don't
+//! shift anything.
+
+use std::path::Path;
+
+/// Image formats frames can be in.
+const EXTENSIONS: [&str; 5] = ["png", "jpg", "jpeg", "webp", "gif"];
+
+/// Frame files out of `names`, sorted by their number. Names that aren't
+/// images or have no number are skipped, and gaps or duplicate numbers are
+/// logged. `set` names the frame set in the warnings.
+pub fn order<'a>(set: &str, names: impl IntoIterator<Item = &'a str>) -> Vec<(u32, &'a str)> {
+    let mut frames = Vec::new();
+    for name in names {
+        let path = Path::new(name);
+        let is_image = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .is_some_and(|extension| {
+                EXTENSIONS
+                    .iter()
+                    .any(|known| extension.eq_ignore_ascii_case(known))
+            });
+        if !is_image {
+            log::warn!("skipping {name} in {set} frames: not a png, jpg, webp or gif");
+            continue;
+        }
+        let stem = path
+            .file_stem()
+            .and_then(|stem| stem.to_str())
+            .unwrap_or("");
+        match index(stem) {
+            Some(index) => frames.push((index, name)),
+            None => log::warn!("skipping {name} in {set} frames: no frame number in its name"),
+        }
+    }
+
+    // NOTE: ties broken by name so duplicates are dropped deterministically
+    frames.sort();
+    frames.dedup_by(|later, earlier| {
+        let duplicate = later.0 == earlier.0;
+        if duplicate {
+            log::warn!(
+                "{set} frames {} and {} share number {}, using the first",
+                earlier.1,
+                later.1,
+                later.0
+            );
+        }
+        duplicate
+    });
+
+    let missing = frames
+        .windows(2)
+        .filter(|pair| pair[1].0 > pair[0].0 + 1)
+        .map(|pair| match pair[1].0 - pair[0].0 {
+            2 => format!("{}", pair[0].0 + 1),
+            _ => format!("{}-{}", pair[0].0 + 1, pair[1].0 - 1),
+        })
+        .collect::<Vec<_>>();
+    if !missing.is_empty() {
+        log::warn!("{set} frames {} are missing", missing.join(", "));
+    }
+
+    frames
+}
+
+/// First run of digits in `stem`.
+fn index(stem: &str) -> Option<u32> {
+    let start = stem.find(|c: char| c.is_ascii_digit())?;
+    let digits = &stem[start..];
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end].parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(frames: &[(u32, &str)]) -> Vec<String> {
+        frames.iter().map(|(_, name)| name.to_string()).collect()
+    }
+
+    #[test]
+    fn sorted_by_number_not_by_name() {
+        let frames = order("test", ["frame_10.png", "frame_9.png", "frame_100.png"]);
+        assert_eq!(
+            frames,
+            [
+                (9, "frame_9.png"),
+                (10, "frame_10.png"),
+                (100, "frame_100.png")
+            ]
+        );
+    }
+
+    #[test]
+    fn zero_padded_and_gapped_numbers_keep_their_index() {
+        let frames = order(
+            "test",
+            [
+                "frame_003.png",
+                "frame_001.png",
+                "frame_007.jpg",
+                "frame_004.webp",
+            ],
+        );
+        assert_eq!(
+            frames.iter().map(|(index, _)| *index).collect::<Vec<_>>(),
+            [1, 3, 4, 7]
+        );
+    }
+
+    #[test]
+    fn mixed_extensions_in_any_case_are_accepted() {
+        let frames = order("test", ["a1.PNG", "a2.jpeg", "a3.Jpg", "a4.webp", "a5.gif"]);
+        assert_eq!(frames.len(), 5);
+    }
+
+    #[test]
+    fn non_images_and_unnumbered_names_are_skipped() {
+        let frames = order(
+            "test",
+            ["readme.txt", "frame_2.png", "cover.png", "frame_1.png", "3"],
+        );
+        assert_eq!(names(&frames), ["frame_1.png", "frame_2.png"]);
+    }
+
+    #[test]
+    fn duplicate_numbers_keep_the_first_name() {
+        let frames = order("test", ["b_01.png", "a_1.jpg", "c_2.png"]);
+        assert_eq!(names(&frames), ["a_1.jpg", "c_2.png"]);
+    }
+
+    #[test]
+    fn the_first_run_of_digits_is_the_index() {
+        assert_eq!(index("frame_12_delay-0.03s"), Some(12));
+        assert_eq!(index("007"), Some(7));
+        assert_eq!(index("frame"), None);
+        assert_eq!(index("frame_99999999999"), None);
+    }
+
+    #[test]
+    fn builtin_frames_are_contiguous() {
+        let names = crate::ASSET_DIR
+            .files()
+            .map(|file| file.path().to_str().unwrap());
+        let frames = order("built-in", names);
+        assert!(!frames.is_empty());
+        assert!(frames
+            .iter()
+            .enumerate()
+            .all(|(i, (index, _))| *index == i as u32));
+    }
+}
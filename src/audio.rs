@@ -0,0 +1,958 @@
+//! Sound playback on its own thread, which owns the output stream and every
+//! decoded sound. Everything else just sends [`Command`]s.
+
+use crate::config::SoundSpec;
+use crate::metrics::Metrics;
+use crate::monitor_sound::Cues;
+use crate::zones::Zones;
+use crossbeam::channel::{RecvTimeoutError, Sender};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use rodio::source::{Source, Spatial};
+use rodio::{Decoder, OutputStream, OutputStreamHandle, PlayError};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::io::Cursor;
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often a missing output device is looked for again.
+const REOPEN_INTERVAL: Duration = Duration::from_secs(2);
+/// Silence played into a freshly opened device, so it's spun up before the first ping.
+const WARM_UP: Duration = Duration::from_millis(50);
+/// Pitch and length of the system beep standing in for a ping.
+const BEEP_FREQUENCY: f32 = 880.0;
+const BEEP_LENGTH: Duration = Duration::from_millis(80);
+/// Length of the prime and unprime cues.
+const CUE_LENGTH: Duration = Duration::from_millis(35);
+/// How long a lazy output stays open after its last sound ends.
+const LAZY_LINGER: Duration = Duration::from_secs(5);
+/// How often open outputs are checked against their device's format.
+const CHECK_INTERVAL: Duration = Duration::from_secs(2);
+
+pub enum Command {
+    /// Plays the sound for a ping on 1-based `monitor` in `zone`.
+    /// `pan` runs from -1 (left) to 1 (right), `volume` from 0 to 1.
+    Play {
+        monitor: Option<usize>,
+        zone: Option<usize>,
+        pan: f32,
+        volume: f32,
+        /// When the gesture was seen, for the latency logged in debug builds.
+        fired: Instant,
+    },
+    /// The short click for the ping key arming (`true`) or disarming pings,
+    /// `volume` from 0 to 1.
+    Cue { primed: bool, volume: f32 },
+    /// Master volume, 0 to 1.
+    SetVolume(f32),
+    /// Silences pings and cues while `true`, e.g. during Focus Assist.
+    Mute(bool),
+    /// Output device by name, `None` for the system default.
+    SetDevice(Option<String>),
+    /// Swaps in a new theme's sound.
+    Reload(Option<Sound>),
+    /// Swaps in the user's own ping sound, `None` goes back to the theme's.
+    SetPing(Option<Layered>),
+    /// Monitors changed, the per monitor sounds follow them.
+    Layout(crate::layout::Layout),
+    /// Only keeps the device open around sounds, to let it sleep in between.
+    Lazy(bool),
+}
+
+/// What pings are heard through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// The output device, with the ping sounds.
+    Stream,
+    /// No device could be opened, a system beep stands in.
+    Beep,
+    /// No device could be opened, pings are silent.
+    Silent,
+}
+
+impl Backend {
+    fn from_u8(value: u8) -> Self {
+        match value {
+            0 => Backend::Stream,
+            1 => Backend::Beep,
+            _ => Backend::Silent,
+        }
+    }
+}
+
+/// A sound decoded to samples in full, so playing it never waits on a
+/// decoder. Clones share the samples and play from the start.
+#[derive(Clone)]
+pub struct Sound {
+    samples: Arc<[f32]>,
+    channels: u16,
+    sample_rate: u32,
+    position: usize,
+}
+
+impl Sound {
+    pub fn decode(bytes: Arc<[u8]>) -> Result<Self, rodio::decoder::DecoderError> {
+        let decoder = Decoder::new(Cursor::new(bytes))?;
+        let (channels, sample_rate) = (decoder.channels(), decoder.sample_rate());
+        Ok(Self {
+            samples: decoder.convert_samples::<f32>().collect(),
+            channels,
+            sample_rate,
+            position: 0,
+        })
+    }
+
+    fn length(&self) -> Duration {
+        let rate = self.sample_rate as f64 * self.channels as f64;
+        Duration::from_secs_f64(self.samples.len() as f64 / rate.max(1.0))
+    }
+}
+
+impl Iterator for Sound {
+    type Item = f32;
+
+    fn next(&mut self) -> Option<f32> {
+        let sample = self.samples.get(self.position).copied();
+        self.position += 1;
+        sample
+    }
+}
+
+impl Source for Sound {
+    fn current_frame_len(&self) -> Option<usize> {
+        Some(self.samples.len().saturating_sub(self.position))
+    }
+
+    fn channels(&self) -> u16 {
+        self.channels
+    }
+
+    fn sample_rate(&self) -> u32 {
+        self.sample_rate
+    }
+
+    fn total_duration(&self) -> Option<Duration> {
+        Some(self.length())
+    }
+}
+
+/// Sounds picked from for each ping, decoded up front.
+pub struct Sounds {
+    pub theme: Option<Sound>,
+    /// The user's own sound, over the theme's.
+    pub ping: Option<Layered>,
+    pub monitors: Cues,
+    pub zones: Arc<Zones>,
+}
+
+/// One or more sounds played together, each after its own delay.
+#[derive(Clone)]
+pub struct Layered(Vec<Layer>);
+
+#[derive(Clone)]
+struct Layer {
+    sound: Sound,
+    delay: Duration,
+    volume: f32,
+}
+
+impl Layered {
+    pub fn single(sound: Sound) -> Self {
+        Self(vec![Layer {
+            sound,
+            delay: Duration::ZERO,
+            volume: 1.0,
+        }])
+    }
+
+    /// Decodes every layer of `spec`. `name` is only for the warning about
+    /// sounds outlasting the ping.
+    pub fn load(spec: &SoundSpec, name: &str) -> Result<Self, String> {
+        let layers = spec
+            .layers()
+            .into_iter()
+            .map(|layer| {
+                let sound =
+                    decode(&layer.file).map_err(|e| format!("{}: {e}", layer.file.display()))?;
+                Ok(Layer {
+                    sound,
+                    delay: Duration::from_millis(layer.delay_ms),
+                    volume: layer.volume.clamp(0.0, 1.0),
+                })
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        let layered = Self(layers);
+        let length = layered.length();
+        if length > crate::ANIMATION_DURATION {
+            log::warn!(
+                "{name} plays for {length:?}, longer than the {:?} ping it belongs to",
+                crate::ANIMATION_DURATION
+            );
+        }
+        Ok(layered)
+    }
+
+    /// Time until the last layer ends.
+    fn length(&self) -> Duration {
+        self.0
+            .iter()
+            .map(|layer| layer.delay + layer.sound.length())
+            .max()
+            .unwrap_or_default()
+    }
+}
+
+/// The format a device mixes at, which an open stream is stuck with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct OutputConfig {
+    device: String,
+    sample_rate: u32,
+    channels: u16,
+}
+
+impl OutputConfig {
+    fn of(device: &rodio::cpal::Device) -> Option<Self> {
+        let config = device.default_output_config().ok()?;
+        Some(Self {
+            device: device.name().ok()?,
+            sample_rate: config.sample_rate().0,
+            channels: config.channels(),
+        })
+    }
+}
+
+impl std::fmt::Display for OutputConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{:?} at {} Hz, {} channels",
+            self.device, self.sample_rate, self.channels
+        )
+    }
+}
+
+fn decode(path: &std::path::Path) -> Result<Sound, Box<dyn std::error::Error>> {
+    let bytes: Arc<[u8]> = std::fs::read(path)?.into();
+    Ok(Sound::decode(bytes)?)
+}
+
+/// Handle to the audio thread. It exits once every handle is dropped.
+#[derive(Clone)]
+pub struct Audio {
+    commands: Sender<Command>,
+    backend: Arc<AtomicU8>,
+}
+
+impl Audio {
+    /// With `beep_fallback`, pings beep through the system while no output
+    /// device can be opened.
+    pub fn spawn(sounds: Sounds, metrics: Arc<Metrics>, beep_fallback: bool) -> Self {
+        let (commands, receiver) = crossbeam::channel::unbounded();
+        let backend = Arc::new(AtomicU8::new(Backend::Stream as u8));
+        let outputs_backend = backend.clone();
+        std::thread::spawn(move || {
+            let mut player = Player {
+                sounds,
+                volume: 1.0,
+                muted: false,
+                sink: Outputs {
+                    metrics,
+                    backend: outputs_backend,
+                    beep_fallback,
+                    device: None,
+                    output: None,
+                    output_config: None,
+                    checked_at: Instant::now(),
+                    monitor_outputs: HashMap::new(),
+                    missing: HashMap::new(),
+                    last_open: None,
+                    lazy: false,
+                    close_at: None,
+                },
+            };
+            player.sink.reopen();
+            loop {
+                let command = match player.sink.deadline() {
+                    Some(at) => receiver.recv_deadline(at),
+                    None => receiver.recv().map_err(|_| RecvTimeoutError::Disconnected),
+                };
+                match command {
+                    Ok(command) => player.handle(command),
+                    Err(RecvTimeoutError::Timeout) => player.sink.wake(),
+                    Err(RecvTimeoutError::Disconnected) => break,
+                }
+            }
+        });
+        Self { commands, backend }
+    }
+
+    pub fn send(&self, command: Command) {
+        self.commands.send(command).ok();
+    }
+
+    /// What the last attempt to open the output device left pings to.
+    pub fn backend(&self) -> Backend {
+        Backend::from_u8(self.backend.load(Ordering::Relaxed))
+    }
+}
+
+/// Where the player's sounds go: [`Outputs`] in the app, a recording in tests.
+trait Sink {
+    /// Opens the main output if it isn't, and says what sounds go to.
+    fn open(&mut self) -> Backend;
+    /// Plays `source` on the output for `device`, the main one when that's
+    /// `None` or can't be opened.
+    fn play(&mut self, device: Option<&str>, source: Box<dyn Source<Item = f32> + Send>);
+    /// The system beep standing in for a ping.
+    fn beep(&mut self, frequency: u32, length: Duration);
+    /// A sound `length` long started, a lazy output stays open past its end.
+    fn hold(&mut self, length: Duration);
+    fn set_device(&mut self, device: Option<String>);
+    fn set_lazy(&mut self, lazy: bool);
+}
+
+/// Turns commands into sounds, whatever they end up played on.
+struct Player<S> {
+    sounds: Sounds,
+    volume: f32,
+    muted: bool,
+    sink: S,
+}
+
+impl<S: Sink> Player<S> {
+    fn handle(&mut self, command: Command) {
+        match command {
+            Command::Play {
+                monitor,
+                zone,
+                pan,
+                volume,
+                fired,
+            } => {
+                if self.muted {
+                    return;
+                }
+                let Some((layered, speed)) = self.resolve(monitor, zone) else {
+                    return;
+                };
+                let device = monitor
+                    .and_then(|index| self.sounds.monitors.get(index))
+                    .and_then(|cue| cue.device.clone());
+                match self.sink.open() {
+                    Backend::Stream => {}
+                    // NOTE: no volume control, the beep only stays out when muted to 0
+                    Backend::Beep if volume * self.volume > 0.0 => {
+                        return self.sink.beep((BEEP_FREQUENCY * speed) as u32, BEEP_LENGTH);
+                    }
+                    Backend::Beep | Backend::Silent => return,
+                }
+                self.sink.hold(layered.length());
+                // NOTE: the output mixes sounds played at once, so each layer goes on its own
+                for layer in layered.0 {
+                    let source = layer
+                        .sound
+                        .speed(speed)
+                        .amplify(layer.volume * volume * self.volume)
+                        .delay(layer.delay);
+                    // NOTE: centered sounds skip the spatial mix, which always outputs stereo
+                    let source: Box<dyn Source<Item = f32> + Send> = if pan == 0.0 {
+                        Box::new(source.convert_samples())
+                    } else {
+                        let emitter = [pan.clamp(-1.0, 1.0), 1.0, 0.0];
+                        let spatial =
+                            Spatial::new(source, emitter, [-1.0, 0.0, 0.0], [1.0, 0.0, 0.0]);
+                        Box::new(spatial.convert_samples())
+                    };
+                    self.sink.play(device.as_deref(), source);
+                }
+                if cfg!(debug_assertions) {
+                    log::info!("ping sound started {:?} after the click", fired.elapsed());
+                }
+            }
+            Command::Cue { primed, volume } => {
+                if self.muted || self.sink.open() != Backend::Stream {
+                    return;
+                }
+                self.sink.hold(CUE_LENGTH);
+                // NOTE: a rising pitch to arm, a lower one to disarm; short fades keep
+                // the edges from popping
+                let pitch = if primed { 1320.0 } else { 880.0 };
+                let mut cue = rodio::source::SineWave::new(pitch).take_duration(CUE_LENGTH);
+                cue.set_filter_fadeout();
+                let cue = cue
+                    .fade_in(Duration::from_millis(3))
+                    .amplify(volume.clamp(0.0, 1.0) * self.volume);
+                self.sink.play(None, Box::new(cue));
+            }
+            Command::SetVolume(volume) => self.volume = volume.clamp(0.0, 1.0),
+            Command::Mute(muted) => self.muted = muted,
+            Command::SetDevice(device) => self.sink.set_device(device),
+            Command::Reload(sound) => self.sounds.theme = sound,
+            Command::SetPing(ping) => self.sounds.ping = ping,
+            Command::Layout(layout) => self.sounds.monitors.resolve(&layout),
+            Command::Lazy(lazy) => self.sink.set_lazy(lazy),
+        }
+    }
+
+    /// Sound and playback speed for a ping: the zone's own sound as is, else
+    /// the monitor's cue, else the user's or the theme's sound at the monitor's pitch.
+    fn resolve(&self, monitor: Option<usize>, zone: Option<usize>) -> Option<(Layered, f32)> {
+        if let Some(sound) = zone.and_then(|index| self.sounds.zones.sound(index)) {
+            return Some((sound, 1.0));
+        }
+        // NOTE: resolved per ping so it follows monitor changes
+        let cue = monitor.and_then(|index| self.sounds.monitors.get(index));
+        let speed = cue.map_or(1.0, |cue| cue.speed);
+        cue.and_then(|cue| cue.sound.clone())
+            .or_else(|| self.sounds.ping.clone())
+            .or_else(|| self.sounds.theme.clone().map(Layered::single))
+            .map(|sound| (sound, speed))
+    }
+}
+
+/// The output devices sounds are played on, reopened when they go away.
+struct Outputs {
+    metrics: Arc<Metrics>,
+    /// Shared with [`Audio::backend`].
+    backend: Arc<AtomicU8>,
+    beep_fallback: bool,
+    device: Option<String>,
+    /// `None` while there is no usable output device.
+    output: Option<(OutputStream, OutputStreamHandle)>,
+    /// What the main output was opened at, `None` if it couldn't be read.
+    output_config: Option<OutputConfig>,
+    /// When the open outputs were last checked against their devices.
+    checked_at: Instant,
+    /// Devices of monitors with their own, by configured name, opened on
+    /// their first ping, with the format they were opened at.
+    monitor_outputs: HashMap<String, (OutputStream, OutputStreamHandle, Option<OutputConfig>)>,
+    /// Monitor devices that couldn't be opened, with when they were last tried.
+    missing: HashMap<String, Instant>,
+    last_open: Option<Instant>,
+    /// Opens the device for a sound and closes it again after [`LAZY_LINGER`].
+    lazy: bool,
+    /// When the lazy output is closed, `None` while it stays open.
+    close_at: Option<Instant>,
+}
+
+impl Sink for Outputs {
+    fn open(&mut self) -> Backend {
+        // NOTE: before the sound, so it never goes to a stale stream
+        if self.checked_at.elapsed() >= CHECK_INTERVAL {
+            self.check_outputs();
+        }
+        if self.output.is_none() {
+            self.reopen();
+        }
+        match self.output {
+            Some(_) => Backend::Stream,
+            None if self.beep_fallback => Backend::Beep,
+            None => Backend::Silent,
+        }
+    }
+
+    fn play(&mut self, device: Option<&str>, source: Box<dyn Source<Item = f32> + Send>) {
+        let Some(name) = device else {
+            return self.play_main(source);
+        };
+        let Some(handle) = self.monitor_output(name) else {
+            return self.play_main(source);
+        };
+        match handle.play_raw(source) {
+            Ok(()) => {}
+            Err(e) => {
+                // NOTE: the sound went with it, the next ping falls back until it's back
+                log::warn!("audio device {name:?} failed, using the main output: {e}");
+                Metrics::bump(&self.metrics.audio_errors);
+                self.monitor_outputs.remove(name);
+                self.missing.insert(name.to_owned(), Instant::now());
+            }
+        }
+    }
+
+    fn beep(&mut self, frequency: u32, length: Duration) {
+        crate::platform::beep(frequency, length);
+    }
+
+    fn hold(&mut self, length: Duration) {
+        if self.lazy {
+            self.close_at = Some(Instant::now() + length + LAZY_LINGER);
+        }
+    }
+
+    fn set_device(&mut self, device: Option<String>) {
+        self.device = device;
+        // NOTE: matching is fuzzy, a monitor's device may resolve differently now
+        self.monitor_outputs.clear();
+        self.missing.clear();
+        self.last_open = None;
+        if self.lazy {
+            self.output = None;
+        } else {
+            self.reopen();
+        }
+    }
+
+    fn set_lazy(&mut self, lazy: bool) {
+        self.lazy = lazy;
+        if lazy {
+            self.output = None;
+            self.monitor_outputs.clear();
+            self.last_open = None;
+        } else {
+            self.close_at = None;
+            if self.output.is_none() {
+                self.last_open = None;
+                self.reopen();
+            }
+        }
+    }
+}
+
+impl Outputs {
+    /// When [`Outputs::wake`] is due without a command coming in first.
+    fn deadline(&self) -> Option<Instant> {
+        let check_at = self.is_open().then(|| self.checked_at + CHECK_INTERVAL);
+        match (self.close_at, check_at) {
+            (Some(close), Some(check)) => Some(close.min(check)),
+            (close, check) => close.or(check),
+        }
+    }
+
+    /// Closes an idle lazy output, or checks the open ones.
+    fn wake(&mut self) {
+        if self.close_at.is_some_and(|at| at <= Instant::now()) {
+            log::info!("closing the idle audio output");
+            self.output = None;
+            self.monitor_outputs.clear();
+            self.close_at = None;
+            self.last_open = None;
+        } else {
+            self.check_outputs();
+        }
+    }
+
+    /// The output for a monitor's own device, opened on first use. `None`
+    /// while it's missing, looked for again at most every [`REOPEN_INTERVAL`].
+    fn monitor_output(&mut self, name: &str) -> Option<OutputStreamHandle> {
+        if let Some((_, handle, _)) = self.monitor_outputs.get(name) {
+            return Some(handle.clone());
+        }
+        if self
+            .missing
+            .get(name)
+            .is_some_and(|last| last.elapsed() < REOPEN_INTERVAL)
+        {
+            return None;
+        }
+        let opened = find_device(name)
+            .ok_or_else(|| "not found".to_owned())
+            .and_then(|device| {
+                let output = OutputStream::try_from_device(&device).map_err(|e| e.to_string())?;
+                Ok((output, OutputConfig::of(&device)))
+            });
+        match opened {
+            Ok(((stream, handle), config)) => {
+                if self.missing.remove(name).is_some() {
+                    log::info!("audio device {name:?} is back");
+                }
+                self.monitor_outputs
+                    .insert(name.to_owned(), (stream, handle.clone(), config));
+                Some(handle)
+            }
+            Err(e) => {
+                // NOTE: warned once, not on every ping while it's unplugged
+                if self
+                    .missing
+                    .insert(name.to_owned(), Instant::now())
+                    .is_none()
+                {
+                    log::warn!("audio device {name:?} unavailable ({e}), using the main output");
+                }
+                None
+            }
+        }
+    }
+
+    fn play_main(&mut self, source: Box<dyn Source<Item = f32> + Send>) {
+        if self.output.is_none() {
+            self.reopen();
+        }
+        let Some((_, handle)) = &self.output else {
+            return;
+        };
+        match handle.play_raw(source) {
+            Ok(()) => {}
+            Err(PlayError::NoDevice) => {
+                log::warn!("audio output device lost, reopening");
+                Metrics::bump(&self.metrics.audio_errors);
+                self.output = None;
+                self.last_open = None;
+                self.reopen();
+            }
+            Err(e) => {
+                log::warn!("failed to play the ping sound: {e}");
+                Metrics::bump(&self.metrics.audio_errors);
+            }
+        }
+    }
+
+    fn is_open(&self) -> bool {
+        self.output.is_some() || !self.monitor_outputs.is_empty()
+    }
+
+    /// Rebuilds outputs whose device now mixes at another format, or, for
+    /// the main one, whose device is no longer the one it would open. A
+    /// stream keeps the format it was opened at, and sounds come out at the
+    /// wrong pitch or not at all once the device moves on.
+    fn check_outputs(&mut self) {
+        self.checked_at = Instant::now();
+        if self.output.is_some() {
+            let current = self
+                .chosen_device()
+                .and_then(|device| OutputConfig::of(&device));
+            // NOTE: unreadable formats aren't a change, the device may just be busy
+            if current.is_some() && current != self.output_config {
+                log::info!(
+                    "audio output changed from {} to {}, rebuilding",
+                    describe(&self.output_config),
+                    describe(&current)
+                );
+                self.output = None;
+                self.last_open = None;
+                self.reopen();
+            }
+        }
+        // NOTE: rebuilt on their next ping, like any monitor device
+        self.monitor_outputs.retain(|name, (_, _, opened)| {
+            let current = find_device(name).and_then(|device| OutputConfig::of(&device));
+            let changed = current.is_some() && current != *opened;
+            if changed {
+                log::info!(
+                    "audio device {name:?} changed from {} to {}, rebuilding",
+                    describe(opened),
+                    describe(&current)
+                );
+            }
+            !changed
+        });
+    }
+
+    /// The device to open: the configured one, else the default.
+    fn chosen_device(&self) -> Option<rodio::cpal::Device> {
+        self.device
+            .as_deref()
+            .and_then(find_device)
+            .or_else(|| rodio::cpal::default_host().default_output_device())
+    }
+
+    /// Opens the chosen device, or the default one when it's gone. Retried at
+    /// most every [`REOPEN_INTERVAL`] while nothing can be opened.
+    fn reopen(&mut self) {
+        if self
+            .last_open
+            .is_some_and(|last| last.elapsed() < REOPEN_INTERVAL)
+        {
+            return;
+        }
+        self.last_open = Some(Instant::now());
+
+        let chosen = self.device.as_deref().and_then(|name| {
+            let device = find_device(name);
+            if device.is_none() {
+                log::warn!("audio device {name:?} not found, using the default");
+            }
+            device
+        });
+        let opened = match &chosen {
+            Some(device) => OutputStream::try_from_device(device),
+            None => OutputStream::try_default(),
+        };
+        // NOTE: read apart from the stream, rodio doesn't say what it opened at
+        self.output_config = chosen
+            .or_else(|| rodio::cpal::default_host().default_output_device())
+            .and_then(|device| OutputConfig::of(&device));
+        self.checked_at = Instant::now();
+        match opened {
+            Ok(output) => {
+                // NOTE: devices come out of power saving on the first samples, better
+                // on these than on the first ping
+                let silence = rodio::source::Zero::<f32>::new(2, 48_000).take_duration(WARM_UP);
+                if let Err(e) = output.1.play_raw(silence) {
+                    log::info!("failed to warm up the audio output: {e}");
+                }
+                self.output = Some(output);
+                self.set_backend(Backend::Stream);
+                if let Err(e) = crate::platform::name_audio_session("Screen Pinger") {
+                    log::info!("audio session left unnamed: {e}");
+                }
+            }
+            Err(e) => {
+                self.output = None;
+                let e = crate::error::Error::Audio(e.to_string());
+                if self.beep_fallback {
+                    log::warn!("{e}, pings will beep");
+                    self.set_backend(Backend::Beep);
+                } else {
+                    log::warn!("{e}, pings will be silent");
+                    self.set_backend(Backend::Silent);
+                }
+            }
+        }
+    }
+
+    fn set_backend(&self, backend: Backend) {
+        self.backend.store(backend as u8, Ordering::Relaxed);
+    }
+}
+
+fn describe(config: &Option<OutputConfig>) -> String {
+    config
+        .as_ref()
+        .map_or_else(|| "an unknown format".into(), ToString::to_string)
+}
+
+/// The output device called `name`, or else the only one whose name
+/// contains it ignoring case, e.g. `"realtek"` for `"Speakers (Realtek(R)
+/// Audio)"`. `None` when there's no match or several.
+fn find_device(name: &str) -> Option<rodio::cpal::Device> {
+    let devices: Vec<_> = rodio::cpal::default_host()
+        .output_devices()
+        .ok()?
+        .filter_map(|device| Some((device.name().ok()?, device)))
+        .collect();
+    if let Some(index) = devices.iter().position(|(device, _)| device == name) {
+        return devices.into_iter().nth(index).map(|(_, device)| device);
+    }
+    let name = name.to_lowercase();
+    let mut partial = devices
+        .into_iter()
+        .filter(|(device, _)| device.to_lowercase().contains(&name));
+    match (partial.next(), partial.next()) {
+        (Some((_, device)), None) => Some(device),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Records what the player asks of it, with its output stuck on `backend`.
+    struct Recorder {
+        backend: Backend,
+        /// Device and loudest sample of each sound played.
+        played: Vec<(Option<String>, f32)>,
+        beeps: Vec<u32>,
+        held: Vec<Duration>,
+        device: Option<String>,
+        lazy: bool,
+    }
+
+    impl Sink for Recorder {
+        fn open(&mut self) -> Backend {
+            self.backend
+        }
+
+        fn play(&mut self, device: Option<&str>, source: Box<dyn Source<Item = f32> + Send>) {
+            let peak = source.fold(0.0, |peak: f32, sample| peak.max(sample.abs()));
+            self.played.push((device.map(str::to_owned), peak));
+        }
+
+        fn beep(&mut self, frequency: u32, _: Duration) {
+            self.beeps.push(frequency);
+        }
+
+        fn hold(&mut self, length: Duration) {
+            self.held.push(length);
+        }
+
+        fn set_device(&mut self, device: Option<String>) {
+            self.device = device;
+        }
+
+        fn set_lazy(&mut self, lazy: bool) {
+            self.lazy = lazy;
+        }
+    }
+
+    /// A tenth of a second of a constant `level`.
+    fn sound(level: f32) -> Sound {
+        Sound {
+            samples: vec![level; 4_800].into(),
+            channels: 1,
+            sample_rate: 48_000,
+            position: 0,
+        }
+    }
+
+    fn player(backend: Backend, theme: Option<Sound>) -> Player<Recorder> {
+        Player {
+            sounds: Sounds {
+                theme,
+                ping: None,
+                monitors: Cues::default(),
+                zones: Arc::new(Zones::new(Vec::new())),
+            },
+            volume: 1.0,
+            muted: false,
+            sink: Recorder {
+                backend,
+                played: Vec::new(),
+                beeps: Vec::new(),
+                held: Vec::new(),
+                device: None,
+                lazy: false,
+            },
+        }
+    }
+
+    fn play(volume: f32) -> Command {
+        Command::Play {
+            monitor: None,
+            zone: None,
+            pan: 0.0,
+            volume,
+            fired: Instant::now(),
+        }
+    }
+
+    fn peaks(player: &Player<Recorder>) -> Vec<f32> {
+        player.sink.played.iter().map(|(_, peak)| *peak).collect()
+    }
+
+    #[test]
+    fn pings_play_the_theme_sound_at_both_volumes() {
+        let mut player = player(Backend::Stream, Some(sound(0.5)));
+        player.handle(play(1.0));
+        player.handle(Command::SetVolume(0.5));
+        player.handle(play(0.8));
+        assert_eq!(peaks(&player), [0.5, 0.2]);
+        assert_eq!(player.sink.played[0].0, None);
+        assert_eq!(player.sink.held, [Duration::from_millis(100); 2]);
+    }
+
+    #[test]
+    fn volume_is_clamped() {
+        let mut player = player(Backend::Stream, Some(sound(0.5)));
+        player.handle(Command::SetVolume(3.0));
+        player.handle(play(1.0));
+        player.handle(Command::SetVolume(-1.0));
+        player.handle(play(1.0));
+        assert_eq!(peaks(&player), [0.5, 0.0]);
+    }
+
+    #[test]
+    fn mute_silences_pings_and_cues_until_lifted() {
+        let mut player = player(Backend::Stream, Some(sound(0.5)));
+        player.handle(Command::Mute(true));
+        player.handle(play(1.0));
+        player.handle(Command::Cue {
+            primed: true,
+            volume: 1.0,
+        });
+        assert!(player.sink.played.is_empty());
+        assert!(player.sink.held.is_empty());
+
+        player.handle(Command::Mute(false));
+        player.handle(play(1.0));
+        player.handle(Command::Cue {
+            primed: true,
+            volume: 1.0,
+        });
+        assert_eq!(player.sink.played.len(), 2);
+    }
+
+    #[test]
+    fn the_users_sound_plays_over_the_theme() {
+        let mut player = player(Backend::Stream, Some(sound(0.5)));
+        player.handle(Command::SetPing(Some(Layered::single(sound(0.25)))));
+        player.handle(play(1.0));
+        player.handle(Command::Reload(Some(sound(0.75))));
+        player.handle(play(1.0));
+        player.handle(Command::SetPing(None));
+        player.handle(play(1.0));
+        assert_eq!(peaks(&player), [0.25, 0.25, 0.75]);
+    }
+
+    #[test]
+    fn each_layer_is_played_and_held_until_the_last_ends() {
+        let mut player = player(Backend::Stream, None);
+        let layered = Layered(vec![
+            Layer {
+                sound: sound(0.5),
+                delay: Duration::ZERO,
+                volume: 1.0,
+            },
+            Layer {
+                sound: sound(0.5),
+                delay: Duration::from_millis(200),
+                volume: 0.5,
+            },
+        ]);
+        player.handle(Command::SetPing(Some(layered)));
+        player.handle(play(1.0));
+        assert_eq!(peaks(&player), [0.5, 0.25]);
+        assert_eq!(player.sink.held, [Duration::from_millis(300)]);
+    }
+
+    #[test]
+    fn nothing_to_play_leaves_the_output_alone() {
+        let mut player = player(Backend::Stream, None);
+        player.handle(play(1.0));
+        assert!(player.sink.played.is_empty());
+        assert!(player.sink.held.is_empty());
+    }
+
+    #[test]
+    fn without_an_output_pings_beep_unless_silent_or_muted_to_zero() {
+        let mut beeping = player(Backend::Beep, Some(sound(0.5)));
+        beeping.handle(play(1.0));
+        beeping.handle(play(0.0));
+        beeping.handle(Command::SetVolume(0.0));
+        beeping.handle(play(1.0));
+        assert_eq!(beeping.sink.beeps, [BEEP_FREQUENCY as u32]);
+        assert!(beeping.sink.played.is_empty());
+
+        let mut silent = player(Backend::Silent, Some(sound(0.5)));
+        silent.handle(play(1.0));
+        assert!(silent.sink.beeps.is_empty());
+        assert!(silent.sink.played.is_empty());
+    }
+
+    #[test]
+    fn cues_need_an_output_and_follow_the_volume() {
+        let cue = || Command::Cue {
+            primed: false,
+            volume: 0.5,
+        };
+        let mut beeping = player(Backend::Beep, None);
+        beeping.handle(cue());
+        assert!(beeping.sink.played.is_empty());
+        assert!(beeping.sink.beeps.is_empty());
+
+        let mut playing = player(Backend::Stream, None);
+        playing.handle(Command::SetVolume(0.5));
+        playing.handle(cue());
+        let [peak] = peaks(&playing)[..] else {
+            panic!("one cue plays");
+        };
+        assert!(peak > 0.0 && peak <= 0.25, "{peak}");
+        assert_eq!(playing.sink.held, [CUE_LENGTH]);
+    }
+
+    #[test]
+    fn device_and_lazy_changes_go_to_the_outputs() {
+        let mut player = player(Backend::Stream, None);
+        player.handle(Command::SetDevice(Some("Speakers".to_owned())));
+        player.handle(Command::Lazy(true));
+        assert_eq!(player.sink.device.as_deref(), Some("Speakers"));
+        assert!(player.sink.lazy);
+        player.handle(Command::SetDevice(None));
+        player.handle(Command::Lazy(false));
+        assert_eq!(player.sink.device, None);
+        assert!(!player.sink.lazy);
+    }
+}
@@ -0,0 +1,80 @@
+use crate::platform;
+use crossbeam::channel::{self, Sender};
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a ping may wait for its backdrop sample before rendering default art.
+pub const CAPTURE_BUDGET: Duration = Duration::from_millis(33);
+const CAPTURE_SIZE: i32 = 64;
+
+/// What the screen under a ping looked like when it was fired.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Backdrop {
+    /// Not sampled, or the sample did not arrive in time.
+    Unknown,
+    Light,
+    Dark,
+}
+
+struct Request {
+    id: usize,
+    position: (i32, i32),
+    requested: Instant,
+}
+
+/// Samples screen luminance around pings off the listener thread.
+pub struct Sampler {
+    requests: Sender<Request>,
+    results: Arc<Mutex<HashMap<usize, (Instant, Backdrop)>>>,
+}
+
+impl Sampler {
+    pub fn spawn(threshold: f32) -> Arc<Self> {
+        let (requests, receiver) = channel::unbounded::<Request>();
+        let results: Arc<Mutex<HashMap<usize, (Instant, Backdrop)>>> = Default::default();
+
+        let thread_results = results.clone();
+        std::thread::spawn(move || {
+            for request in receiver {
+                let Some(luminance) = platform::average_luminance(request.position, CAPTURE_SIZE)
+                else {
+                    continue;
+                };
+                if request.requested.elapsed() > CAPTURE_BUDGET {
+                    log::info!("backdrop sample for ping {} arrived too late", request.id);
+                    continue;
+                }
+
+                let backdrop = if luminance >= threshold {
+                    Backdrop::Light
+                } else {
+                    Backdrop::Dark
+                };
+                let mut results = thread_results.lock().unwrap();
+                // NOTE: drop results nobody picked up so the map can't grow unbounded
+                results.retain(|_, (sampled, _)| sampled.elapsed() < Duration::from_secs(1));
+                results.insert(request.id, (Instant::now(), backdrop));
+            }
+        });
+
+        Arc::new(Self { requests, results })
+    }
+
+    pub fn request(&self, id: usize, position: (i32, i32)) {
+        let request = Request {
+            id,
+            position,
+            requested: Instant::now(),
+        };
+        self.requests.send(request).ok();
+    }
+
+    pub fn take(&self, id: usize) -> Option<Backdrop> {
+        self.results
+            .lock()
+            .unwrap()
+            .remove(&id)
+            .map(|(_, backdrop)| backdrop)
+    }
+}
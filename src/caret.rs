@@ -0,0 +1,45 @@
+//! Where the text caret is, to ping where someone types rather than where the
+//! mouse is. Asked on a thread of its own: UI Automation wants an STA, and
+//! every query calls into the focused app, which may be hung.
+
+use crate::platform;
+use crossbeam::channel::{self, Sender};
+use device_query::mouse_state::MousePosition;
+use std::time::Duration;
+
+/// How long the listener waits for an answer, it holds up input meanwhile.
+const TIMEOUT: Duration = Duration::from_millis(150);
+
+pub struct Caret {
+    requests: Sender<Sender<Option<MousePosition>>>,
+}
+
+impl Caret {
+    pub fn spawn() -> Self {
+        let (requests, incoming) = channel::unbounded::<Sender<Option<MousePosition>>>();
+        std::thread::spawn(move || {
+            let automation = platform::Automation::new()
+                .map_err(|e| {
+                    log::warn!("UI Automation unavailable, only reading the system caret: {e}")
+                })
+                .ok();
+            for reply in incoming {
+                let position = automation
+                    .as_ref()
+                    .and_then(platform::Automation::caret)
+                    .or_else(platform::system_caret);
+                // NOTE: the asker may have given up already
+                reply.send(position).ok();
+            }
+        });
+        Self { requests }
+    }
+
+    /// The caret's position in desktop pixels, `None` when there is none or
+    /// it took longer than [`TIMEOUT`] to find.
+    pub fn locate(&self) -> Option<MousePosition> {
+        let (reply, answer) = channel::bounded(1);
+        self.requests.send(reply).ok()?;
+        answer.recv_timeout(TIMEOUT).ok().flatten()
+    }
+}
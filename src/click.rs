@@ -0,0 +1,170 @@
+//! Lifecycle of a primed click in the listener: pressed, then released or
+//! abandoned. Kept apart from the hook so later gestures (drag to cancel, a
+//! radial wheel) can hang off the same states.
+
+use device_query::mouse_state::MousePosition;
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+
+/// Which end of the click the ping lands on.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PingAt {
+    /// Where the button went down.
+    #[default]
+    OnPress,
+    /// Where it came up, so the spot can be adjusted while held.
+    OnRelease,
+}
+
+/// A click from press to release.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Gesture {
+    pub press: MousePosition,
+    pub release: MousePosition,
+}
+
+impl Gesture {
+    pub fn target(&self, ping_at: PingAt) -> MousePosition {
+        match ping_at {
+            PingAt::OnPress => self.press,
+            PingAt::OnRelease => self.release,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Idle,
+    Pressed { at: MousePosition, since: Instant },
+}
+
+#[derive(Debug, Clone)]
+pub struct Click {
+    state: State,
+    /// A release later than this is taken as lost, e.g. focus moved mid-click.
+    timeout: Duration,
+}
+
+impl Click {
+    pub fn new(timeout: Duration) -> Self {
+        Self {
+            state: State::Idle,
+            timeout,
+        }
+    }
+
+    /// A primed press. One still pending is dropped, its release was missed.
+    pub fn press(&mut self, at: MousePosition, now: Instant) {
+        if let State::Pressed { at: stale, .. } = self.state {
+            log::info!("click at {stale:?} never released, dropping it");
+        }
+        self.state = State::Pressed { at, since: now };
+    }
+
+    /// The release of the pending press, `None` without one or once it timed out.
+    pub fn release(&mut self, at: MousePosition, now: Instant) -> Option<Gesture> {
+        let State::Pressed { at: press, since } = std::mem::replace(&mut self.state, State::Idle)
+        else {
+            return None;
+        };
+        if now.saturating_duration_since(since) > self.timeout {
+            log::info!(
+                "click at {press:?} released after {:?}, cancelled",
+                self.timeout
+            );
+            return None;
+        }
+        Some(Gesture { press, release: at })
+    }
+
+    /// Forgets the pending press, e.g. released unprimed or while paused.
+    pub fn cancel(&mut self) {
+        self.state = State::Idle;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TIMEOUT: Duration = Duration::from_secs(2);
+
+    #[test]
+    fn release_after_press_gives_both_positions() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((10, 20), start);
+        let gesture = click.release((15, 25), start + Duration::from_millis(300));
+        assert_eq!(
+            gesture,
+            Some(Gesture {
+                press: (10, 20),
+                release: (15, 25),
+            })
+        );
+    }
+
+    #[test]
+    fn gesture_targets_the_configured_end() {
+        let gesture = Gesture {
+            press: (1, 2),
+            release: (3, 4),
+        };
+        assert_eq!(gesture.target(PingAt::OnPress), (1, 2));
+        assert_eq!(gesture.target(PingAt::OnRelease), (3, 4));
+    }
+
+    #[test]
+    fn release_without_press_is_ignored() {
+        let mut click = Click::new(TIMEOUT);
+        assert_eq!(click.release((0, 0), Instant::now()), None);
+    }
+
+    #[test]
+    fn release_returns_to_idle() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((0, 0), start);
+        assert!(click.release((1, 1), start).is_some());
+        assert_eq!(click.release((2, 2), start), None);
+    }
+
+    #[test]
+    fn release_at_the_timeout_still_counts() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((0, 0), start);
+        assert!(click.release((1, 1), start + TIMEOUT).is_some());
+    }
+
+    #[test]
+    fn release_past_the_timeout_is_cancelled() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((0, 0), start);
+        let late = start + TIMEOUT + Duration::from_millis(1);
+        assert_eq!(click.release((1, 1), late), None);
+        // NOTE: the timed out press is gone, not waiting for another release
+        assert_eq!(click.release((1, 1), start), None);
+    }
+
+    #[test]
+    fn second_press_replaces_a_pending_one() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((0, 0), start);
+        click.press((5, 5), start + TIMEOUT);
+        let gesture = click.release((6, 6), start + TIMEOUT + Duration::from_millis(100));
+        assert_eq!(gesture.map(|gesture| gesture.press), Some((5, 5)));
+    }
+
+    #[test]
+    fn cancel_drops_the_pending_press() {
+        let mut click = Click::new(TIMEOUT);
+        let start = Instant::now();
+        click.press((0, 0), start);
+        click.cancel();
+        assert_eq!(click.release((1, 1), start), None);
+    }
+}
@@ -0,0 +1,582 @@
+//! Decides what a new ping becomes: merged into one its source just fired
+//! next to it, or a ping of its own, numbered when sequences are on. Also
+//! which pings give way when the queue or the sound budget runs out.
+
+use crate::config;
+use crate::ping_source::PingSource;
+use crate::protocol::PingKind;
+use device_query::mouse_state::MousePosition;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, PartialEq)]
+pub enum Decision {
+    /// Count the ping on animation `id` instead of showing another.
+    Merge(usize),
+    /// Show a new ping with this sequence number, if numbering is on.
+    New { sequence: Option<u32> },
+}
+
+#[derive(Debug)]
+struct Recent {
+    id: usize,
+    position: MousePosition,
+    kind: PingKind,
+    source: PingSource,
+    at: Instant,
+}
+
+#[derive(Debug)]
+pub struct Coalescer {
+    /// Bursts merge while each ping follows the last within this; zero turns merging off.
+    window: Duration,
+    radius: f32,
+    recent: VecDeque<Recent>,
+    sequence: Option<Sequence>,
+}
+
+impl Coalescer {
+    /// `idle_gap` turns on numbering, which starts over after that long without a ping.
+    pub fn new(window: Duration, radius: f32, idle_gap: Option<Duration>) -> Self {
+        Self {
+            window,
+            radius,
+            recent: VecDeque::new(),
+            sequence: idle_gap.map(Sequence::new),
+        }
+    }
+
+    /// What a ping of `kind` at `position` fired at `now` becomes. A merge
+    /// keeps the burst open for another window; a merged ping doesn't take a
+    /// sequence number. Markers are never merged, and only local pings are
+    /// numbered.
+    pub fn decide(
+        &mut self,
+        position: MousePosition,
+        kind: &PingKind,
+        source: &PingSource,
+        persistent: bool,
+        now: Instant,
+    ) -> Decision {
+        let window = self.window;
+        self.recent
+            .retain(|recent| now.saturating_duration_since(recent.at) <= window);
+
+        let radius = self.radius;
+        let nearby = self
+            .recent
+            .iter_mut()
+            .filter(|_| !persistent)
+            .find(|recent| {
+                let (dx, dy) = (
+                    recent.position.0 - position.0,
+                    recent.position.1 - position.1,
+                );
+                // NOTE: merged into someone else's ping, a ping would lose its label
+                recent.kind == *kind
+                    && recent.source == *source
+                    && (dx as f32).hypot(dy as f32) <= radius
+            });
+        if let Some(recent) = nearby {
+            recent.at = now;
+            return Decision::Merge(recent.id);
+        }
+
+        let sequence = match source {
+            PingSource::Local => self.sequence.as_mut().map(|sequence| sequence.next(now)),
+            _ => None,
+        };
+        Decision::New { sequence }
+    }
+
+    /// Notes the ping queued as animation `id` after a [`Decision::New`], for
+    /// later ones to merge into. Only for pings, not markers.
+    pub fn record(
+        &mut self,
+        id: usize,
+        position: MousePosition,
+        kind: PingKind,
+        source: PingSource,
+        now: Instant,
+    ) {
+        if self.window.is_zero() {
+            return;
+        }
+        self.recent.push_back(Recent {
+            id,
+            position,
+            kind,
+            source,
+            at: now,
+        });
+    }
+}
+
+/// Numbers pings fired in quick succession, for walking someone through a
+/// series of steps.
+#[derive(Debug)]
+struct Sequence {
+    idle_gap: Duration,
+    last: Option<Instant>,
+    count: u32,
+}
+
+impl Sequence {
+    fn new(idle_gap: Duration) -> Self {
+        Self {
+            idle_gap,
+            last: None,
+            count: 0,
+        }
+    }
+
+    /// Number of a ping fired at `now`, starting over at 1 after `idle_gap`
+    /// without pings.
+    fn next(&mut self, now: Instant) -> u32 {
+        let continues = self
+            .last
+            .is_some_and(|last| now.saturating_duration_since(last) <= self.idle_gap);
+        self.count = if continues { self.count + 1 } else { 1 };
+        self.last = Some(now);
+        self.count
+    }
+}
+
+/// Who wins when pings compete for a queue slot or a sound. The user's own
+/// pings always beat scripted and remote ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Priority {
+    Scripted,
+    Local,
+}
+
+impl Priority {
+    pub fn of(source: &PingSource) -> Self {
+        match source {
+            PingSource::Local => Priority::Local,
+            PingSource::Remote { .. } | PingSource::Ipc => Priority::Scripted,
+        }
+    }
+}
+
+/// Which of the `queued` pings, oldest first, makes room for an `incoming`
+/// one in a full queue: the oldest of lower priority. `None` drops the
+/// incoming ping instead.
+pub fn evict(queued: &[Priority], incoming: Priority) -> Option<usize> {
+    queued.iter().position(|queued| *queued < incoming)
+}
+
+/// Caps how many ping sounds start within a second. Scripted sounds stop
+/// short of the cap by the headroom reserved for local ones; local sounds
+/// count towards the cap but always play.
+#[derive(Debug)]
+pub struct SoundBudget {
+    cap: usize,
+    reserved: usize,
+    started: VecDeque<(Priority, Instant)>,
+}
+
+impl SoundBudget {
+    const WINDOW: Duration = Duration::from_secs(1);
+
+    pub fn new(config: &config::Coalesce) -> Self {
+        Self {
+            cap: config.max_sounds_per_sec as usize,
+            reserved: config.reserved_local_sounds as usize,
+            started: VecDeque::new(),
+        }
+    }
+
+    /// Whether a sound of `priority` may start at `now`, counting it if so.
+    pub fn take(&mut self, priority: Priority, now: Instant) -> bool {
+        while let Some((_, at)) = self.started.front() {
+            if now.saturating_duration_since(*at) < Self::WINDOW {
+                break;
+            }
+            self.started.pop_front();
+        }
+        let allowed = match priority {
+            Priority::Local => true,
+            Priority::Scripted => {
+                let scripted = self
+                    .started
+                    .iter()
+                    .filter(|(priority, _)| *priority == Priority::Scripted)
+                    .count();
+                scripted + self.reserved < self.cap && self.started.len() < self.cap
+            }
+        };
+        if allowed {
+            self.started.push_back((priority, now));
+        }
+        allowed
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_millis(300);
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    /// A local ping at `position` `after` the start, queued as `id` if it's new.
+    fn fire(
+        coalescer: &mut Coalescer,
+        start: Instant,
+        id: usize,
+        position: MousePosition,
+        after: Duration,
+    ) -> Decision {
+        fire_from(coalescer, &PingSource::Local, start, id, position, after)
+    }
+
+    /// [`fire`] for a ping from `source`.
+    fn fire_from(
+        coalescer: &mut Coalescer,
+        source: &PingSource,
+        start: Instant,
+        id: usize,
+        position: MousePosition,
+        after: Duration,
+    ) -> Decision {
+        let now = start + after;
+        let decision = coalescer.decide(position, &PingKind::Danger, source, false, now);
+        if let Decision::New { .. } = decision {
+            coalescer.record(id, position, PingKind::Danger, source.clone(), now);
+        }
+        decision
+    }
+
+    fn merging() -> Coalescer {
+        Coalescer::new(WINDOW, 40.0, None)
+    }
+
+    #[test]
+    fn pings_merge_up_to_the_end_of_the_window() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (100, 100), WINDOW),
+            Decision::Merge(1)
+        );
+
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (100, 100), WINDOW + ms(1)),
+            Decision::New { sequence: None }
+        );
+    }
+
+    #[test]
+    fn each_merge_keeps_the_burst_open() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        for step in 1..=5 {
+            assert_eq!(
+                fire(
+                    &mut coalescer,
+                    start,
+                    1 + step,
+                    (100, 100),
+                    ms(250 * step as u64)
+                ),
+                Decision::Merge(1),
+                "{step}"
+            );
+        }
+    }
+
+    #[test]
+    fn pings_merge_up_to_the_radius() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        // NOTE: 24, 32 is exactly 40 pixels away, 25, 32 just past it
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (124, 132), ms(10)),
+            Decision::Merge(1)
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 3, (75, 68), ms(20)),
+            Decision::New { sequence: None }
+        );
+    }
+
+    #[test]
+    fn the_burst_stays_where_it_started() {
+        // NOTE: merging doesn't move the recent ping, a drag can't walk a burst across the screen
+        let start = Instant::now();
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (130, 100), ms(10)),
+            Decision::Merge(1)
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 3, (160, 100), ms(20)),
+            Decision::New { sequence: None }
+        );
+    }
+
+    #[test]
+    fn other_kinds_and_markers_are_never_merged() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            coalescer.decide(
+                (100, 100),
+                &PingKind::Assist,
+                &PingSource::Local,
+                false,
+                start
+            ),
+            Decision::New { sequence: None }
+        );
+        assert_eq!(
+            coalescer.decide(
+                (100, 100),
+                &PingKind::Danger,
+                &PingSource::Local,
+                true,
+                start
+            ),
+            Decision::New { sequence: None }
+        );
+    }
+
+    #[test]
+    fn a_zero_window_turns_merging_off() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(Duration::ZERO, 40.0, None);
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (100, 100), ms(0)),
+            Decision::New { sequence: None }
+        );
+    }
+
+    #[test]
+    fn numbering_starts_over_after_the_idle_gap() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(Duration::ZERO, 40.0, Some(ms(2000)));
+        let mut sequence =
+            |id, after| match fire(&mut coalescer, start, id, (id as i32 * 500, 0), after) {
+                Decision::New { sequence } => sequence,
+                Decision::Merge(_) => panic!("nothing merges with merging off"),
+            };
+        assert_eq!(sequence(1, ms(0)), Some(1));
+        assert_eq!(sequence(2, ms(2000)), Some(2));
+        assert_eq!(sequence(3, ms(4001)), Some(1));
+    }
+
+    #[test]
+    fn merged_pings_dont_take_a_number() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(WINDOW, 40.0, Some(ms(2000)));
+        assert_eq!(
+            fire(&mut coalescer, start, 1, (100, 100), ms(0)),
+            Decision::New { sequence: Some(1) }
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 2, (100, 100), ms(100)),
+            Decision::Merge(1)
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 3, (900, 100), ms(200)),
+            Decision::New { sequence: Some(2) }
+        );
+    }
+
+    #[test]
+    fn a_script_burst_merges_into_its_own_ping() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        let script = PingSource::Ipc;
+        fire_from(&mut coalescer, &script, start, 1, (100, 100), ms(0));
+        for step in 1..5 {
+            assert_eq!(
+                fire_from(
+                    &mut coalescer,
+                    &script,
+                    start,
+                    1 + step,
+                    (100, 100),
+                    ms(40 * step as u64)
+                ),
+                Decision::Merge(1)
+            );
+        }
+    }
+
+    #[test]
+    fn pings_from_other_sources_never_merge() {
+        let start = Instant::now();
+        let mut coalescer = merging();
+        let laptop = PingSource::Remote {
+            peer: "laptop".into(),
+        };
+        fire(&mut coalescer, start, 1, (100, 100), ms(0));
+        assert_eq!(
+            fire_from(&mut coalescer, &laptop, start, 2, (100, 100), ms(10)),
+            Decision::New { sequence: None }
+        );
+        assert_eq!(
+            fire_from(
+                &mut coalescer,
+                &PingSource::Ipc,
+                start,
+                3,
+                (100, 100),
+                ms(20)
+            ),
+            Decision::New { sequence: None }
+        );
+        assert_eq!(
+            fire_from(&mut coalescer, &laptop, start, 4, (100, 100), ms(30)),
+            Decision::Merge(2)
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 5, (100, 100), ms(40)),
+            Decision::Merge(1)
+        );
+    }
+
+    #[test]
+    fn only_local_pings_are_numbered() {
+        let start = Instant::now();
+        let mut coalescer = Coalescer::new(Duration::ZERO, 40.0, Some(ms(2000)));
+        assert_eq!(
+            fire(&mut coalescer, start, 1, (100, 100), ms(0)),
+            Decision::New { sequence: Some(1) }
+        );
+        assert_eq!(
+            fire_from(
+                &mut coalescer,
+                &PingSource::Ipc,
+                start,
+                2,
+                (500, 100),
+                ms(10)
+            ),
+            Decision::New { sequence: None }
+        );
+        assert_eq!(
+            fire(&mut coalescer, start, 3, (900, 100), ms(20)),
+            Decision::New { sequence: Some(2) }
+        );
+    }
+
+    #[test]
+    fn local_pings_evict_the_oldest_scripted_one() {
+        use Priority::*;
+        assert_eq!(evict(&[Local, Scripted, Scripted], Local), Some(1));
+        assert_eq!(evict(&[Local, Local], Local), None);
+        assert_eq!(evict(&[Scripted, Local], Scripted), None);
+    }
+
+    #[test]
+    fn scripted_sounds_leave_room_for_local_ones() {
+        let start = Instant::now();
+        let mut budget = SoundBudget::new(&config::Coalesce::default());
+        // NOTE: 8 a second, 3 of them kept for local pings
+        let scripted = (0..8)
+            .filter(|_| budget.take(Priority::Scripted, start))
+            .count();
+        assert_eq!(scripted, 5);
+        assert!((0..5).all(|_| budget.take(Priority::Local, start)));
+        assert!(!budget.take(Priority::Scripted, start + ms(999)));
+        assert!(budget.take(Priority::Scripted, start + ms(1000)));
+    }
+
+    fn capped(max_sounds_per_sec: u32, reserved_local_sounds: u32) -> SoundBudget {
+        SoundBudget::new(&config::Coalesce {
+            max_sounds_per_sec,
+            reserved_local_sounds,
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn only_the_user_outranks_scripts() {
+        assert_eq!(Priority::of(&PingSource::Local), Priority::Local);
+        assert_eq!(Priority::of(&PingSource::Ipc), Priority::Scripted);
+        assert!(Priority::Local > Priority::Scripted);
+    }
+
+    #[test]
+    fn full_queue_of_local_pings_drops_every_incoming_one() {
+        use Priority::*;
+        assert_eq!(evict(&[Local, Local, Local], Local), None);
+        assert_eq!(evict(&[Local, Local, Local], Scripted), None);
+        assert_eq!(evict(&[], Local), None);
+    }
+
+    #[test]
+    fn scripted_burst_gives_way_to_each_local_ping() {
+        use Priority::*;
+        let mut queue = vec![Scripted, Local, Scripted, Scripted];
+        for _ in 0..3 {
+            let slot = evict(&queue, Local).unwrap();
+            queue.remove(slot);
+            queue.push(Local);
+        }
+        assert_eq!(queue, [Local, Local, Local, Local]);
+        assert_eq!(evict(&queue, Local), None);
+    }
+
+    #[test]
+    fn local_sounds_past_the_cap_still_play() {
+        let start = Instant::now();
+        let mut budget = capped(4, 1);
+        assert!((0..10).all(|_| budget.take(Priority::Local, start)));
+    }
+
+    #[test]
+    fn local_sounds_use_up_the_room_scripted_ones_have() {
+        let start = Instant::now();
+        let mut budget = capped(4, 1);
+        assert!((0..2).all(|_| budget.take(Priority::Local, start)));
+        assert!(budget.take(Priority::Scripted, start));
+        assert!(budget.take(Priority::Scripted, start));
+        // NOTE: 2 local and 2 scripted reach the cap, although only 2 of the
+        // 3 scripted slots were used
+        assert!(!budget.take(Priority::Scripted, start));
+    }
+
+    #[test]
+    fn reserving_the_whole_cap_silences_scripts() {
+        let start = Instant::now();
+        let mut budget = capped(3, 3);
+        assert!(!budget.take(Priority::Scripted, start));
+        assert!(budget.take(Priority::Local, start));
+    }
+
+    #[test]
+    fn sounds_leave_the_budget_a_second_after_they_start() {
+        let start = Instant::now();
+        let mut budget = capped(2, 0);
+        assert!(budget.take(Priority::Scripted, start));
+        assert!(budget.take(Priority::Scripted, start + ms(500)));
+        assert!(!budget.take(Priority::Scripted, start + ms(999)));
+        assert!(budget.take(Priority::Scripted, start + ms(1000)));
+        assert!(!budget.take(Priority::Scripted, start + ms(1499)));
+        assert!(budget.take(Priority::Scripted, start + ms(1500)));
+    }
+
+    #[test]
+    fn refused_sounds_dont_count() {
+        let start = Instant::now();
+        let mut budget = capped(1, 0);
+        assert!(budget.take(Priority::Scripted, start));
+        assert!((1..10).all(|i| !budget.take(Priority::Scripted, start + ms(i * 100))));
+        assert!(budget.take(Priority::Scripted, start + ms(1000)));
+    }
+}
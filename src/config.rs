@@ -0,0 +1,1006 @@
+use serde::{Deserialize, Serialize};
+use std::ops::RangeInclusive;
+use std::time::Duration;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Config {
+    pub ui: Ui,
+    pub input: Input,
+    pub flick: Flick,
+    pub arrow: Arrow,
+    pub marker: Marker,
+    /// Ping kind to easing curve name, e.g. `missing = "ease_out_back"`.
+    pub easing: std::collections::HashMap<String, String>,
+    /// Ping kind to display overrides, e.g. `[kinds.danger]`.
+    pub kinds: std::collections::HashMap<String, KindStyle>,
+    pub theme: Theme,
+    pub restrict: Restrict,
+    pub expiry_ring: ExpiryRing,
+    pub emphasis: Emphasis,
+    pub backdrop: Backdrop,
+    pub sound: Sound,
+    pub taskbar: Taskbar,
+    pub copy_coordinates: CopyCoordinates,
+    pub sequence: Sequence,
+    pub trail: Trail,
+    pub ripples: Ripples,
+    pub schedule: Schedule,
+    pub limit: Limit,
+    pub coalesce: Coalesce,
+    pub sweep: Sweep,
+    pub quality: Quality,
+    pub power: Power,
+    pub overlay: Overlay,
+    pub publish: Publish,
+    pub remote: Remote,
+    pub ipc: Ipc,
+    pub zones: Vec<Zone>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ui {
+    /// Code of a translation in `locales/`, e.g. `"de"`. Unset follows the
+    /// Windows display language.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+    /// Icon and menu in the notification area. Without it the taskbar entry
+    /// is shown, closing it quits.
+    pub tray: bool,
+    /// A taskbar entry whose close button quits, for shells without a tray.
+    pub taskbar_entry: bool,
+    /// A short confirmation on screen when a setting is changed from the tray.
+    pub toasts: bool,
+    /// Tray icon color, by default the one that stands out on the taskbar.
+    pub tray_icon: crate::tray::IconShade,
+}
+
+impl Default for Ui {
+    fn default() -> Self {
+        Self {
+            language: None,
+            tray: true,
+            taskbar_entry: false,
+            toasts: true,
+            tray_icon: crate::tray::IconShade::Auto,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Input {
+    /// Holding Alt arms pings for at most this long without a key repeat,
+    /// in case its release was never seen.
+    pub prime_expiry_secs: f32,
+    /// Drop the ping when the window under the cursor moved between press and
+    /// release, i.e. the click was really an Alt+drag window move.
+    pub inhibit_window_drag: bool,
+    /// Key held to arm pings.
+    pub prime_key: rdev::Key,
+    /// Tapping the ping key twice, without any other key or click, pings at
+    /// the cursor.
+    pub double_tap: bool,
+    /// Longest time between the two taps.
+    pub double_tap_ms: u64,
+    /// After a ping, taps a key no app uses before the ping key is let go, so
+    /// a bare Alt release doesn't open the foreground app's menu bar. Off by
+    /// default, anti-cheat software may take injected input badly.
+    pub mask_modifier: bool,
+    /// Pressed while the ping key is held, opens the note of the marker under
+    /// the cursor, or of the newest one.
+    pub note_key: rdev::Key,
+    /// Pressed while the ping key and Ctrl are held, pings at the text caret
+    /// instead of the mouse.
+    pub caret_key: rdev::Key,
+    /// Whether pings land where the click was pressed or released.
+    pub ping_at: crate::click::PingAt,
+    /// A click released later than this is cancelled, its release was
+    /// probably lost to another window.
+    pub release_timeout_ms: u64,
+}
+
+impl Default for Input {
+    fn default() -> Self {
+        Self {
+            prime_expiry_secs: 3.0,
+            inhibit_window_drag: false,
+            prime_key: rdev::Key::Alt,
+            double_tap: false,
+            double_tap_ms: 350,
+            mask_modifier: false,
+            note_key: rdev::Key::KeyN,
+            caret_key: rdev::Key::SemiColon,
+            ping_at: crate::click::PingAt::OnPress,
+            release_timeout_ms: 5000,
+        }
+    }
+}
+
+/// Picking the ping kind by flicking the mouse right after the click.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Flick {
+    pub enabled: bool,
+    /// How long after the press the movement counts.
+    pub window_ms: u64,
+    /// Slower movement, in pixels per second, is a drag or a shaky click.
+    pub min_speed: f32,
+    /// Flicks further off straight up, down, left or right than this many
+    /// degrees fall back to the default kind.
+    pub max_angle: f32,
+    pub up: crate::protocol::PingKind,
+    pub down: crate::protocol::PingKind,
+    pub left: crate::protocol::PingKind,
+    pub right: crate::protocol::PingKind,
+}
+
+impl Flick {
+    pub fn kind(&self, direction: crate::flick::Direction) -> &crate::protocol::PingKind {
+        match direction {
+            crate::flick::Direction::Up => &self.up,
+            crate::flick::Direction::Down => &self.down,
+            crate::flick::Direction::Left => &self.left,
+            crate::flick::Direction::Right => &self.right,
+        }
+    }
+}
+
+/// A ping with an arrow toward where the right button was let go, from a
+/// primed right drag.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Arrow {
+    pub enabled: bool,
+    /// Shorter drags, in pixels, ping without an arrow.
+    pub min_distance: f32,
+    /// RGBA.
+    pub color: [u8; 4],
+    pub thickness: f32,
+}
+
+impl Default for Arrow {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min_distance: 60.0,
+            color: [255, 215, 90, 230],
+            thickness: 4.0,
+        }
+    }
+}
+
+impl Default for Flick {
+    fn default() -> Self {
+        use crate::protocol::PingKind;
+        Self {
+            enabled: false,
+            window_ms: 120,
+            min_speed: 1500.0,
+            max_angle: 25.0,
+            up: PingKind::Danger,
+            down: PingKind::Assist,
+            left: PingKind::Missing,
+            right: PingKind::OnMyWay,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Marker {
+    pub style: crate::marker::Style,
+    /// RGBA, unmultiplied. Only used by procedural styles.
+    pub color: [u8; 4],
+    /// Width of procedural markers in pixels.
+    pub size: f32,
+    /// Overrides the theme's anchors when set.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<crate::layout::Anchor>,
+    /// Pixels a ping may be randomly moved by, for a hand-drawn feel.
+    pub jitter: f32,
+    /// What pings move with while they're shown. Markers stay put.
+    pub follow: crate::follow::Follow,
+}
+
+impl Default for Marker {
+    fn default() -> Self {
+        Self {
+            style: crate::marker::Style::Gif,
+            color: [255, 215, 90, 255],
+            size: 200.0,
+            anchor: None,
+            jitter: 0.0,
+            follow: crate::follow::Follow::Off,
+        }
+    }
+}
+
+/// How long pings may be shown for.
+pub const DURATION_RANGE_MS: RangeInclusive<u32> = 100..=10_000;
+
+/// Display settings of one ping kind, from the config or a theme manifest.
+/// Unset values fall through to the next layer.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct KindStyle {
+    /// Width and height of the artwork in pixels.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anchor: Option<crate::layout::Anchor>,
+}
+
+/// A [`KindStyle`] with every layer applied.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ResolvedStyle {
+    pub size: f32,
+    pub duration: Duration,
+    pub anchor: crate::layout::Anchor,
+}
+
+impl Config {
+    /// Style of `kind`, each value taken from the first layer setting it: the
+    /// user's `[kinds]` entry, the user's `[marker]` section, the theme's
+    /// manifest entry `pack`, then `defaults`.
+    pub fn kind_style(
+        &self,
+        kind: &crate::protocol::PingKind,
+        pack: Option<&KindStyle>,
+        defaults: ResolvedStyle,
+    ) -> ResolvedStyle {
+        let marker = KindStyle {
+            anchor: self.marker.anchor,
+            ..KindStyle::default()
+        };
+        let layers = [self.kinds.get(kind.as_str()), Some(&marker), pack];
+        let layers = || layers.iter().flatten();
+
+        let duration = layers().find_map(|layer| layer.duration_ms).map(|ms| {
+            let ms = ms.clamp(*DURATION_RANGE_MS.start(), *DURATION_RANGE_MS.end());
+            Duration::from_millis(ms as u64)
+        });
+        ResolvedStyle {
+            size: layers()
+                .find_map(|layer| layer.size)
+                .unwrap_or(defaults.size),
+            duration: duration.unwrap_or(defaults.duration),
+            anchor: layers()
+                .find_map(|layer| layer.anchor)
+                .unwrap_or(defaults.anchor),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Restrict {
+    /// Pings fired on other monitors are rejected.
+    pub monitor: crate::layout::Restriction,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Theme {
+    /// `.pingpack` to load at startup, the built-in theme when unset.
+    pub path: Option<std::path::PathBuf>,
+    /// Pack frames wider or taller than this many pixels are shrunk at load.
+    pub max_frame_size: u32,
+    pub downscale_filter: crate::theme::Filter,
+    /// Packs whose frames take more than this decoded, after shrinking, are refused.
+    pub memory_budget_mb: u32,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            path: None,
+            max_frame_size: 1024,
+            downscale_filter: crate::theme::Filter::Triangle,
+            memory_budget_mb: 256,
+        }
+    }
+}
+
+/// Arc around a ping showing how much of its lifetime is left.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ExpiryRing {
+    pub enabled: bool,
+    /// RGBA, unmultiplied.
+    pub color: [u8; 4],
+    pub radius: f32,
+    pub thickness: f32,
+}
+
+impl Default for ExpiryRing {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            color: [255, 215, 90, 220],
+            radius: 130.0,
+            thickness: 3.0,
+        }
+    }
+}
+
+/// Dims and shrinks older pings while newer ones are on screen. The newest
+/// is always drawn as is, and markers aren't ranked.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Emphasis {
+    pub enabled: bool,
+    /// Opacity multiplier of the ping one older than the newest, two older
+    /// and so on. Older ones than listed use the last.
+    pub alpha: Vec<f32>,
+    /// Size multipliers, the same way.
+    pub scale: Vec<f32>,
+}
+
+impl Default for Emphasis {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            alpha: vec![0.6, 0.4, 0.25],
+            scale: vec![1.0],
+        }
+    }
+}
+
+impl Emphasis {
+    /// Opacity and size multipliers of the ping `rank` places older than the
+    /// newest.
+    pub fn factors(&self, rank: usize) -> (f32, f32) {
+        let pick = |factors: &[f32]| match rank {
+            0 => 1.0,
+            _ => factors
+                .get(rank - 1)
+                .or(factors.last())
+                .map_or(1.0, |factor| factor.clamp(0.0, 1.0)),
+        };
+        (pick(&self.alpha), pick(&self.scale))
+    }
+}
+
+/// Samples the screen under a ping and draws a halo behind it on bright backgrounds.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Backdrop {
+    pub enabled: bool,
+    /// Average luminance (0-1) above which the backdrop counts as light.
+    pub threshold: f32,
+    /// RGBA, unmultiplied.
+    pub halo_color: [u8; 4],
+    pub halo_radius: f32,
+}
+
+impl Default for Backdrop {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 0.6,
+            halo_color: [20, 20, 20, 150],
+            halo_radius: 110.0,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sound {
+    /// Skip the ping sound while Focus Assist, presentation mode or a
+    /// full-screen app holds back notifications.
+    pub mute_during_focus_assist: bool,
+    /// 0 to 1.
+    pub volume: f32,
+    /// Output device name, the system default when unset or missing.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    /// Beep through the system when no output device can be opened at all,
+    /// rather than staying silent.
+    pub beep_fallback: bool,
+    /// Played instead of the theme's sound.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ping: Option<SoundSpec>,
+    /// Monitor, 1-based or by the name shown in the tray, to the sound played
+    /// for pings on it. Monitors without an entry play the theme's sound.
+    pub monitors: std::collections::BTreeMap<String, MonitorSound>,
+    /// Clicks played as the ping key arms and disarms pings.
+    pub prime: PrimeCues,
+}
+
+impl Default for Sound {
+    fn default() -> Self {
+        Self {
+            mute_during_focus_assist: true,
+            volume: 1.0,
+            device: None,
+            beep_fallback: false,
+            ping: None,
+            monitors: Default::default(),
+            prime: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct PrimeCues {
+    pub on_prime: bool,
+    pub on_unprime: bool,
+    /// 0 to 1, relative to [`Sound::volume`].
+    pub volume: f32,
+}
+
+impl Default for PrimeCues {
+    fn default() -> Self {
+        Self {
+            on_prime: false,
+            on_unprime: false,
+            volume: 0.3,
+        }
+    }
+}
+
+/// A sound file, or several played together such as a whoosh and an impact
+/// right after it. A plain path is a single layer.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum SoundSpec {
+    File(std::path::PathBuf),
+    Layers(Vec<SoundLayer>),
+}
+
+impl SoundSpec {
+    pub fn layers(&self) -> Vec<SoundLayer> {
+        match self {
+            SoundSpec::File(file) => vec![SoundLayer {
+                file: file.clone(),
+                delay_ms: 0,
+                volume: 1.0,
+            }],
+            SoundSpec::Layers(layers) => layers.clone(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SoundLayer {
+    pub file: std::path::PathBuf,
+    /// Wait after the ping before this layer starts.
+    #[serde(default)]
+    pub delay_ms: u64,
+    /// 0 to 1, on top of the master volume.
+    #[serde(default = "full_volume")]
+    pub volume: f32,
+}
+
+fn full_volume() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MonitorSound {
+    /// Played instead of the theme's sound.
+    pub path: Option<SoundSpec>,
+    /// Pitch shift, applied by speeding up or slowing down playback.
+    pub semitones: f32,
+    /// Output device for pings on this monitor, matched like `sound.device`.
+    /// The main output when unset or missing.
+    pub device: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Taskbar {
+    pub avoid: crate::taskbar::Avoidance,
+    /// Distance kept between the ping center and the taskbar, in pixels.
+    pub margin: i32,
+}
+
+impl Default for Taskbar {
+    fn default() -> Self {
+        Self {
+            avoid: crate::taskbar::Avoidance::Off,
+            margin: 130,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Overlay {
+    pub mode: crate::overlay::Mode,
+    pub renderer: crate::overlay::Backend,
+    /// Window classes or executable names, e.g. `"Magnify.exe"`, that stay
+    /// above the overlay while they're open.
+    pub above: Vec<String>,
+}
+
+/// Ctrl + Alt + click pings and copies the clicked position to the clipboard.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CopyCoordinates {
+    pub enabled: bool,
+    /// Supports `{x}`, `{y}`, `{monitor}` (1-based), `{w}` and `{h}` (monitor size).
+    pub template: String,
+}
+
+impl Default for CopyCoordinates {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            template: "{x}, {y} (monitor {monitor}, {w}x{h})".into(),
+        }
+    }
+}
+
+/// Numbers consecutive pings 1, 2, 3…
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sequence {
+    pub enabled: bool,
+    /// Numbering starts over after this long without a ping.
+    pub idle_gap_secs: f32,
+    /// How long the number stays after the ping artwork is gone.
+    pub linger_secs: f32,
+}
+
+impl Default for Sequence {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            idle_gap_secs: 3.0,
+            linger_secs: 1.0,
+        }
+    }
+}
+
+/// Days and local hours pings are on, they pause outside them.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Schedule {
+    pub enabled: bool,
+    pub days: Vec<crate::schedule::Weekday>,
+    pub start: crate::schedule::ClockTime,
+    /// Before `start` for hours running past midnight.
+    pub end: crate::schedule::ClockTime,
+}
+
+impl Default for Schedule {
+    fn default() -> Self {
+        use crate::schedule::{ClockTime, Weekday};
+
+        Self {
+            enabled: false,
+            days: vec![
+                Weekday::Mon,
+                Weekday::Tue,
+                Weekday::Wed,
+                Weekday::Thu,
+                Weekday::Fri,
+            ],
+            start: ClockTime(9 * 60),
+            end: ClockTime(18 * 60),
+        }
+    }
+}
+
+/// Caps how fast pings can be fired, see [`crate::limiter`].
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Limit {
+    pub enabled: bool,
+    /// Pings that can be fired in a row.
+    pub burst: u32,
+    /// Time to regain one.
+    pub refill_secs: f32,
+    /// RGBA of the cooldown ring.
+    pub color: [u8; 4],
+    pub radius: f32,
+}
+
+impl Default for Limit {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            burst: 5,
+            refill_secs: 2.0,
+            color: [230, 80, 80, 220],
+            radius: 22.0,
+        }
+    }
+}
+
+/// Leaves a fading dot where each ping ended.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Trail {
+    pub enabled: bool,
+    /// How long a dot takes to fade out.
+    pub fade_secs: f32,
+    /// Older dots are dropped past this many.
+    pub max_dots: usize,
+    /// RGBA.
+    pub color: [u8; 4],
+    pub radius: f32,
+}
+
+impl Trail {
+    /// [`Self::fade_secs`], 10 seconds when it isn't a valid duration.
+    pub fn fade(&self) -> Duration {
+        Duration::try_from_secs_f32(self.fade_secs).unwrap_or(Duration::from_secs(10))
+    }
+}
+
+impl Default for Trail {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            fade_secs: 10.0,
+            max_dots: 20,
+            color: [255, 215, 90, 200],
+            radius: 6.0,
+        }
+    }
+}
+
+/// A small ring on every click, not just pings, e.g. for screen recordings.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ripples {
+    pub enabled: bool,
+    /// How long a ring takes to open and fade.
+    pub duration_ms: u64,
+    /// Older rings are dropped past this many.
+    pub max: usize,
+    /// RGBA.
+    pub color: [u8; 4],
+    pub radius: f32,
+}
+
+impl Ripples {
+    pub fn duration(&self) -> Duration {
+        Duration::from_millis(self.duration_ms)
+    }
+}
+
+impl Default for Ripples {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            duration_ms: 300,
+            max: 32,
+            color: [255, 255, 255, 160],
+            radius: 18.0,
+        }
+    }
+}
+
+/// Merges bursts of pings at nearly the same place into one with a count.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Coalesce {
+    /// A ping within this long of the last one nearby is merged into it, 0 turns merging off.
+    pub window_ms: u64,
+    /// How close, in pixels, counts as nearby.
+    pub radius: f32,
+    /// Ping sounds started within a second at most, local ones always play.
+    pub max_sounds_per_sec: u32,
+    /// Share of `max_sounds_per_sec` scripted and remote pings can't use.
+    pub reserved_local_sounds: u32,
+}
+
+impl Default for Coalesce {
+    fn default() -> Self {
+        Self {
+            window_ms: 300,
+            radius: 40.0,
+            max_sounds_per_sec: 8,
+            reserved_local_sounds: 3,
+        }
+    }
+}
+
+/// Safety net for animations that never got their final frame.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Sweep {
+    pub interval_secs: f32,
+    /// How far past its end an animation may run before it's removed.
+    pub grace_secs: f32,
+}
+
+impl Default for Sweep {
+    fn default() -> Self {
+        Self {
+            interval_secs: 10.0,
+            grace_secs: 5.0,
+        }
+    }
+}
+
+/// When to drop to every other animation frame because redraws are too slow.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Quality {
+    pub adaptive: bool,
+    /// Redraws slower than this count as falling behind.
+    pub slow_frame_ms: f32,
+    pub degrade_after_frames: u32,
+    pub restore_after_frames: u32,
+}
+
+impl Default for Quality {
+    fn default() -> Self {
+        Self {
+            adaptive: true,
+            slow_frame_ms: 16.7,
+            degrade_after_frames: 30,
+            restore_after_frames: 120,
+        }
+    }
+}
+
+/// When to save power, see [`crate::power`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PowerMode {
+    /// Follow the power status.
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+/// Cutting down on battery or with Windows battery saver on.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Power {
+    pub mode: PowerMode,
+    /// Save power while running on battery.
+    pub on_battery: bool,
+    /// On battery, only once the charge drops under this percentage. 101
+    /// saves at any charge.
+    pub below_percent: u8,
+    /// Save power while Windows battery saver is on, plugged in or not.
+    pub battery_saver: bool,
+    /// Drawn instead of artwork while saving, `"gif"` keeps the artwork.
+    pub marker: crate::marker::Style,
+    pub poll_secs: u64,
+}
+
+impl Default for Power {
+    fn default() -> Self {
+        Self {
+            mode: PowerMode::Auto,
+            on_battery: true,
+            below_percent: 101,
+            battery_saver: true,
+            marker: crate::marker::Style::Ring,
+            poll_secs: 30,
+        }
+    }
+}
+
+/// Webhook every ping is posted to. Needs the `webhook` build feature.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Publish {
+    pub enabled: bool,
+    /// Supports `{monitor}` (1-based, `none` off every monitor) and `{kind}`.
+    pub url: String,
+}
+
+/// A named region of one monitor.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Zone {
+    pub name: String,
+    /// 1-based as listed in the tray, or the monitor's name shown there.
+    pub monitor: crate::layout::MonitorRef,
+    /// `[x, y, width, height]`, 0-1 relative to the monitor.
+    pub rect: [f32; 4],
+    /// Played instead of the usual sound.
+    #[serde(default)]
+    pub sound: Option<SoundSpec>,
+    /// Run through `cmd /C` when a ping lands in the zone.
+    #[serde(default)]
+    pub command: Option<String>,
+    #[serde(default = "enabled")]
+    pub enabled: bool,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+/// How pings from other machines or processes are told apart from local ones.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Remote {
+    /// RGBA, unmultiplied. Each peer gets one of these based on its name.
+    pub palette: Vec<[u8; 4]>,
+    pub ring_radius: f32,
+    pub ring_thickness: f32,
+    /// Play the ping sound for remote and IPC pings too.
+    pub sound: bool,
+}
+
+impl Default for Remote {
+    fn default() -> Self {
+        Self {
+            palette: vec![
+                [90, 200, 250, 230],
+                [255, 120, 120, 230],
+                [140, 230, 120, 230],
+                [200, 140, 255, 230],
+                [255, 170, 60, 230],
+                [255, 240, 110, 230],
+            ],
+            ring_radius: 150.0,
+            ring_thickness: 4.0,
+            sound: true,
+        }
+    }
+}
+
+/// The control pipe, see [`crate::ipc`].
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Ipc {
+    /// Full pipe name, e.g. `\\.\pipe\my-pinger`, instead of the one
+    /// derived from the user, session and instance.
+    pub pipe: Option<String>,
+}
+
+/// Why a config file didn't parse, and where.
+#[derive(Debug)]
+pub struct ParseError {
+    /// 1-based.
+    pub line: usize,
+    /// 1-based, in characters.
+    pub column: usize,
+    pub message: String,
+}
+
+impl std::fmt::Display for ParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "line {}, column {}: {}",
+            self.line, self.column, self.message
+        )
+    }
+}
+
+impl Config {
+    /// Parses the contents of a config file, defaults filling in whatever
+    /// it leaves out.
+    pub fn parse(text: &str) -> Result<Self, ParseError> {
+        toml::from_str(text).map_err(|e: toml::de::Error| {
+            let offset = e.span().map_or(0, |span| span.start);
+            let before = &text[..offset];
+            let line_start = before.rfind('\n').map_or(0, |at| at + 1);
+            ParseError {
+                line: before.matches('\n').count() + 1,
+                column: before[line_start..].chars().count() + 1,
+                message: e.message().trim().to_owned(),
+            }
+        })
+    }
+
+    /// Reads the config file, defaults when there's none.
+    pub fn try_load() -> Result<Self, ParseError> {
+        match std::fs::read_to_string(crate::storage::config_path()) {
+            Ok(text) => Self::parse(&text),
+            Err(_) => Ok(Self::default()),
+        }
+    }
+
+    /// Reads the config file, falling back to defaults when it is missing or invalid.
+    pub fn load() -> Self {
+        Self::try_load().unwrap_or_else(|e| {
+            let path = crate::storage::config_path();
+            log::warn!("invalid config {}, using defaults: {e}", path.display());
+            Self::default()
+        })
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let contents = toml::to_string_pretty(self).expect("config is always serializable");
+        std::fs::write(crate::storage::config_path(), contents)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::Anchor;
+    use crate::protocol::PingKind;
+
+    const DEFAULTS: ResolvedStyle = ResolvedStyle {
+        size: 200.0,
+        duration: Duration::from_millis(1000),
+        anchor: Anchor::Center,
+    };
+
+    fn pack() -> KindStyle {
+        KindStyle {
+            size: Some(120.0),
+            duration_ms: Some(1500),
+            anchor: Some(Anchor::Below),
+        }
+    }
+
+    fn parse(text: &str) -> Config {
+        Config::parse(text).unwrap()
+    }
+
+    #[test]
+    fn built_in_defaults_apply_when_nothing_is_set() {
+        let style = Config::default().kind_style(&PingKind::Danger, None, DEFAULTS);
+        assert_eq!(style, DEFAULTS);
+    }
+
+    #[test]
+    fn the_pack_manifest_overrides_the_defaults() {
+        let style = Config::default().kind_style(&PingKind::Danger, Some(&pack()), DEFAULTS);
+        assert_eq!(
+            style,
+            ResolvedStyle {
+                size: 120.0,
+                duration: Duration::from_millis(1500),
+                anchor: Anchor::Below,
+            }
+        );
+    }
+
+    #[test]
+    fn the_user_config_overrides_only_what_it_sets() {
+        let config = parse("[kinds.danger]\nsize = 300.0\n");
+        let style = config.kind_style(&PingKind::Danger, Some(&pack()), DEFAULTS);
+        assert_eq!(style.size, 300.0);
+        // NOTE: left to the pack, the user didn't set them
+        assert_eq!(style.duration, Duration::from_millis(1500));
+        assert_eq!(style.anchor, Anchor::Below);
+    }
+
+    #[test]
+    fn marker_anchor_sits_between_kinds_and_the_pack() {
+        let config = parse("[marker]\nanchor = \"above\"\n");
+        let style = config.kind_style(&PingKind::Danger, Some(&pack()), DEFAULTS);
+        assert_eq!(style.anchor, Anchor::Above);
+
+        let config = parse("[marker]\nanchor = \"above\"\n[kinds.danger]\nanchor = \"center\"\n");
+        let style = config.kind_style(&PingKind::Danger, Some(&pack()), DEFAULTS);
+        assert_eq!(style.anchor, Anchor::Center);
+    }
+
+    #[test]
+    fn kinds_entries_only_apply_to_their_kind() {
+        let config = parse("[kinds.danger]\nduration_ms = 4000\n[kinds.laser]\nsize = 50.0\n");
+        let assist = config.kind_style(&PingKind::Assist, None, DEFAULTS);
+        assert_eq!(assist, DEFAULTS);
+        let laser = config.kind_style(&PingKind::Other("laser".to_owned()), None, DEFAULTS);
+        assert_eq!(laser.size, 50.0);
+        assert_eq!(laser.duration, DEFAULTS.duration);
+    }
+
+    #[test]
+    fn durations_are_clamped_from_any_layer() {
+        let config = parse("[kinds.danger]\nduration_ms = 60000\n");
+        let style = config.kind_style(&PingKind::Danger, None, DEFAULTS);
+        assert_eq!(style.duration, Duration::from_millis(10_000));
+
+        let pack = KindStyle {
+            duration_ms: Some(1),
+            ..KindStyle::default()
+        };
+        let style = Config::default().kind_style(&PingKind::Danger, Some(&pack), DEFAULTS);
+        assert_eq!(style.duration, Duration::from_millis(100));
+    }
+}
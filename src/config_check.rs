@@ -0,0 +1,265 @@
+//! What a config comes to once parsed: every setting with defaults and the
+//! theme's manifest layered in, and whatever in it the app would ignore or
+//! trip over. Backs `--check-config` and the tray's "Reload config".
+
+use crate::config::{Config, ResolvedStyle};
+use crate::protocol::PingKind;
+use crate::theme::Theme;
+use crate::{easing, keybindings, storage};
+use std::path::{Path, PathBuf};
+
+/// A value the app won't use as written, by its dotted config key.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub key: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.key, self.message)
+    }
+}
+
+pub struct ResolvedConfig {
+    pub config: Config,
+    pub theme: String,
+    /// Every registered kind, then those only the config or theme names.
+    pub kinds: Vec<(PingKind, ResolvedStyle)>,
+    pub problems: Vec<Problem>,
+}
+
+/// Layers `config` over `theme`, the configured pack as loaded or why it
+/// didn't load, in which case the built-in theme stands in as in the app.
+pub fn resolve(config: Config, theme: Result<Theme, String>) -> ResolvedConfig {
+    let mut problems = problems(&config);
+    let theme = theme.unwrap_or_else(|reason| {
+        problems.push(Problem {
+            key: "theme.path".into(),
+            message: reason,
+        });
+        Theme::builtin()
+    });
+    let mut kinds: Vec<PingKind> = PingKind::REGISTERED.into();
+    let named = config.kinds.keys().map(|kind| PingKind::from(kind.clone()));
+    for kind in named.chain(theme.kinds().cloned()) {
+        if !kinds.contains(&kind) {
+            kinds.push(kind);
+        }
+    }
+    let kinds = kinds
+        .into_iter()
+        .map(|kind| {
+            let style = crate::kind_style(&config, &theme, &kind);
+            (kind, style)
+        })
+        .collect();
+    ResolvedConfig {
+        theme: theme.name.clone(),
+        config,
+        kinds,
+        problems,
+    }
+}
+
+/// Values that parse but that the app replaces or can't act on.
+pub fn problems(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    let mut problem = |key: String, message: String| problems.push(Problem { key, message });
+
+    if let Err(conflict) = keybindings::Bindings::new(config) {
+        problem("input".into(), conflict.to_string());
+    }
+    for (kind, name) in &config.easing {
+        if easing::Curve::parse(name).is_none() {
+            problem(
+                format!("easing.{kind}"),
+                format!("unknown curve {name:?}, linear is used"),
+            );
+        }
+    }
+    if config.schedule.enabled && config.schedule.days.is_empty() {
+        problem(
+            "schedule.days".into(),
+            "no days listed, pings stay paused".into(),
+        );
+    }
+    if config.limit.enabled && config.limit.burst == 0 {
+        problem(
+            "limit.burst".into(),
+            "0 lets no ping through while the limit is on".into(),
+        );
+    }
+    for (i, zone) in config.zones.iter().enumerate() {
+        let [x, y, width, height] = zone.rect;
+        let inside = [x, y, width, height]
+            .iter()
+            .all(|v| (0.0..=1.0).contains(v))
+            && width > 0.0
+            && height > 0.0
+            && x + width <= 1.0
+            && y + height <= 1.0;
+        if !inside {
+            problem(
+                format!("zones[{i}].rect"),
+                format!("{:?} is not inside the monitor", zone.name),
+            );
+        }
+    }
+    problems
+}
+
+/// The file given with `--check-config`, if any.
+pub fn from_args() -> Option<Result<PathBuf, String>> {
+    let args: Vec<String> = std::env::args().collect();
+    let at = args.iter().position(|arg| arg == "--check-config")?;
+    Some(
+        args.get(at + 1)
+            .map(PathBuf::from)
+            .ok_or_else(|| "usage: --check-config <config.toml>".into()),
+    )
+}
+
+/// Checks the config at `path` and prints what it resolves to. Fails on a
+/// parse error or any problem.
+pub fn run(path: &Path) -> Result<(), String> {
+    let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {e}", path.display()))?;
+    let resolved =
+        check(&text, storage::resolve).map_err(|e| format!("{}: {e}", path.display()))?;
+    print!("{}", report(&resolved));
+    match resolved.problems.len() {
+        0 => Ok(()),
+        count => Err(format!("{count} problem(s) in {}", path.display())),
+    }
+}
+
+/// Parses and resolves a config, with `pack_path` finding the theme pack
+/// it names. `Err` only when it doesn't parse.
+fn check(text: &str, pack_path: impl Fn(&Path) -> PathBuf) -> Result<ResolvedConfig, String> {
+    let config = Config::parse(text).map_err(|e| e.to_string())?;
+    let theme = match &config.theme.path {
+        Some(pack) => Theme::load_pack(&pack_path(pack), &config.theme)
+            .map_err(|e| format!("{}: {e}", pack.display())),
+        None => Ok(Theme::builtin()),
+    };
+    Ok(resolve(config, theme))
+}
+
+/// Every setting as TOML, the per-kind styles and the problems as comments.
+fn report(resolved: &ResolvedConfig) -> String {
+    let mut report =
+        toml::to_string_pretty(&resolved.config).expect("config is always serializable");
+    report.push_str(&format!("\n# theme: {}\n", resolved.theme));
+    for (kind, style) in &resolved.kinds {
+        report.push_str(&format!(
+            "# {}: size {}, {} ms, anchor {:?}\n",
+            kind.as_str(),
+            style.size,
+            style.duration.as_millis(),
+            style.anchor
+        ));
+    }
+    for problem in &resolved.problems {
+        report.push_str(&format!("# problem: {problem}\n"));
+    }
+    report
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks `tests/fixtures/config/<name>.toml`, with packs beside it.
+    fn fixture(name: &str) -> Result<ResolvedConfig, String> {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/config");
+        let text = std::fs::read_to_string(dir.join(format!("{name}.toml"))).unwrap();
+        check(&text, |pack| dir.join(pack))
+    }
+
+    /// The keys of the problems `name` has.
+    fn problem_keys(name: &str) -> Vec<String> {
+        fixture(name)
+            .unwrap()
+            .problems
+            .into_iter()
+            .map(|problem| problem.key)
+            .collect()
+    }
+
+    #[test]
+    fn valid_config_resolves_without_problems() {
+        let resolved = fixture("valid").unwrap();
+        assert!(resolved.problems.is_empty(), "{:?}", resolved.problems);
+        assert_eq!(resolved.config.sound.volume, 0.5);
+        assert!(resolved.config.input.double_tap);
+        // NOTE: unset keys keep their defaults
+        assert_eq!(resolved.config.input.double_tap_ms, 350);
+        let (_, danger) = resolved
+            .kinds
+            .iter()
+            .find(|(kind, _)| *kind == PingKind::Danger)
+            .unwrap();
+        assert_eq!(danger.size, 320.0);
+        assert!(report(&resolved).contains("volume = 0.5"));
+    }
+
+    #[test]
+    fn every_registered_kind_is_resolved() {
+        let resolved = fixture("valid").unwrap();
+        for kind in PingKind::REGISTERED {
+            assert!(resolved.kinds.iter().any(|(resolved, _)| *resolved == kind));
+        }
+    }
+
+    #[test]
+    fn syntax_error_names_its_line() {
+        let error = fixture("syntax-error").err().unwrap();
+        assert!(error.starts_with("line 3, column "), "{error}");
+    }
+
+    #[test]
+    fn wrong_type_names_its_line() {
+        let error = fixture("wrong-type").err().unwrap();
+        assert!(error.starts_with("line 2, column "), "{error}");
+    }
+
+    #[test]
+    fn conflicting_keys_are_a_problem() {
+        assert_eq!(problem_keys("key-conflict"), ["input"]);
+    }
+
+    #[test]
+    fn unknown_curve_is_a_problem() {
+        assert_eq!(problem_keys("unknown-curve"), ["easing.danger"]);
+    }
+
+    #[test]
+    fn schedule_without_days_is_a_problem() {
+        assert_eq!(problem_keys("no-schedule-days"), ["schedule.days"]);
+    }
+
+    #[test]
+    fn zero_burst_is_a_problem() {
+        assert_eq!(problem_keys("zero-burst"), ["limit.burst"]);
+    }
+
+    #[test]
+    fn zone_outside_the_monitor_is_a_problem() {
+        assert_eq!(problem_keys("zone-outside"), ["zones[0].rect"]);
+    }
+
+    #[test]
+    fn pack_that_wont_load_is_a_problem() {
+        let resolved = fixture("missing-pack").unwrap();
+        assert_eq!(resolved.problems.len(), 1);
+        assert_eq!(resolved.problems[0].key, "theme.path");
+        // NOTE: the built-in theme stands in, as in the app
+        assert_eq!(resolved.theme, Theme::builtin().name);
+    }
+
+    #[test]
+    fn report_lists_the_problems() {
+        let resolved = fixture("zero-burst").unwrap();
+        assert!(report(&resolved).contains("# problem: limit.burst: "));
+    }
+}
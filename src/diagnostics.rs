@@ -0,0 +1,348 @@
+//! Recent ping history for bug reports, copied from the tray as JSON, and
+//! what the app runs on, for the About dialog.
+
+use serde::{Deserialize, Serialize};
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Instant;
+
+const CAPACITY: usize = 50;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Shown,
+    /// The animation queue was full.
+    Evicted,
+    /// Outside the monitor pings are restricted to.
+    Denied,
+    /// The gesture moved a window.
+    Dragged,
+    /// Counted on a ping just shown next to it.
+    Merged,
+    /// Fired faster than `[limit]` allows.
+    Limited,
+    /// A note was typed on the marker there.
+    Noted,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PingRecord {
+    /// Milliseconds since startup.
+    pub at_ms: u64,
+    /// Cursor position as read from device_query.
+    pub raw: (i32, i32),
+    /// Where the ping was placed, after taskbar avoidance.
+    pub placed: (i32, i32),
+    /// 1-based, `None` when off every known monitor.
+    pub monitor: Option<usize>,
+    pub persistent: bool,
+    pub outcome: Outcome,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// The last pings, kept for "Copy diagnostics". Recording only holds the lock
+/// for a push, so it's fine on the hook thread.
+pub struct Diagnostics {
+    started: Instant,
+    pings: Mutex<VecDeque<PingRecord>>,
+}
+
+impl Diagnostics {
+    pub fn new() -> Self {
+        Self {
+            started: Instant::now(),
+            pings: Mutex::new(VecDeque::with_capacity(CAPACITY)),
+        }
+    }
+
+    pub fn record(
+        &self,
+        raw: (i32, i32),
+        placed: (i32, i32),
+        monitor: Option<usize>,
+        persistent: bool,
+        outcome: Outcome,
+    ) {
+        let record = PingRecord {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            raw,
+            placed,
+            monitor,
+            persistent,
+            outcome,
+            note: None,
+        };
+        self.push(record);
+    }
+
+    /// Records `note` typed on the marker at `placed`, empty when it was removed.
+    pub fn record_note(&self, placed: (i32, i32), monitor: Option<usize>, note: &str) {
+        self.push(PingRecord {
+            at_ms: self.started.elapsed().as_millis() as u64,
+            raw: placed,
+            placed,
+            monitor,
+            persistent: true,
+            outcome: Outcome::Noted,
+            note: Some(note.to_owned()),
+        });
+    }
+
+    fn push(&self, record: PingRecord) {
+        let mut pings = self.pings.lock().unwrap();
+        if pings.len() == CAPACITY {
+            pings.pop_front();
+        }
+        pings.push_back(record);
+    }
+
+    pub fn uptime(&self) -> std::time::Duration {
+        self.started.elapsed()
+    }
+
+    pub fn pings(&self) -> Vec<PingRecord> {
+        self.pings.lock().unwrap().iter().cloned().collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Monitor {
+    /// As shown in the tray, e.g. "DELL U2720Q (3840x2160)".
+    pub name: String,
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+    pub scale_factor: f64,
+}
+
+/// Commit the app was built from, set by the build script.
+pub const GIT_HASH: &str = match option_env!("GIT_HASH") {
+    Some(hash) => hash,
+    None => "unknown",
+};
+
+/// How the overlay draws, captured when its device is set up.
+#[derive(Debug, Clone, Serialize)]
+pub struct Graphics {
+    /// wgpu backend, e.g. "Dx12".
+    pub backend: String,
+    pub adapter: String,
+    pub driver: String,
+    pub surface_format: String,
+    pub present_mode: String,
+}
+
+impl Graphics {
+    pub fn new(adapter: &wgpu::Adapter, surface: &wgpu::SurfaceConfiguration) -> Self {
+        let info = adapter.get_info();
+        Self {
+            backend: format!("{:?}", info.backend),
+            adapter: info.name,
+            driver: format!("{} {}", info.driver, info.driver_info)
+                .trim()
+                .to_owned(),
+            surface_format: format!("{:?}", surface.format),
+            present_mode: format!("{:?}", surface.present_mode),
+        }
+    }
+}
+
+/// The app and the system it runs on, as shown in the About dialog.
+#[derive(Debug, Clone, Serialize)]
+pub struct System {
+    pub version: &'static str,
+    pub git_hash: &'static str,
+    pub os: String,
+    /// `None` until the overlay is first built.
+    pub graphics: Option<Graphics>,
+    pub monitors: Vec<Monitor>,
+    pub audio: crate::audio::Backend,
+}
+
+impl System {
+    pub fn new(
+        graphics: Option<Graphics>,
+        monitors: Vec<Monitor>,
+        audio: crate::audio::Backend,
+    ) -> Self {
+        Self {
+            version: env!("CARGO_PKG_VERSION"),
+            git_hash: GIT_HASH,
+            os: crate::platform::os_version(),
+            graphics,
+            monitors,
+            audio,
+        }
+    }
+
+    /// Plain text, one `label: value` per line, for the clipboard.
+    pub fn text(&self) -> String {
+        let mut text = format!(
+            "Screen pinger {} ({})\nOS: {}\n",
+            self.version, self.git_hash, self.os
+        );
+        match &self.graphics {
+            Some(graphics) => {
+                text += &format!(
+                    "Graphics: {} on {} ({})\nSurface: {}, {}\n",
+                    graphics.backend,
+                    graphics.adapter,
+                    graphics.driver,
+                    graphics.surface_format,
+                    graphics.present_mode
+                )
+            }
+            None => text += "Graphics: not set up yet\n",
+        }
+        for (i, monitor) in self.monitors.iter().enumerate() {
+            text += &format!(
+                "Monitor {}: {} at ({}, {}), scale {}\n",
+                i + 1,
+                monitor.name,
+                monitor.x,
+                monitor.y,
+                monitor.scale_factor
+            );
+        }
+        text += &format!("Audio: {:?}\n", self.audio);
+        text
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ReportedPing<'a> {
+    #[serde(flatten)]
+    ping: &'a PingRecord,
+    /// `placed` in overlay window coordinates.
+    overlay: Option<(f32, f32)>,
+}
+
+#[derive(Debug, Serialize)]
+struct Report<'a> {
+    version: &'static str,
+    os: &'a str,
+    adapter: Option<&'a str>,
+    overlay_offset: Option<f32>,
+    monitors: &'a [Monitor],
+    audio: crate::audio::Backend,
+    pings: Vec<ReportedPing<'a>>,
+}
+
+/// Pretty JSON of `pings` and the system they happened on. `overlay_offset`
+/// is `None` while there is no overlay.
+pub fn report(
+    pings: &[PingRecord],
+    os: &str,
+    adapter: Option<&str>,
+    overlay_offset: Option<f32>,
+    monitors: &[Monitor],
+    audio: crate::audio::Backend,
+) -> String {
+    let report = Report {
+        version: env!("CARGO_PKG_VERSION"),
+        os,
+        adapter,
+        overlay_offset,
+        monitors,
+        audio,
+        pings: pings
+            .iter()
+            .map(|ping| ReportedPing {
+                ping,
+                overlay: overlay_offset
+                    .map(|offset| (ping.placed.0 as f32 + offset, ping.placed.1 as f32)),
+            })
+            .collect(),
+    };
+    serde_json::to_string_pretty(&report).expect("diagnostics are always serializable")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn monitors() -> Vec<Monitor> {
+        vec![Monitor {
+            name: "DELL U2720Q (3840x2160)".to_owned(),
+            x: -3840,
+            y: 0,
+            width: 3840,
+            height: 2160,
+            scale_factor: 1.5,
+        }]
+    }
+
+    fn pings() -> Vec<PingRecord> {
+        let diagnostics = Diagnostics::new();
+        diagnostics.record((-100, 50), (-100, 60), Some(1), false, Outcome::Shown);
+        diagnostics.record((9000, 9000), (-1, 2159), None, true, Outcome::Denied);
+        diagnostics.record_note((-100, 60), Some(1), "Look here");
+        diagnostics.pings()
+    }
+
+    #[test]
+    fn only_the_last_pings_are_kept() {
+        let diagnostics = Diagnostics::new();
+        for i in 0..CAPACITY as i32 + 5 {
+            diagnostics.record((i, 0), (i, 0), None, false, Outcome::Shown);
+        }
+        let pings = diagnostics.pings();
+        assert_eq!(pings.len(), CAPACITY);
+        assert_eq!(pings[0].raw, (5, 0));
+        assert_eq!(pings[CAPACITY - 1].raw, (CAPACITY as i32 + 4, 0));
+    }
+
+    #[test]
+    fn report_round_trips() {
+        let pings = pings();
+        let json = report(
+            &pings,
+            "Windows 11",
+            Some("GPU"),
+            Some(3840.0),
+            &monitors(),
+            crate::audio::Backend::Stream,
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(value["os"], "Windows 11");
+        assert_eq!(value["adapter"], "GPU");
+        assert_eq!(value["audio"], "stream");
+        let reported: Vec<PingRecord> = serde_json::from_value(value["pings"].clone()).unwrap();
+        assert_eq!(reported, pings);
+        let decoded: Vec<Monitor> = serde_json::from_value(value["monitors"].clone()).unwrap();
+        assert_eq!(decoded[0].name, "DELL U2720Q (3840x2160)");
+        assert_eq!(decoded[0].scale_factor, 1.5);
+    }
+
+    #[test]
+    fn report_places_pings_on_the_overlay() {
+        let json = report(
+            &pings(),
+            "",
+            None,
+            Some(3840.0),
+            &[],
+            crate::audio::Backend::Silent,
+        );
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value["pings"][0]["overlay"],
+            serde_json::json!([3740.0, 60.0])
+        );
+        assert_eq!(value["pings"][1]["outcome"], "denied");
+        assert_eq!(value["pings"][2]["note"], "Look here");
+        assert!(value["pings"][0].get("note").is_none());
+    }
+
+    #[test]
+    fn report_without_an_overlay() {
+        let json = report(&pings(), "", None, None, &[], crate::audio::Backend::Silent);
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert!(value["overlay_offset"].is_null());
+        assert!(value["adapter"].is_null());
+        assert!(value["pings"][0]["overlay"].is_null());
+    }
+}
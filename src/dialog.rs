@@ -0,0 +1,206 @@
+use crate::error::Error;
+use egui_wgpu::renderer::ScreenDescriptor;
+use egui_wgpu::Renderer;
+use std::collections::HashMap;
+use winit::event::WindowEvent;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowBuilder, WindowId};
+
+/// A small decorated egui window that takes regular input, unlike the overlay.
+/// Draws with the overlay's device and queue. Image loaders are only installed
+/// for dialogs that show ping frames, see [`Dialog::enable_images`].
+pub struct Dialog {
+    // NOTE: declared before the window, the surface must go first
+    surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    window: Window,
+    context: egui::Context,
+    state: egui_winit::State,
+    renderer: Renderer,
+    /// Bytes of each texture the renderer holds, to report what reuse saves.
+    textures: HashMap<egui::TextureId, u64>,
+}
+
+/// egui state kept between dialogs, so opening one doesn't rebuild and
+/// re-upload the font atlas.
+pub struct Spare {
+    context: egui::Context,
+    renderer: Renderer,
+    format: wgpu::TextureFormat,
+    textures: HashMap<egui::TextureId, u64>,
+}
+
+impl Dialog {
+    pub fn new<T>(
+        target: &EventLoopWindowTarget<T>,
+        instance: &wgpu::Instance,
+        adapter: &wgpu::Adapter,
+        device: &wgpu::Device,
+        title: &str,
+        size: (f64, f64),
+        spare: Option<Spare>,
+    ) -> Result<Self, Error> {
+        let window = WindowBuilder::new()
+            .with_title(title)
+            .with_inner_size(winit::dpi::LogicalSize::new(size.0, size.1))
+            .with_resizable(false)
+            .build(target)?;
+
+        let surface =
+            unsafe { instance.create_surface(&window) }.map_err(|e| Error::graphics(adapter, e))?;
+        let physical_size = window.inner_size();
+        let surface_config = surface
+            .get_default_config(adapter, physical_size.width, physical_size.height)
+            .ok_or_else(|| Error::graphics(adapter, "the dialog's surface isn't supported"))?;
+        surface.configure(device, &surface_config);
+
+        let mut state = egui_winit::State::new(target);
+        state.set_pixels_per_point(window.scale_factor() as f32);
+        let (context, renderer, textures) = match spare {
+            Some(spare) if spare.format == surface_config.format => {
+                log::info!(
+                    "dialog reuses {} KB of textures",
+                    spare.textures.values().sum::<u64>() / 1024
+                );
+                (spare.context, spare.renderer, spare.textures)
+            }
+            _ => (
+                egui::Context::default(),
+                Renderer::new(device, surface_config.format, None, 1),
+                HashMap::new(),
+            ),
+        };
+
+        Ok(Self {
+            window,
+            surface,
+            surface_config,
+            context,
+            state,
+            renderer,
+            textures,
+        })
+    }
+
+    /// Lets the dialog draw ping frames. Their textures are dropped again
+    /// when it closes.
+    pub fn enable_images(&self) {
+        egui_extras::install_image_loaders(&self.context);
+    }
+
+    /// Closes the window, keeping what the next dialog can reuse.
+    pub fn into_spare(self) -> Spare {
+        log::info!(
+            "dialog closed holding {} KB of textures",
+            self.textures.values().sum::<u64>() / 1024
+        );
+        self.context.forget_all_images();
+        Spare {
+            format: self.surface_config.format,
+            context: self.context,
+            renderer: self.renderer,
+            textures: self.textures,
+        }
+    }
+
+    /// Moves the window's top left corner to `position` on the desktop.
+    pub fn move_to(&self, position: winit::dpi::PhysicalPosition<i32>) {
+        self.window.set_outer_position(position);
+    }
+
+    pub fn id(&self) -> WindowId {
+        self.window.id()
+    }
+
+    /// Feeds a window event to egui. Returns `true` when the dialog was closed.
+    pub fn on_event(&mut self, device: &wgpu::Device, event: &WindowEvent) -> bool {
+        match event {
+            WindowEvent::CloseRequested => return true,
+            WindowEvent::Resized(size) if size.width > 0 && size.height > 0 => {
+                self.surface_config.width = size.width;
+                self.surface_config.height = size.height;
+                self.surface.configure(device, &self.surface_config);
+            }
+            WindowEvent::ScaleFactorChanged { scale_factor, .. } => {
+                self.state.set_pixels_per_point(*scale_factor as f32);
+            }
+            _ => {}
+        }
+
+        let response = self.state.on_event(&self.context, event);
+        if response.repaint {
+            self.window.request_redraw();
+        }
+        false
+    }
+
+    pub fn render(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        ui: impl FnOnce(&egui::Context),
+    ) {
+        let frame = match self.surface.get_current_texture() {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("dialog surface unavailable: {e}");
+                return;
+            }
+        };
+        let view = frame
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let input = self.state.take_egui_input(&self.window);
+        let output = self.context.run(input, ui);
+        self.state
+            .handle_platform_output(&self.window, &self.context, output.platform_output);
+        let paint_jobs = self.context.tessellate(output.shapes);
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [self.surface_config.width, self.surface_config.height],
+            pixels_per_point: self.context.pixels_per_point(),
+        };
+
+        let mut encoder =
+            device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        for (id, image_delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(device, queue, *id, image_delta);
+            // NOTE: partial updates patch a texture in place, only full ones size it
+            if image_delta.pos.is_none() {
+                let [width, height] = image_delta.image.size();
+                self.textures.insert(*id, width as u64 * height as u64 * 4);
+            }
+        }
+        self.renderer
+            .update_buffers(device, queue, &mut encoder, &paint_jobs, &screen_descriptor);
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.renderer
+                .render(&mut render_pass, &paint_jobs, &screen_descriptor);
+        }
+
+        queue.submit(Some(encoder.finish()));
+        frame.present();
+
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+            self.textures.remove(id);
+        }
+        if output.repaint_after.is_zero() {
+            self.window.request_redraw();
+        }
+    }
+}
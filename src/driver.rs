@@ -0,0 +1,464 @@
+//! The animation driver: takes queued pings, holds them for their backdrop
+//! sample, and tells the event loop when each one starts, is merged into
+//! and ends. The overlay derives the frames in between from each
+//! animation's clock. No thread, window or proxy in here, the caller owns
+//! those and hands events on.
+
+use crate::clock::Clock;
+use crate::{backdrop, Animation, CustomEvent, DriverCommand, Step, MERGE_REWIND};
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+
+pub struct Driver {
+    /// Animations taken off the queue and not finished yet.
+    playing: Vec<Animation>,
+    /// Ids merged into, applied once the queue is emptied.
+    merges: Vec<usize>,
+    sampler: Option<Arc<backdrop::Sampler>>,
+    /// Frames advanced per step, raised when redraws can't keep up.
+    stride: Arc<AtomicU8>,
+    clock: Arc<dyn Clock>,
+}
+
+impl Driver {
+    pub fn new(
+        sampler: Option<Arc<backdrop::Sampler>>,
+        stride: Arc<AtomicU8>,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
+        Self {
+            playing: Vec::new(),
+            merges: Vec::new(),
+            sampler,
+            stride,
+            clock,
+        }
+    }
+
+    /// Nothing playing or waiting to be merged, the thread can park.
+    pub fn is_idle(&self) -> bool {
+        self.playing.is_empty()
+    }
+
+    /// Handles an out of band command, with `incoming` the animations queued
+    /// since the last call. Returns `false` on [`DriverCommand::Shutdown`].
+    pub fn command(
+        &mut self,
+        command: DriverCommand,
+        incoming: impl IntoIterator<Item = Animation>,
+        emit: &mut impl FnMut(CustomEvent),
+    ) -> bool {
+        match command {
+            DriverCommand::Drain => {
+                self.playing.extend(incoming);
+                log::info!("draining {} animations", self.playing.len());
+                let now = self.clock.now();
+                for mut animation in self.playing.drain(..) {
+                    emit(animation.finish(now));
+                }
+            }
+            // NOTE: applied once the queue is emptied, the animation may not be popped yet
+            DriverCommand::Merge(id) => {
+                self.playing.extend(incoming);
+                self.merges.push(id);
+            }
+            DriverCommand::Shutdown => return false,
+        }
+        true
+    }
+
+    /// Takes `incoming` on, applies merges and moves every animation along.
+    pub fn tick(
+        &mut self,
+        incoming: impl IntoIterator<Item = Animation>,
+        emit: &mut impl FnMut(CustomEvent),
+    ) {
+        self.playing.extend(incoming);
+
+        for id in self.merges.drain(..) {
+            let Some(animation) = self.playing.iter_mut().find(|a| a.id == id) else {
+                continue;
+            };
+            let now = self.clock.now();
+            let frame = animation.frame_at(now);
+            animation.seek(frame - frame.min(MERGE_REWIND), now);
+            animation.count += 1;
+            emit(CustomEvent::Animate(animation.clone()));
+        }
+
+        // NOTE: the overlay derives frames from each animation's clock, it only
+        // hears of an animation when it's spawned, merged into or cleared
+        for animation in self.playing.iter_mut() {
+            let now = self.clock.now();
+            if animation.sampling {
+                if let Some(sampler) = &self.sampler {
+                    match sampler.take(animation.id) {
+                        Some(backdrop) => animation.backdrop = backdrop,
+                        // NOTE: hold the first frame until the sample lands or the budget runs out
+                        None if now.saturating_duration_since(animation.started)
+                            < backdrop::CAPTURE_BUDGET =>
+                        {
+                            continue
+                        }
+                        None => {}
+                    }
+                }
+                animation.sampling = false;
+                // NOTE: the clock starts once the ping is shown, not when it was fired
+                animation.seek(animation.frame, now);
+                animation.last_update = now;
+                emit(CustomEvent::Animate(animation.clone()));
+                continue;
+            }
+
+            let stride = self.stride.load(Ordering::Relaxed);
+            match animation.step(now, stride) {
+                Step::Wait => {}
+                Step::Advance => {
+                    animation.frame = animation.frame_at(now);
+                    animation.last_update = now;
+                    // NOTE: markers stay until `retain` drops them at their resting frame
+                    if !animation.persistent && animation.frame >= animation.last_frame() {
+                        emit(CustomEvent::Clear(animation.id));
+                    }
+                }
+                Step::Cancel => {
+                    let gap = now.saturating_duration_since(animation.last_update);
+                    log::info!("cancelling animation {} after a {gap:?} gap", animation.id);
+                    animation.last_update = now;
+                    emit(animation.finish(now));
+                }
+            }
+        }
+
+        self.playing
+            .retain(|animation| animation.frame < animation.last_frame());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation_builder::AnimationBuilder;
+    use crate::clock::ManualClock;
+    use crate::{ANIMATION_DURATION, FRAME_TIME, MARKER_FRAME, MAX_CATCH_UP};
+    use device_query::mouse_state::MousePosition;
+    use std::collections::{BTreeMap, HashMap, VecDeque};
+    use std::time::{Duration, Instant};
+
+    fn driver(clock: &Arc<ManualClock>) -> Driver {
+        Driver::new(None, Arc::new(AtomicU8::new(1)), clock.clone())
+    }
+
+    fn tick(driver: &mut Driver, incoming: Vec<Animation>) -> Vec<CustomEvent> {
+        let mut events = Vec::new();
+        driver.tick(incoming, &mut |event| events.push(event));
+        events
+    }
+
+    /// Starts `animation` on `driver`, past its sampling step.
+    fn start(driver: &mut Driver, clock: &ManualClock, persistent: bool) -> usize {
+        let animation = AnimationBuilder::at((10, 10))
+            .persistent(persistent)
+            .build_at(clock.now())
+            .animation;
+        let id = animation.id;
+        let events = tick(driver, vec![animation]);
+        assert!(matches!(events[..], [CustomEvent::Animate(ref a)] if a.id == id));
+        id
+    }
+
+    #[test]
+    fn pings_stalled_past_their_end_are_cleared_in_one_step() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let id = start(&mut driver, &clock, false);
+
+        clock.advance(Duration::from_secs(60));
+        let events = tick(&mut driver, Vec::new());
+        assert!(matches!(events[..], [CustomEvent::Clear(cleared)] if cleared == id));
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn pings_started_just_before_a_stall_are_cleared() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let id = start(&mut driver, &clock, false);
+        clock.advance(FRAME_TIME * 2);
+        assert!(tick(&mut driver, Vec::new()).is_empty());
+
+        // NOTE: well short of the ping's end, cancelled rather than played on
+        let stall = MAX_CATCH_UP + FRAME_TIME;
+        assert!(FRAME_TIME * 2 + stall < ANIMATION_DURATION);
+        clock.advance(stall);
+        let events = tick(&mut driver, Vec::new());
+        assert!(matches!(events[..], [CustomEvent::Clear(cleared)] if cleared == id));
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn stalled_markers_rest_without_replaying() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let id = start(&mut driver, &clock, true);
+
+        clock.advance(MAX_CATCH_UP + FRAME_TIME);
+        let events = tick(&mut driver, Vec::new());
+        assert!(matches!(
+            events[..],
+            [CustomEvent::Animate(ref a)] if a.id == id
+                && a.frame == MARKER_FRAME
+                && a.frame_at(clock.now()) == MARKER_FRAME
+        ));
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn short_gaps_advance_without_clearing() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        start(&mut driver, &clock, false);
+
+        clock.advance(MAX_CATCH_UP / 2);
+        assert!(tick(&mut driver, Vec::new()).is_empty());
+        assert!(!driver.is_idle());
+        assert!(driver.playing[0].frame > 0 && driver.playing[0].frame < MARKER_FRAME);
+    }
+
+    #[test]
+    fn drain_finishes_playing_and_queued_pings() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let playing = start(&mut driver, &clock, false);
+        let queue = crossbeam::queue::ArrayQueue::new(10);
+        let queued = (0..3)
+            .map(|i| {
+                let animation = AnimationBuilder::at((i, i)).build_at(clock.now()).animation;
+                let id = animation.id;
+                queue.push(animation).unwrap();
+                id
+            })
+            .collect::<Vec<_>>();
+
+        let mut events = Vec::new();
+        let incoming = std::iter::from_fn(|| queue.pop());
+        assert!(
+            driver.command(DriverCommand::Drain, incoming, &mut |event| {
+                events.push(event)
+            })
+        );
+        let mut cleared = events
+            .iter()
+            .map(|event| match event {
+                CustomEvent::Clear(id) => *id,
+                _ => panic!("drained pings are only cleared"),
+            })
+            .collect::<Vec<_>>();
+        cleared.sort();
+        let mut expected = [vec![playing], queued].concat();
+        expected.sort();
+        assert_eq!(cleared, expected);
+        assert!(queue.is_empty());
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn nothing_plays_after_resuming_from_a_drain() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        start(&mut driver, &clock, false);
+        let queued = AnimationBuilder::at((5, 5)).build_at(clock.now()).animation;
+        driver.command(DriverCommand::Drain, [queued], &mut |_| {});
+
+        for _ in 0..10 {
+            clock.advance(Duration::from_secs(30));
+            assert!(tick(&mut driver, Vec::new()).is_empty());
+        }
+        assert!(driver.is_idle());
+    }
+
+    #[test]
+    fn drained_markers_go_to_their_resting_frame() {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let marker = AnimationBuilder::at((5, 5))
+            .persistent(true)
+            .build_at(clock.now())
+            .animation;
+        let mut events = Vec::new();
+        driver.command(DriverCommand::Drain, [marker], &mut |event| {
+            events.push(event)
+        });
+        assert!(matches!(
+            events[..],
+            [CustomEvent::Animate(ref a)] if a.frame == MARKER_FRAME
+                && a.frame_at(clock.now()) == MARKER_FRAME
+        ));
+        assert!(driver.is_idle());
+    }
+
+    /// The event loop's side: events land after a delay, in the order they
+    /// were sent, and redraws bring every animation to its clock.
+    #[derive(Default)]
+    struct Overlay {
+        animations: HashMap<usize, Animation>,
+        in_flight: VecDeque<(Instant, CustomEvent)>,
+    }
+
+    impl Overlay {
+        fn send(&mut self, event: CustomEvent, at: Instant) {
+            // NOTE: the proxy keeps order, a late event holds back the ones after it
+            let at = self.in_flight.back().map_or(at, |(last, _)| at.max(*last));
+            self.in_flight.push_back((at, event));
+        }
+
+        /// Frames drawn at `now`, by where each ping is.
+        fn redraw(&mut self, now: Instant) -> BTreeMap<MousePosition, u8> {
+            while self.in_flight.front().is_some_and(|(at, _)| *at <= now) {
+                match self.in_flight.pop_front().unwrap().1 {
+                    CustomEvent::Animate(animation) => {
+                        self.animations.insert(animation.id, animation);
+                    }
+                    CustomEvent::Clear(id) => {
+                        self.animations.remove(&id);
+                    }
+                    _ => {}
+                }
+            }
+            self.animations
+                .values_mut()
+                .map(|animation| {
+                    animation.frame = animation.frame_at(now);
+                    (animation.position, animation.frame)
+                })
+                .collect()
+        }
+    }
+
+    /// Pings at `(0, 0)` right away, `(1, 1)` after 100ms and `(2, 2)` after
+    /// 400ms, and a marker at `(3, 3)` after 200ms, with `merge` merging
+    /// into the first one after 300ms. The driver ticks every 5ms and the
+    /// overlay redraws every 16ms; returns what each redraw drew.
+    fn simulate(
+        mut delay: impl FnMut() -> Duration,
+        merge: bool,
+    ) -> Vec<BTreeMap<MousePosition, u8>> {
+        let clock = Arc::new(ManualClock::new());
+        let mut driver = driver(&clock);
+        let mut overlay = Overlay::default();
+        let mut redraws = Vec::new();
+        let mut first = None;
+        for ms in 0..1500 {
+            let now = clock.now();
+            let incoming = match ms {
+                0 | 100 | 400 => vec![(ms / 100, false)],
+                200 => vec![(3, true)],
+                _ => Vec::new(),
+            }
+            .into_iter()
+            .map(|(at, persistent)| {
+                AnimationBuilder::at((at, at))
+                    .persistent(persistent)
+                    .build_at(now)
+                    .animation
+            })
+            .inspect(|animation| {
+                first.get_or_insert(animation.id);
+            })
+            .collect::<Vec<_>>();
+            let mut emit = |event| overlay.send(event, now + delay());
+            if merge && ms == 300 {
+                driver.command(DriverCommand::Merge(first.unwrap()), [], &mut emit);
+            }
+            if ms % 5 == 0 || !incoming.is_empty() {
+                driver.tick(incoming, &mut emit);
+            }
+            if ms % 16 == 0 {
+                redraws.push(overlay.redraw(now));
+            }
+            clock.advance(Duration::from_millis(1));
+        }
+        redraws
+    }
+
+    /// Event delays from none up to 60ms, the same on every run.
+    fn late() -> impl FnMut() -> Duration {
+        let mut seed = 0x2545_f491_u32;
+        move || {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            Duration::from_millis((seed >> 16) as u64 % 61)
+        }
+    }
+
+    /// Frames each ping was drawn with, one per redraw, `None` where it
+    /// wasn't drawn.
+    fn frames_of(
+        redraws: &[BTreeMap<MousePosition, u8>],
+    ) -> BTreeMap<MousePosition, Vec<Option<u8>>> {
+        let mut frames = BTreeMap::new();
+        for (i, redraw) in redraws.iter().enumerate() {
+            for (position, frame) in redraw {
+                frames
+                    .entry(*position)
+                    .or_insert_with(|| vec![None; redraws.len()])[i] = Some(*frame);
+            }
+        }
+        frames
+    }
+
+    #[test]
+    fn late_events_dont_change_the_frames_drawn() {
+        let on_time = simulate(|| Duration::ZERO, false);
+        let late = simulate(late(), false);
+        for (on_time, late) in on_time.iter().zip(&late) {
+            for (position, frame) in late {
+                // NOTE: a late clear leaves a ping on its last frame a little longer
+                if let Some(on_time) = on_time.get(position) {
+                    assert_eq!(frame, on_time, "ping at {position:?}");
+                }
+            }
+        }
+        assert_eq!(frames_of(&late).len(), 4);
+    }
+
+    #[test]
+    fn late_events_never_blank_or_rewind_a_ping() {
+        for (position, frames) in frames_of(&simulate(late(), false)) {
+            let shown = frames
+                .iter()
+                .skip_while(|frame| frame.is_none())
+                .take_while(|frame| frame.is_some())
+                .flatten()
+                .collect::<Vec<_>>();
+            let drawn = frames.iter().flatten().count();
+            assert_eq!(shown.len(), drawn, "ping at {position:?} blinked");
+            for pair in shown.windows(2) {
+                // NOTE: a redraw is a little shorter than a frame, so frames repeat but never skip
+                assert!(
+                    *pair[1] == *pair[0] || *pair[1] == *pair[0] + 1,
+                    "ping at {position:?} went from frame {} to {}",
+                    pair[0],
+                    pair[1]
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn late_merge_rewinds_once_when_it_lands() {
+        let frames = frames_of(&simulate(late(), true));
+        let merged = frames[&(0, 0)].iter().flatten().collect::<Vec<_>>();
+        let rewinds = merged
+            .windows(2)
+            .filter(|pair| pair[1] < pair[0])
+            .map(|pair| pair[0] - pair[1])
+            .collect::<Vec<_>>();
+        // NOTE: one redraw passes between the frame it rewound from and the next
+        assert!(
+            matches!(rewinds[..], [rewind] if rewind < MERGE_REWIND),
+            "{rewinds:?}"
+        );
+        assert!(frames[&(1, 1)].iter().flatten().is_sorted());
+    }
+}
@@ -0,0 +1,149 @@
+//! Easing curves mapping animation progress in `0..=1` to a shaped value.
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Curve {
+    #[default]
+    Linear,
+    EaseOutCubic,
+    /// Overshoots past 1 before settling, for a bouncy pop.
+    EaseOutBack,
+    EaseOutBounce,
+    /// Barely moves, then rushes to the end, for a quick fade.
+    EaseInExpo,
+}
+
+impl Curve {
+    /// Parses a config name like `"ease_out_back"`, `None` for unknown names.
+    pub fn parse(name: &str) -> Option<Self> {
+        match name {
+            "linear" => Some(Curve::Linear),
+            "ease_out_cubic" => Some(Curve::EaseOutCubic),
+            "ease_out_back" => Some(Curve::EaseOutBack),
+            "ease_out_bounce" => Some(Curve::EaseOutBounce),
+            "ease_in_expo" => Some(Curve::EaseInExpo),
+            _ => None,
+        }
+    }
+
+    pub fn apply(self, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+        match self {
+            Curve::Linear => t,
+            Curve::EaseOutCubic => ease_out_cubic(t),
+            Curve::EaseOutBack => ease_out_back(t),
+            Curve::EaseOutBounce => ease_out_bounce(t),
+            Curve::EaseInExpo => ease_in_expo(t),
+        }
+    }
+}
+
+pub fn ease_out_cubic(t: f32) -> f32 {
+    1.0 - (1.0 - t).powi(3)
+}
+
+/// Overshoots past 1 before settling, for a "pop".
+pub fn ease_out_back(t: f32) -> f32 {
+    const C1: f32 = 1.70158;
+    const C3: f32 = C1 + 1.0;
+    1.0 + C3 * (t - 1.0).powi(3) + C1 * (t - 1.0).powi(2)
+}
+
+pub fn ease_out_bounce(t: f32) -> f32 {
+    const N1: f32 = 7.5625;
+    const D1: f32 = 2.75;
+    if t < 1.0 / D1 {
+        N1 * t * t
+    } else if t < 2.0 / D1 {
+        let t = t - 1.5 / D1;
+        N1 * t * t + 0.75
+    } else if t < 2.5 / D1 {
+        let t = t - 2.25 / D1;
+        N1 * t * t + 0.9375
+    } else {
+        let t = t - 2.625 / D1;
+        N1 * t * t + 0.984375
+    }
+}
+
+pub fn ease_in_expo(t: f32) -> f32 {
+    if t <= 0.0 {
+        0.0
+    } else {
+        2f32.powf(10.0 * t - 10.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CURVES: [Curve; 5] = [
+        Curve::Linear,
+        Curve::EaseOutCubic,
+        Curve::EaseOutBack,
+        Curve::EaseOutBounce,
+        Curve::EaseInExpo,
+    ];
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 1e-4
+    }
+
+    #[test]
+    fn curves_start_at_0_and_end_at_1() {
+        for curve in CURVES {
+            assert!(close(curve.apply(0.0), 0.0), "{curve:?} at 0");
+            assert!(close(curve.apply(1.0), 1.0), "{curve:?} at 1");
+        }
+    }
+
+    #[test]
+    fn progress_outside_0_to_1_is_clamped() {
+        for curve in CURVES {
+            assert_eq!(curve.apply(-0.5), curve.apply(0.0), "{curve:?}");
+            assert_eq!(curve.apply(1.5), curve.apply(1.0), "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn midpoints() {
+        assert!(close(Curve::Linear.apply(0.5), 0.5));
+        assert!(close(Curve::EaseOutCubic.apply(0.5), 0.875));
+        assert!(close(Curve::EaseOutBack.apply(0.5), 1.0876975));
+        assert!(close(Curve::EaseOutBounce.apply(0.5), 0.765625));
+        assert!(close(Curve::EaseInExpo.apply(0.5), 0.03125));
+    }
+
+    #[test]
+    fn back_overshoots_and_settles() {
+        let peak = (0..=100)
+            .map(|i| ease_out_back(i as f32 / 100.0))
+            .fold(f32::MIN, f32::max);
+        assert!(peak > 1.09 && peak < 1.11);
+    }
+
+    #[test]
+    fn bounce_touches_1_between_bounces() {
+        const D1: f32 = 2.75;
+        assert!(close(ease_out_bounce(1.0 / D1), 1.0));
+        assert!(close(ease_out_bounce(2.0 / D1), 1.0));
+        assert!(close(ease_out_bounce(2.5 / D1), 1.0));
+        assert!(ease_out_bounce(1.5 / D1) < 1.0);
+    }
+
+    #[test]
+    fn monotonic_curves_never_go_back() {
+        for curve in [Curve::Linear, Curve::EaseOutCubic, Curve::EaseInExpo] {
+            let values = (0..=100).map(|i| curve.apply(i as f32 / 100.0));
+            let pairs = values.clone().zip(values.skip(1));
+            assert!(pairs.into_iter().all(|(a, b)| b >= a), "{curve:?}");
+        }
+    }
+
+    #[test]
+    fn config_names() {
+        assert_eq!(Curve::parse("ease_out_back"), Some(Curve::EaseOutBack));
+        assert_eq!(Curve::parse("linear"), Some(Curve::Linear));
+        assert_eq!(Curve::parse("EaseOutBack"), None);
+    }
+}
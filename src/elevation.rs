@@ -0,0 +1,96 @@
+use crate::platform;
+use device_query::{DeviceQuery, DeviceState};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+/// How often the watcher samples the foreground window and mouse buttons.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+/// How long the hook gets to report a click the watcher has already seen.
+const MISSED_CLICK_WINDOW: Duration = Duration::from_millis(250);
+
+/// Timestamp of the last mouse press the low-level hook delivered.
+pub struct HookActivity {
+    epoch: Instant,
+    last_press_ms: AtomicU64,
+}
+
+impl HookActivity {
+    pub fn new() -> Self {
+        Self {
+            epoch: Instant::now(),
+            last_press_ms: AtomicU64::new(0),
+        }
+    }
+
+    pub fn record_press(&self) {
+        let now = self.epoch.elapsed().as_millis() as u64;
+        self.last_press_ms.store(now, Ordering::Relaxed);
+    }
+
+    fn pressed_since(&self, instant: Instant) -> bool {
+        let since = instant.saturating_duration_since(self.epoch).as_millis() as u64;
+        // NOTE: the hook may fire a few ms before device_query observes the button
+        self.last_press_ms.load(Ordering::Relaxed) + POLL_INTERVAL.as_millis() as u64 >= since
+    }
+}
+
+/// Watches for clicks that reach an elevated foreground window without passing
+/// through our hook and, once per session, offers to relaunch elevated.
+///
+/// `on_relaunch` is called when the user accepts the offer.
+pub fn spawn_watcher(activity: Arc<HookActivity>, on_relaunch: impl Fn() + Send + 'static) {
+    let Some(own_integrity) = platform::own_integrity() else {
+        return;
+    };
+
+    std::thread::spawn(move || {
+        let device_state = DeviceState::new();
+        let mut was_pressed = false;
+        let mut pending_press: Option<Instant> = None;
+
+        loop {
+            std::thread::sleep(POLL_INTERVAL);
+
+            // NOTE: index 1 is the left button
+            let pressed = device_state
+                .get_mouse()
+                .button_pressed
+                .get(1)
+                .copied()
+                .unwrap_or(false);
+            if pressed && !was_pressed {
+                pending_press.get_or_insert_with(Instant::now);
+            }
+            was_pressed = pressed;
+
+            let Some(press) = pending_press else {
+                continue;
+            };
+            if press.elapsed() < MISSED_CLICK_WINDOW {
+                continue;
+            }
+            pending_press = None;
+
+            if activity.pressed_since(press) {
+                continue;
+            }
+            let foreground_elevated =
+                matches!(platform::foreground_integrity(), Some(level) if level > own_integrity);
+            if !foreground_elevated {
+                continue;
+            }
+
+            log::info!("click reached an elevated window without passing through the hook");
+            let relaunch = platform::ask_yes_no(
+                crate::i18n::t("app.name"),
+                crate::i18n::t("elevation.prompt"),
+            );
+            if relaunch {
+                on_relaunch();
+            }
+            // NOTE: only nag once per session; the tray entry stays available
+            return;
+        }
+    });
+}
@@ -0,0 +1,62 @@
+//! What can go wrong setting the app up, by what failed. Fatal errors end
+//! `run()` and are reported to the user; recoverable ones are logged and the
+//! app carries on without the part that failed.
+
+use crate::{i18n, platform};
+
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    #[error("graphics setup failed on {adapter}: {reason}")]
+    Graphics { adapter: String, reason: String },
+    #[error("failed to create a window: {0}")]
+    Window(#[from] winit::error::OsError),
+    #[error("no audio output: {0}")]
+    Audio(String),
+    #[error("failed to load {path}: {reason}")]
+    Asset { path: String, reason: String },
+    #[error("tray icon unavailable: {0}")]
+    Tray(String),
+    #[error("{call} failed: {reason}")]
+    Platform { call: &'static str, reason: String },
+    #[error("the input hook stopped: {0}")]
+    Hook(String),
+    #[error("already running in this session as {0}, start another with --instance <name>")]
+    AlreadyRunning(String),
+}
+
+impl Error {
+    /// Whether the app can go on without what failed: pings are silent
+    /// without audio, the built-in artwork stands in for a broken asset and
+    /// the taskbar entry for the tray.
+    pub fn recoverable(&self) -> bool {
+        matches!(self, Error::Audio(_) | Error::Asset { .. } | Error::Tray(_))
+    }
+
+    /// For errors where the graphics adapter is already picked.
+    pub fn graphics(adapter: &wgpu::Adapter, reason: impl ToString) -> Self {
+        Error::Graphics {
+            adapter: adapter.get_info().name,
+            reason: reason.to_string(),
+        }
+    }
+}
+
+impl From<tray_icon::Error> for Error {
+    fn from(e: tray_icon::Error) -> Self {
+        Error::Tray(e.to_string())
+    }
+}
+
+impl From<tray_icon::menu::Error> for Error {
+    fn from(e: tray_icon::menu::Error) -> Self {
+        Error::Tray(e.to_string())
+    }
+}
+
+/// Logs a fatal error and tells the user in a message box. Blocks until the
+/// box is dismissed.
+pub fn report(error: &Error) {
+    log::error!("{error}");
+    let text = i18n::tf("error.fatal", &[("error", error)]);
+    platform::show_error(i18n::t("app.name"), &text);
+}
@@ -0,0 +1,280 @@
+//! Backpressure between the threads sending user events and the event loop,
+//! so a stalled loop (a modal dialog, a slow present) can't pile up events
+//! that are stale by the time it catches up.
+
+use crate::metrics::Metrics;
+use crate::CustomEvent;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Condvar, Mutex};
+use winit::event_loop::EventLoopProxy;
+
+/// User events sent but not yet handled before frame updates are dropped.
+pub const MAX_IN_FLIGHT: usize = 64;
+
+/// Whether an event may go out with `in_flight` already queued. Only
+/// `droppable` ones are ever refused, the cap is soft for everything else.
+pub fn admit(in_flight: usize, cap: usize, droppable: bool) -> bool {
+    !droppable || in_flight < cap
+}
+
+/// Events the next one supersedes, or that are only decoration.
+// NOTE: the overlay derives frames itself, the driver's spawns, merges and
+// clears all have to arrive
+pub fn droppable(event: &CustomEvent) -> bool {
+    matches!(event, CustomEvent::Ripple(_))
+}
+
+/// Where a [`Proxy`] hands events on, the event loop outside of tests.
+pub trait Sink: Clone + Send + 'static {
+    /// Returns `false` once the receiving end is gone.
+    fn send(&self, event: CustomEvent) -> bool;
+}
+
+impl Sink for EventLoopProxy<CustomEvent> {
+    fn send(&self, event: CustomEvent) -> bool {
+        self.send_event(event).is_ok()
+    }
+}
+
+/// [`EventLoopProxy`] counting what it sends, shared by every producer.
+#[derive(Clone)]
+pub struct Proxy<S = EventLoopProxy<CustomEvent>> {
+    inner: S,
+    in_flight: Arc<AtomicUsize>,
+    metrics: Arc<Metrics>,
+    /// Set on the first send that found the loop gone, to log it once.
+    closed: Arc<AtomicBool>,
+}
+
+impl<S: Sink> Proxy<S> {
+    pub fn new(inner: S, metrics: Arc<Metrics>) -> Self {
+        Self {
+            inner,
+            in_flight: Default::default(),
+            metrics,
+            closed: Default::default(),
+        }
+    }
+
+    /// Sends `event` unless the loop is behind and it can be dropped.
+    /// Returns `false` once the loop is gone, a dropped event still counts
+    /// as delivered.
+    pub fn send_event(&self, event: CustomEvent) -> bool {
+        let in_flight = self.in_flight.load(Ordering::Relaxed);
+        if !admit(in_flight, MAX_IN_FLIGHT, droppable(&event)) {
+            Metrics::bump(&self.metrics.events_dropped);
+            return true;
+        }
+        self.in_flight.fetch_add(1, Ordering::Relaxed);
+        let sent = self.inner.send(event);
+        if !sent {
+            self.in_flight.fetch_sub(1, Ordering::Relaxed);
+            // NOTE: every producer outlives the loop on exit, that's no reason to panic
+            if !self.closed.swap(true, Ordering::Relaxed) {
+                log::info!("event loop gone, dropping user events");
+            }
+        }
+        sent
+    }
+
+    /// The event loop took a user event off the queue.
+    pub fn received(&self) {
+        // NOTE: saturating, winit may hand over events sent by a bare proxy
+        self.in_flight
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |n| n.checked_sub(1))
+            .ok();
+    }
+}
+
+/// Opened once the event loop runs with the app built, so producers started
+/// ahead of it hold back until their events can be handled.
+#[derive(Default)]
+pub struct Ready {
+    ready: Mutex<bool>,
+    opened: Condvar,
+}
+
+impl Ready {
+    pub fn open(&self) {
+        *self.ready.lock().unwrap() = true;
+        self.opened.notify_all();
+    }
+
+    /// Blocks until [`Self::open`], returns at once after.
+    pub fn wait(&self) {
+        let ready = self.ready.lock().unwrap();
+        drop(self.opened.wait_while(ready, |ready| !*ready).unwrap());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation_builder::AnimationBuilder;
+    use crate::tray::TrayCommand;
+    use std::time::{Duration, Instant};
+
+    #[test]
+    fn droppable_events_are_refused_at_the_cap() {
+        assert!(admit(0, MAX_IN_FLIGHT, true));
+        assert!(admit(MAX_IN_FLIGHT - 1, MAX_IN_FLIGHT, true));
+        assert!(!admit(MAX_IN_FLIGHT, MAX_IN_FLIGHT, true));
+        assert!(!admit(MAX_IN_FLIGHT * 10, MAX_IN_FLIGHT, true));
+    }
+
+    #[test]
+    fn everything_else_goes_out_past_the_cap() {
+        assert!(admit(MAX_IN_FLIGHT, MAX_IN_FLIGHT, false));
+        assert!(admit(usize::MAX, MAX_IN_FLIGHT, false));
+    }
+
+    #[test]
+    fn only_ripples_are_droppable() {
+        assert!(droppable(&CustomEvent::Ripple((10, 10))));
+        let kept = [
+            CustomEvent::Animate(AnimationBuilder::at((10, 10)).build().animation),
+            CustomEvent::Clear(1),
+            CustomEvent::Tray(TrayCommand::RemoveAllData),
+            CustomEvent::Tray(TrayCommand::Restart),
+            CustomEvent::Pinged,
+            CustomEvent::WindowsChanged,
+            CustomEvent::SettingsChanged,
+            CustomEvent::Toast("hello".to_owned()),
+        ];
+        for event in &kept {
+            assert!(!droppable(event));
+        }
+    }
+
+    #[test]
+    fn a_flood_past_the_cap_keeps_what_matters() {
+        // NOTE: the loop stalled, so nothing is received while a burst is sent
+        let mut in_flight = 0;
+        let (mut sent, mut dropped) = (Vec::new(), 0);
+        for i in 0..MAX_IN_FLIGHT * 3 {
+            let event = match i % 3 {
+                0 => CustomEvent::Clear(i),
+                _ => CustomEvent::Ripple((i as i32, 0)),
+            };
+            if admit(in_flight, MAX_IN_FLIGHT, droppable(&event)) {
+                in_flight += 1;
+                sent.push(event);
+            } else {
+                dropped += 1;
+            }
+        }
+        let clears = sent
+            .iter()
+            .filter(|event| matches!(event, CustomEvent::Clear(_)))
+            .count();
+        assert_eq!(clears, MAX_IN_FLIGHT);
+        assert_eq!(sent.len() + dropped, MAX_IN_FLIGHT * 3);
+        assert!(dropped > 0);
+    }
+
+    #[test]
+    fn ready_returns_at_once_after_opening() {
+        let ready = Ready::default();
+        ready.open();
+        ready.wait();
+        ready.open();
+        ready.wait();
+    }
+
+    impl Sink for crossbeam::channel::Sender<CustomEvent> {
+        fn send(&self, event: CustomEvent) -> bool {
+            crossbeam::channel::Sender::send(self, event).is_ok()
+        }
+    }
+
+    /// Producers started ahead of a loop that takes `startup` to come up,
+    /// each sending `pings` pings through a [`Proxy`] as soon as it's let
+    /// through, every one followed by two ripples. The loop is busy for
+    /// `first_frame` more after opening before it takes anything off the
+    /// queue. Fails on an event sent before the loop ran, a ping lost or a
+    /// producer never let through. Returns the ripples dropped.
+    fn slow_startup(
+        producers: usize,
+        pings: usize,
+        startup: Duration,
+        first_frame: Duration,
+    ) -> u64 {
+        let ready = Arc::new(Ready::default());
+        let running = Arc::new(AtomicBool::new(false));
+        let metrics = Arc::new(Metrics::default());
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        let proxy = Proxy::new(sender, metrics.clone());
+        let threads = (0..producers)
+            .map(|producer| {
+                let (ready, running, proxy) = (ready.clone(), running.clone(), proxy.clone());
+                std::thread::spawn(move || {
+                    ready.wait();
+                    for _ in 0..pings {
+                        assert!(running.load(Ordering::SeqCst), "ping before the loop ran");
+                        assert!(proxy.send_event(CustomEvent::Clear(producer)));
+                        for _ in 0..2 {
+                            assert!(proxy.send_event(CustomEvent::Ripple((0, 0))));
+                        }
+                    }
+                })
+            })
+            .collect::<Vec<_>>();
+
+        std::thread::sleep(startup);
+        running.store(true, Ordering::SeqCst);
+        ready.open();
+        std::thread::sleep(first_frame);
+
+        let mut received = vec![0; producers];
+        let mut ripples = 0;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        loop {
+            let finished = threads.iter().all(|thread| thread.is_finished());
+            while let Ok(event) = receiver.try_recv() {
+                proxy.received();
+                match event {
+                    CustomEvent::Clear(producer) => received[producer] += 1,
+                    CustomEvent::Ripple(_) => ripples += 1,
+                    _ => unreachable!(),
+                }
+            }
+            if finished {
+                break;
+            }
+            // NOTE: a lost wake-up leaves a producer waiting for good
+            assert!(
+                Instant::now() < deadline,
+                "a producer was never let through"
+            );
+            std::thread::yield_now();
+        }
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        let dropped = metrics.events_dropped.load(Ordering::Relaxed);
+        assert_eq!(received, vec![pings; producers]);
+        assert_eq!(ripples + dropped, (producers * pings * 2) as u64);
+        assert_eq!(proxy.in_flight.load(Ordering::Relaxed), 0);
+        dropped
+    }
+
+    #[test]
+    fn producers_hammering_a_slow_startup_wait_for_the_loop() {
+        let dropped = slow_startup(
+            8,
+            1000,
+            Duration::from_millis(200),
+            Duration::from_millis(100),
+        );
+        // NOTE: the loop took nothing for 100ms, surplus ripples had to go
+        assert!(dropped > 0);
+    }
+
+    #[test]
+    fn no_wake_up_is_lost_however_startup_races_the_producers() {
+        // NOTE: from opening before any producer waits to well after they all do
+        for round in 0..200 {
+            slow_startup(4, 10, Duration::from_micros(round * 10), Duration::ZERO);
+        }
+    }
+}
@@ -0,0 +1,255 @@
+//! Replays a session from "Copy diagnostics" into an animated GIF, for
+//! pasting into documentation: `--export-session report.json out.gif`.
+//! Pings are drawn off-screen by the same code as the overlay.
+
+use crate::animation_builder::AnimationBuilder;
+use crate::diagnostics::{Monitor, Outcome};
+use crate::offscreen::{over, unpremultiply, Canvas};
+use crate::{config, protocol, theme, MyApp, FRAME_COUNT, MARKER_FRAME};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame};
+use serde::Deserialize;
+use std::path::PathBuf;
+use std::time::Duration;
+
+const DEFAULT_WIDTH: u32 = 1280;
+const DEFAULT_FPS: u32 = 15;
+/// GIF delays are in hundredths of a second, faster would be rounded anyway.
+const MAX_FPS: u32 = 50;
+/// Shown before the first ping and after the last one ends.
+const PADDING: Duration = Duration::from_millis(500);
+/// Speed of the GIF color quantizer, 1 to 30: higher is faster and coarser.
+const GIF_SPEED: i32 = 10;
+
+/// The session as copied by "Copy diagnostics", only what the replay needs.
+#[derive(Debug, Deserialize)]
+struct Session {
+    overlay_offset: Option<f32>,
+    monitors: Vec<Monitor>,
+    pings: Vec<Ping>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Ping {
+    at_ms: u64,
+    placed: (i32, i32),
+    persistent: bool,
+    outcome: Outcome,
+    #[serde(default)]
+    note: Option<String>,
+}
+
+/// What's drawn behind the pings.
+#[derive(Debug)]
+pub enum Background {
+    /// Nothing, the GIF is transparent around the pings.
+    Transparent,
+    /// An image stretched over the whole desktop, e.g. a screenshot of it.
+    Screenshot(PathBuf),
+}
+
+#[derive(Debug)]
+pub struct Job {
+    session: PathBuf,
+    output: PathBuf,
+    /// Output width in pixels, the height follows the desktop's aspect.
+    width: u32,
+    fps: u32,
+    background: Background,
+}
+
+impl Job {
+    /// Parsed from `--export-session <report.json> <out.gif>` and its options
+    /// `--width <px>`, `--fps <n>` and `--background <image>`, `None` when not
+    /// exporting.
+    pub fn from_args() -> Option<Result<Self, String>> {
+        let args: Vec<String> = std::env::args().collect();
+        let at = args.iter().position(|arg| arg == "--export-session")?;
+        Some(Self::parse(&args[at + 1..]))
+    }
+
+    fn parse(args: &[String]) -> Result<Self, String> {
+        const USAGE: &str = "usage: --export-session <report.json> <out.gif> \
+                             [--width <px>] [--fps <n>] [--background <image>]";
+        let [session, output, ..] = args else {
+            return Err(USAGE.into());
+        };
+        let mut job = Self {
+            session: session.into(),
+            output: output.into(),
+            width: DEFAULT_WIDTH,
+            fps: DEFAULT_FPS,
+            background: Background::Transparent,
+        };
+        let mut options = args[2..].iter();
+        while let Some(option) = options.next() {
+            let value = options.next().ok_or(USAGE)?;
+            match option.as_str() {
+                "--width" => {
+                    job.width = value
+                        .parse()
+                        .ok()
+                        .filter(|width| *width > 0)
+                        .ok_or("--width expects a number of pixels")?
+                }
+                "--fps" => {
+                    job.fps = value
+                        .parse()
+                        .ok()
+                        .filter(|fps| (1..=MAX_FPS).contains(fps))
+                        .ok_or(format!("--fps expects a number from 1 to {MAX_FPS}"))?
+                }
+                "--background" => job.background = Background::Screenshot(value.into()),
+                _ => return Err(USAGE.into()),
+            }
+        }
+        match job.output.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("gif") => Ok(job),
+            _ => Err("only GIF export is supported, name the output *.gif".into()),
+        }
+    }
+
+    /// Renders the session and writes the GIF. Returns the number of frames.
+    pub fn run(&self) -> Result<usize, String> {
+        let session = std::fs::read_to_string(&self.session)
+            .map_err(|e| format!("failed to read {}: {e}", self.session.display()))?;
+        let session: Session = serde_json::from_str(&session)
+            .map_err(|e| format!("{} isn't a diagnostics report: {e}", self.session.display()))?;
+        let (offset, desktop) = overlay_bounds(&session)?;
+        let scale = self.width as f32 / desktop.x;
+        let size = [self.width, ((desktop.y * scale).round() as u32).max(1)];
+
+        let background = match &self.background {
+            Background::Transparent => None,
+            Background::Screenshot(path) => Some(
+                image::open(path)
+                    .map_err(|e| format!("failed to open {}: {e}", path.display()))?
+                    .resize_exact(size[0], size[1], image::imageops::FilterType::Triangle)
+                    .into_rgba8(),
+            ),
+        };
+
+        let config = config::Config::load();
+        crate::i18n::init(config.ui.language.as_deref());
+        let theme = theme::load(&config.theme);
+        // NOTE: diagnostics don't record the kind, every ping replays as the default one
+        let frame_time = crate::kind_style(&config, &theme, &protocol::PingKind::Missing).duration
+            / FRAME_COUNT as u32;
+        let mut app = MyApp::new(config, theme);
+        app.offset = offset;
+
+        let mut canvas = Canvas::new(size)?;
+        let file = std::fs::File::create(&self.output)
+            .map_err(|e| format!("failed to create {}: {e}", self.output.display()))?;
+        let mut encoder = GifEncoder::new_with_speed(file, GIF_SPEED);
+        encoder
+            .set_repeat(Repeat::Infinite)
+            .map_err(|e| e.to_string())?;
+
+        let shown = |ping: &&Ping| matches!(ping.outcome, Outcome::Shown | Outcome::Denied);
+        let first = session
+            .pings
+            .iter()
+            .filter(shown)
+            .map(|ping| ping.at_ms)
+            .min();
+        let last = session
+            .pings
+            .iter()
+            .filter(shown)
+            .map(|ping| ping.at_ms)
+            .max();
+        let (Some(first), Some(last)) = (first, last) else {
+            return Err("the session has no pings to replay".into());
+        };
+        let start = Duration::from_millis(first).saturating_sub(PADDING);
+        let end = Duration::from_millis(last) + frame_time * FRAME_COUNT as u32 + PADDING;
+        let step = Duration::from_secs(1) / self.fps;
+
+        let mut frames = 0;
+        let mut now = start;
+        while now <= end {
+            for (i, ping) in session.pings.iter().enumerate() {
+                // NOTE: 0 is the tutorial's
+                let id = i + 1;
+                match replayed(ping, &session.pings, now, frame_time) {
+                    Some(mut animation) if shown(&ping) => {
+                        animation.id = id;
+                        app.add_animation(animation);
+                    }
+                    _ => app.remove_animation(id),
+                }
+            }
+            let mut image = canvas.render(&mut app, desktop, scale)?;
+            match &background {
+                Some(background) => image = over(&image, background),
+                None => unpremultiply(&mut image),
+            }
+            let delay = Delay::from_numer_denom_ms(1000, self.fps);
+            encoder
+                .encode_frame(Frame::from_parts(image, 0, 0, delay))
+                .map_err(|e| format!("failed to write {}: {e}", self.output.display()))?;
+            frames += 1;
+            now += step;
+        }
+        Ok(frames)
+    }
+}
+
+/// `ping` as it looked `now`, `None` before it was fired or after it ended.
+/// Notes typed on a marker by then are drawn with it.
+fn replayed(
+    ping: &Ping,
+    pings: &[Ping],
+    now: Duration,
+    frame_time: Duration,
+) -> Option<crate::Animation> {
+    let elapsed = now.checked_sub(Duration::from_millis(ping.at_ms))?;
+    let frame = (elapsed.as_micros() / frame_time.as_micros().max(1)) as u64;
+    let frame = if ping.persistent {
+        frame.min(MARKER_FRAME as u64) as u8
+    } else if frame < FRAME_COUNT as u64 {
+        frame as u8
+    } else {
+        return None;
+    };
+    let note = pings
+        .iter()
+        .filter(|noted| noted.outcome == Outcome::Noted && noted.placed == ping.placed)
+        .rfind(|noted| noted.at_ms >= ping.at_ms && Duration::from_millis(noted.at_ms) <= now)
+        .and_then(|noted| noted.note.as_deref())
+        .filter(|note| !note.is_empty());
+
+    let mut builder = AnimationBuilder::at(ping.placed)
+        .persistent(ping.persistent)
+        .note(note.map(Into::into))
+        .start_frame(frame)
+        .silent();
+    if ping.outcome == Outcome::Denied {
+        builder = builder.denied();
+    }
+    Some(builder.build().animation)
+}
+
+/// Distance from the leftmost monitor edge to the desktop origin and the size
+/// of the overlay spanning every monitor, the way [`crate::overlay`] lays it out.
+fn overlay_bounds(session: &Session) -> Result<(f32, egui::Vec2), String> {
+    let monitors = &session.monitors;
+    let left = monitors.iter().map(|monitor| monitor.x).min();
+    let right = monitors
+        .iter()
+        .map(|monitor| monitor.x + monitor.width as i32)
+        .max();
+    let bottom = monitors
+        .iter()
+        .map(|monitor| monitor.y + monitor.height as i32)
+        .max();
+    let (Some(left), Some(right), Some(bottom)) = (left, right, bottom) else {
+        return Err("the session has no monitors".into());
+    };
+    let offset = session.overlay_offset.unwrap_or(-left as f32);
+    Ok((
+        offset,
+        egui::Vec2::new((right - left) as f32, bottom as f32),
+    ))
+}
@@ -0,0 +1,112 @@
+use crate::config;
+use device_query::mouse_state::MousePosition;
+use device_query::{DeviceQuery, DeviceState};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(4);
+/// Shorter movements are a shaky click, however fast.
+const MIN_DISTANCE: f32 = 20.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Up,
+    Down,
+    Left,
+    Right,
+}
+
+/// Polls the cursor for a short while after a press, to tell a flick from a
+/// click or a slow drag.
+// NOTE: the hook only sees the press and the release, the movement in between
+// has to be polled
+pub struct Sampler {
+    origin: MousePosition,
+    samples: Arc<Mutex<Vec<(Duration, MousePosition)>>>,
+    done: Arc<AtomicBool>,
+}
+
+impl Sampler {
+    /// Samples for `window` after a press at `origin`.
+    pub fn start(origin: MousePosition, window: Duration) -> Self {
+        let samples: Arc<Mutex<Vec<_>>> = Default::default();
+        let done = Arc::new(AtomicBool::new(false));
+
+        let thread_samples = samples.clone();
+        let thread_done = done.clone();
+        std::thread::spawn(move || {
+            let device_state = DeviceState::new();
+            let started = Instant::now();
+            while !thread_done.load(Ordering::Relaxed) && started.elapsed() < window {
+                let position = device_state.get_mouse().coords;
+                thread_samples
+                    .lock()
+                    .unwrap()
+                    .push((started.elapsed(), position));
+                std::thread::sleep(SAMPLE_INTERVAL);
+            }
+        });
+
+        Self {
+            origin,
+            samples,
+            done,
+        }
+    }
+
+    /// Stops sampling and classifies what was seen so far. Never blocks, a
+    /// release within the window uses the samples up to it.
+    pub fn finish(self, config: &config::Flick) -> Option<Direction> {
+        self.done.store(true, Ordering::Relaxed);
+        let samples = std::mem::take(&mut *self.samples.lock().unwrap());
+        classify(self.origin, &samples, config)
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.done.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Direction of the sample farthest from `origin`, if it was reached fast
+/// enough and close enough to an axis. Diagonals are `None` rather than a guess.
+// NOTE: the farthest sample rather than the last, a flick tends to bounce back
+fn classify(
+    origin: MousePosition,
+    samples: &[(Duration, MousePosition)],
+    config: &config::Flick,
+) -> Option<Direction> {
+    let (elapsed, dx, dy) = samples
+        .iter()
+        .map(|(elapsed, (x, y))| (*elapsed, (x - origin.0) as f32, (y - origin.1) as f32))
+        .max_by(|(_, ax, ay), (_, bx, by)| (ax.hypot(*ay)).total_cmp(&bx.hypot(*by)))?;
+
+    let distance = dx.hypot(dy);
+    let speed = distance / elapsed.as_secs_f32().max(SAMPLE_INTERVAL.as_secs_f32());
+    if distance < MIN_DISTANCE || speed < config.min_speed {
+        return None;
+    }
+
+    let off_axis = dx.abs().min(dy.abs()).atan2(dx.abs().max(dy.abs()));
+    if off_axis.to_degrees() > config.max_angle {
+        log::info!(
+            "flick at {:.0} degrees off axis, using the default kind",
+            off_axis.to_degrees()
+        );
+        return None;
+    }
+
+    Some(if dx.abs() > dy.abs() {
+        if dx > 0.0 {
+            Direction::Right
+        } else {
+            Direction::Left
+        }
+    } else if dy > 0.0 {
+        Direction::Down
+    } else {
+        Direction::Up
+    })
+}
@@ -0,0 +1,49 @@
+use crate::platform;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a notification state query stays valid.
+const CACHE_TTL: Duration = Duration::from_secs(2);
+/// How often the tray indicator is refreshed when nothing is pinging.
+const POLL_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Cached view of Focus Assist, used to mute ping sounds while it is on.
+pub struct FocusAssist {
+    cache: Mutex<Option<(Instant, bool)>>,
+    on_change: Box<dyn Fn(bool) + Send + Sync>,
+}
+
+impl FocusAssist {
+    /// `on_change` is called whenever the muted state flips.
+    pub fn new(on_change: impl Fn(bool) + Send + Sync + 'static) -> Self {
+        Self {
+            cache: Mutex::new(None),
+            on_change: Box::new(on_change),
+        }
+    }
+
+    pub fn mutes_sound(&self) -> bool {
+        let mut cache = self.cache.lock().unwrap();
+        match *cache {
+            Some((checked, muted)) if checked.elapsed() < CACHE_TTL => muted,
+            previous => {
+                let muted = platform::notifications_suppressed();
+                *cache = Some((Instant::now(), muted));
+                if previous.map_or(muted, |(_, was_muted)| was_muted != muted) {
+                    log::info!("ping sound auto-muted by Focus Assist: {muted}");
+                    (self.on_change)(muted);
+                }
+                muted
+            }
+        }
+    }
+
+    /// Keeps the cache (and with it the tray indicator) fresh between pings.
+    pub fn spawn_poller(self: &std::sync::Arc<Self>) {
+        let focus_assist = self.clone();
+        std::thread::spawn(move || loop {
+            focus_assist.mutes_sound();
+            std::thread::sleep(POLL_INTERVAL);
+        });
+    }
+}
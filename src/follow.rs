@@ -0,0 +1,142 @@
+//! Pings that move with what they point at, smoothed between redraws.
+
+use device_query::mouse_state::MousePosition;
+use device_query::{DeviceQuery, DeviceState};
+use serde::{Deserialize, Serialize};
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::HWND;
+
+/// How long smoothing takes to cover most of the distance to the target.
+/// Long enough to hide 60 Hz steps on faster displays, short enough not to lag.
+const SMOOTHING: Duration = Duration::from_millis(50);
+
+/// What pings move with while they're shown, `marker.follow` in the config.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Follow {
+    /// They stay where they were fired.
+    #[default]
+    Off,
+    Cursor,
+    /// The window clicked on, so a ping stays on it while it's dragged.
+    Window,
+}
+
+impl Follow {
+    /// What a ping fired at `position` tracks, `None` to leave it there.
+    pub fn target(self, position: MousePosition) -> Option<Target> {
+        match self {
+            Follow::Off => None,
+            Follow::Cursor => Some(Target::Cursor),
+            Follow::Window => {
+                let (window, origin) = crate::platform::window_origin_at(position)?;
+                Some(Target::Window {
+                    window,
+                    offset: (position.0 - origin.0, position.1 - origin.1),
+                })
+            }
+        }
+    }
+}
+
+/// What a moving ping tracks.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    Cursor,
+    /// Stays at `offset` from the window's top-left corner.
+    Window {
+        window: HWND,
+        offset: (i32, i32),
+    },
+}
+
+impl Target {
+    /// Where the target is right now, `None` once a window is gone.
+    pub fn position(self) -> Option<(f32, f32)> {
+        let (x, y) = match self {
+            Target::Cursor => DeviceState::new().get_mouse().coords,
+            Target::Window { window, offset } => {
+                let (x, y) = crate::platform::window_origin(window)?;
+                (x + offset.0, y + offset.1)
+            }
+        };
+        Some((x as f32, y as f32))
+    }
+}
+
+/// Last drawn position of a moving ping.
+#[derive(Debug, Clone, Copy)]
+pub struct Smoothed {
+    position: (f32, f32),
+    at: Instant,
+}
+
+impl Smoothed {
+    pub fn new(position: (f32, f32), at: Instant) -> Self {
+        Self { position, at }
+    }
+
+    /// Moves towards `target` by how much time passed since the last step,
+    /// so the result doesn't depend on the redraw rate.
+    pub fn step(&mut self, target: (f32, f32), now: Instant) -> (f32, f32) {
+        let elapsed = now.saturating_duration_since(self.at).as_secs_f32();
+        let t = 1.0 - (-elapsed / SMOOTHING.as_secs_f32()).exp();
+        self.position = (
+            self.position.0 + (target.0 - self.position.0) * t,
+            self.position.1 + (target.1 - self.position.1) * t,
+        );
+        self.at = now;
+        self.position
+    }
+
+    pub fn rounded(&self) -> (i32, i32) {
+        (
+            self.position.0.round() as i32,
+            self.position.1.round() as i32,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn close(a: (f32, f32), b: (f32, f32)) -> bool {
+        (a.0 - b.0).abs() < 1e-3 && (a.1 - b.1).abs() < 1e-3
+    }
+
+    #[test]
+    fn smoothing_covers_most_of_the_way_in_its_time() {
+        let start = Instant::now();
+        let mut smoothed = Smoothed::new((0.0, 0.0), start);
+        let (x, y) = smoothed.step((100.0, -100.0), start + SMOOTHING);
+        assert!((x - 63.212).abs() < 0.01 && (y + 63.212).abs() < 0.01);
+    }
+
+    #[test]
+    fn smoothing_does_not_depend_on_the_redraw_rate() {
+        let start = Instant::now();
+        let target = (300.0, 40.0);
+        let mut slow = Smoothed::new((0.0, 0.0), start);
+        let mut fast = slow;
+        slow.step(target, start + Duration::from_micros(16_667));
+        for i in 1..=2 {
+            fast.step(target, start + Duration::from_nanos(8_333_500 * i));
+        }
+        assert!(close(slow.position, fast.position));
+    }
+
+    #[test]
+    fn no_time_no_movement() {
+        let start = Instant::now();
+        let mut smoothed = Smoothed::new((10.4, 20.6), start);
+        assert_eq!(smoothed.step((500.0, 500.0), start), (10.4, 20.6));
+        assert_eq!(smoothed.rounded(), (10, 21));
+    }
+
+    #[test]
+    fn follow_off_leaves_pings_in_place() {
+        assert_eq!(Follow::Off.target((5, 5)), None);
+        assert_eq!(Follow::Cursor.target((5, 5)), Some(Target::Cursor));
+    }
+}
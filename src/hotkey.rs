@@ -0,0 +1,133 @@
+//! The key that arms pings, and recording a new one from the keyboard.
+
+use crate::i18n::{t, tf};
+use device_query::Keycode;
+use rdev::Key;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// Shared between the hook, which reads it on every key event, and the UI.
+pub struct Hotkey {
+    key: Mutex<Key>,
+    capturing: AtomicBool,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub enum Capture {
+    Cancelled,
+    Key(Key),
+}
+
+impl Hotkey {
+    pub fn new(key: Key) -> Self {
+        Self {
+            key: Mutex::new(key),
+            capturing: AtomicBool::new(false),
+        }
+    }
+
+    pub fn get(&self) -> Key {
+        *self.key.lock().unwrap()
+    }
+
+    pub fn set(&self, key: Key) {
+        *self.key.lock().unwrap() = key;
+    }
+
+    /// Makes the next key press a capture instead of a regular key event.
+    pub fn start_capture(&self) {
+        self.capturing.store(true, Ordering::Relaxed);
+    }
+
+    pub fn cancel_capture(&self) {
+        self.capturing.store(false, Ordering::Relaxed);
+    }
+
+    /// Called by the hook on every key press. `Some` when a capture was
+    /// waiting for it, in which case the press shouldn't be handled further.
+    pub fn capture(&self, key: Key) -> Option<Capture> {
+        if !self.capturing.swap(false, Ordering::Relaxed) {
+            return None;
+        }
+        Some(match key {
+            Key::Escape => Capture::Cancelled,
+            key => Capture::Key(key),
+        })
+    }
+}
+
+/// Why `key` can't arm pings, if it can't.
+pub fn conflict(key: Key) -> Option<&'static str> {
+    match key {
+        Key::ControlLeft | Key::ControlRight => Some(t("hotkey.conflict_ctrl")),
+        Key::Escape => Some(t("hotkey.conflict_escape")),
+        _ => None,
+    }
+}
+
+/// Polled key state to cross-check the hook against, `None` for keys the
+/// poller can't tell apart.
+pub fn keycodes(key: Key) -> Option<&'static [Keycode]> {
+    match key {
+        Key::Alt => Some(&[Keycode::LAlt, Keycode::RAlt]),
+        Key::AltGr => Some(&[Keycode::RAlt]),
+        Key::ShiftLeft => Some(&[Keycode::LShift]),
+        Key::ShiftRight => Some(&[Keycode::RShift]),
+        Key::MetaLeft | Key::MetaRight => Some(&[Keycode::Meta]),
+        _ => None,
+    }
+}
+
+/// Name of `key` as printed on a keyboard.
+pub fn label(key: Key) -> String {
+    let name = match key {
+        Key::Alt => "Alt",
+        Key::AltGr => "AltGr",
+        Key::ControlLeft | Key::ControlRight => t("keys.ctrl"),
+        Key::ShiftLeft => t("keys.left_shift"),
+        Key::ShiftRight => t("keys.right_shift"),
+        Key::MetaLeft => t("keys.left_windows"),
+        Key::MetaRight => t("keys.right_windows"),
+        Key::CapsLock => t("keys.caps_lock"),
+        Key::Space => t("keys.space"),
+        Key::Tab => t("keys.tab"),
+        Key::BackQuote => "`",
+        Key::Insert => t("keys.insert"),
+        Key::ScrollLock => t("keys.scroll_lock"),
+        Key::Pause => t("keys.pause"),
+        _ => {
+            // NOTE: the rest read fine once their prefix is gone, e.g. KeyA, Num1, F5
+            let name = format!("{key:?}");
+            return name
+                .strip_prefix("Key")
+                .or_else(|| name.strip_prefix("Num"))
+                .unwrap_or(&name)
+                .to_owned();
+        }
+    };
+    name.to_owned()
+}
+
+/// Panel shown while recording. Returns `true` when the user cancelled.
+pub fn record_ui(ctx: &egui::Context, current: &str, bindings: &[(String, &str)]) -> bool {
+    let mut cancel = false;
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading(t("hotkey.heading"));
+        ui.add_space(8.0);
+        ui.label(t("hotkey.prompt"));
+        ui.label(tf("hotkey.current", &[("key", &current)]));
+        ui.weak(t("hotkey.escape_hint"));
+        ui.add_space(8.0);
+        ui.label(t("hotkey.shortcuts"));
+        egui::Grid::new("shortcuts").show(ui, |ui| {
+            for (chord, action) in bindings {
+                ui.strong(chord);
+                ui.label(*action);
+                ui.end_row();
+            }
+        });
+        ui.add_space(8.0);
+        cancel = ui.button(t("dialog.cancel")).clicked();
+    });
+    cancel
+}
@@ -0,0 +1,110 @@
+//! User-facing text. Each language is a TOML file in `locales/`, embedded at
+//! build time and named after its code, so adding one takes no code changes.
+//! Keys a translation leaves out fall back to English.
+
+use include_dir::{include_dir, Dir};
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+use std::sync::{Mutex, OnceLock};
+
+static LOCALES: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/locales");
+static CATALOG: OnceLock<Catalog> = OnceLock::new();
+
+const FALLBACK: &str = "en";
+
+struct Catalog {
+    strings: HashMap<String, String>,
+    fallback: HashMap<String, String>,
+    /// Keys already warned about, so a missing label drawn every frame logs once.
+    warned: Mutex<HashSet<&'static str>>,
+}
+
+/// Picks the language: `language` from the config if set, else the Windows
+/// display language, else English. Only the first call counts.
+pub fn init(language: Option<&str>) {
+    CATALOG.get_or_init(|| {
+        let requested = match language {
+            Some(language) => language.to_owned(),
+            None => crate::platform::ui_language().unwrap_or_else(|| FALLBACK.to_owned()),
+        };
+        // NOTE: "de-AT" uses de.toml unless there's a de-AT.toml
+        let code = [
+            requested.as_str(),
+            requested.split('-').next().unwrap_or(""),
+        ]
+        .into_iter()
+        .find(|code| LOCALES.get_file(format!("{code}.toml")).is_some())
+        .unwrap_or_else(|| {
+            log::info!("no translation for {requested:?}, using English");
+            FALLBACK
+        });
+        log::info!("language: {code}");
+        Catalog {
+            strings: load(code),
+            fallback: load(FALLBACK),
+            warned: Default::default(),
+        }
+    });
+}
+
+/// Text for `key`, e.g. `"tray.quit"`.
+pub fn t(key: &'static str) -> &'static str {
+    init(None);
+    let catalog = CATALOG.get().unwrap();
+    if let Some(text) = catalog.strings.get(key) {
+        return text;
+    }
+    let fallback = catalog.fallback.get(key);
+    if catalog.warned.lock().unwrap().insert(key) {
+        match fallback {
+            Some(_) => log::warn!("translation is missing {key:?}, using English"),
+            None => log::warn!("no text for {key:?}"),
+        }
+    }
+    fallback.map_or(key, String::as_str)
+}
+
+/// Text for `key` with each `{name}` in it replaced by its value from `args`.
+pub fn tf(key: &'static str, args: &[(&str, &dyn Display)]) -> String {
+    args.iter().fold(t(key).to_owned(), |text, (name, value)| {
+        text.replace(&format!("{{{name}}}"), &value.to_string())
+    })
+}
+
+/// Flattened `section.key` strings of one locale file. A broken file is
+/// logged and left empty, which falls back to English key by key.
+fn load(code: &str) -> HashMap<String, String> {
+    let Some(contents) = LOCALES
+        .get_file(format!("{code}.toml"))
+        .and_then(|file| file.contents_utf8())
+    else {
+        return HashMap::new();
+    };
+    let table: toml::Table = match toml::from_str(contents) {
+        Ok(table) => table,
+        Err(e) => {
+            log::warn!("locale {code} is invalid: {e}");
+            return HashMap::new();
+        }
+    };
+    let mut strings = HashMap::new();
+    flatten("", &table, &mut strings);
+    strings
+}
+
+fn flatten(prefix: &str, table: &toml::Table, strings: &mut HashMap<String, String>) {
+    for (key, value) in table {
+        let key = if prefix.is_empty() {
+            key.clone()
+        } else {
+            format!("{prefix}.{key}")
+        };
+        match value {
+            toml::Value::String(text) => {
+                strings.insert(key, text.clone());
+            }
+            toml::Value::Table(table) => flatten(&key, table, strings),
+            _ => log::warn!("locale entry {key:?} is not text"),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! Files dropped on one of the app's windows: a sound becomes the ping sound,
+//! a `.pingpack` the theme. Both are checked, then copied into the data
+//! folder so the original can be deleted.
+
+use crate::audio::Layered;
+use crate::config::{self, SoundSpec};
+use crate::error::Error;
+use crate::{storage, theme};
+use std::path::{Path, PathBuf};
+
+/// What rodio decodes with its default features.
+const SOUND_EXTENSIONS: &[&str] = &["ogg", "wav", "mp3", "flac"];
+const PACK_EXTENSION: &str = "pingpack";
+
+/// A dropped file, ready to use from its copy at the path given.
+pub enum Imported {
+    Sound(Layered, PathBuf),
+    Pack(theme::Theme, PathBuf),
+}
+
+pub fn import(file: &Path, limits: &config::Theme) -> Result<Imported, Error> {
+    let failed = |reason: String| Error::Asset {
+        path: file.display().to_string(),
+        reason,
+    };
+    let extension = file
+        .extension()
+        .map(|extension| extension.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    if SOUND_EXTENSIONS.contains(&extension.as_str()) {
+        let sound =
+            Layered::load(&SoundSpec::File(file.to_owned()), "dropped sound").map_err(failed)?;
+        let copy =
+            storage::import(file, &storage::sounds_dir()).map_err(|e| failed(e.to_string()))?;
+        Ok(Imported::Sound(sound, copy))
+    } else if extension == PACK_EXTENSION {
+        let theme = theme::Theme::load_pack(file, limits).map_err(|e| failed(e.to_string()))?;
+        let copy =
+            storage::import(file, &storage::packs_dir()).map_err(|e| failed(e.to_string()))?;
+        Ok(Imported::Pack(theme, copy))
+    } else {
+        Err(failed("not a sound or a .pingpack".into()))
+    }
+}
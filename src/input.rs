@@ -0,0 +1,578 @@
+//! Input, split in two: an [`InputSource`] turns whatever the backend sees
+//! into [`InputEvent`]s, and the [`Interpreter`] turns those into what the
+//! user meant, without touching the hook, the cursor or the clock itself.
+
+use crate::click::{self, Click, PingAt};
+use crate::error::Error;
+use crate::flick::Direction;
+use crate::keybindings::{self, Bindings, Held};
+use crate::priming::{Cues, DoubleTap, Priming};
+use crate::{config, platform, protocol};
+use device_query::mouse_state::MousePosition;
+use device_query::{DeviceQuery, DeviceState};
+use rdev::{Button, Key};
+use std::time::Instant;
+
+/// Shorter drags are a click.
+const DRAG_DISTANCE: f64 = 100.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputEvent {
+    KeyDown(Key),
+    KeyUp(Key),
+    /// With the cursor position, in desktop pixels.
+    ButtonDown(Button, MousePosition),
+    ButtonUp(Button, MousePosition),
+}
+
+/// Where input comes from: the low-level hook, or a test's channel.
+pub trait InputSource {
+    /// Hands every event to `handle` until the source ends. Blocks.
+    fn run(self, handle: impl FnMut(InputEvent) + 'static) -> Result<(), Error>;
+}
+
+/// The global low-level hooks, through rdev. Clicks are placed by polling
+/// the cursor, the hook events don't carry it.
+pub struct RdevSource;
+
+impl InputSource for RdevSource {
+    fn run(self, mut handle: impl FnMut(InputEvent) + 'static) -> Result<(), Error> {
+        let device_state = DeviceState::new();
+        rdev::listen(move |e: rdev::Event| {
+            let event = match e.event_type {
+                // NOTE: our own mask taps come back through the hook, they're no input
+                rdev::EventType::KeyPress(Key::Unknown(key))
+                | rdev::EventType::KeyRelease(Key::Unknown(key))
+                    if key == platform::MASK_KEY as u32 =>
+                {
+                    return;
+                }
+                rdev::EventType::KeyPress(key) => InputEvent::KeyDown(key),
+                rdev::EventType::KeyRelease(key) => InputEvent::KeyUp(key),
+                rdev::EventType::ButtonPress(button) => {
+                    InputEvent::ButtonDown(button, device_state.get_mouse().coords)
+                }
+                rdev::EventType::ButtonRelease(button) => {
+                    InputEvent::ButtonUp(button, device_state.get_mouse().coords)
+                }
+                _ => return,
+            };
+            handle(event);
+        })
+        .map_err(|e| Error::Hook(format!("{e:?}")))
+    }
+}
+
+/// Events sent down a channel, to drive the interpreter without a hook.
+#[cfg(test)]
+pub struct ChannelSource(pub crossbeam::channel::Receiver<InputEvent>);
+
+#[cfg(test)]
+impl InputSource for ChannelSource {
+    fn run(self, mut handle: impl FnMut(InputEvent) + 'static) -> Result<(), Error> {
+        for event in self.0 {
+            handle(event);
+        }
+        Ok(())
+    }
+}
+
+/// What the interpreter needs from outside for one event.
+pub struct Context<'a> {
+    pub now: Instant,
+    pub paused: bool,
+    /// Polls whether the ping key is really down, only asked on left clicks.
+    pub prime_down: &'a dyn Fn() -> bool,
+    /// Flick that ended with this release, if one was being watched.
+    pub flick: Option<Direction>,
+}
+
+/// What the user meant by an event, for the listener to carry out.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Action {
+    /// The ping key was armed or disarmed, play its cue.
+    Cue(bool),
+    /// The ping key was tapped twice, ping at the cursor.
+    DoubleTapped,
+    /// The note chord was pressed, open the note at the cursor.
+    EditNote,
+    /// The caret chord was pressed, ping at the text caret.
+    PingCaret,
+    /// A primed click went down here, watch it for a flick or a window drag.
+    Pressed(MousePosition),
+    /// A primed right drag came up: ping at `target`, with an arrow
+    /// `toward` the release unless the drag was too short.
+    Aimed {
+        target: MousePosition,
+        toward: Option<MousePosition>,
+    },
+    /// The primed click came up. `persistent` is `None` for a drag that
+    /// neither pings nor places a marker.
+    Released {
+        target: MousePosition,
+        kind: protocol::PingKind,
+        persistent: Option<bool>,
+        copy: bool,
+    },
+    /// The click ended without a ping, stop watching it.
+    Abandoned,
+}
+
+/// The priming, chord and click state machines, fed one event at a time.
+pub struct Interpreter {
+    priming: Priming,
+    double_tap: Option<DoubleTap>,
+    bindings: Bindings,
+    held: Held,
+    click: Click,
+    /// The right button's press, for arrows.
+    aim: Click,
+    cues: Cues,
+    ping_at: PingAt,
+    flick: config::Flick,
+    arrow: config::Arrow,
+}
+
+impl Interpreter {
+    pub fn new(
+        bindings: Bindings,
+        priming: Priming,
+        double_tap: Option<DoubleTap>,
+        click: Click,
+        ping_at: PingAt,
+        flick: config::Flick,
+        arrow: config::Arrow,
+    ) -> Self {
+        Self {
+            priming,
+            double_tap,
+            bindings,
+            held: Held::default(),
+            aim: click.clone(),
+            click,
+            cues: Cues::default(),
+            ping_at,
+            flick,
+            arrow,
+        }
+    }
+
+    /// Follows a ping key recorded anew, if it doesn't conflict.
+    pub fn set_prime(&mut self, key: Key) {
+        if self.bindings.prime() != key {
+            if let Ok(updated) = self.bindings.with_prime(key) {
+                self.bindings = updated;
+            }
+        }
+    }
+
+    pub fn handle(&mut self, event: InputEvent, context: &Context) -> Vec<Action> {
+        let mut actions = Vec::new();
+        let now = context.now;
+        let prime = self.bindings.prime();
+        self.held.update(event);
+
+        let primed = match event {
+            InputEvent::ButtonDown(Button::Left, _) | InputEvent::ButtonUp(Button::Left, _) => {
+                self.priming.check(now, (context.prime_down)())
+            }
+            InputEvent::ButtonDown(Button::Right, _) | InputEvent::ButtonUp(Button::Right, _)
+                if self.arrow.enabled =>
+            {
+                self.priming.check(now, (context.prime_down)())
+            }
+            _ => false,
+        };
+        // NOTE: an expired or missed release unprimes here too
+        self.cue(now, &mut actions);
+
+        if let Some(double_tap) = &mut self.double_tap {
+            match event {
+                InputEvent::KeyDown(key) if key == prime => double_tap.press(now),
+                InputEvent::KeyUp(key) if key == prime => {
+                    let tapped_twice = double_tap.release();
+                    if tapped_twice && !context.paused {
+                        actions.push(Action::DoubleTapped);
+                    }
+                }
+                InputEvent::KeyDown(_) | InputEvent::ButtonDown(..) => double_tap.interrupt(),
+                _ => {}
+            }
+        }
+
+        match event {
+            InputEvent::KeyDown(key) if key == prime => {
+                self.priming.press(now);
+                self.cue(now, &mut actions);
+            }
+            InputEvent::KeyUp(key) if key == prime => {
+                self.priming.release();
+                self.cue(now, &mut actions);
+            }
+            InputEvent::KeyDown(key)
+                if self.resolve(keybindings::Trigger::Key(key))
+                    == Some(keybindings::Action::Note)
+                    && self.priming.check(now, true) =>
+            {
+                actions.push(Action::EditNote);
+            }
+            InputEvent::KeyDown(key)
+                if self.resolve(keybindings::Trigger::Key(key))
+                    == Some(keybindings::Action::Caret)
+                    && !context.paused
+                    && self.priming.check(now, true) =>
+            {
+                actions.push(Action::PingCaret);
+            }
+            InputEvent::ButtonDown(Button::Left, position) if primed => {
+                self.click.press(position, now);
+                actions.push(Action::Pressed(position));
+            }
+            InputEvent::ButtonUp(Button::Left, _) if primed && context.paused => {
+                self.click.cancel();
+                actions.push(Action::Abandoned);
+            }
+            InputEvent::ButtonUp(Button::Left, position) if primed => {
+                let Some(gesture) = self.click.release(position, now) else {
+                    actions.push(Action::Abandoned);
+                    return actions;
+                };
+                actions.push(self.released(gesture, context.flick));
+            }
+            InputEvent::ButtonUp(Button::Left, _) => {
+                self.click.cancel();
+                actions.push(Action::Abandoned);
+            }
+            InputEvent::ButtonDown(Button::Right, position) if primed => {
+                self.aim.press(position, now);
+            }
+            InputEvent::ButtonUp(Button::Right, position) if primed && !context.paused => {
+                if let Some(gesture) = self.aim.release(position, now) {
+                    let (_, distance) = movement(gesture.press, gesture.release);
+                    // NOTE: a short drag is a slip, not a direction
+                    let toward = (distance >= self.arrow.min_distance as f64).then_some(position);
+                    actions.push(Action::Aimed {
+                        target: gesture.press,
+                        toward,
+                    });
+                }
+            }
+            InputEvent::ButtonUp(Button::Right, _) => self.aim.cancel(),
+            _ => {}
+        }
+        actions
+    }
+
+    /// What a primed click from press to release pings.
+    fn released(&self, gesture: click::Gesture, flicked: Option<Direction>) -> Action {
+        let (direction, distance) = movement(gesture.press, gesture.release);
+        let kind = match flicked {
+            Some(direction) => self.flick.kind(direction).clone(),
+            None => protocol::PingKind::Missing,
+        };
+        // NOTE: Ctrl + Alt + click (no drag) pings and copies the position
+        let copy = self.resolve(keybindings::Trigger::Click)
+            == Some(keybindings::Action::CopyCoordinates)
+            && distance < DRAG_DISTANCE;
+        let persistent = match direction {
+            _ if copy => Some(false),
+            // NOTE: a flick always pings, even one long enough to be a drag
+            _ if flicked.is_some() => Some(false),
+            Direction::Left if distance >= DRAG_DISTANCE => Some(false),
+            Direction::Right if distance >= DRAG_DISTANCE => Some(true),
+            _ => None,
+        };
+        Action::Released {
+            target: gesture.target(self.ping_at),
+            kind,
+            persistent,
+            copy,
+        }
+    }
+
+    fn resolve(&self, trigger: keybindings::Trigger) -> Option<keybindings::Action> {
+        let prime = self.bindings.prime();
+        self.bindings
+            .resolve(trigger, |key| key == prime || self.held.contains(key))
+    }
+
+    fn cue(&mut self, now: Instant, actions: &mut Vec<Action>) {
+        if let Some(primed) = self.cues.update(self.priming.is_primed(), now) {
+            actions.push(Action::Cue(primed));
+        }
+    }
+}
+
+/// Main direction and length of a drag.
+fn movement(press: MousePosition, release: MousePosition) -> (Direction, f64) {
+    let (dx, dy) = (release.0 - press.0, release.1 - press.1);
+    let direction = match (dx.abs() > dy.abs(), dx > 0, dy > 0) {
+        (true, true, _) => Direction::Right,
+        (true, false, _) => Direction::Left,
+        (false, _, true) => Direction::Down,
+        (false, _, false) => Direction::Up,
+    };
+    let distance = ((dx * dx + dy * dy) as f64).sqrt();
+    (direction, distance)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use InputEvent::{ButtonDown, ButtonUp, KeyDown, KeyUp};
+
+    /// An interpreter fed events at set times, with the polled key state
+    /// and pause under the test's control.
+    struct Script {
+        interpreter: Interpreter,
+        start: Instant,
+        prime_down: bool,
+        paused: bool,
+    }
+
+    impl Script {
+        fn new(configure: impl FnOnce(&mut config::Config)) -> Self {
+            let mut config = config::Config::default();
+            configure(&mut config);
+            let input = &config.input;
+            let interpreter = Interpreter::new(
+                Bindings::new(&config).unwrap(),
+                Priming::new(Duration::from_secs_f32(input.prime_expiry_secs)),
+                input
+                    .double_tap
+                    .then(|| DoubleTap::new(Duration::from_millis(input.double_tap_ms))),
+                Click::new(Duration::from_millis(input.release_timeout_ms)),
+                input.ping_at,
+                config.flick.clone(),
+                config.arrow.clone(),
+            );
+            Self {
+                interpreter,
+                start: Instant::now(),
+                prime_down: true,
+                paused: false,
+            }
+        }
+
+        fn feed(&mut self, at_ms: u64, event: InputEvent) -> Vec<Action> {
+            let prime_down = self.prime_down;
+            let context = Context {
+                now: self.start + Duration::from_millis(at_ms),
+                paused: self.paused,
+                prime_down: &move || prime_down,
+                flick: None,
+            };
+            self.interpreter.handle(event, &context)
+        }
+
+        /// Left button down at `from` and up at `to`, returning the release.
+        fn click(&mut self, at_ms: u64, from: MousePosition, to: MousePosition) -> Vec<Action> {
+            self.feed(at_ms, ButtonDown(Button::Left, from));
+            self.feed(at_ms + 50, ButtonUp(Button::Left, to))
+        }
+    }
+
+    fn released(actions: &[Action]) -> Option<(MousePosition, Option<bool>, bool)> {
+        actions.iter().find_map(|action| match action {
+            Action::Released {
+                target,
+                persistent,
+                copy,
+                ..
+            } => Some((*target, *persistent, *copy)),
+            _ => None,
+        })
+    }
+
+    #[test]
+    fn ping_key_cues_once_per_change() {
+        let mut script = Script::new(|_| {});
+        assert_eq!(script.feed(0, KeyDown(Key::Alt)), [Action::Cue(true)]);
+        // NOTE: auto-repeat presses the key again and again
+        assert_eq!(script.feed(500, KeyDown(Key::Alt)), []);
+        assert_eq!(script.feed(1500, KeyUp(Key::Alt)), [Action::Cue(false)]);
+    }
+
+    #[test]
+    fn primed_drags_ping_left_and_place_markers_right() {
+        let mut script = Script::new(|_| {});
+        script.feed(0, KeyDown(Key::Alt));
+        let left = script.click(100, (500, 500), (300, 500));
+        assert_eq!(released(&left), Some(((500, 500), Some(false), false)));
+        let right = script.click(200, (500, 500), (700, 500));
+        assert_eq!(released(&right), Some(((500, 500), Some(true), false)));
+    }
+
+    #[test]
+    fn short_primed_click_does_nothing() {
+        let mut script = Script::new(|_| {});
+        script.feed(0, KeyDown(Key::Alt));
+        let down = script.feed(100, ButtonDown(Button::Left, (10, 10)));
+        assert_eq!(down, [Action::Pressed((10, 10))]);
+        let up = script.feed(150, ButtonUp(Button::Left, (20, 10)));
+        assert_eq!(released(&up), Some(((10, 10), None, false)));
+    }
+
+    #[test]
+    fn unprimed_clicks_are_left_alone() {
+        let mut script = Script::new(|_| {});
+        assert_eq!(script.feed(0, ButtonDown(Button::Left, (0, 0))), []);
+        assert_eq!(
+            script.feed(50, ButtonUp(Button::Left, (300, 0))),
+            [Action::Abandoned]
+        );
+    }
+
+    #[test]
+    fn ping_at_release_targets_where_the_button_came_up() {
+        let mut script = Script::new(|config| config.input.ping_at = PingAt::OnRelease);
+        script.feed(0, KeyDown(Key::Alt));
+        let actions = script.click(100, (500, 500), (300, 500));
+        assert_eq!(released(&actions), Some(((300, 500), Some(false), false)));
+    }
+
+    #[test]
+    fn stuck_ping_key_is_caught_by_the_polled_state() {
+        let mut script = Script::new(|_| {});
+        script.feed(0, KeyDown(Key::Alt));
+        // NOTE: Alt+Tab swallowed the release, the poller says it's up
+        script.prime_down = false;
+        let actions = script.feed(1500, ButtonDown(Button::Left, (0, 0)));
+        assert_eq!(actions, [Action::Cue(false)]);
+        script.prime_down = true;
+        assert_eq!(script.feed(1600, ButtonDown(Button::Left, (0, 0))), []);
+    }
+
+    #[test]
+    fn stuck_ping_key_expires() {
+        let mut script = Script::new(|_| {});
+        script.feed(0, KeyDown(Key::Alt));
+        let actions = script.feed(3001, ButtonDown(Button::Left, (0, 0)));
+        assert_eq!(actions, [Action::Cue(false)]);
+    }
+
+    #[test]
+    fn key_repeat_keeps_a_long_hold_primed() {
+        let mut script = Script::new(|_| {});
+        for at_ms in (0..=6000).step_by(500) {
+            script.feed(at_ms, KeyDown(Key::Alt));
+        }
+        let actions = script.feed(6100, ButtonDown(Button::Left, (0, 0)));
+        assert_eq!(actions, [Action::Pressed((0, 0))]);
+    }
+
+    #[test]
+    fn release_after_the_timeout_is_abandoned() {
+        let mut script = Script::new(|config| config.input.release_timeout_ms = 1000);
+        script.feed(0, KeyDown(Key::Alt));
+        script.feed(100, ButtonDown(Button::Left, (0, 0)));
+        script.feed(1000, KeyDown(Key::Alt));
+        let actions = script.feed(1200, ButtonUp(Button::Left, (300, 0)));
+        assert_eq!(actions, [Action::Abandoned]);
+    }
+
+    #[test]
+    fn ctrl_chord_click_copies_the_position() {
+        let mut script = Script::new(|config| config.copy_coordinates.enabled = true);
+        script.feed(0, KeyDown(Key::Alt));
+        script.feed(10, KeyDown(Key::ControlLeft));
+        let actions = script.click(100, (40, 40), (45, 40));
+        assert_eq!(released(&actions), Some(((40, 40), Some(false), true)));
+    }
+
+    #[test]
+    fn released_ctrl_no_longer_completes_the_chord() {
+        let mut script = Script::new(|config| config.copy_coordinates.enabled = true);
+        script.feed(0, KeyDown(Key::ControlRight));
+        script.feed(10, KeyUp(Key::ControlRight));
+        script.feed(20, KeyDown(Key::Alt));
+        let actions = script.click(100, (40, 40), (45, 40));
+        assert_eq!(released(&actions), Some(((40, 40), None, false)));
+    }
+
+    #[test]
+    fn note_key_edits_a_note_only_while_primed() {
+        let mut script = Script::new(|_| {});
+        assert_eq!(script.feed(0, KeyDown(Key::KeyN)), []);
+        script.feed(10, KeyDown(Key::Alt));
+        assert_eq!(script.feed(20, KeyDown(Key::KeyN)), [Action::EditNote]);
+    }
+
+    #[test]
+    fn caret_chord_needs_ctrl_and_no_pause() {
+        let mut script = Script::new(|_| {});
+        script.feed(0, KeyDown(Key::Alt));
+        assert_eq!(script.feed(10, KeyDown(Key::SemiColon)), []);
+        script.feed(20, KeyDown(Key::ControlLeft));
+        assert_eq!(
+            script.feed(30, KeyDown(Key::SemiColon)),
+            [Action::PingCaret]
+        );
+        script.paused = true;
+        assert_eq!(script.feed(40, KeyDown(Key::SemiColon)), []);
+    }
+
+    fn double_tapping() -> Script {
+        Script::new(|config| config.input.double_tap = true)
+    }
+
+    fn tap(script: &mut Script, at_ms: u64) -> Vec<Action> {
+        script.feed(at_ms, KeyDown(Key::Alt));
+        script.feed(at_ms + 50, KeyUp(Key::Alt))
+    }
+
+    #[test]
+    fn two_quick_taps_ping() {
+        let mut script = double_tapping();
+        assert!(!tap(&mut script, 0).contains(&Action::DoubleTapped));
+        assert!(tap(&mut script, 200).contains(&Action::DoubleTapped));
+        // NOTE: a third tap starts over rather than pinging again
+        assert!(!tap(&mut script, 400).contains(&Action::DoubleTapped));
+    }
+
+    #[test]
+    fn slow_taps_dont_ping() {
+        let mut script = double_tapping();
+        tap(&mut script, 0);
+        assert!(!tap(&mut script, 351).contains(&Action::DoubleTapped));
+    }
+
+    #[test]
+    fn keys_or_clicks_between_taps_cancel_them() {
+        let mut script = double_tapping();
+        tap(&mut script, 0);
+        script.feed(100, KeyDown(Key::Tab));
+        assert!(!tap(&mut script, 200).contains(&Action::DoubleTapped));
+
+        tap(&mut script, 1000);
+        script.feed(1100, ButtonDown(Button::Left, (0, 0)));
+        assert!(!tap(&mut script, 1200).contains(&Action::DoubleTapped));
+    }
+
+    #[test]
+    fn double_tap_is_ignored_while_paused() {
+        let mut script = double_tapping();
+        script.paused = true;
+        tap(&mut script, 0);
+        assert!(!tap(&mut script, 200).contains(&Action::DoubleTapped));
+    }
+
+    #[test]
+    fn channel_source_feeds_every_event_in_order() {
+        let (sender, receiver) = crossbeam::channel::unbounded();
+        for event in [KeyDown(Key::Alt), ButtonDown(Button::Left, (1, 2))] {
+            sender.send(event).unwrap();
+        }
+        drop(sender);
+        let seen = std::rc::Rc::new(std::cell::RefCell::new(Vec::new()));
+        let sink = seen.clone();
+        ChannelSource(receiver)
+            .run(move |event| sink.borrow_mut().push(event))
+            .unwrap();
+        assert_eq!(
+            *seen.borrow(),
+            [KeyDown(Key::Alt), ButtonDown(Button::Left, (1, 2))]
+        );
+    }
+}
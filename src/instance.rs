@@ -0,0 +1,158 @@
+//! Names that keep instances apart. Each user runs one instance per Windows
+//! session, plus any started with `--instance <name>`, which get their own
+//! data folder too. The lock and the control pipe are named after the user,
+//! the session and the instance, so nothing is shared across sessions.
+
+use crate::platform;
+
+const APP: &str = "screen-pinger";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub session: u32,
+    pub user: String,
+    /// From `--instance`, `None` for the default instance.
+    pub instance: Option<String>,
+}
+
+impl Identity {
+    pub fn current() -> Self {
+        Self {
+            session: platform::session_id(),
+            user: std::env::var("USERNAME").unwrap_or_default(),
+            instance: from_args(),
+        }
+    }
+
+    /// The mutex held while the instance runs. `Local\` names are per
+    /// session already.
+    pub fn lock_name(&self) -> String {
+        format!(r"Local\{}", self.name())
+    }
+
+    /// The control pipe unless `ipc.pipe` names one. Pipe names are machine
+    /// wide, so this one carries the session.
+    pub fn pipe_name(&self) -> String {
+        format!(r"\\.\pipe\{}.{}", self.name(), self.session)
+    }
+
+    fn name(&self) -> String {
+        let mut name = format!("{APP}.{}", sanitize(&self.user));
+        if let Some(instance) = &self.instance {
+            name.push('.');
+            name.push_str(&sanitize(instance));
+        }
+        name
+    }
+}
+
+/// The name given with `--instance`, if any.
+pub fn from_args() -> Option<String> {
+    instance_arg(std::env::args().skip(1))
+}
+
+fn instance_arg(args: impl IntoIterator<Item = String>) -> Option<String> {
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        if arg == "--instance" {
+            return args.next().filter(|name| !name.is_empty());
+        }
+    }
+    None
+}
+
+/// Lowercase ASCII letters, digits, `-` and `_`, anything else is `_`. Fit
+/// for kernel object, pipe and folder names, and the same however the user
+/// name is cased.
+pub fn sanitize(part: &str) -> String {
+    part.chars()
+        .map(|c| match c.to_ascii_lowercase() {
+            c @ ('a'..='z' | '0'..='9' | '-' | '_') => c,
+            _ => '_',
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn identity(session: u32, user: &str, instance: Option<&str>) -> Identity {
+        Identity {
+            session,
+            user: user.to_owned(),
+            instance: instance.map(str::to_owned),
+        }
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|arg| arg.to_string()).collect()
+    }
+
+    #[test]
+    fn sanitize_keeps_lowercase_letters_digits_dashes_and_underscores() {
+        assert_eq!(sanitize("alice-2_b"), "alice-2_b");
+        assert_eq!(sanitize("Alice"), "alice");
+        assert_eq!(sanitize(r"DOMAIN\Bob Smith"), "domain_bob_smith");
+        assert_eq!(sanitize("a.b/c:d"), "a_b_c_d");
+        // NOTE: one `_` per character, not per byte
+        assert_eq!(sanitize("José"), "jos_");
+        assert_eq!(sanitize(""), "");
+    }
+
+    #[test]
+    fn default_instance_is_named_after_the_user() {
+        let identity = identity(1, "Alice", None);
+        assert_eq!(identity.lock_name(), r"Local\screen-pinger.alice");
+        assert_eq!(identity.pipe_name(), r"\\.\pipe\screen-pinger.alice.1");
+    }
+
+    #[test]
+    fn named_instance_adds_its_name() {
+        let identity = identity(2, "Alice", Some("Streaming Setup"));
+        assert_eq!(
+            identity.lock_name(),
+            r"Local\screen-pinger.alice.streaming_setup"
+        );
+        assert_eq!(
+            identity.pipe_name(),
+            r"\\.\pipe\screen-pinger.alice.streaming_setup.2"
+        );
+    }
+
+    #[test]
+    fn users_in_other_sessions_get_other_pipes() {
+        let alice = identity(1, "alice", None);
+        let bob = identity(2, "bob", None);
+        assert_ne!(alice.pipe_name(), bob.pipe_name());
+        // NOTE: the same user logged on twice, e.g. console and remote desktop
+        assert_ne!(alice.pipe_name(), identity(3, "alice", None).pipe_name());
+    }
+
+    #[test]
+    fn user_name_case_doesnt_make_another_instance() {
+        let lower = identity(1, "alice", None);
+        let upper = identity(1, "ALICE", None);
+        assert_eq!(lower.lock_name(), upper.lock_name());
+        assert_eq!(lower.pipe_name(), upper.pipe_name());
+    }
+
+    #[test]
+    fn instance_names_cant_pass_for_a_user() {
+        // NOTE: `.` is sanitized away, so the separators stay unambiguous
+        let named = identity(1, "alice", Some("bob"));
+        let dotted = identity(1, "alice.bob", None);
+        assert_ne!(named.lock_name(), dotted.lock_name());
+    }
+
+    #[test]
+    fn instance_comes_from_the_flag_after_it() {
+        assert_eq!(
+            instance_arg(args(&["--no-tray", "--instance", "work"])),
+            Some("work".to_owned())
+        );
+        assert_eq!(instance_arg(args(&["--no-tray"])), None);
+        assert_eq!(instance_arg(args(&["--instance"])), None);
+        assert_eq!(instance_arg(args(&["--instance", ""])), None);
+    }
+}
@@ -0,0 +1,353 @@
+//! Local control pipe for scripts: one request line in, one JSON line back.
+//!
+//! ```text
+//! > status
+//! < {"version":"0.1.0","uptime_secs":42,"paused":false,...}
+//! > ping {"x":200,"y":300,"kind":"danger","seq":7}
+//! < {"ok":true,"seq":7}
+//! ```
+//!
+//! A ping is only answered once the overlay drew it, so a client knows it
+//! was seen rather than just queued.
+
+use crate::layout::MonitorRect;
+use crate::platform::PipeConnection;
+use crate::protocol::PingMessage;
+use crossbeam::channel::Sender;
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::ERROR_ACCESS_DENIED;
+
+/// How long a request waits on the event loop before giving up.
+const REPLY_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// How long a ping may take to be drawn before its sender is told it
+/// wasn't, short of [`REPLY_TIMEOUT`] so the answer still gets through.
+pub const ACK_TIMEOUT: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Serialize)]
+pub struct Status {
+    pub version: &'static str,
+    pub uptime_secs: u64,
+    pub paused: bool,
+    /// Sound muted for Focus Assist or a full-screen app.
+    pub muted: bool,
+    pub animations: usize,
+    /// Whether the overlay exists yet, it's only built on the first ping.
+    pub overlay: bool,
+    /// How the overlay draws, `None` before it's built.
+    pub graphics: Option<crate::diagnostics::Graphics>,
+    pub monitors: Vec<MonitorRect>,
+    /// Sounds that failed to play since startup.
+    pub audio_errors: u64,
+    /// What pings are heard through.
+    pub audio_backend: crate::audio::Backend,
+    /// Last warning or error logged.
+    pub last_error: Option<String>,
+}
+
+/// A request for the event loop, answered on the sender it carries.
+pub enum Request {
+    Status(Sender<Status>),
+    /// Shows a ping, `Err` says why it wasn't.
+    Ping(PingMessage, Sender<Result<Ack, String>>),
+}
+
+/// A ping made it on screen, echoing the `seq` it was sent with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Ack {
+    pub seq: Option<u64>,
+}
+
+/// Answers for pings fired but not drawn yet, held by the event loop.
+#[derive(Default)]
+pub struct PendingAcks {
+    pending: Vec<Pending>,
+}
+
+struct Pending {
+    animation: usize,
+    seq: Option<u64>,
+    deadline: Instant,
+    reply: Sender<Result<Ack, String>>,
+}
+
+impl PendingAcks {
+    /// Holds `reply` until `animation` is drawn or [`ACK_TIMEOUT`] passes.
+    pub fn wait(
+        &mut self,
+        animation: usize,
+        seq: Option<u64>,
+        reply: Sender<Result<Ack, String>>,
+        now: Instant,
+    ) {
+        self.pending.push(Pending {
+            animation,
+            seq,
+            deadline: now + ACK_TIMEOUT,
+            reply,
+        });
+    }
+
+    /// Acks every ping whose animation is on screen after a redraw.
+    pub fn drawn(&mut self, on_screen: impl Fn(usize) -> bool) {
+        self.pending.retain(|pending| {
+            if !on_screen(pending.animation) {
+                return true;
+            }
+            pending.reply.send(Ok(Ack { seq: pending.seq })).ok();
+            false
+        });
+    }
+
+    /// When the next ping runs out of time, to wake the event loop for it.
+    pub fn deadline(&self) -> Option<Instant> {
+        self.pending.iter().map(|pending| pending.deadline).min()
+    }
+
+    /// Tells the senders of pings still not drawn by `now` they weren't.
+    pub fn expire(&mut self, now: Instant) {
+        self.pending.retain(|pending| {
+            if pending.deadline > now {
+                return true;
+            }
+            log::info!(
+                "ping {} not drawn within {ACK_TIMEOUT:?}",
+                pending.animation
+            );
+            let error = format!("not drawn within {ACK_TIMEOUT:?}");
+            pending.reply.send(Err(error)).ok();
+            false
+        });
+    }
+}
+
+/// A request line, parsed.
+#[derive(Debug, PartialEq)]
+enum Line {
+    Status,
+    Ping(PingMessage),
+}
+
+fn parse(line: &str) -> Result<Line, String> {
+    let (verb, rest) = line.split_once(' ').unwrap_or((line, ""));
+    match verb {
+        "status" if rest.is_empty() => Ok(Line::Status),
+        "ping" => serde_json::from_str(rest)
+            .map(Line::Ping)
+            .map_err(|e| format!("invalid ping: {e}")),
+        _ => Err(format!("unknown request {line:?}")),
+    }
+}
+
+/// Serves pipe `name` on background threads. `forward` hands each request
+/// to the event loop, which answers on its sender; it returns `false` once
+/// the event loop is gone.
+pub fn spawn(name: String, forward: impl Fn(Request) -> bool + Send + Sync + 'static) {
+    let forward = Arc::new(forward);
+    let mut first = true;
+    std::thread::spawn(move || loop {
+        let pipe = match PipeConnection::accept(&name, first) {
+            Ok(pipe) => pipe,
+            Err(e) if first && e.code() == ERROR_ACCESS_DENIED.to_hresult() => {
+                log::warn!(
+                    "control pipe {name} is served by another process, set ipc.pipe to run both"
+                );
+                return;
+            }
+            Err(e) => {
+                log::warn!("control pipe {name} unavailable: {e}");
+                return;
+            }
+        };
+        first = false;
+        // NOTE: one thread per client, so a client that never sends can't block the rest
+        let forward = forward.clone();
+        std::thread::spawn(move || {
+            let Some(line) = read_request(&pipe) else {
+                return;
+            };
+            let reply = match parse(&line) {
+                Ok(Line::Status) => status(&*forward),
+                Ok(Line::Ping(message)) => ping(&*forward, message),
+                Err(e) => error(&e),
+            };
+            if let Err(e) = pipe.write_all(format!("{reply}\n").as_bytes()) {
+                log::info!("control pipe client left early: {e}");
+            }
+        });
+    });
+}
+
+fn status(forward: &dyn Fn(Request) -> bool) -> String {
+    let (sender, receiver) = crossbeam::channel::bounded(1);
+    if !forward(Request::Status(sender)) {
+        return error("shutting down");
+    }
+    match receiver.recv_timeout(REPLY_TIMEOUT) {
+        Ok(status) => serde_json::to_string(&status).expect("status is always serializable"),
+        Err(_) => error("event loop did not answer"),
+    }
+}
+
+fn ping(forward: &dyn Fn(Request) -> bool, message: PingMessage) -> String {
+    let (sender, receiver) = crossbeam::channel::bounded(1);
+    if !forward(Request::Ping(message, sender)) {
+        return error("shutting down");
+    }
+    match receiver.recv_timeout(REPLY_TIMEOUT) {
+        Ok(Ok(Ack { seq: None })) => serde_json::json!({ "ok": true }).to_string(),
+        Ok(Ok(Ack { seq: Some(seq) })) => serde_json::json!({ "ok": true, "seq": seq }).to_string(),
+        Ok(Err(e)) => error(&e),
+        Err(_) => error("event loop did not answer"),
+    }
+}
+
+fn error(message: &str) -> String {
+    serde_json::json!({ "error": message }).to_string()
+}
+
+/// First line the client sent, trimmed. `None` if it disconnected first.
+fn read_request(pipe: &PipeConnection) -> Option<String> {
+    let mut request = Vec::new();
+    let mut buffer = [0; 512];
+    while !request.contains(&b'\n') && request.len() < 4096 {
+        match pipe.read(&mut buffer) {
+            Ok(0) => break,
+            Ok(read) => request.extend_from_slice(&buffer[..read]),
+            Err(_) if request.is_empty() => return None,
+            Err(_) => break,
+        }
+    }
+    let line = request.split(|&byte| byte == b'\n').next().unwrap_or(&[]);
+    Some(String::from_utf8_lossy(line).trim().to_owned())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::protocol::PingKind;
+
+    #[test]
+    fn status() {
+        assert_eq!(parse("status"), Ok(Line::Status));
+        assert!(parse("status now").is_err());
+    }
+
+    #[test]
+    fn ping() {
+        assert_eq!(
+            parse(r#"ping {"x":-20,"y":300,"kind":"danger"}"#),
+            Ok(Line::Ping(PingMessage {
+                kind: PingKind::Danger,
+                x: -20,
+                y: 300,
+                persistent: false,
+                from: None,
+                seq: None,
+            }))
+        );
+        assert!(matches!(
+            parse(r#"ping {"x":1,"y":2,"kind":"laser","persistent":true}"#),
+            Ok(Line::Ping(PingMessage {
+                kind: PingKind::Other(kind),
+                persistent: true,
+                ..
+            })) if kind == "laser"
+        ));
+        assert!(matches!(
+            parse(r#"ping {"x":1,"y":2,"from":"laptop"}"#),
+            Ok(Line::Ping(PingMessage { from: Some(from), .. })) if from == "laptop"
+        ));
+        assert!(matches!(
+            parse(r#"ping {"x":1,"y":2,"seq":7}"#),
+            Ok(Line::Ping(PingMessage { seq: Some(7), .. }))
+        ));
+    }
+
+    #[test]
+    fn malformed_pings_are_refused() {
+        assert!(parse("ping").unwrap_err().starts_with("invalid ping"));
+        assert!(parse(r#"ping {"x":1}"#).is_err());
+        assert!(parse(r#"ping {"x":"left","y":2}"#).is_err());
+    }
+
+    #[test]
+    fn unknown_requests_are_refused() {
+        assert_eq!(parse("quit"), Err(r#"unknown request "quit""#.to_owned()));
+        assert!(parse("").is_err());
+    }
+
+    /// Serves pings like the event loop does, firing each as animation `id`
+    /// and redrawing with `on_screen` drawn until `until` runs out.
+    fn event_loop(id: usize, on_screen: usize, until: Duration) -> impl Fn(Request) -> bool {
+        move |request| {
+            let Request::Ping(message, reply) = request else {
+                return false;
+            };
+            std::thread::spawn(move || {
+                let mut pending = PendingAcks::default();
+                let start = Instant::now();
+                pending.wait(id, message.seq, reply, start);
+                assert_eq!(pending.deadline(), Some(start + ACK_TIMEOUT));
+                let mut now = start;
+                while pending.deadline().is_some() && now - start < until {
+                    now += Duration::from_millis(16);
+                    pending.drawn(|id| id == on_screen);
+                    pending.expire(now);
+                }
+            });
+            true
+        }
+    }
+
+    fn message(seq: Option<u64>) -> PingMessage {
+        PingMessage {
+            kind: PingKind::Danger,
+            x: 1,
+            y: 2,
+            persistent: false,
+            from: None,
+            seq,
+        }
+    }
+
+    #[test]
+    fn pings_are_acked_once_drawn() {
+        let forward = event_loop(3, 3, ACK_TIMEOUT * 2);
+        assert_eq!(
+            super::ping(&forward, message(Some(7))),
+            r#"{"ok":true,"seq":7}"#
+        );
+        assert_eq!(super::ping(&forward, message(None)), r#"{"ok":true}"#);
+    }
+
+    #[test]
+    fn pings_never_drawn_time_out() {
+        let forward = event_loop(3, 4, ACK_TIMEOUT * 2);
+        assert_eq!(
+            super::ping(&forward, message(Some(7))),
+            r#"{"error":"not drawn within 1s"}"#
+        );
+    }
+
+    #[test]
+    fn acks_wait_for_their_own_animation() {
+        let (first, first_answer) = crossbeam::channel::bounded(1);
+        let (second, second_answer) = crossbeam::channel::bounded(1);
+        let start = Instant::now();
+        let mut pending = PendingAcks::default();
+        pending.wait(1, Some(1), first, start);
+        pending.wait(2, Some(2), second, start + Duration::from_millis(500));
+        pending.drawn(|id| id == 2);
+        assert_eq!(second_answer.try_recv(), Ok(Ok(Ack { seq: Some(2) })));
+        assert!(first_answer.try_recv().is_err());
+        assert_eq!(pending.deadline(), Some(start + ACK_TIMEOUT));
+        pending.expire(start + ACK_TIMEOUT - Duration::from_millis(1));
+        assert!(first_answer.try_recv().is_err());
+        pending.expire(start + ACK_TIMEOUT);
+        assert!(first_answer.try_recv().unwrap().is_err());
+        assert_eq!(pending.deadline(), None);
+    }
+}
@@ -0,0 +1,485 @@
+//! Every key chord the app reacts to, in one place: built from the config,
+//! checked against each other, matched against hook events and listed for
+//! the UI.
+
+use crate::config::Config;
+use crate::hotkey;
+use crate::i18n::{t, tf};
+use crate::input::InputEvent;
+use rdev::Key;
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// Ping key + click, the drag decides between a ping and a marker.
+    Ping,
+    /// Ping key + Ctrl + click.
+    CopyCoordinates,
+    /// Ping key + the note key.
+    Note,
+    /// Ping key + Ctrl + the caret key, pings at the text caret.
+    Caret,
+}
+
+impl Action {
+    pub fn label(self) -> &'static str {
+        t(match self {
+            Action::Ping => "keybindings.ping",
+            Action::CopyCoordinates => "keybindings.copy_coordinates",
+            Action::Note => "keybindings.note",
+            Action::Caret => "keybindings.caret",
+        })
+    }
+}
+
+/// What completes a chord once its held keys are down.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Key(Key),
+    Click,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord {
+    pub held: Vec<Key>,
+    pub trigger: Trigger,
+}
+
+impl Chord {
+    fn new(held: &[Key], trigger: Trigger) -> Self {
+        Self {
+            held: held.iter().copied().map(canonical).collect(),
+            trigger,
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let trigger = match self.trigger {
+            Trigger::Key(key) => hotkey::label(key),
+            Trigger::Click => t("keys.click").to_owned(),
+        };
+        // NOTE: modifiers first, the way chords are usually written
+        let mut held = self.held.to_vec();
+        held.sort_by_key(|key| *key != Key::ControlLeft);
+        held.into_iter()
+            .map(hotkey::label)
+            .chain(Some(trigger))
+            .collect::<Vec<_>>()
+            .join("+")
+    }
+
+    fn matches(&self, trigger: Trigger, is_down: &impl Fn(Key) -> bool) -> bool {
+        self.trigger == trigger && self.held.iter().all(|key| is_down(*key))
+    }
+
+    /// Whether pressing toward `other` completes this one on the way, e.g.
+    /// Alt+Shift as a trigger against Alt+Shift held for a click.
+    fn shadows(&self, other: &Chord) -> bool {
+        let Trigger::Key(key) = self.trigger else {
+            return false;
+        };
+        other.held.contains(&canonical(key))
+            && self.held.iter().all(|held| other.held.contains(held))
+    }
+
+    /// Same keys in any order.
+    fn same(&self, other: &Chord) -> bool {
+        let held: HashSet<_> = self.held.iter().collect();
+        self.trigger == other.trigger && held == other.held.iter().collect()
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Binding {
+    pub action: Action,
+    pub chord: Chord,
+}
+
+/// Two bindings that can't both be active.
+#[derive(Debug)]
+pub struct Conflict {
+    pub first: Binding,
+    pub second: Binding,
+}
+
+impl std::fmt::Display for Conflict {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let text = if self.first.action == self.second.action {
+            tf(
+                "keybindings.holds_trigger",
+                &[
+                    ("chord", &self.first.chord.label()),
+                    ("action", &self.first.action.label()),
+                ],
+            )
+        } else {
+            tf(
+                "keybindings.conflicts",
+                &[
+                    ("first", &self.first.chord.label()),
+                    ("first_action", &self.first.action.label()),
+                    ("second", &self.second.chord.label()),
+                    ("second_action", &self.second.action.label()),
+                ],
+            )
+        };
+        f.write_str(&text)
+    }
+}
+
+/// The active chords, free of conflicts.
+#[derive(Debug, Clone)]
+pub struct Bindings {
+    prime: Key,
+    bindings: Vec<Binding>,
+}
+
+impl Bindings {
+    /// The chords `config` asks for, `Err` naming the first two that conflict.
+    pub fn new(config: &Config) -> Result<Self, Conflict> {
+        let input = &config.input;
+        let prime = input.prime_key;
+        let mut bindings = vec![Binding {
+            action: Action::Ping,
+            chord: Chord::new(&[prime], Trigger::Click),
+        }];
+        if config.copy_coordinates.enabled {
+            bindings.push(Binding {
+                action: Action::CopyCoordinates,
+                chord: Chord::new(&[prime, Key::ControlLeft], Trigger::Click),
+            });
+        }
+        bindings.push(Binding {
+            action: Action::Note,
+            chord: Chord::new(&[prime], Trigger::Key(input.note_key)),
+        });
+        bindings.push(Binding {
+            action: Action::Caret,
+            chord: Chord::new(&[prime, Key::ControlLeft], Trigger::Key(input.caret_key)),
+        });
+        check(&bindings)?;
+        Ok(Self { prime, bindings })
+    }
+
+    /// The same chords with `key` arming pings instead.
+    pub fn with_prime(&self, key: Key) -> Result<Self, Conflict> {
+        let (old, new) = (canonical(self.prime), canonical(key));
+        let mut bindings = self.bindings.clone();
+        for binding in &mut bindings {
+            for held in &mut binding.chord.held {
+                if *held == old {
+                    *held = new;
+                }
+            }
+        }
+        check(&bindings)?;
+        Ok(Self {
+            prime: key,
+            bindings,
+        })
+    }
+
+    pub fn prime(&self) -> Key {
+        self.prime
+    }
+
+    /// The action `trigger` completes, with `is_down` telling which keys are
+    /// held. The chord holding the most keys wins, so Alt+Ctrl+click beats
+    /// Alt+click; between equally specific ones the first defined does.
+    pub fn resolve(&self, trigger: Trigger, is_down: impl Fn(Key) -> bool) -> Option<Action> {
+        let trigger = match trigger {
+            Trigger::Key(key) => Trigger::Key(canonical(key)),
+            Trigger::Click => Trigger::Click,
+        };
+        self.bindings
+            .iter()
+            .filter(|binding| binding.chord.matches(trigger, &is_down))
+            .min_by_key(|binding| std::cmp::Reverse(binding.chord.held.len()))
+            .map(|binding| binding.action)
+    }
+
+    /// Chord and action names, for display.
+    pub fn describe(&self) -> Vec<(String, &'static str)> {
+        self.bindings
+            .iter()
+            .map(|binding| (binding.chord.label(), binding.action.label()))
+            .collect()
+    }
+}
+
+fn check(bindings: &[Binding]) -> Result<(), Conflict> {
+    for (i, first) in bindings.iter().enumerate() {
+        if first
+            .chord
+            .shadows(&Chord::new(&first.chord.held, Trigger::Click))
+        {
+            return Err(Conflict {
+                first: first.clone(),
+                second: first.clone(),
+            });
+        }
+        for second in &bindings[i + 1..] {
+            if first.chord.same(&second.chord)
+                || first.chord.shadows(&second.chord)
+                || second.chord.shadows(&first.chord)
+            {
+                return Err(Conflict {
+                    first: first.clone(),
+                    second: second.clone(),
+                });
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Both Ctrl keys count as the left one, chords don't tell them apart.
+fn canonical(key: Key) -> Key {
+    match key {
+        Key::ControlRight => Key::ControlLeft,
+        key => key,
+    }
+}
+
+/// Keys down as seen by the hook. Repeats of a held key change nothing, a
+/// release that was never pressed is ignored.
+#[derive(Debug, Default)]
+pub struct Held(HashSet<Key>);
+
+impl Held {
+    pub fn update(&mut self, event: InputEvent) {
+        match event {
+            InputEvent::KeyDown(key) => {
+                self.0.insert(canonical(key));
+            }
+            InputEvent::KeyUp(key) => {
+                self.0.remove(&canonical(key));
+            }
+            _ => {}
+        }
+    }
+
+    pub fn contains(&self, key: Key) -> bool {
+        self.0.contains(&canonical(key))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use InputEvent::{KeyDown, KeyUp};
+
+    fn bindings() -> Bindings {
+        Bindings::new(&Config::default()).unwrap()
+    }
+
+    /// The action completed by `trigger` once `events` went through the hook.
+    fn after(bindings: &Bindings, events: &[InputEvent], trigger: Trigger) -> Option<Action> {
+        let mut held = Held::default();
+        for event in events {
+            held.update(*event);
+        }
+        bindings.resolve(trigger, |key| held.contains(key))
+    }
+
+    fn conflict(configure: impl FnOnce(&mut Config)) -> (Action, Action) {
+        let mut config = Config::default();
+        configure(&mut config);
+        let conflict = Bindings::new(&config).unwrap_err();
+        (conflict.first.action, conflict.second.action)
+    }
+
+    #[test]
+    fn the_chord_holding_more_keys_wins() {
+        let bindings = bindings();
+        assert_eq!(
+            after(&bindings, &[KeyDown(Key::Alt)], Trigger::Click),
+            Some(Action::Ping)
+        );
+        assert_eq!(
+            after(
+                &bindings,
+                &[KeyDown(Key::Alt), KeyDown(Key::ControlLeft)],
+                Trigger::Click
+            ),
+            Some(Action::CopyCoordinates)
+        );
+    }
+
+    #[test]
+    fn either_ctrl_key_counts() {
+        let bindings = bindings();
+        assert_eq!(
+            after(
+                &bindings,
+                &[KeyDown(Key::ControlRight), KeyDown(Key::Alt)],
+                Trigger::Click
+            ),
+            Some(Action::CopyCoordinates)
+        );
+        // NOTE: pressed right, released left, Ctrl is up
+        assert_eq!(
+            after(
+                &bindings,
+                &[
+                    KeyDown(Key::ControlRight),
+                    KeyDown(Key::Alt),
+                    KeyUp(Key::ControlLeft)
+                ],
+                Trigger::Click
+            ),
+            Some(Action::Ping)
+        );
+    }
+
+    #[test]
+    fn keys_released_before_the_trigger_dont_count() {
+        let bindings = bindings();
+        let released = [
+            KeyDown(Key::Alt),
+            KeyDown(Key::ControlLeft),
+            KeyUp(Key::ControlLeft),
+        ];
+        assert_eq!(
+            after(&bindings, &released, Trigger::Click),
+            Some(Action::Ping)
+        );
+        let released = [KeyDown(Key::Alt), KeyUp(Key::Alt)];
+        assert_eq!(after(&bindings, &released, Trigger::Click), None);
+        assert_eq!(after(&bindings, &released, Trigger::Key(Key::KeyN)), None);
+    }
+
+    #[test]
+    fn repeats_and_stray_releases_change_nothing() {
+        let bindings = bindings();
+        let events = [
+            KeyUp(Key::ShiftLeft),
+            KeyDown(Key::Alt),
+            KeyDown(Key::Alt),
+            KeyDown(Key::Alt),
+            KeyUp(Key::ControlLeft),
+        ];
+        assert_eq!(
+            after(&bindings, &events, Trigger::Click),
+            Some(Action::Ping)
+        );
+        // NOTE: auto-repeat sends no matching releases, one is enough
+        let events = [KeyDown(Key::Alt), KeyDown(Key::Alt), KeyUp(Key::Alt)];
+        assert_eq!(after(&bindings, &events, Trigger::Click), None);
+    }
+
+    #[test]
+    fn key_triggers_need_their_held_keys() {
+        let bindings = bindings();
+        let alt = [KeyDown(Key::Alt)];
+        assert_eq!(
+            after(&bindings, &alt, Trigger::Key(Key::KeyN)),
+            Some(Action::Note)
+        );
+        assert_eq!(after(&bindings, &[], Trigger::Key(Key::KeyN)), None);
+        assert_eq!(
+            after(
+                &bindings,
+                &[KeyDown(Key::Alt), KeyDown(Key::ControlRight)],
+                Trigger::Key(Key::SemiColon)
+            ),
+            Some(Action::Caret)
+        );
+        assert_eq!(after(&bindings, &alt, Trigger::Key(Key::SemiColon)), None);
+    }
+
+    #[test]
+    fn the_same_key_resolves_by_what_else_is_held() {
+        let mut config = Config::default();
+        config.input.note_key = Key::SemiColon;
+        let bindings = Bindings::new(&config).unwrap();
+        let trigger = Trigger::Key(Key::SemiColon);
+        assert_eq!(
+            after(&bindings, &[KeyDown(Key::Alt)], trigger),
+            Some(Action::Note)
+        );
+        assert_eq!(
+            after(
+                &bindings,
+                &[KeyDown(Key::Alt), KeyDown(Key::ControlLeft)],
+                trigger
+            ),
+            Some(Action::Caret)
+        );
+    }
+
+    #[test]
+    fn a_trigger_held_by_its_own_chord_conflicts() {
+        let held_by_itself = conflict(|config| {
+            config.copy_coordinates.enabled = false;
+            config.input.caret_key = Key::ControlRight;
+        });
+        assert_eq!(held_by_itself, (Action::Caret, Action::Caret));
+    }
+
+    #[test]
+    fn the_prime_key_as_a_trigger_conflicts_with_pinging() {
+        assert_eq!(
+            conflict(|config| config.input.note_key = Key::Alt),
+            (Action::Ping, Action::Note)
+        );
+    }
+
+    #[test]
+    fn a_trigger_pressed_on_the_way_to_another_chord_conflicts() {
+        // NOTE: Alt+Ctrl for a click would fire the note on the way
+        assert_eq!(
+            conflict(|config| config.input.note_key = Key::ControlLeft),
+            (Action::CopyCoordinates, Action::Note)
+        );
+    }
+
+    #[test]
+    fn overlapping_chords_without_shadowing_are_fine() {
+        // NOTE: Alt+Shift completes the note, Alt alone still pings
+        let mut config = Config::default();
+        config.input.note_key = Key::ShiftLeft;
+        let bindings = Bindings::new(&config).unwrap();
+        let alt = [KeyDown(Key::Alt)];
+        assert_eq!(
+            after(&bindings, &alt, Trigger::Key(Key::ShiftLeft)),
+            Some(Action::Note)
+        );
+        assert_eq!(after(&bindings, &alt, Trigger::Click), Some(Action::Ping));
+    }
+
+    #[test]
+    fn changing_the_prime_key_is_checked_too() {
+        let bindings = bindings();
+        let shift = bindings.with_prime(Key::ShiftLeft).unwrap();
+        assert_eq!(shift.prime(), Key::ShiftLeft);
+        assert_eq!(
+            after(&shift, &[KeyDown(Key::ShiftLeft)], Trigger::Click),
+            Some(Action::Ping)
+        );
+        assert_eq!(after(&shift, &[KeyDown(Key::Alt)], Trigger::Click), None);
+
+        let conflict = bindings.with_prime(Key::ControlLeft).unwrap_err();
+        assert_eq!(
+            (conflict.first.action, conflict.second.action),
+            (Action::Ping, Action::CopyCoordinates)
+        );
+        assert!(bindings.with_prime(Key::KeyN).is_err());
+    }
+
+    #[test]
+    fn copy_coordinates_can_be_left_out() {
+        let mut config = Config::default();
+        config.copy_coordinates.enabled = false;
+        let bindings = Bindings::new(&config).unwrap();
+        assert_eq!(
+            after(
+                &bindings,
+                &[KeyDown(Key::Alt), KeyDown(Key::ControlLeft)],
+                Trigger::Click
+            ),
+            Some(Action::Ping)
+        );
+        assert_eq!(bindings.describe().len(), 3);
+    }
+}
@@ -0,0 +1,681 @@
+use device_query::mouse_state::MousePosition;
+use egui::{Pos2, Rect, Vec2};
+use serde::{Deserialize, Serialize};
+use winit::monitor::MonitorHandle;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+pub struct MonitorRect {
+    pub x: i32,
+    pub y: i32,
+    pub width: u32,
+    pub height: u32,
+}
+
+impl MonitorRect {
+    pub fn of(monitor: &MonitorHandle) -> Self {
+        let position = monitor.position();
+        let size = monitor.size();
+        Self {
+            x: position.x,
+            y: position.y,
+            width: size.width,
+            height: size.height,
+        }
+    }
+
+    pub fn contains(&self, (x, y): MousePosition) -> bool {
+        x >= self.x
+            && y >= self.y
+            && x < self.x + self.width as i32
+            && y < self.y + self.height as i32
+    }
+}
+
+/// Where a ping is drawn relative to the click, so it doesn't have to cover
+/// the very pixel being pointed at.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Anchor {
+    #[default]
+    Center,
+    /// Bottom edge on the click.
+    Above,
+    /// Top edge on the click.
+    Below,
+    /// Center moved by `dx`, `dy` pixels.
+    Offset { dx: i32, dy: i32 },
+}
+
+impl Anchor {
+    /// Center of a ping `size` pixels across anchored at `position`. Sides
+    /// that would stick out of `monitor` are flipped to the other side.
+    pub fn place(
+        self,
+        position: MousePosition,
+        size: f32,
+        monitor: Option<&MonitorRect>,
+    ) -> (f32, f32) {
+        let half = size / 2.0;
+        let (dx, dy) = match self {
+            Anchor::Center => (0.0, 0.0),
+            Anchor::Above => (0.0, -half),
+            Anchor::Below => (0.0, half),
+            Anchor::Offset { dx, dy } => (dx as f32, dy as f32),
+        };
+        let (x, y) = (position.0 as f32, position.1 as f32);
+        let Some(monitor) = monitor else {
+            return (x + dx, y + dy);
+        };
+
+        // NOTE: only flip towards the middle, a ping bigger than the monitor stays put
+        let flip = |start: f32, delta: f32, low: i32, length: u32| {
+            let (low, high) = (low as f32, low as f32 + length as f32);
+            let center = start + delta;
+            if (delta < 0.0 && center - half < low) || (delta > 0.0 && center + half > high) {
+                start - delta
+            } else {
+                center
+            }
+        };
+        (
+            flip(x, dx, monitor.x, monitor.width),
+            flip(y, dy, monitor.y, monitor.height),
+        )
+    }
+}
+
+/// Rect `size` pixels across around `center`, on whole physical pixels.
+// NOTE: the corner and size are rounded rather than both edges, so a ping keeps the
+// same size wherever it lands and doesn't shimmer at fractional scale factors
+pub fn snap(center: Pos2, size: f32) -> Rect {
+    let size = size.round();
+    let min = (center - Vec2::splat(size / 2.0)).round();
+    Rect::from_min_size(min, Vec2::splat(size))
+}
+
+/// `size` scaled to fit `rect` with its aspect ratio kept, centered, the way
+/// egui fits an image into a rect.
+pub fn fit(size: Vec2, rect: Rect) -> Rect {
+    let scale = (rect.width() / size.x).min(rect.height() / size.y);
+    Rect::from_center_size(rect.center(), size * scale)
+}
+
+/// The part of `rect` inside `clip`, with the same part of a texture drawn
+/// over all of `rect`, in 0-1 texture coordinates. A ping near a monitor
+/// edge is cut there rather than moved, so it stays centered on the click.
+/// `None` when none of it is inside.
+pub fn clip(rect: Rect, clip: Rect) -> Option<(Rect, Rect)> {
+    let visible = rect.intersect(clip);
+    if !visible.is_positive() {
+        return None;
+    }
+    let uv = |point: Pos2| ((point - rect.min) / rect.size()).to_pos2();
+    Some((
+        visible,
+        Rect::from_min_max(uv(visible.min), uv(visible.max)),
+    ))
+}
+
+/// Offset of up to `amount` pixels on each axis, fixed per ping `id` so the
+/// ping doesn't wander between frames.
+pub fn jitter(id: usize, amount: f32) -> Vec2 {
+    if amount <= 0.0 {
+        return Vec2::ZERO;
+    }
+    // NOTE: splitmix64, good enough to scatter sequential ids
+    let mut z = (id as u64).wrapping_add(0x9e37_79b9_7f4a_7c15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    z ^= z >> 31;
+    let unit = |bits: u64| (bits & 0xffff) as f32 / 0xffff as f32 * 2.0 - 1.0;
+    Vec2::new(unit(z), unit(z >> 16)) * amount
+}
+
+/// A monitor as the config names it.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum MonitorRef {
+    /// 1-based, in the order monitors are listed in the tray. Windows can
+    /// reorder them, e.g. after docking.
+    Index(usize),
+    /// The name shown in the tray, e.g. `"DELL U2720Q (3840x2160)"`.
+    Id(String),
+    /// `id`, or the monitor at `index` while none has it.
+    Either { id: String, index: usize },
+}
+
+impl MonitorRef {
+    /// Sound and zone configs key monitors by a number or a name.
+    pub fn parse(key: &str) -> Self {
+        match key.parse() {
+            Ok(index) => MonitorRef::Index(index),
+            Err(_) => MonitorRef::Id(key.to_owned()),
+        }
+    }
+}
+
+/// Which monitor pings are allowed on, e.g. only the one being screen shared.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Restriction {
+    #[default]
+    All,
+    /// The monitor the cursor was on when the app started.
+    CursorAtStartup,
+    /// 1-based, in the order monitors are listed in the tray.
+    // NOTE: from before monitors had ids, `Monitor` takes either
+    Index(usize),
+    Monitor(MonitorRef),
+}
+
+/// Physical rects of all monitors on the virtual desktop.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Layout {
+    pub monitors: Vec<MonitorRect>,
+    /// Per monitor, what people know it by and its id in the config, e.g.
+    /// "DELL U2720Q (3840x2160)".
+    pub names: Vec<String>,
+}
+
+impl Layout {
+    pub fn new(monitors: impl Iterator<Item = MonitorHandle>) -> Self {
+        let friendly = crate::platform::monitor_names();
+        let (monitors, names) = monitors
+            .map(|monitor| {
+                let rect = MonitorRect::of(&monitor);
+                // NOTE: the GDI name (\\.\DISPLAY1) when there's no EDID name, it's
+                // less stable but still tells monitors apart
+                let name = monitor
+                    .name()
+                    .map(|gdi| friendly.get(&gdi).cloned().unwrap_or(gdi))
+                    .unwrap_or_else(|| "unknown".to_owned());
+                (rect, format!("{name} ({}x{})", rect.width, rect.height))
+            })
+            .unzip();
+        Self { monitors, names }
+    }
+
+    /// Name of the 1-based monitor `index`.
+    pub fn name(&self, index: usize) -> Option<&str> {
+        self.names.get(index.checked_sub(1)?).map(String::as_str)
+    }
+
+    /// 1-based index of the monitor `monitor` refers to. An id no monitor has
+    /// falls back to its index with a warning.
+    pub fn resolve(&self, monitor: &MonitorRef) -> Option<usize> {
+        let by_index = |index: usize| (1..=self.monitors.len()).contains(&index).then_some(index);
+        let by_id = |id: &str| {
+            let found = self.names.iter().position(|name| name == id).map(|i| i + 1);
+            if found.is_none() {
+                log::warn!("no monitor is {id:?}, monitors are {:?}", self.names);
+            }
+            found
+        };
+        match monitor {
+            MonitorRef::Index(index) => by_index(*index),
+            MonitorRef::Id(id) => by_id(id),
+            MonitorRef::Either { id, index } => by_id(id).or_else(|| {
+                let fallback = by_index(*index);
+                if fallback.is_some() {
+                    log::warn!("using monitor {index} in place of {id:?}");
+                }
+                fallback
+            }),
+        }
+    }
+
+    pub fn contains(&self, position: MousePosition) -> bool {
+        self.monitors
+            .iter()
+            .any(|monitor| monitor.contains(position))
+    }
+
+    /// Rect spanning every monitor, `None` while there are none.
+    pub fn bounds(&self) -> Option<MonitorRect> {
+        let left = self.monitors.iter().map(|m| m.x).min()?;
+        let top = self.monitors.iter().map(|m| m.y).min()?;
+        let right = self.monitors.iter().map(|m| m.x + m.width as i32).max()?;
+        let bottom = self.monitors.iter().map(|m| m.y + m.height as i32).max()?;
+        Some(MonitorRect {
+            x: left,
+            y: top,
+            width: (right - left) as u32,
+            height: (bottom - top) as u32,
+        })
+    }
+
+    /// `position` moved onto the virtual desktop if it's outside of it.
+    pub fn clamp(&self, position: MousePosition) -> MousePosition {
+        let Some(bounds) = self.bounds() else {
+            return position;
+        };
+        (
+            position
+                .0
+                .clamp(bounds.x, bounds.x + bounds.width as i32 - 1),
+            position
+                .1
+                .clamp(bounds.y, bounds.y + bounds.height as i32 - 1),
+        )
+    }
+
+    /// Checks a ping position before it's queued. Off-desktop positions from
+    /// the cursor are clamped, device_query can lag behind a monitor change;
+    /// other sources get `Err` with the clamped position, to show the denial at.
+    // NOTE: an empty layout hasn't been read yet, so nothing can be ruled out
+    pub fn validate(
+        &self,
+        position: MousePosition,
+        source: &crate::ping_source::PingSource,
+    ) -> Result<MousePosition, MousePosition> {
+        let clamped = self.clamp(position);
+        if clamped == position {
+            return Ok(position);
+        }
+        match source {
+            crate::ping_source::PingSource::Local => {
+                log::info!("ping at {position:?} is off the desktop, moved to {clamped:?}");
+                Ok(clamped)
+            }
+            _ => {
+                log::info!("ping at {position:?} from {source:?} is off the desktop, rejected");
+                Err(clamped)
+            }
+        }
+    }
+
+    /// 1-based index and rect of the monitor containing `position`.
+    pub fn monitor_at(&self, position: MousePosition) -> Option<(usize, &MonitorRect)> {
+        self.monitors
+            .iter()
+            .enumerate()
+            .find(|(_, monitor)| monitor.contains(position))
+            .map(|(index, monitor)| (index + 1, monitor))
+    }
+
+    /// Fills `{x}`, `{y}`, `{monitor}`, `{w}` and `{h}` in `template` for `position`.
+    /// Monitor placeholders become `?` when it is off every monitor.
+    pub fn format_position(&self, template: &str, position: MousePosition) -> String {
+        let (monitor, w, h) = match self.monitor_at(position) {
+            Some((index, rect)) => (
+                index.to_string(),
+                rect.width.to_string(),
+                rect.height.to_string(),
+            ),
+            None => ("?".into(), "?".into(), "?".into()),
+        };
+        template
+            .replace("{x}", &position.0.to_string())
+            .replace("{y}", &position.1.to_string())
+            .replace("{monitor}", &monitor)
+            .replace("{w}", &w)
+            .replace("{h}", &h)
+    }
+
+    /// Resolves `restriction` against this layout. `None` allows every monitor,
+    /// which is also the fallback when the chosen monitor is gone.
+    pub fn allowed(
+        &self,
+        restriction: &Restriction,
+        startup_cursor: MousePosition,
+    ) -> Option<MonitorRect> {
+        let allowed = match restriction {
+            Restriction::All => return None,
+            Restriction::CursorAtStartup => self.monitor_at(startup_cursor).map(|(index, _)| index),
+            Restriction::Index(index) => self.resolve(&MonitorRef::Index(*index)),
+            Restriction::Monitor(monitor) => self.resolve(monitor),
+        }
+        .map(|index| (index, &self.monitors[index - 1]));
+        if allowed.is_none() {
+            log::warn!("monitor restriction {restriction:?} matches no monitor, allowing all");
+        }
+        allowed.map(|(index, monitor)| {
+            log::info!(
+                "pings restricted to {}",
+                self.name(index).unwrap_or_default()
+            );
+            *monitor
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ping_source::PingSource;
+
+    fn rect(x: i32, y: i32, width: u32, height: u32) -> MonitorRect {
+        MonitorRect {
+            x,
+            y,
+            width,
+            height,
+        }
+    }
+
+    fn layout(monitors: &[MonitorRect]) -> Layout {
+        Layout {
+            monitors: monitors.to_vec(),
+            names: (1..=monitors.len()).map(|i| format!("Test {i}")).collect(),
+        }
+    }
+
+    /// 1080p with a 1440p monitor to its left, top edges aligned.
+    fn docked() -> Layout {
+        layout(&[rect(0, 0, 1920, 1080), rect(-2560, 0, 2560, 1440)])
+    }
+
+    #[test]
+    fn positions_on_the_desktop_pass() {
+        let layout = docked();
+        for position in [(0, 0), (-2560, 1439), (1919, 1079), (-1, 500)] {
+            assert_eq!(layout.validate(position, &PingSource::Local), Ok(position));
+            assert_eq!(layout.validate(position, &PingSource::Ipc), Ok(position));
+        }
+    }
+
+    #[test]
+    fn local_pings_off_the_desktop_are_clamped() {
+        let layout = docked();
+        assert_eq!(
+            layout.validate((5000, -40), &PingSource::Local),
+            Ok((1919, 0))
+        );
+        assert_eq!(
+            layout.validate((-9000, 9000), &PingSource::Local),
+            Ok((-2560, 1439))
+        );
+    }
+
+    #[test]
+    fn other_pings_off_the_desktop_are_rejected_at_the_edge() {
+        let layout = docked();
+        assert_eq!(
+            layout.validate((5000, -40), &PingSource::Ipc),
+            Err((1919, 0))
+        );
+        assert_eq!(
+            layout.validate((1920, 10), &PingSource::Ipc),
+            Err((1919, 10))
+        );
+    }
+
+    #[test]
+    fn nothing_is_ruled_out_before_the_layout_is_read() {
+        let layout = Layout::default();
+        assert_eq!(layout.bounds(), None);
+        assert_eq!(
+            layout.validate((-9000, 9000), &PingSource::Ipc),
+            Ok((-9000, 9000))
+        );
+    }
+
+    #[test]
+    fn undocked_monitor_positions_go_stale() {
+        // NOTE: device_query can still report the cursor on a monitor that was just unplugged
+        let before = docked();
+        let after = layout(&[rect(0, 0, 1920, 1080)]);
+        let stale = (-1200, 700);
+        assert_eq!(before.validate(stale, &PingSource::Ipc), Ok(stale));
+        assert_eq!(after.validate(stale, &PingSource::Local), Ok((0, 700)));
+        assert_eq!(after.validate(stale, &PingSource::Ipc), Err((0, 700)));
+    }
+
+    #[test]
+    fn cursor_reset_to_the_origin_stays_valid() {
+        // NOTE: (0, 0) is briefly reported after a change, it's on the primary monitor
+        let layout = layout(&[rect(1920, 0, 1920, 1080), rect(0, 0, 1920, 1080)]);
+        assert_eq!(layout.validate((0, 0), &PingSource::Local), Ok((0, 0)));
+    }
+
+    #[test]
+    fn gaps_inside_the_bounds_are_left_alone() {
+        // NOTE: below the shorter monitor, but inside the desktop's bounding box
+        let layout = docked();
+        assert_eq!(
+            layout.validate((100, 1300), &PingSource::Ipc),
+            Ok((100, 1300))
+        );
+        assert!(!layout.contains((100, 1300)));
+    }
+
+    const MONITOR: MonitorRect = MonitorRect {
+        x: 0,
+        y: 0,
+        width: 1920,
+        height: 1080,
+    };
+
+    #[test]
+    fn anchors_place_the_ping_beside_the_click() {
+        let place = |anchor: Anchor| anchor.place((960, 540), 200.0, Some(&MONITOR));
+        assert_eq!(place(Anchor::Center), (960.0, 540.0));
+        assert_eq!(place(Anchor::Above), (960.0, 440.0));
+        assert_eq!(place(Anchor::Below), (960.0, 640.0));
+        assert_eq!(place(Anchor::Offset { dx: 30, dy: -40 }), (990.0, 500.0));
+    }
+
+    #[test]
+    fn above_near_the_top_flips_below() {
+        assert_eq!(
+            Anchor::Above.place((500, 50), 200.0, Some(&MONITOR)),
+            (500.0, 150.0)
+        );
+    }
+
+    #[test]
+    fn below_near_the_bottom_flips_above() {
+        assert_eq!(
+            Anchor::Below.place((500, 1030), 200.0, Some(&MONITOR)),
+            (500.0, 930.0)
+        );
+    }
+
+    #[test]
+    fn offsets_near_the_left_and_right_flip() {
+        let offset = Anchor::Offset { dx: -150, dy: 0 };
+        assert_eq!(
+            offset.place((40, 500), 200.0, Some(&MONITOR)),
+            (190.0, 500.0)
+        );
+        let offset = Anchor::Offset { dx: 150, dy: 0 };
+        assert_eq!(
+            offset.place((1880, 500), 200.0, Some(&MONITOR)),
+            (1730.0, 500.0)
+        );
+    }
+
+    #[test]
+    fn anchors_flip_on_secondary_monitors() {
+        let left = rect(-2560, -200, 2560, 1440);
+        assert_eq!(
+            Anchor::Above.place((-1000, -180), 100.0, Some(&left)),
+            (-1000.0, -130.0)
+        );
+        assert_eq!(
+            Anchor::Below.place((-1000, 1230), 100.0, Some(&left)),
+            (-1000.0, 1180.0)
+        );
+    }
+
+    #[test]
+    fn anchors_off_every_monitor_stay_put() {
+        assert_eq!(Anchor::Above.place((500, 50), 200.0, None), (500.0, -50.0));
+    }
+
+    /// Centers a click can map to at `scale`, from logical positions a
+    /// fifth of a pixel apart.
+    fn scaled_centers(scale: f32) -> impl Iterator<Item = Pos2> {
+        (0..50).map(move |step| {
+            let logical = 100.0 + step as f32 * 0.2;
+            Pos2::new(logical * scale, (logical + 37.0) * scale)
+        })
+    }
+
+    #[test]
+    fn snapped_rects_sit_on_whole_pixels_at_fractional_scale_factors() {
+        for scale in [1.25, 1.5] {
+            for size in [64.0, 100.0, 150.0] {
+                let size = size * scale;
+                for center in scaled_centers(scale) {
+                    let rect = snap(center, size);
+                    assert_eq!(rect.min, rect.min.round(), "{scale} {size} {center:?}");
+                    assert_eq!(rect.max, rect.max.round(), "{scale} {size} {center:?}");
+                    assert_eq!(rect.size(), Vec2::splat(size.round()));
+                    assert!((rect.center() - center).abs().max_elem() <= 0.5);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn snapped_rects_dont_change_size_as_the_center_moves() {
+        // NOTE: a size that changed by a pixel between frames is the shimmer
+        for scale in [1.25, 1.5] {
+            let sizes: Vec<_> = scaled_centers(scale)
+                .map(|center| snap(center, 100.0 * scale).size())
+                .collect();
+            assert!(sizes.iter().all(|size| *size == sizes[0]), "{scale}");
+        }
+    }
+
+    #[test]
+    fn odd_sizes_at_125_percent_round_the_corner_not_the_size() {
+        // NOTE: 100 logical pixels are 125 physical ones, half a pixel either side
+        let rect = snap(Pos2::new(960.0, 540.0), 125.0);
+        assert_eq!(rect.min, Pos2::new(898.0, 478.0));
+        assert_eq!(rect.size(), Vec2::splat(125.0));
+    }
+
+    #[test]
+    fn shrinking_pings_stay_on_whole_pixels() {
+        let center = Pos2::new(1200.0 * 1.5, 700.0 * 1.5);
+        for step in 0..=20 {
+            let shrink = 1.0 - step as f32 * 0.03;
+            let rect = snap(center, 150.0 * shrink);
+            assert_eq!(rect.min, rect.min.round(), "{shrink}");
+            assert_eq!(rect.size(), Vec2::splat(rect.width().round()));
+        }
+    }
+
+    fn close(a: Rect, b: Rect) -> bool {
+        (a.min - b.min).length() < 1e-4 && (a.max - b.max).length() < 1e-4
+    }
+
+    /// The 1366x768 laptop screen a 500 pixel ping overhangs at every corner.
+    fn laptop() -> Rect {
+        Rect::from_min_size(Pos2::ZERO, Vec2::new(1366.0, 768.0))
+    }
+
+    const FULL: Rect = Rect {
+        min: Pos2::ZERO,
+        max: Pos2::new(1.0, 1.0),
+    };
+
+    #[test]
+    fn pings_inside_the_monitor_are_drawn_whole() {
+        let rect = snap(Pos2::new(683.0, 384.0), 500.0);
+        assert_eq!(clip(rect, laptop()), Some((rect, FULL)));
+    }
+
+    #[test]
+    fn corner_pings_are_cut_at_the_edge_and_stay_centered() {
+        let uv = |min: (f32, f32), max: (f32, f32)| {
+            Rect::from_min_max(Pos2::new(min.0, min.1), Pos2::new(max.0, max.1))
+        };
+        let cases = [
+            // NOTE: click, visible part on screen, visible part of the texture
+            (
+                (10.0, 10.0),
+                uv((0.0, 0.0), (260.0, 260.0)),
+                uv((0.48, 0.48), (1.0, 1.0)),
+            ),
+            (
+                (1356.0, 10.0),
+                uv((1106.0, 0.0), (1366.0, 260.0)),
+                uv((0.0, 0.48), (0.52, 1.0)),
+            ),
+            (
+                (10.0, 758.0),
+                uv((0.0, 508.0), (260.0, 768.0)),
+                uv((0.48, 0.0), (1.0, 0.52)),
+            ),
+            (
+                (1356.0, 758.0),
+                uv((1106.0, 508.0), (1366.0, 768.0)),
+                uv((0.0, 0.0), (0.52, 0.52)),
+            ),
+        ];
+        for ((x, y), visible, texture) in cases {
+            let rect = snap(Pos2::new(x, y), 500.0);
+            assert_eq!(rect.center(), Pos2::new(x, y));
+            let (drawn, uv) = clip(rect, laptop()).expect("corner pings are partly visible");
+            assert!(close(drawn, visible), "{drawn:?} for ({x}, {y})");
+            assert!(close(uv, texture), "{uv:?} for ({x}, {y})");
+        }
+    }
+
+    #[test]
+    fn pings_bigger_than_the_monitor_show_their_middle() {
+        let rect = snap(Pos2::new(683.0, 384.0), 2000.0);
+        let (drawn, uv) = clip(rect, laptop()).unwrap();
+        assert_eq!(drawn, laptop());
+        assert!(close(
+            uv,
+            Rect::from_min_max(Pos2::new(0.1585, 0.308), Pos2::new(0.8415, 0.692))
+        ));
+    }
+
+    #[test]
+    fn pings_off_the_monitor_or_touching_its_edge_draw_nothing() {
+        assert_eq!(clip(snap(Pos2::new(-400.0, 300.0), 500.0), laptop()), None);
+        assert_eq!(clip(snap(Pos2::new(3000.0, 3000.0), 500.0), laptop()), None);
+        // NOTE: sharing only the edge is no visible area
+        assert_eq!(clip(snap(Pos2::new(-250.0, 300.0), 500.0), laptop()), None);
+        assert_eq!(clip(snap(Pos2::new(683.0, 1018.0), 500.0), laptop()), None);
+    }
+
+    #[test]
+    fn flipped_anchors_are_not_cut_by_the_clip() {
+        // NOTE: the flip moves the ping back onto the monitor first, so nothing is left to clip
+        let monitor = rect(0, 0, 1366, 768);
+        for (anchor, position) in [
+            (Anchor::Above, (600, 100)),
+            (Anchor::Below, (600, 700)),
+            (Anchor::Offset { dx: 300, dy: 0 }, (1300, 384)),
+        ] {
+            let (x, y) = anchor.place(position, 200.0, Some(&monitor));
+            let rect = snap(Pos2::new(x, y), 200.0);
+            assert_eq!(clip(rect, laptop()), Some((rect, FULL)), "{anchor:?}");
+        }
+    }
+
+    #[test]
+    fn fit_keeps_the_aspect_ratio_and_centers() {
+        let rect = Rect::from_min_size(Pos2::new(100.0, 100.0), Vec2::splat(200.0));
+        assert_eq!(
+            fit(Vec2::new(400.0, 200.0), rect),
+            Rect::from_min_size(Pos2::new(100.0, 150.0), Vec2::new(200.0, 100.0))
+        );
+        assert_eq!(
+            fit(Vec2::new(50.0, 100.0), rect),
+            Rect::from_min_size(Pos2::new(150.0, 100.0), Vec2::new(100.0, 200.0))
+        );
+        assert_eq!(fit(Vec2::splat(64.0), rect), rect);
+    }
+
+    #[test]
+    fn fitted_artwork_clips_like_square_artwork() {
+        // NOTE: a wide sprite at the corner is cut on the side that overhangs, not squashed
+        let rect = fit(Vec2::new(400.0, 200.0), snap(Pos2::new(10.0, 384.0), 500.0));
+        let (drawn, uv) = clip(rect, laptop()).unwrap();
+        assert!(close(
+            drawn,
+            Rect::from_min_max(Pos2::new(0.0, 259.0), Pos2::new(260.0, 509.0))
+        ));
+        assert!(close(
+            uv,
+            Rect::from_min_max(Pos2::new(0.48, 0.0), Pos2::new(1.0, 1.0))
+        ));
+    }
+}
@@ -0,0 +1,82 @@
+//! Caps how fast pings can be fired, per source: a token bucket holding up
+//! to `burst` pings and refilling one every `refill_secs`. While it's empty a
+//! cooldown ring at the cursor shows when the next ping is allowed.
+
+use crate::config;
+use crate::ping_source::PingSource;
+use device_query::mouse_state::MousePosition;
+use std::collections::HashMap;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+struct Bucket {
+    tokens: f32,
+    updated: Instant,
+}
+
+/// One bucket per source, so pings from peers can't use up the local user's.
+#[derive(Debug)]
+pub struct Limiter {
+    burst: f32,
+    /// Time to regain one ping.
+    refill: Duration,
+    buckets: HashMap<PingSource, Bucket>,
+}
+
+impl Limiter {
+    /// `None` when limiting is off.
+    pub fn new(config: &config::Limit) -> Option<Self> {
+        if !config.enabled {
+            return None;
+        }
+        let refill = Duration::try_from_secs_f32(config.refill_secs)
+            .ok()
+            .filter(|refill| !refill.is_zero())
+            .unwrap_or_else(|| {
+                log::warn!("invalid limit.refill_secs, using 2 seconds");
+                Duration::from_secs(2)
+            });
+        Some(Self {
+            burst: config.burst.max(1) as f32,
+            refill,
+            buckets: HashMap::new(),
+        })
+    }
+
+    /// Takes a ping from `source`'s bucket, or `Err` with the time until it
+    /// has one again.
+    pub fn take(&mut self, source: &PingSource, now: Instant) -> Result<(), Duration> {
+        let bucket = self.buckets.entry(source.clone()).or_insert(Bucket {
+            tokens: self.burst,
+            updated: now,
+        });
+        let regained =
+            now.saturating_duration_since(bucket.updated).as_secs_f32() / self.refill.as_secs_f32();
+        bucket.tokens = (bucket.tokens + regained).min(self.burst);
+        bucket.updated = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(self.refill.mul_f32(1.0 - bucket.tokens))
+    }
+}
+
+/// The ring drawn at the cursor when a ping was refused, emptying as the next
+/// one becomes allowed.
+#[derive(Debug, Clone, Copy)]
+pub struct Cooldown {
+    /// Desktop pixels.
+    pub position: MousePosition,
+    pub until: Instant,
+    /// Wait as it was when refused, the ring starts full from there.
+    pub wait: Duration,
+}
+
+impl Cooldown {
+    /// Share of the wait still left, 0 once pinging is allowed again.
+    pub fn remaining(&self, now: Instant) -> f32 {
+        let left = self.until.saturating_duration_since(now).as_secs_f32();
+        (left / self.wait.as_secs_f32().max(f32::EPSILON)).clamp(0.0, 1.0)
+    }
+}
@@ -0,0 +1,201 @@
+//! What a reloaded config takes to apply, key by key. The new config is
+//! diffed against the one in use and every changed key falls in one of three
+//! buckets: applied on the spot, applied by rebuilding the renderer, or left
+//! for the next start.
+
+use crate::config::Config;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Apply {
+    /// Read from the config on every use, or handed on where it's used.
+    Instant,
+    /// Takes a new renderer, built through the device-loss recovery path.
+    Rebuild,
+    /// Read once at startup, by a thread or a window that lives on.
+    Restart,
+}
+
+/// Keys by their first segments, the longest match wins. Keys missing here
+/// need a restart, so a new one can't be half applied by mistake.
+const RULES: &[(&str, Apply)] = &[
+    ("ui.toasts", Apply::Instant),
+    ("input.prime_key", Apply::Instant),
+    ("arrow.color", Apply::Instant),
+    ("arrow.thickness", Apply::Instant),
+    ("marker", Apply::Instant),
+    ("easing", Apply::Instant),
+    ("kinds", Apply::Instant),
+    ("theme", Apply::Instant),
+    ("restrict", Apply::Instant),
+    ("expiry_ring", Apply::Instant),
+    ("emphasis", Apply::Instant),
+    ("backdrop.halo_color", Apply::Instant),
+    ("backdrop.halo_radius", Apply::Instant),
+    ("sound.volume", Apply::Instant),
+    ("sound.device", Apply::Instant),
+    ("copy_coordinates.template", Apply::Instant),
+    ("trail", Apply::Instant),
+    // NOTE: `ripples.enabled` is read by the hook at startup
+    ("ripples.duration_ms", Apply::Instant),
+    ("ripples.max", Apply::Instant),
+    ("ripples.color", Apply::Instant),
+    ("ripples.radius", Apply::Instant),
+    ("limit", Apply::Instant),
+    ("quality.adaptive", Apply::Instant),
+    ("power.marker", Apply::Instant),
+    ("remote", Apply::Instant),
+    ("overlay.renderer", Apply::Rebuild),
+];
+
+/// What it takes to apply `key`, dotted as in the config file.
+pub fn classify(key: &str) -> Apply {
+    RULES
+        .iter()
+        .filter(|(prefix, _)| within(key, prefix))
+        .max_by_key(|(prefix, _)| prefix.len())
+        .map_or(Apply::Restart, |(_, apply)| *apply)
+}
+
+/// Whether any of `changes` is `section` or a key in it.
+pub fn touches(changes: &[String], section: &str) -> bool {
+    changes.iter().any(|key| within(key, section))
+}
+
+fn within(key: &str, section: &str) -> bool {
+    key.strip_prefix(section)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('.'))
+}
+
+/// Dotted keys whose value differs between `old` and `new`. Lists count as
+/// one value.
+pub fn changes(old: &Config, new: &Config) -> Vec<String> {
+    let old = toml::Value::try_from(old).expect("config is always serializable");
+    let new = toml::Value::try_from(new).expect("config is always serializable");
+    let mut changed = Vec::new();
+    diff("", &old, &new, &mut changed);
+    changed
+}
+
+fn diff(key: &str, old: &toml::Value, new: &toml::Value, changed: &mut Vec<String>) {
+    let (toml::Value::Table(old), toml::Value::Table(new)) = (old, new) else {
+        if old != new {
+            changed.push(key.to_owned());
+        }
+        return;
+    };
+    let mut names: Vec<&String> = old.keys().chain(new.keys()).collect();
+    names.sort();
+    names.dedup();
+    for name in names {
+        let nested = match key {
+            "" => name.clone(),
+            _ => format!("{key}.{name}"),
+        };
+        match (old.get(name), new.get(name)) {
+            (Some(old), Some(new)) => diff(&nested, old, new, changed),
+            // NOTE: unset optional keys aren't written, a key coming or going is a change
+            _ => changed.push(nested),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keys_are_classified_by_their_longest_rule() {
+        let table = [
+            // NOTE: a whole section, and keys in it
+            ("marker", Apply::Instant),
+            ("marker.style", Apply::Instant),
+            ("kinds.danger.color", Apply::Instant),
+            ("sound.volume", Apply::Instant),
+            ("input.prime_key", Apply::Instant),
+            ("limit.burst", Apply::Instant),
+            ("overlay.renderer", Apply::Rebuild),
+            ("overlay.renderer.present_mode", Apply::Rebuild),
+            // NOTE: a rule for one key leaves the rest of its section to restart
+            ("sound", Apply::Restart),
+            ("input.note_key", Apply::Restart),
+            ("ripples.enabled", Apply::Restart),
+            ("ripples.radius", Apply::Instant),
+            ("overlay", Apply::Restart),
+            // NOTE: a prefix only matches whole segments
+            ("themes", Apply::Restart),
+            ("sound.volumes", Apply::Restart),
+            ("marker_style", Apply::Restart),
+            ("", Apply::Restart),
+            ("ipc.pipe", Apply::Restart),
+        ];
+        for (key, apply) in table {
+            assert_eq!(classify(key), apply, "{key:?}");
+        }
+    }
+
+    #[test]
+    fn every_rule_names_a_config_key() {
+        let mut config = Config::default();
+        // NOTE: unset optional keys aren't written
+        config.sound.device = Some("Speakers".into());
+        let config = toml::Value::try_from(config).unwrap();
+        for (prefix, _) in RULES {
+            let found = prefix
+                .split('.')
+                .try_fold(&config, |value, name| value.get(name));
+            assert!(found.is_some(), "{prefix:?} is not in the config");
+        }
+    }
+
+    #[test]
+    fn instant_keys_change_what_is_drawn_on_reload() {
+        let mut app = crate::MyApp::new(Config::default(), crate::theme::Theme::builtin());
+        let mut reloaded = app.config.clone();
+        reloaded.easing = [("missing".into(), "ease_out_back".into())].into();
+        reloaded.marker.color = [1, 2, 3, 4];
+        let changed = changes(&app.config, &reloaded);
+        assert!(changed.iter().all(|key| classify(key) == Apply::Instant));
+
+        app.apply_config(reloaded);
+        assert_eq!(
+            app.easing.get(&crate::protocol::PingKind::Missing),
+            Some(&crate::easing::Curve::EaseOutBack)
+        );
+        assert_eq!(app.config.marker.color, [1, 2, 3, 4]);
+    }
+
+    #[test]
+    fn touches_matches_a_section_and_its_keys() {
+        let changes = ["sound.volume".to_owned(), "theme".to_owned()];
+        assert!(touches(&changes, "sound"));
+        assert!(touches(&changes, "sound.volume"));
+        assert!(touches(&changes, "theme"));
+        assert!(!touches(&changes, "sound.device"));
+        assert!(!touches(&changes, "soun"));
+        assert!(!touches(&[], "sound"));
+    }
+
+    #[test]
+    fn changes_lists_the_keys_that_differ() {
+        let old = Config::default();
+        assert!(changes(&old, &old.clone()).is_empty());
+
+        let mut new = old.clone();
+        new.sound.volume += 0.1;
+        new.input.double_tap = !new.input.double_tap;
+        new.schedule.days.pop();
+        assert_eq!(
+            changes(&old, &new),
+            ["input.double_tap", "schedule.days", "sound.volume"]
+        );
+    }
+
+    #[test]
+    fn optional_keys_coming_or_going_are_changes() {
+        let old = Config::default();
+        let mut new = old.clone();
+        new.theme.path = Some("pack.pingpack".into());
+        assert_eq!(changes(&old, &new), ["theme.path"]);
+        assert_eq!(changes(&new, &old), ["theme.path"]);
+    }
+}
@@ -0,0 +1,66 @@
+use std::fs::File;
+use std::io::Write;
+use std::sync::Mutex;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Last warning or error, for the status query.
+static LAST_PROBLEM: Mutex<Option<String>> = Mutex::new(None);
+/// Shared with [`close`], which has no way to reach the installed logger.
+static FILE: Mutex<Option<File>> = Mutex::new(None);
+
+struct Logger;
+
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Info
+    }
+
+    fn log(&self, record: &log::Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        let timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let line = format!("[{timestamp}] {} {}", record.level(), record.args());
+        println!("{line}");
+        if record.level() <= log::Level::Warn {
+            *LAST_PROBLEM.lock().unwrap() = Some(line.clone());
+        }
+        if let Some(file) = FILE.lock().unwrap().as_mut() {
+            writeln!(file, "{line}").ok();
+        }
+    }
+
+    fn flush(&self) {
+        if let Some(file) = FILE.lock().unwrap().as_mut() {
+            file.flush().ok();
+        }
+    }
+}
+
+pub fn last_problem() -> Option<String> {
+    LAST_PROBLEM.lock().unwrap().clone()
+}
+
+/// Logs to stdout and appends to the log file in the data directory.
+pub fn init() {
+    let file = File::options()
+        .create(true)
+        .append(true)
+        .open(crate::storage::log_path())
+        .ok();
+    *FILE.lock().unwrap() = file;
+    if log::set_boxed_logger(Box::new(Logger)).is_ok() {
+        log::set_max_level(log::LevelFilter::Info);
+    }
+}
+
+/// Stops writing to the log file and closes it, so it can be deleted. Later
+/// lines still go to stdout.
+pub fn close() {
+    if let Some(mut file) = FILE.lock().unwrap().take() {
+        file.flush().ok();
+    }
+}
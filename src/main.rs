@@ -1,386 +1,2202 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
+mod about;
+mod animation_builder;
+mod assets;
+mod audio;
+mod backdrop;
+mod caret;
+mod click;
+mod clock;
+mod coalesce;
+mod config;
+mod config_check;
+mod diagnostics;
+mod dialog;
+mod driver;
+mod easing;
+mod elevation;
+mod error;
+mod events;
+mod export;
+mod flick;
+mod focus_assist;
+mod follow;
+mod hotkey;
+mod i18n;
+mod import;
+mod input;
+mod instance;
+mod ipc;
+mod keybindings;
+mod layout;
+mod limiter;
+mod live;
+mod logging;
+mod marker;
+mod metrics;
+mod monitor_sound;
+mod notes;
+mod offscreen;
+mod overlay;
+mod pause;
+mod ping_source;
+mod platform;
+mod power;
+mod preview;
+mod priming;
+mod protocol;
+#[cfg(feature = "webhook")]
+mod publisher;
+mod quality;
+mod ripple;
+mod schedule;
+mod self_check;
+#[cfg(feature = "snapshots")]
+mod snapshots;
+mod sprites;
+mod state;
+mod storage;
+mod taskbar;
+// NOTE: the binary has no use for it, it backs tests until the core is a lib crate
+#[cfg(all(test, feature = "testing"))]
+mod testing;
+mod textures;
+mod theme;
+mod toast;
+mod trail;
+mod tray;
+mod tutorial;
+mod zones;
+
+use animation_builder::AnimationBuilder;
 use crossbeam::queue::ArrayQueue;
 use device_query::mouse_state::MousePosition;
-use device_query::{DeviceQuery, DeviceState, MouseState};
-use egui::{self, ImageSource, Pos2, Rect, Vec2};
-use egui_wgpu::renderer::ScreenDescriptor;
-use egui_wgpu::{wgpu::Dx12Compiler, Renderer};
+use device_query::{DeviceQuery, DeviceState};
+use egui::{self, Pos2, Vec2};
 use include_dir::include_dir;
 use include_dir::Dir;
-use raw_window_handle::HasRawWindowHandle;
-use rodio::{source::Source, Decoder};
-use std::collections::HashMap;
-use std::fs::File;
-use std::io::BufReader;
-use std::sync::Arc;
+use std::collections::{BTreeSet, HashMap};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
 use std::thread::JoinHandle;
-use tray_icon::{menu, menu::Menu, TrayIconBuilder};
+use std::time::{Duration, Instant};
+use tray::TrayCommand;
 use winit::event_loop::EventLoopBuilder;
-use winit::{event::*, event_loop::ControlFlow, window::WindowLevel};
+use winit::{event::*, event_loop::ControlFlow};
 
 static ASSET_DIR: Dir<'_> = include_dir!("$CARGO_MANIFEST_DIR/assets/gif");
 static ICON: &[u8] = include_bytes!("../assets/question.png");
+const SOUND_PATH: &str = "assets/ping_missing.ogg";
+
+const FRAME_COUNT: u8 = 60;
+const FRAME_TIME: Duration = Duration::from_micros(1_000_000 / 60);
+/// How far a merged ping winds its animation back, keeping it up a little longer.
+const MERGE_REWIND: u8 = 15;
+const ANIMATION_DURATION: Duration = Duration::from_micros(1_000_000 / 60 * FRAME_COUNT as u64);
+/// Frame persistent markers stop on, the one where the artwork is fully shown.
+const MARKER_FRAME: u8 = 30;
+/// Longest gap between driver steps treated as ordinary jitter. Anything
+/// longer means the machine slept or the driver was starved, and cancels
+/// the animation.
+const MAX_CATCH_UP: Duration = Duration::from_millis(250);
+const MONITOR_RETRY_MIN: Duration = Duration::from_secs(1);
+/// How long after startup the overlay is built in the background, so the
+/// first ping doesn't pay for the GPU device and surface.
+const OVERLAY_WARM_UP: Duration = Duration::from_secs(5);
+const MONITOR_RETRY_MAX: Duration = Duration::from_secs(30);
+/// Longest wait between checks of the schedule.
+const SCHEDULE_CHECK_MAX: Duration = Duration::from_secs(60);
+/// How often the tray tooltip countdown of a timed pause is updated.
+const TOOLTIP_REFRESH: Duration = Duration::from_secs(15);
+/// How long startup waits on an instance that holds the lock, long enough
+/// for one that relaunched this one to exit.
+const INSTANCE_WAIT: Duration = Duration::from_secs(3);
+/// How often the monitor layout is compared against the last one seen.
+const LAYOUT_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Opaque overlay window id of the tutorial bubble, animation ids start at 1.
+const TUTORIAL_SPOT: usize = 0;
+/// How far below the middle of the primary monitor the tutorial sits, clear
+/// of the self-check's demo ping.
+const TUTORIAL_DROP: f32 = 300.0;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    pollster::block_on(run());
+    // NOTE: before logging starts, it would recreate the log file
+    if std::env::args().any(|arg| arg == "--uninstall-cleanup") {
+        let steps = storage::remove_all();
+        let report = storage::describe(&steps);
+        let failed = steps
+            .iter()
+            .any(|(_, _, removal)| matches!(removal, storage::Removal::Failed(_)));
+        // NOTE: release builds have no console of their own, run from Explorer or a
+        // shortcut there's nowhere to print to
+        if platform::attach_parent_console() {
+            println!("{report}");
+        } else if failed {
+            platform::show_error(i18n::t("app.name"), &report);
+        } else {
+            platform::show_info(i18n::t("app.name"), &report);
+        }
+        if failed {
+            return Err("some data could not be removed".into());
+        }
+        return Ok(());
+    }
+    // NOTE: before logging too, the report is all it prints
+    if let Some(path) = config_check::from_args() {
+        config_check::run(&path?)?;
+        return Ok(());
+    }
+    logging::init();
+    if let Some(job) = export::Job::from_args() {
+        let frames = job?.run()?;
+        println!("exported {frames} frames");
+        return Ok(());
+    }
+    #[cfg(feature = "snapshots")]
+    if let Some(mode) = snapshots::mode_from_args() {
+        let dir = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        snapshots::run(mode?, &dir)?;
+        return Ok(());
+    }
+    if let Err(e) = pollster::block_on(run()) {
+        error::report(&e);
+        return Err(e.into());
+    }
     Ok(())
 }
 
-struct AnimationDesc {
-    frames: u32,
-}
-
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 struct Animation {
     id: usize,
+    /// Frame as of the last [`Animation::frame_at`], for drawing.
     frame: u8,
     position: MousePosition,
-    last_update: std::time::Instant,
+    /// When frame 0 was shown, the clock every frame is derived from.
+    started: Instant,
+    last_update: Instant,
+    /// Held back by the driver until the backdrop sample lands.
+    sampling: bool,
+    persistent: bool,
+    backdrop: backdrop::Backdrop,
+    source: ping_source::PingSource,
+    kind: protocol::PingKind,
+    /// Soft feedback for a ping that was rejected, e.g. on a restricted monitor.
+    denied: bool,
+    /// Position in a run of quick pings, drawn as a badge.
+    sequence: Option<u32>,
+    /// Pings merged into this one, drawn as a badge above 1.
+    count: u32,
+    /// Extra frames the badge stays for after the artwork ends.
+    linger: u8,
+    /// Name of the zone the ping landed in, drawn under it.
+    zone: Option<Arc<str>>,
+    /// Typed by the user on a persistent marker, drawn under it.
+    note: Option<Arc<str>>,
+    /// Set for pings that move with what they point at; `position` is then
+    /// only where they started.
+    follow: Option<follow::Target>,
+    /// Where the arrow drawn from the ping points, in desktop pixels.
+    toward: Option<MousePosition>,
+    /// Time between frames, stretched or squeezed to the kind's duration.
+    frame_time: Duration,
+}
+
+impl Animation {
+    fn new(position: MousePosition, persistent: bool, now: Instant) -> Self {
+        static NEXT_ID: AtomicUsize = AtomicUsize::new(1);
+
+        Self {
+            id: NEXT_ID.fetch_add(1, Ordering::Relaxed),
+            frame: 0,
+            position,
+            started: now,
+            last_update: now,
+            sampling: true,
+            persistent,
+            backdrop: backdrop::Backdrop::Unknown,
+            source: ping_source::PingSource::Local,
+            kind: protocol::PingKind::Missing,
+            denied: false,
+            sequence: None,
+            count: 1,
+            linger: 0,
+            follow: None,
+            toward: None,
+            zone: None,
+            note: None,
+            frame_time: FRAME_TIME,
+        }
+    }
+
+    /// What the driver should do with this animation at `now` when it
+    /// advances `stride` frames at a time.
+    fn step(&self, now: Instant, stride: u8) -> Step {
+        let elapsed = now.saturating_duration_since(self.last_update);
+        if elapsed > MAX_CATCH_UP {
+            Step::Cancel
+        } else if elapsed > self.frame_time * stride as u32 {
+            Step::Advance
+        } else {
+            Step::Wait
+        }
+    }
+
+    /// Frame this animation is on at `now`, from its own clock. The driver
+    /// and the overlay both go by it, so a late event can't leave the overlay
+    /// a frame behind or blank.
+    fn frame_at(&self, now: Instant) -> u8 {
+        let elapsed = now.saturating_duration_since(self.started);
+        let frames = elapsed.as_nanos() / self.frame_time.as_nanos().max(1);
+        frames.min(self.last_frame() as u128) as u8
+    }
+
+    /// Moves the clock so `frame` is the one showing at `now`.
+    fn seek(&mut self, frame: u8, now: Instant) {
+        self.frame = frame;
+        self.started = now
+            .checked_sub(self.frame_time * frame as u32)
+            .unwrap_or(now);
+    }
+
+    /// Jumps to the end without playing the frames in between: pings are
+    /// cleared, markers go straight to their resting frame. Returns the event
+    /// telling the overlay.
+    fn finish(&mut self, now: Instant) -> CustomEvent {
+        if self.persistent {
+            self.seek(MARKER_FRAME, now);
+            CustomEvent::Animate(self.clone())
+        } else {
+            self.frame = FRAME_COUNT;
+            CustomEvent::Clear(self.id)
+        }
+    }
+
+    /// Persistent markers stay on screen holding `MARKER_FRAME` until cleared.
+    fn last_frame(&self) -> u8 {
+        if self.persistent {
+            MARKER_FRAME
+        } else {
+            FRAME_COUNT + self.linger
+        }
+    }
+}
+
+/// What the dialog window currently shows.
+enum Panel {
+    SelfCheck(self_check::SelfCheck),
+    Legend(Vec<(String, egui::Color32)>),
+    /// Recording a new ping hotkey, showing the current one and every shortcut.
+    Hotkey(String, Vec<(String, &'static str)>),
+    Preview(Box<preview::Preview>),
+    Note(notes::Editor),
+    About(Box<about::About>),
+}
+
+impl Panel {
+    /// Returns `true` when the user dismissed the dialog.
+    fn ui(&mut self, ctx: &egui::Context) -> bool {
+        match self {
+            Panel::SelfCheck(check) => check.ui(ctx),
+            Panel::Legend(peers) => ping_source::legend_ui(ctx, peers),
+            Panel::Hotkey(current, bindings) => hotkey::record_ui(ctx, current, bindings),
+            Panel::Preview(preview) => preview.ui(ctx),
+            Panel::Note(editor) => editor.ui(ctx),
+            Panel::About(about) => about.ui(ctx),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Step {
+    Wait,
+    Advance,
+    /// Too much time passed since the last step. Playing on would flash the
+    /// stale frames the overlay's clock has run to, so pings are dropped and
+    /// markers jump to their resting frame.
+    Cancel,
+}
+
+/// Out of band requests for the animation driver thread.
+#[derive(Debug, Clone, Copy)]
+enum DriverCommand {
+    /// Finish everything queued or playing, e.g. on pause so nothing bursts
+    /// out on resume.
+    Drain,
+    /// Another ping landed on animation `id`: count it and keep it up a little longer.
+    Merge(usize),
+    Shutdown,
 }
 
 enum CustomEvent {
     Animate(Animation),
     Clear(usize),
+    /// A tray menu click, turned into a `Tray` command on the event loop.
+    Menu(tray_icon::menu::MenuId),
+    Tray(TrayCommand),
+    CopyCoordinates(MousePosition),
+    #[cfg(feature = "webhook")]
+    PublisherConnected(bool),
+    HotkeyCaptured(hotkey::Capture),
+    SoundAutoMuted(bool),
+    /// A ping was fired while the tutorial waited for one.
+    Pinged,
+    /// The note chord was pressed with the cursor here.
+    EditNote(MousePosition),
+    /// Power saving started or stopped.
+    PowerSaving(bool),
+    /// A Windows setting changed, maybe the taskbar theme or the scale.
+    SettingsChanged,
+    /// A ping was refused by the limiter, the ring shows the wait.
+    Cooldown(limiter::Cooldown),
+    /// The foreground window changed or a window was shown or hidden.
+    WindowsChanged,
+    /// Something the app can't go on without failed off the event loop.
+    Failed(error::Error),
+    /// Confirms a setting change on screen, see [`toast`].
+    Toast(String),
+    /// Any mouse button went down here, see [`ripple`].
+    Ripple(MousePosition),
+    /// A control pipe request, answered on the sender it carries.
+    Ipc(ipc::Request),
 }
 
-async fn run() {
-    let tray_menu = Menu::new();
-    tray_menu
-        .append(&menu::PredefinedMenuItem::quit(Some("Quit")))
-        .unwrap();
-    let icon = load_icon();
-    let _tray_icon = TrayIconBuilder::new()
-        .with_menu(Box::new(tray_menu))
-        .with_tooltip("Screen pinger")
-        .with_icon(icon)
-        .build()
-        .unwrap();
+async fn run() -> Result<(), error::Error> {
+    let identity = instance::Identity::current();
+    let lock_name = identity.lock_name();
+    // NOTE: never dropped, the event loop doesn't return; the lock goes with the process
+    let _lock = platform::InstanceLock::acquire(&lock_name, INSTANCE_WAIT)
+        .map_err(|e| error::Error::Platform {
+            call: "CreateMutexW",
+            reason: e.to_string(),
+        })?
+        .ok_or(error::Error::AlreadyRunning(lock_name))?;
+    let first_run = !storage::config_path().exists();
+    let mut config = config::Config::load();
+    i18n::init(config.ui.language.as_deref());
+    let mut bindings = keybindings::Bindings::new(&config).unwrap_or_else(|conflict| {
+        log::warn!("conflicting shortcuts in the config, using the default keys: {conflict}");
+        let text = i18n::tf("keybindings.conflict", &[("conflict", &conflict)]);
+        std::thread::spawn(move || platform::show_error(i18n::t("app.name"), &text));
+        config.input.prime_key = config::Input::default().prime_key;
+        config.input.note_key = config::Input::default().note_key;
+        keybindings::Bindings::new(&config).expect("the default shortcuts don't conflict")
+    });
+    let config_written = first_run.then(|| config.save());
 
+    let zones = Arc::new(zones::Zones::new(config.zones.clone()));
+    let with_tray = config.ui.tray && !std::env::args().any(|arg| arg == "--no-tray");
+    // NOTE: before the tray, creating it makes the process DPI aware, which the
+    // icon size depends on
     let event_loop = EventLoopBuilder::<CustomEvent>::with_user_event().build();
-    let event_loop_proxy = event_loop.create_proxy();
+    let icon_shade = config.ui.tray_icon;
+    let mut tray = match with_tray
+        .then(|| tray::Tray::new(&config, &zones, tray::IconLook::current(icon_shade)))
+        .transpose()
+    {
+        Ok(tray) => tray,
+        // NOTE: the taskbar entry below takes over, it's the only other way to quit
+        Err(e) if e.recoverable() => {
+            log::warn!("{e}, showing a taskbar entry instead");
+            None
+        }
+        Err(e) => return Err(e),
+    };
+    // NOTE: a window of its own, the overlay can't be in the taskbar and stay
+    // click-through; it's kept minimized and only there to be closed
+    let companion =
+        if tray.is_none() || config.ui.taskbar_entry {
+            let icon = icon_rgba().map_err(|e| log::warn!("{e}")).ok().and_then(
+                |(rgba, width, height)| winit::window::Icon::from_rgba(rgba, width, height).ok(),
+            );
+            let companion = winit::window::WindowBuilder::new()
+                .with_title(i18n::t("app.name"))
+                .with_window_icon(icon)
+                .with_inner_size(winit::dpi::LogicalSize::new(320.0, 120.0))
+                .with_active(false)
+                .build(&event_loop)?;
+            companion.set_minimized(true);
+            Some(companion)
+        } else {
+            None
+        };
+    let metrics = Arc::new(metrics::Metrics::default());
+    if let Some(interval) = metrics::interval_from_args() {
+        metrics.spawn_reporter(interval);
+    }
+    let event_loop_proxy = events::Proxy::new(event_loop.create_proxy(), metrics.clone());
+
+    let menu_proxy = event_loop_proxy.clone();
+    let loop_proxy = event_loop_proxy.clone();
+    tray::Tray::spawn_forwarder(move |id| menu_proxy.send_event(CustomEvent::Menu(id)));
+
+    let pause = Arc::new(pause::Pause::new(&config.schedule));
+    let scheduled = config.schedule.enabled;
+    let listener_pause = pause.clone();
+
+    let startup_cursor = DeviceState::new().get_mouse().coords;
+    let allowed_monitor: Arc<Mutex<Option<layout::MonitorRect>>> = Default::default();
+    let fire_allowed_monitor = allowed_monitor.clone();
+
+    let fire_metrics = metrics.clone();
+
+    let stats = Arc::new(state::Stats::default());
+    let session_state = state::SessionState::load().unwrap_or_default();
+    stats.restore(&session_state.counters);
+
+    let focus_assist = config.sound.mute_during_focus_assist.then(|| {
+        let proxy = event_loop_proxy.clone();
+        let focus_assist = Arc::new(focus_assist::FocusAssist::new(move |muted| {
+            proxy.send_event(CustomEvent::SoundAutoMuted(muted));
+        }));
+        focus_assist.spawn_poller();
+        focus_assist
+    });
+
+    {
+        let proxy = event_loop_proxy.clone();
+        power::spawn_poller(config.power.clone(), move |saving| {
+            proxy.send_event(CustomEvent::PowerSaving(saving));
+        });
+    }
+    if !config.overlay.above.is_empty() {
+        let proxy = event_loop_proxy.clone();
+        platform::watch_windows(move || {
+            proxy.send_event(CustomEvent::WindowsChanged);
+        });
+    }
+    if tray.is_some() {
+        let proxy = event_loop_proxy.clone();
+        platform::watch_setting_changes(move || {
+            proxy.send_event(CustomEvent::SettingsChanged);
+        });
+    }
+
+    let hook_activity = Arc::new(elevation::HookActivity::new());
+    if !platform::is_elevated() {
+        let relaunch_proxy = event_loop_proxy.clone();
+        elevation::spawn_watcher(hook_activity.clone(), move || {
+            relaunch_proxy.send_event(CustomEvent::Tray(TrayCommand::RelaunchElevated));
+        });
+    }
 
     let animations: Arc<ArrayQueue<Animation>> = Arc::new(ArrayQueue::new(10));
     let animations_clone = animations.clone();
+    let (driver_control, driver_commands) = crossbeam::channel::unbounded();
+    let mut governor = quality::Governor::new(&config.quality);
+    let driver_stride = governor.stride();
+    let clock: Arc<dyn clock::Clock> = Arc::new(clock::SystemClock);
+    let driver_clock = clock.clone();
+    let listener_clock = clock;
+    let demo_animations = animations.clone();
+
+    let theme = theme::load(&config.theme);
+    let frame_times = Arc::new(Mutex::new(frame_times(&config, &theme)));
+    let fire_frame_times = frame_times.clone();
+    let audio = audio::Audio::spawn(
+        audio::Sounds {
+            theme: theme.decode_sound(),
+            ping: config.sound.ping.as_ref().and_then(|spec| {
+                audio::Layered::load(spec, "ping sound")
+                    .map_err(|e| log::warn!("ping sound unusable: {e}"))
+                    .ok()
+            }),
+            monitors: monitor_sound::load(&config.sound.monitors),
+            zones: zones.clone(),
+        },
+        metrics.clone(),
+        config.sound.beep_fallback,
+    );
+    audio.send(audio::Command::SetVolume(config.sound.volume));
+    if config.sound.device.is_some() {
+        audio.send(audio::Command::SetDevice(config.sound.device.clone()));
+    }
+    let listener_audio = audio.clone();
+    let listener_prime_cues = config.sound.prime.clone();
+    // NOTE: shared so a reloaded config can swap it under the listener
+    let limiter = Arc::new(Mutex::new(limiter::Limiter::new(&config.limit)));
+    let fire_limiter = limiter.clone();
+    let shared_layout: Arc<Mutex<layout::Layout>> = Default::default();
+    let fire_layout = shared_layout.clone();
+    let fire_zones = zones.clone();
+    let diagnostics = Arc::new(diagnostics::Diagnostics::new());
+    #[cfg(feature = "webhook")]
+    let publisher = config.publish.enabled.then(|| {
+        let proxy = event_loop_proxy.clone();
+        publisher::Publisher::spawn(&config.publish, move |connected| {
+            proxy.send_event(CustomEvent::PublisherConnected(connected));
+        })
+    });
+    #[cfg(not(feature = "webhook"))]
+    if config.publish.enabled {
+        log::warn!("publish.enabled is set, but this build has no webhook support");
+    }
+    let fire_diagnostics = diagnostics.clone();
+    {
+        let proxy = event_loop_proxy.clone();
+        let pipe = config
+            .ipc
+            .pipe
+            .clone()
+            .unwrap_or_else(|| identity.pipe_name());
+        ipc::spawn(pipe, move |request| {
+            proxy.send_event(CustomEvent::Ipc(request))
+        });
+    }
+
+    let sampler = config
+        .backdrop
+        .enabled
+        .then(|| backdrop::Sampler::spawn(config.backdrop.threshold));
+    let driver_sampler = sampler.clone();
+    let copy_proxy = event_loop_proxy.clone();
+    let cooldown_proxy = event_loop_proxy.clone();
+    let hotkey_proxy = event_loop_proxy.clone();
+    let tutorial_proxy = event_loop_proxy.clone();
+    let note_proxy = event_loop_proxy.clone();
+    let hook_proxy = event_loop_proxy.clone();
+    let ripple_proxy = event_loop_proxy.clone();
+    let ripples = config.ripples.enabled;
+    // NOTE: the hook is the one producer a user can trigger at will, it's held
+    // back until the event loop runs
+    let ready = Arc::new(events::Ready::default());
+    let listener_ready = ready.clone();
+    let listener_bindings = bindings.clone();
+    // NOTE: set while the tutorial waits, so pings only tell the event loop then
+    let tutorial_waiting = Arc::new(AtomicBool::new(false));
+    let fire_tutorial_waiting = tutorial_waiting.clone();
+    let hotkey = Arc::new(hotkey::Hotkey::new(config.input.prime_key));
+    let listener_hotkey = hotkey.clone();
 
-    let frame_time = 1.0 / 60.0;
     let animation_driver_handle = std::thread::spawn(move || {
-        let mut local_animation_queue = Vec::new();
         let animations = animations_clone;
+        let mut driver = driver::Driver::new(driver_sampler, driver_stride, driver_clock);
+        let mut emit = |event| {
+            event_loop_proxy.send_event(event);
+        };
 
         loop {
             // NOTE: avoid spinning with `park`
-            while animations.is_empty() && local_animation_queue.is_empty() {
+            while animations.is_empty() && driver.is_idle() && driver_commands.is_empty() {
                 std::thread::park();
             }
 
-            while let Some(animation) = animations.pop() {
-                local_animation_queue.push(animation);
-            }
-
-            for animation in local_animation_queue.iter_mut() {
-                let elapsed = animation.last_update.elapsed();
-                if elapsed.as_secs_f64() > frame_time {
-                    animation.frame += 1;
-                    animation.last_update = std::time::Instant::now();
-                    if animation.frame < 60 {
-                        event_loop_proxy
-                            .send_event(CustomEvent::Animate(animation.clone()))
-                            .ok();
-                    } else {
-                        event_loop_proxy
-                            .send_event(CustomEvent::Clear(animation.id))
-                            .ok();
-                    }
+            let incoming = || std::iter::from_fn(|| animations.pop());
+            if let Ok(command) = driver_commands.try_recv() {
+                let drain = matches!(command, DriverCommand::Drain);
+                if !driver.command(command, incoming(), &mut emit) {
+                    return;
+                }
+                if drain {
+                    continue;
                 }
             }
-
-            local_animation_queue.retain(|animation| animation.frame < 60);
+            driver.tick(incoming(), &mut emit);
         }
     });
+    let driver_thread = animation_driver_handle.thread().clone();
 
-    std::thread::spawn(move || {
-        struct AnimationQueue {
-            id: usize,
+    let fire_stats = stats.clone();
+    let fire_taskbar = config.taskbar.clone();
+    let inhibit_window_drag = config.input.inhibit_window_drag;
+    let mask_modifier = config.input.mask_modifier;
+    let flick_config = config.flick.clone();
+    let arrow_config = config.arrow.clone();
+    let idle_gap = config.sequence.enabled.then(|| {
+        Duration::try_from_secs_f32(config.sequence.idle_gap_secs).unwrap_or_else(|_| {
+            log::warn!("invalid sequence.idle_gap_secs, using 3 seconds");
+            Duration::from_secs(3)
+        })
+    });
+    let mut coalescer = coalesce::Coalescer::new(
+        Duration::from_millis(config.coalesce.window_ms),
+        config.coalesce.radius,
+        idle_gap,
+    );
+    let mut sound_budget = coalesce::SoundBudget::new(&config.coalesce);
+    // NOTE: shared so a reloaded config can turn the sound of pipe pings off
+    let remote_sound = Arc::new(AtomicBool::new(config.remote.sound));
+    let fire_remote_sound = remote_sound.clone();
+    let fire_driver_control = driver_control.clone();
+    let sequence_linger = (config.sequence.linger_secs.max(0.0) * 60.0).min(120.0) as u8;
+    let double_tap_interval = config
+        .input
+        .double_tap
+        .then(|| Duration::from_millis(config.input.double_tap_ms));
+    let ping_at = config.input.ping_at;
+    let release_timeout = Duration::from_millis(config.input.release_timeout_ms);
+    let prime_expiry =
+        Duration::try_from_secs_f32(config.input.prime_expiry_secs).unwrap_or_else(|_| {
+            log::warn!("invalid input.prime_expiry_secs, using 3 seconds");
+            Duration::from_secs(3)
+        });
+    struct AnimationQueue {
+        queue: Arc<ArrayQueue<Animation>>,
+        animation_driver_handle: JoinHandle<()>,
+        sampler: Option<Arc<backdrop::Sampler>>,
+        frame_times: Arc<Mutex<HashMap<protocol::PingKind, Duration>>>,
+        driver_control: crossbeam::channel::Sender<DriverCommand>,
+    }
+
+    impl AnimationQueue {
+        fn new(
             queue: Arc<ArrayQueue<Animation>>,
             animation_driver_handle: JoinHandle<()>,
+            sampler: Option<Arc<backdrop::Sampler>>,
+            frame_times: Arc<Mutex<HashMap<protocol::PingKind, Duration>>>,
+            driver_control: crossbeam::channel::Sender<DriverCommand>,
+        ) -> Self {
+            Self {
+                queue,
+                animation_driver_handle,
+                sampler,
+                frame_times,
+                driver_control,
+            }
         }
 
-        impl AnimationQueue {
-            fn new(
-                queue: Arc<ArrayQueue<Animation>>,
-                animation_driver_handle: JoinHandle<()>,
-            ) -> Self {
-                Self {
-                    id: 0,
-                    queue,
-                    animation_driver_handle,
-                }
-            }
+        /// Counts another ping on the queued or playing animation `id`.
+        fn merge(&mut self, id: usize) {
+            self.driver_control.send(DriverCommand::Merge(id)).ok();
+            self.animation_driver_handle.thread().unpark();
+        }
 
-            fn run_animation(&mut self, pos: (i32, i32)) -> bool {
-                self.id += 1;
-                let animation = Animation {
-                    id: self.id,
-                    frame: 0,
-                    position: pos,
-                    last_update: std::time::Instant::now(),
-                };
-                // NOTE: Blocking here causes mouse to freeze so we do this the quick way
-                if let Ok(_) = self.queue.push(animation) {
-                    self.animation_driver_handle.thread().unpark();
-                    return true;
+        /// How long pings of `kind` show, from the config and theme.
+        fn duration(&self, kind: &protocol::PingKind) -> Duration {
+            let frame_times = self.frame_times.lock().unwrap();
+            let frame_time = frame_times
+                .get(kind)
+                .or_else(|| frame_times.get(&protocol::PingKind::Missing))
+                .copied()
+                .unwrap_or(FRAME_TIME);
+            frame_time * FRAME_COUNT as u32
+        }
+
+        fn run_animation(&mut self, animation: Animation) -> bool {
+            let (id, pos, denied) = (animation.id, animation.position, animation.denied);
+            // NOTE: Blocking here causes mouse to freeze so we do this the quick way
+            let queued = match self.queue.push(animation) {
+                Ok(()) => true,
+                Err(animation) => self.make_room(animation),
+            };
+            if queued {
+                if let (Some(sampler), false) = (&self.sampler, denied) {
+                    sampler.request(id, pos);
                 }
-                false
+                self.animation_driver_handle.thread().unpark();
             }
+            queued
         }
 
-        #[derive(Debug, Clone, Copy)]
-        enum Direction {
-            Up,
-            Down,
-            Left,
-            Right,
+        /// Queues `animation` in place of a lower priority one when the
+        /// queue is full, see [`coalesce::evict`].
+        fn make_room(&mut self, animation: Animation) -> bool {
+            let mut queued = Vec::with_capacity(self.queue.capacity());
+            while let Some(queued_animation) = self.queue.pop() {
+                queued.push(queued_animation);
+            }
+            // NOTE: the driver may have taken some meanwhile
+            let admitted = queued.len() < self.queue.capacity() || {
+                let priorities = queued
+                    .iter()
+                    .map(|queued| coalesce::Priority::of(&queued.source))
+                    .collect::<Vec<_>>();
+                let incoming = coalesce::Priority::of(&animation.source);
+                match coalesce::evict(&priorities, incoming) {
+                    Some(index) => {
+                        let evicted = queued.remove(index);
+                        log::info!("ping {} evicted for ping {}", evicted.id, animation.id);
+                        true
+                    }
+                    None => false,
+                }
+            };
+            if admitted {
+                queued.push(animation);
+            }
+            for queued_animation in queued {
+                self.queue.push(queued_animation).ok();
+            }
+            admitted
         }
+    }
 
-        fn calc_movement(prev: (i32, i32), curr: (i32, i32)) -> (Direction, f64) {
-            let dx = curr.0 - prev.0;
-            let dy = curr.1 - prev.1;
-
-            let direction = if dx.abs() > dy.abs() {
-                if dx > 0 {
-                    Direction::Right
+    // NOTE: everything after the gesture is decided, shared by clicks, double
+    // taps and pings sent through the control pipe
+    let fire = {
+        let mut animation_queue = AnimationQueue::new(
+            animations,
+            animation_driver_handle,
+            sampler,
+            fire_frame_times,
+            fire_driver_control,
+        );
+        let stats = fire_stats;
+        let limiter = fire_limiter;
+        let allowed_monitor = fire_allowed_monitor;
+        let metrics = fire_metrics;
+        let taskbar_config = fire_taskbar;
+        let taskbar = taskbar::TaskbarCache::default();
+        let audio = audio.clone();
+        let fire_clock = listener_clock.clone();
+        let focus_assist = focus_assist.clone();
+        let remote_sound = fire_remote_sound;
+        let fire = move |pos: MousePosition,
+                         kind: protocol::PingKind,
+                         persistent: Option<bool>,
+                         copy: bool,
+                         dragged: bool,
+                         toward: Option<MousePosition>,
+                         source: ping_source::PingSource|
+              -> Result<Option<usize>, String> {
+            let fired = fire_clock.now();
+            let raw = pos;
+            let local = source == ping_source::PingSource::Local;
+            let (pos, off_desktop, toward, monitor_rect, zone) = {
+                let layout = fire_layout.lock().unwrap();
+                // NOTE: local pings are moved onto the desktop, anyone else's are denied at its edge
+                let (pos, off_desktop) = match layout.validate(pos, &source) {
+                    Ok(pos) => (pos, false),
+                    Err(clamped) => (clamped, true),
+                };
+                let toward = toward.map(|toward| layout.clamp(toward));
+                let monitor_rect = layout.monitor_at(pos).map(|(index, rect)| (index, *rect));
+                let zone = fire_zones.find(&layout, pos);
+                (pos, off_desktop, toward, monitor_rect, zone)
+            };
+            let monitor = monitor_rect.map(|(index, _)| index);
+            let record = |placed, persistent: Option<bool>, outcome| {
+                if let Some(persistent) = persistent {
+                    fire_diagnostics.record(raw, placed, monitor, persistent, outcome);
+                }
+            };
+            if persistent.is_some() && dragged {
+                record(pos, persistent, diagnostics::Outcome::Dragged);
+                log::info!("ping at {pos:?} dropped: a window was dragged");
+                metrics::Metrics::bump(&metrics.pings_rejected);
+                return Err("a window was dragged".to_owned());
+            }
+            let allowed = match *allowed_monitor.lock().unwrap() {
+                Some(monitor) => monitor.contains(pos),
+                None => true,
+            };
+            if persistent.is_some() && (off_desktop || !allowed) {
+                let reason = match off_desktop {
+                    true => "off the desktop",
+                    false => "outside the allowed monitor",
+                };
+                record(pos, persistent, diagnostics::Outcome::Denied);
+                log::info!("ping at {pos:?} rejected: {reason}");
+                metrics::Metrics::bump(&metrics.pings_rejected);
+                let denied = AnimationBuilder::at(pos)
+                    .denied()
+                    .source(source)
+                    .duration(animation_queue.duration(&protocol::PingKind::Missing))
+                    .build_at(fired);
+                animation_queue.run_animation(denied.animation);
+                return Err(reason.to_owned());
+            }
+            // NOTE: kept out of the interpreter, IPC pings take from the same
+            // limiter (each source its own bucket) and a reload swaps it
+            if let (Some(limiter), Some(_)) = (&mut *limiter.lock().unwrap(), persistent) {
+                if let Err(wait) = limiter.take(&source, fired) {
+                    record(pos, persistent, diagnostics::Outcome::Limited);
+                    log::info!("ping at {pos:?} refused: allowed again in {wait:?}");
+                    metrics::Metrics::bump(&metrics.pings_rejected);
+                    // NOTE: the ring is for the user at the mouse, a script gets the wait back
+                    if local {
+                        cooldown_proxy.send_event(CustomEvent::Cooldown(limiter::Cooldown {
+                            position: pos,
+                            until: fired + wait,
+                            wait,
+                        }));
+                    }
+                    return Err(format!("rate limited for {wait:?}"));
+                }
+            }
+            if copy {
+                copy_proxy.send_event(CustomEvent::CopyCoordinates(pos));
+            }
+            let pos = match taskbar_config.avoid {
+                taskbar::Avoidance::Clamp => taskbar.avoid(pos, taskbar_config.margin),
+                _ => pos,
+            };
+            let mut merged = false;
+            let mut heard = false;
+            let mut shown = None;
+            let success = persistent.map(|persistent| {
+                let now = fire_clock.now();
+                let mut builder = AnimationBuilder::at(pos)
+                    .kind(kind.clone())
+                    .source(source.clone())
+                    .persistent(persistent)
+                    .duration(animation_queue.duration(&kind))
+                    .toward(toward);
+                // NOTE: merged into a nearby ping the arrow would be lost
+                let decision = match toward {
+                    Some(_) => coalesce::Decision::New { sequence: None },
+                    None => coalescer.decide(pos, &kind, &source, persistent, now),
+                };
+                match decision {
+                    // NOTE: one sound and one visual for the whole burst
+                    coalesce::Decision::Merge(id) => {
+                        animation_queue.merge(id);
+                        record(pos, Some(persistent), diagnostics::Outcome::Merged);
+                        stats.pings.fetch_add(1, Ordering::Relaxed);
+                        metrics::Metrics::bump(&metrics.pings_accepted);
+                        merged = true;
+                        shown = Some(id);
+                        return true;
+                    }
+                    coalesce::Decision::New { sequence } => {
+                        builder = builder.sequence(sequence, sequence_linger);
+                    }
+                }
+                let built = builder
+                    .zone(zone.map(|index| fire_zones.zones()[index].name.as_str().into()))
+                    .build_at(now);
+                let id = built.animation.id;
+                heard = built.sound;
+                let success = animation_queue.run_animation(built.animation);
+                if success && !persistent {
+                    coalescer.record(id, pos, kind.clone(), source.clone(), now);
+                }
+                shown = success.then_some(id);
+                let outcome = if success {
+                    diagnostics::Outcome::Shown
                 } else {
-                    Direction::Left
+                    diagnostics::Outcome::Evicted
+                };
+                record(pos, Some(persistent), outcome);
+                if let (Some(index), true) = (zone, success) {
+                    fire_zones.run_command(index);
                 }
-            } else {
-                if dy > 0 {
-                    Direction::Down
+                #[cfg(feature = "webhook")]
+                if let (Some(publisher), true) = (&publisher, success) {
+                    publisher.publish(publisher::PingEvent {
+                        x: pos.0,
+                        y: pos.1,
+                        monitor,
+                        normalized: monitor_rect.map(|(_, rect)| {
+                            (
+                                (pos.0 - rect.x) as f32 / rect.width as f32,
+                                (pos.1 - rect.y) as f32 / rect.height as f32,
+                            )
+                        }),
+                        kind: kind.as_str().to_owned(),
+                        persistent,
+                    });
+                }
+                let counter = match (success, persistent) {
+                    (false, _) => &stats.dropped,
+                    (true, false) => &stats.pings,
+                    (true, true) => &stats.markers,
+                };
+                counter.fetch_add(1, Ordering::Relaxed);
+                metrics::Metrics::bump(if success {
+                    &metrics.pings_accepted
                 } else {
-                    Direction::Up
+                    &metrics.pings_evicted
+                });
+                success
+            });
+            if success == Some(true)
+                && local
+                && fire_tutorial_waiting.swap(false, Ordering::Relaxed)
+            {
+                tutorial_proxy.send_event(CustomEvent::Pinged);
+            }
+
+            let muted = focus_assist
+                .as_ref()
+                .is_some_and(|focus_assist| focus_assist.mutes_sound());
+            let wanted = local || remote_sound.load(Ordering::Relaxed);
+            if let (Some(true), false, false, true, true) = (success, muted, merged, heard, wanted)
+            {
+                // NOTE: local sounds are always granted, counted so scripted ones back off around them
+                let granted = sound_budget.take(coalesce::Priority::of(&source), fire_clock.now());
+                if granted {
+                    audio.send(audio::Command::Play {
+                        monitor,
+                        zone,
+                        pan: 0.0,
+                        volume: 1.0,
+                        fired,
+                    });
                 }
+            }
+            match success {
+                Some(false) => Err("queue full".to_owned()),
+                _ => Ok(shown),
+            }
+        };
+        Arc::new(Mutex::new(fire))
+    };
+    let listener_fire = fire.clone();
+    std::thread::spawn(move || {
+        let pause = listener_pause;
+        let caret = caret::Caret::spawn();
+        let mut interpreter = input::Interpreter::new(
+            listener_bindings,
+            priming::Priming::new(prime_expiry),
+            double_tap_interval.map(priming::DoubleTap::new),
+            click::Click::new(release_timeout),
+            ping_at,
+            flick_config.clone(),
+            arrow_config,
+        );
+        let mut press_window = None;
+        let mut flick_sampler = None;
+        let device_state = DeviceState::new();
+        let hotkey = listener_hotkey;
+        let prime_cues = listener_prime_cues;
+        let cue_audio = listener_audio;
+        let cue_focus_assist = focus_assist;
+        let cue = move |primed: bool| {
+            let muted = cue_focus_assist
+                .as_ref()
+                .is_some_and(|focus_assist| focus_assist.mutes_sound());
+            let enabled = match primed {
+                true => prime_cues.on_prime,
+                false => prime_cues.on_unprime,
             };
-            let distance = ((dx * dx + dy * dy) as f64).sqrt();
-            (direction, distance)
-        }
+            if enabled && !muted {
+                cue_audio.send(audio::Command::Cue {
+                    primed,
+                    volume: prime_cues.volume,
+                });
+            }
+        };
 
-        let mut animation_queue = AnimationQueue::new(animations, animation_driver_handle);
-        let mut primed = false;
-        let mut start_position = None;
-        let device_state = DeviceState::new();
-        let (_stream, stream_handle) = rodio::OutputStream::try_default().unwrap();
-        let file = BufReader::new(File::open("assets/ping_missing.ogg").unwrap());
-        let source = Decoder::new(file).unwrap().buffered();
+        let fire = move |pos: MousePosition,
+                         kind: protocol::PingKind,
+                         persistent: Option<bool>,
+                         copy: bool,
+                         dragged: bool,
+                         toward: Option<MousePosition>| {
+            let local = ping_source::PingSource::Local;
+            // NOTE: a refusal is logged, there's nobody to answer
+            (*listener_fire.lock().unwrap())(pos, kind, persistent, copy, dragged, toward, local)
+                .ok();
+        };
 
-        rdev::listen(move |e: rdev::Event| {
-            match e.event_type {
-                rdev::EventType::KeyPress(rdev::Key::Alt) => {
-                    primed = true;
-                }
-                rdev::EventType::KeyRelease(rdev::Key::Alt) => {
-                    primed = false;
+        listener_ready.wait();
+        log::info!("event loop running, listening for input");
+        let listened = input::InputSource::run(input::RdevSource, move |event| {
+            if let input::InputEvent::ButtonDown(_, pos) = event {
+                hook_activity.record_press();
+                // NOTE: past the limiter, the queue and the sound, ripples are only drawn
+                if ripples && !pause.is_paused() {
+                    ripple_proxy.send_event(CustomEvent::Ripple(pos));
                 }
-                rdev::EventType::ButtonPress(rdev::Button::Left) if primed => {
-                    let mouse: MouseState = device_state.get_mouse();
-                    start_position = Some(mouse.coords);
-                }
-                rdev::EventType::ButtonRelease(rdev::Button::Left) => match start_position {
-                    Some(pos) if primed => {
-                        const BREAKEPOINT: f64 = 100.0;
-
-                        let mouse: MouseState = device_state.get_mouse();
-                        let current_pos = mouse.coords;
-                        let (direction, distance) = calc_movement(pos, current_pos);
+            }
+            // NOTE: the ping key can be recorded anew from the tray, already checked there
+            interpreter.set_prime(hotkey.get());
 
-                        let success = match direction {
-                            Direction::Left if distance >= BREAKEPOINT => {
-                                Some(animation_queue.run_animation(pos))
-                            }
-                            _ => None,
-                        };
+            if let input::InputEvent::KeyDown(key) = event {
+                if let Some(capture) = hotkey.capture(key) {
+                    hotkey_proxy.send_event(CustomEvent::HotkeyCaptured(capture));
+                    return;
+                }
+            }
 
-                        if let Some(true) = success {
-                            stream_handle
-                                .play_raw(source.clone().convert_samples())
-                                .ok();
+            // NOTE: keys the poller can't see are trusted to the hook and the expiry
+            let prime_down = || match hotkey::keycodes(hotkey.get()) {
+                Some(keycodes) => {
+                    let keys = device_state.get_keys();
+                    keycodes.iter().any(|keycode| keys.contains(keycode))
+                }
+                None => true,
+            };
+            let flick = match event {
+                input::InputEvent::ButtonUp(rdev::Button::Left, _) => flick_sampler
+                    .take()
+                    .and_then(|sampler: flick::Sampler| sampler.finish(&flick_config)),
+                _ => None,
+            };
+            let context = input::Context {
+                now: listener_clock.now(),
+                paused: pause.is_paused(),
+                prime_down: &prime_down,
+                flick,
+            };
+            for action in interpreter.handle(event, &context) {
+                match action {
+                    input::Action::Cue(primed) => cue(primed),
+                    input::Action::DoubleTapped => {
+                        let pos = device_state.get_mouse().coords;
+                        log::info!("ping key double tapped at {pos:?}");
+                        fire(
+                            pos,
+                            protocol::PingKind::Missing,
+                            Some(false),
+                            false,
+                            false,
+                            None,
+                        );
+                    }
+                    input::Action::EditNote => {
+                        let pos = device_state.get_mouse().coords;
+                        note_proxy.send_event(CustomEvent::EditNote(pos));
+                    }
+                    input::Action::PingCaret => {
+                        let pos = caret.locate().unwrap_or_else(|| {
+                            let pos = device_state.get_mouse().coords;
+                            log::info!("no text caret found, pinging at the mouse");
+                            pos
+                        });
+                        log::info!("pinging at the text caret {pos:?}");
+                        fire(
+                            pos,
+                            protocol::PingKind::Missing,
+                            Some(false),
+                            false,
+                            false,
+                            None,
+                        );
+                    }
+                    input::Action::Pressed(pos) => {
+                        flick_sampler = flick_config.enabled.then(|| {
+                            let window = Duration::from_millis(flick_config.window_ms);
+                            flick::Sampler::start(pos, window)
+                        });
+                        if inhibit_window_drag {
+                            press_window = platform::window_origin_at(pos);
                         }
                     }
-                    _ => start_position = None,
-                },
-                _ => {}
+                    input::Action::Released {
+                        target,
+                        kind,
+                        persistent,
+                        copy,
+                    } => {
+                        // NOTE: AltDrag and friends move windows with the same gesture
+                        let dragged = press_window.take().is_some_and(|(window, origin)| {
+                            platform::window_origin(window).is_some_and(|now| now != origin)
+                        });
+                        fire(target, kind, persistent, copy, dragged, None);
+                        // NOTE: while the key is still held, so menus don't see it released alone
+                        if mask_modifier && persistent.is_some() {
+                            platform::tap_mask_key();
+                        }
+                    }
+                    input::Action::Aimed { target, toward } => {
+                        fire(
+                            target,
+                            protocol::PingKind::Missing,
+                            Some(false),
+                            false,
+                            false,
+                            toward,
+                        );
+                        if mask_modifier {
+                            platform::tap_mask_key();
+                        }
+                    }
+                    input::Action::Abandoned => {
+                        flick_sampler = None;
+                        press_window = None;
+                    }
+                }
             }
-            // return Some(e);
-        })
-        .unwrap();
+        });
+        // NOTE: without the hook there's nothing to ping with, the event loop reports it
+        if let Err(e) = listened {
+            hook_proxy.send_event(CustomEvent::Failed(e));
+        }
     });
 
-    let available_monitors = event_loop.available_monitors();
-    let mut offset = f32::MAX;
-    let mut total_width = 0;
-    let mut total_height = 0;
+    let mut my_app = MyApp::new(config, theme);
+    let mut overlay: Option<overlay::Overlay> = None;
+    let mut pending_markers = Some(session_state.markers);
+    // NOTE: the overlay costs a GPU device and a desktop sized surface, so it's only
+    // built once something needs drawing
+    let mut overlay_wanted = pending_markers
+        .as_ref()
+        .is_some_and(|markers| !markers.is_empty());
+    let mut deferred: Vec<CustomEvent> = Vec::new();
+    // NOTE: set once the user removed all data, so quitting doesn't write it back
+    let mut data_removed = false;
+    let mut restarting = false;
+    let mut monitor_retry = MONITOR_RETRY_MIN;
+    let mut next_monitor_retry = None;
+    // NOTE: only armed once the loop is up, quitting right after launch never
+    // touches the GPU
+    let mut overlay_warm_up: Option<Instant> = None;
 
-    for monitor in available_monitors {
-        let monitor_size = monitor.size();
-        total_width += monitor_size.width;
-        total_height += monitor_size.height;
-        let monitor_position = monitor.position();
-        if (monitor_position.x as f32) < offset {
-            offset = monitor_position.x as f32;
-        }
-    }
+    let mut open_dialog: Option<(dialog::Dialog, Panel)> = None;
+    let mut spare_dialog: Option<dialog::Spare> = None;
+    let mut config_written = config_written;
 
-    let window = winit::window::WindowBuilder::new()
-        .with_inner_size(winit::dpi::PhysicalSize::new(total_width, total_height))
-        .with_position(winit::dpi::PhysicalPosition::new(offset, 0.0))
-        .with_transparent(true)
-        .with_decorations(false)
-        .build(&event_loop)
-        .unwrap();
+    let mut layout = layout::Layout::default();
+    let mut next_layout_check = Instant::now();
+    let mut ping_receiver = protocol::Receiver::default();
+    let sweep_interval = Duration::try_from_secs_f32(my_app.config.sweep.interval_secs)
+        .unwrap_or_else(|_| {
+            log::warn!("invalid sweep.interval_secs, using 10 seconds");
+            Duration::from_secs(10)
+        });
+    let sweep_grace =
+        Duration::try_from_secs_f32(my_app.config.sweep.grace_secs).unwrap_or_else(|_| {
+            log::warn!("invalid sweep.grace_secs, using 5 seconds");
+            Duration::from_secs(5)
+        });
+    let mut next_sweep = Instant::now() + sweep_interval;
 
-    window.set_window_level(WindowLevel::AlwaysOnTop);
-    window.set_cursor_hittest(false).unwrap();
+    let mut sound_auto_muted = false;
+    let mut paused = false;
+    let mut next_tooltip_refresh = Instant::now();
+    let mut next_trail_tick = Instant::now();
+    let mut next_schedule_check = Instant::now();
+    let mut pending_acks = ipc::PendingAcks::default();
 
-    let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
-        backends: wgpu::Backends::all(),
-        dx12_shader_compiler: Dx12Compiler::default(),
-    });
+    event_loop.run(move |event, target, control_flow| {
+        let pause_status = pause.status();
+        let wake_at = [
+            next_monitor_retry.filter(|_| overlay.is_none()),
+            overlay_warm_up.filter(|_| overlay.is_none()),
+            Some(next_layout_check),
+            Some(next_sweep),
+            matches!(pause_status, pause::Status::PausedUntil(_)).then_some(next_tooltip_refresh),
+            (!my_app.trail.is_empty()).then_some(next_trail_tick),
+            scheduled.then_some(next_schedule_check),
+            pending_acks.deadline(),
+        ]
+        .into_iter()
+        .flatten()
+        .min();
+        *control_flow = wake_at.map_or(ControlFlow::Wait, ControlFlow::WaitUntil);
+        if let Event::UserEvent(_) = event {
+            loop_proxy.received();
+            metrics::Metrics::bump(&metrics.user_events);
+        }
+        // NOTE: menu clicks are routed here rather than in the menu thread, the routes
+        // change as submenus are rebuilt
+        let event = match event {
+            Event::UserEvent(CustomEvent::Menu(id)) => {
+                match tray.as_ref().and_then(|tray| tray.route(&id)) {
+                    Some(command) => Event::UserEvent(CustomEvent::Tray(command)),
+                    None => return,
+                }
+            }
+            event => event,
+        };
+        match event {
+            Event::NewEvents(cause @ (StartCause::Init | StartCause::ResumeTimeReached { .. })) => {
+                if matches!(cause, StartCause::Init) {
+                    ready.open();
+                    overlay_warm_up = Some(Instant::now() + OVERLAY_WARM_UP);
+                }
+                if overlay_warm_up.is_some_and(|deadline| deadline <= Instant::now()) {
+                    overlay_warm_up = None;
+                    if overlay.is_none() {
+                        log::debug!("warming up the overlay");
+                        request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    }
+                }
+                if matches!(cause, StartCause::Init) && first_run {
+                    loop_proxy.send_event(CustomEvent::Tray(TrayCommand::RunSelfCheck));
+                    loop_proxy.send_event(CustomEvent::Tray(TrayCommand::ShowTutorial));
+                }
+                if overlay.is_some()
+                    || !overlay_wanted
+                    || next_monitor_retry.is_some_and(|deadline| deadline > Instant::now())
+                {
+                    return;
+                }
 
-    let surface = unsafe { instance.create_surface(&window) }.unwrap();
-    // SAFETY: we windows
-    unsafe {
-        hide_taskbar_entry(window.raw_window_handle());
-    }
+                // NOTE: launched from Task Scheduler before the display is up there are no
+                // monitors to span, so stay dormant and poll until one shows up
+                let built = match overlay::Overlay::new(target, &my_app.config.overlay) {
+                    Ok(built) => built,
+                    Err(e) => {
+                        error::report(&e);
+                        *control_flow = ControlFlow::Exit;
+                        return;
+                    }
+                };
+                let Some(built) = built else {
+                    log::info!("no monitors available, retrying in {monitor_retry:?}");
+                    let deadline = Instant::now() + monitor_retry;
+                    next_monitor_retry = Some(deadline);
+                    monitor_retry = (monitor_retry * 2).min(MONITOR_RETRY_MAX);
+                    *control_flow = ControlFlow::WaitUntil(deadline);
+                    return;
+                };
+                if next_monitor_retry.take().is_some() && monitor_retry > MONITOR_RETRY_MIN {
+                    log::info!("monitors available, overlay created");
+                }
+                my_app.offset = built.offset;
 
-    let adapter = instance
-        .request_adapter(&wgpu::RequestAdapterOptions {
-            power_preference: wgpu::PowerPreference::default(),
-            compatible_surface: Some(&surface),
-            force_fallback_adapter: false,
-        })
-        .await
-        .unwrap();
-
-    let (device, queue) = adapter
-        .request_device(
-            &wgpu::DeviceDescriptor {
-                features: wgpu::Features::empty(),
-                limits: wgpu::Limits::default(),
-                label: None,
-            },
-            None,
-        )
-        .await
-        .unwrap();
-
-    let size = window.inner_size();
-    let mut config = surface
-        .get_default_config(&adapter, size.width, size.height)
-        .expect("Surface isn't supported by the adapter.");
-
-    config.present_mode = wgpu::PresentMode::Immediate;
-    surface.configure(&device, &config);
-
-    let mut egui_state = egui_winit::State::new(&event_loop);
-    let egui_context = egui::Context::default();
-    egui_extras::install_image_loaders(&egui_context);
-    let mut egui_renderer = Renderer::new(&device, config.format, None, 1);
-    let mut my_app = MyApp::new(offset.abs());
-
-    event_loop.run(move |event, _, control_flow| {
-        let _ = (
-            &instance,
-            &adapter,
-            &egui_renderer,
-            &egui_context,
-            &egui_state,
-        );
+                let layout = layout::Layout::new(target.available_monitors());
+                for marker in pending_markers.take().unwrap_or_default() {
+                    let position = (marker.x, marker.y);
+                    if !layout.contains(position) {
+                        log::info!(
+                            "dropping restored marker at {position:?}: outside all monitors"
+                        );
+                        continue;
+                    }
+                    let restored = AnimationBuilder::at(position)
+                        .persistent(true)
+                        .note(marker.note.map(Into::into))
+                        .start_frame(MARKER_FRAME)
+                        .silent()
+                        .build();
+                    my_app.add_animation(restored.animation);
+                }
 
-        *control_flow = ControlFlow::Wait;
-        match event {
+                overlay = Some(built);
+                loop_proxy.send_event(CustomEvent::WindowsChanged);
+                for event in deferred.drain(..) {
+                    loop_proxy.send_event(event);
+                }
+            }
             Event::UserEvent(CustomEvent::Animate(animation)) => {
                 my_app.add_animation(animation);
-                egui_context.request_repaint();
+                match &overlay {
+                    Some(overlay) => overlay.request_redraw(),
+                    None => request_overlay(&mut overlay_wanted, &mut next_monitor_retry),
+                }
+            }
+            Event::UserEvent(CustomEvent::Ripple(position)) => {
+                let ripple = ripple::Ripple {
+                    position,
+                    clicked: Instant::now(),
+                };
+                ripple::push(&mut my_app.ripples, ripple, &my_app.config.ripples);
+                match &overlay {
+                    Some(overlay) => overlay.request_redraw(),
+                    None => request_overlay(&mut overlay_wanted, &mut next_monitor_retry),
+                }
             }
             Event::UserEvent(CustomEvent::Clear(animation_id)) => {
+                my_app.leave_trail(animation_id);
                 my_app.remove_animation(animation_id);
-                egui_context.request_repaint();
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
             }
-            Event::WindowEvent {
-                event: window_event,
-                ..
-            } => match window_event {
-                WindowEvent::CloseRequested => *control_flow = ControlFlow::Exit,
-                _ => {}
-            },
-            Event::RedrawEventsCleared => {
-                window.request_redraw();
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ClearMarkers)) => {
+                my_app.clear_markers();
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
             }
-            Event::RedrawRequested(_) => {
-                let texture = surface.get_current_texture();
-                let frame = match texture {
-                    Ok(f) => f,
-                    Err(e) => {
-                        println!("surface lost: window is probably minimized: {e}");
-                        return;
+            Event::UserEvent(CustomEvent::CopyCoordinates(position)) => {
+                let layout = layout::Layout::new(target.available_monitors());
+                let text =
+                    layout.format_position(&my_app.config.copy_coordinates.template, position);
+                match platform::set_clipboard_text(&text) {
+                    Ok(()) => log::info!("copied coordinates: {text}"),
+                    Err(e) => log::warn!("failed to copy coordinates to the clipboard: {e}"),
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::CopyDiagnostics)) => {
+                let monitors = diagnostic_monitors(target);
+                let adapter = overlay
+                    .as_ref()
+                    .map(|overlay| overlay.adapter.get_info().name);
+                let report = diagnostics::report(
+                    &diagnostics.pings(),
+                    &platform::os_version(),
+                    adapter.as_deref(),
+                    overlay.as_ref().map(|overlay| overlay.offset),
+                    &monitors,
+                    audio.backend(),
+                );
+                match platform::set_clipboard_text(&report) {
+                    Ok(()) => log::info!("copied diagnostics to the clipboard"),
+                    Err(e) => log::warn!("failed to copy diagnostics to the clipboard: {e}"),
+                }
+            }
+            #[cfg(feature = "webhook")]
+            Event::UserEvent(CustomEvent::PublisherConnected(connected)) => {
+                log::info!("webhook reachable: {connected}");
+                if let Some(tray) = &tray {
+                    tray.show_publisher(connected);
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RepairOverlay)) => {
+                if let Some(overlay) = &overlay {
+                    log::info!("repairing overlay window styles");
+                    overlay.repair();
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RestartRenderer)) => {
+                if let Some(overlay) = &overlay {
+                    overlay.mark_lost();
+                    overlay.request_redraw();
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RunSelfCheck)) => {
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Tray(TrayCommand::RunSelfCheck));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                // NOTE: the top left corner of the desktop when Windows names no primary
+                let demo = match target.primary_monitor() {
+                    Some(monitor) => {
+                        AnimationBuilder::normalized(&layout::MonitorRect::of(&monitor), 0.5, 0.5)
                     }
+                    None => AnimationBuilder::at((0, 0)),
                 };
+                if demo_animations.push(demo.build().animation).is_ok() {
+                    driver_thread.unpark();
+                }
 
-                let view = frame
-                    .texture
-                    .create_view(&wgpu::TextureViewDescriptor::default());
-
-                let mut encoder =
-                    device.create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-
-                let input = egui_state.take_egui_input(&window);
-                egui_context.begin_frame(input);
-                my_app.ui(&egui_context);
-
-                let output = egui_context.end_frame();
-                let paint_jobs = egui_context.tessellate(output.shapes);
-                let screen_descriptor = ScreenDescriptor {
-                    size_in_pixels: [config.width, config.height],
-                    pixels_per_point: 1.0,
+                let alpha_modes = overlay
+                    .surface
+                    .get_capabilities(&overlay.adapter)
+                    .alpha_modes;
+                let frame_count = (!my_app.config.marker.style.is_procedural())
+                    .then_some(my_app.theme.frames_for(&protocol::PingKind::Missing).len());
+                let check = self_check::SelfCheck::run(
+                    &alpha_modes,
+                    frame_count,
+                    config_written.take().as_ref(),
+                );
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("app.name"),
+                    (420.0, 300.0),
+                ) else {
+                    return;
                 };
-
-                {
-                    for (id, image_delta) in &output.textures_delta.set {
-                        egui_renderer.update_texture(&device, &queue, *id, image_delta);
-                    }
-                    for id in &output.textures_delta.free {
-                        egui_renderer.free_texture(id);
-                    }
-
-                    {
-                        egui_renderer.update_buffers(
-                            &device,
-                            &queue,
-                            &mut encoder,
-                            &paint_jobs,
-                            &screen_descriptor,
+                open_dialog = Some((dialog, Panel::SelfCheck(check)));
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ShowTutorial)) => {
+                if overlay.is_none() {
+                    deferred.push(CustomEvent::Tray(TrayCommand::ShowTutorial));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                }
+                let (x, y) = primary_center(target);
+                let center = Pos2::new(x as f32 + my_app.offset, y as f32 + TUTORIAL_DROP);
+                my_app.tutorial =
+                    Some(tutorial::Tutorial::new(center, hotkey::label(hotkey.get())));
+                tutorial_waiting.store(true, Ordering::Relaxed);
+            }
+            Event::UserEvent(CustomEvent::Pinged) => {
+                if let Some(tutorial) = &mut my_app.tutorial {
+                    tutorial.pinged();
+                }
+            }
+            Event::UserEvent(CustomEvent::EditNote(pos)) => {
+                let Some(overlay) = &overlay else {
+                    return;
+                };
+                let at = Pos2::new(pos.0 as f32 + my_app.offset, pos.1 as f32);
+                let Some(id) = my_app.marker_for_note(at) else {
+                    log::info!("note chord at {pos:?} without a marker to note");
+                    return;
+                };
+                // NOTE: the chord auto-repeats while held
+                if matches!(&open_dialog, Some((_, Panel::Note(editor))) if editor.id == id) {
+                    return;
+                }
+                let note = my_app.animations[&id].note.clone();
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("note.title"),
+                    (320.0, 100.0),
+                ) else {
+                    return;
+                };
+                dialog.move_to(winit::dpi::PhysicalPosition::new(pos.0 + 24, pos.1 + 24));
+                open_dialog = Some((dialog, Panel::Note(notes::Editor::new(id, note.as_deref()))));
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RecordHotkey)) => {
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Tray(TrayCommand::RecordHotkey));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("dialog.hotkey_title"),
+                    (320.0, 240.0),
+                ) else {
+                    return;
+                };
+                let current = hotkey::label(hotkey.get());
+                open_dialog = Some((dialog, Panel::Hotkey(current, bindings.describe())));
+                hotkey.start_capture();
+            }
+            Event::UserEvent(CustomEvent::HotkeyCaptured(capture)) => {
+                if matches!(open_dialog, Some((_, Panel::Hotkey(..)))) {
+                    let (dialog, _) = open_dialog.take().unwrap();
+                    spare_dialog = Some(dialog.into_spare());
+                }
+                let hotkey::Capture::Key(key) = capture else {
+                    return;
+                };
+                let updated = match hotkey::conflict(key) {
+                    Some(reason) => Err(reason.to_owned()),
+                    None => bindings.with_prime(key).map_err(|conflict| {
+                        i18n::tf("hotkey.conflict_binding", &[("conflict", &conflict)])
+                    }),
+                };
+                let updated = match updated {
+                    Ok(updated) => updated,
+                    Err(reason) => {
+                        let text = i18n::tf(
+                            "hotkey.conflict",
+                            &[("key", &hotkey::label(key)), ("reason", &reason)],
                         );
+                        std::thread::spawn(move || {
+                            platform::show_error(i18n::t("app.name"), &text)
+                        });
+                        return;
                     }
+                };
+                bindings = updated;
+                log::info!("ping hotkey set to {}", hotkey::label(key));
+                hotkey.set(key);
+                my_app.config.input.prime_key = key;
+                if let Err(e) = my_app.config.save() {
+                    log::warn!("failed to remember the hotkey: {e}");
                 }
-
-                {
-                    let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
-                        label: None,
-                        color_attachments: &[Some(wgpu::RenderPassColorAttachment {
-                            view: &view,
-                            resolve_target: None,
-                            ops: wgpu::Operations {
-                                load: wgpu::LoadOp::Load,
-                                store: true,
-                            },
-                        })],
-                        depth_stencil_attachment: None,
-                    });
-
-                    egui_renderer.render(&mut render_pass, &paint_jobs, &screen_descriptor);
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ShowLegend)) => {
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Tray(TrayCommand::ShowLegend));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("dialog.legend_title"),
+                    (260.0, 240.0),
+                ) else {
+                    return;
+                };
+                open_dialog = Some((dialog, Panel::Legend(my_app.legend())));
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ShowAbout)) => {
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Tray(TrayCommand::ShowAbout));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("dialog.about_title"),
+                    (420.0, 300.0),
+                ) else {
+                    return;
+                };
+                let system = diagnostics::System::new(
+                    Some(overlay.graphics.clone()),
+                    diagnostic_monitors(target),
+                    audio.backend(),
+                );
+                open_dialog = Some((dialog, Panel::About(Box::new(about::About::new(system)))));
+            }
+            Event::UserEvent(CustomEvent::Failed(e)) => {
+                error::report(&e);
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::UserEvent(CustomEvent::Ipc(ipc::Request::Ping(message, reply))) => {
+                if pause.is_paused() {
+                    reply.send(Err("paused".to_owned())).ok();
+                    return;
+                }
+                let source = ping_receiver.receive(&message);
+                let seq = message.seq;
+                let fired = (*fire.lock().unwrap())(
+                    (message.x, message.y),
+                    message.kind,
+                    Some(message.persistent),
+                    false,
+                    false,
+                    None,
+                    source,
+                );
+                // NOTE: answered once the overlay drew it, see the redraw below
+                match fired {
+                    Ok(Some(id)) => pending_acks.wait(id, seq, reply, Instant::now()),
+                    Ok(None) => {
+                        reply.send(Ok(ipc::Ack { seq })).ok();
+                    }
+                    Err(e) => {
+                        reply.send(Err(e)).ok();
+                    }
+                }
+            }
+            Event::UserEvent(CustomEvent::Ipc(ipc::Request::Status(reply))) => {
+                let status = ipc::Status {
+                    version: env!("CARGO_PKG_VERSION"),
+                    uptime_secs: diagnostics.uptime().as_secs(),
+                    paused: pause_status != pause::Status::Active,
+                    muted: sound_auto_muted,
+                    animations: my_app.animations.len(),
+                    overlay: overlay.is_some(),
+                    graphics: overlay.as_ref().map(|overlay| overlay.graphics.clone()),
+                    monitors: layout.monitors.clone(),
+                    audio_errors: metrics.snapshot().audio_errors,
+                    audio_backend: audio.backend(),
+                    last_error: logging::last_problem(),
+                };
+                reply.send(status).ok();
+            }
+            Event::UserEvent(CustomEvent::PowerSaving(saving)) => {
+                my_app.power_saving = saving;
+                governor.set_power_saving(saving);
+                audio.send(audio::Command::Lazy(saving));
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
+            }
+            Event::UserEvent(CustomEvent::WindowsChanged) => {
+                let above = &my_app.config.overlay.above;
+                if let (Some(overlay), false) = (&mut overlay, above.is_empty()) {
+                    overlay.stay_below(platform::lowest_window_matching(above));
+                }
+            }
+            Event::UserEvent(CustomEvent::Toast(text)) => {
+                if !my_app.config.ui.toasts {
+                    return;
+                }
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Toast(text));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                let cursor = DeviceState::new().get_mouse().coords;
+                let Some(monitor) = layout
+                    .monitor_at(cursor)
+                    .map(|(_, monitor)| *monitor)
+                    .or_else(|| layout.bounds())
+                else {
+                    return;
+                };
+                my_app.toast = Some(toast::Toast::new(text, monitor, Instant::now()));
+                overlay.request_redraw();
+            }
+            Event::UserEvent(CustomEvent::Cooldown(cooldown)) => {
+                my_app.cooldown = Some(cooldown);
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
+            }
+            Event::UserEvent(CustomEvent::SettingsChanged) => {
+                if let Some(tray) = &mut tray {
+                    tray.show_look(tray::IconLook::current(icon_shade));
+                }
+            }
+            Event::UserEvent(CustomEvent::SoundAutoMuted(muted)) => {
+                sound_auto_muted = muted;
+                audio.send(audio::Command::Mute(muted));
+                next_tooltip_refresh = Instant::now();
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::Pause(duration))) => {
+                match duration {
+                    Some(duration) => pause.pause_for(duration),
+                    None => pause.pause(),
+                }
+                log::info!("pings paused: {:?}", pause.status());
+                let text = match duration {
+                    Some(duration) => i18n::tf(
+                        "toast.paused_for",
+                        &[("minutes", &duration.as_secs().div_ceil(60))],
+                    ),
+                    None => i18n::t("toast.paused").to_owned(),
+                };
+                loop_proxy.send_event(CustomEvent::Toast(text));
+                // NOTE: queued pings would otherwise all burst out on resume
+                driver_control.send(DriverCommand::Drain).ok();
+                driver_thread.unpark();
+                my_app.clear_pings();
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
+                next_tooltip_refresh = Instant::now();
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::PauseUntilTomorrow)) => {
+                let duration = platform::until_local_midnight();
+                loop_proxy.send_event(CustomEvent::Tray(TrayCommand::Pause(Some(duration))));
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::Resume)) => {
+                pause.resume();
+                loop_proxy.send_event(CustomEvent::Toast(i18n::t("toast.resumed").to_owned()));
+                next_tooltip_refresh = Instant::now();
+            }
+            Event::MainEventsCleared
+                if pending_acks
+                    .deadline()
+                    .is_some_and(|deadline| deadline <= Instant::now()) =>
+            {
+                pending_acks.expire(Instant::now());
+            }
+            Event::MainEventsCleared if next_layout_check <= Instant::now() => {
+                next_layout_check = Instant::now() + LAYOUT_CHECK_INTERVAL;
+                let current = layout::Layout::new(target.available_monitors());
+                if current != layout {
+                    log::info!(
+                        "monitor layout changed: {:?}",
+                        current
+                            .names
+                            .iter()
+                            .zip(&current.monitors)
+                            .collect::<Vec<_>>()
+                    );
+                    layout = current;
+                    my_app.layout = layout.clone();
+                    *shared_layout.lock().unwrap() = layout.clone();
+                    // NOTE: pings are stored in physical screen coordinates, so only
+                    // the window and its offset need to follow
+                    if let Some(overlay) = &mut overlay {
+                        if overlay.fit(target) {
+                            my_app.offset = overlay.offset;
+                        }
+                    }
+                    // NOTE: settings keyed by monitor follow it to its new place
+                    zones.resolve(&layout);
+                    audio.send(audio::Command::Layout(layout.clone()));
+                    let restriction = &my_app.config.restrict.monitor;
+                    let allowed = layout.allowed(restriction, startup_cursor);
+                    *allowed_monitor.lock().unwrap() = allowed;
+                    if let Some(tray) = &mut tray {
+                        tray.rebuild_restrict_menu(&layout, restriction, allowed);
+                    }
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ConfirmRemoveAllData)) => {
+                // NOTE: the message box is modal, keep it off the event loop
+                let proxy = loop_proxy.clone();
+                std::thread::spawn(move || {
+                    let text = i18n::tf(
+                        "dialog.remove_data_confirm",
+                        &[("path", &storage::data_dir().display())],
+                    );
+                    if platform::ask_yes_no(i18n::t("app.name"), &text) {
+                        proxy.send_event(CustomEvent::Tray(TrayCommand::RemoveAllData));
+                    }
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RemoveAllData)) => {
+                log::info!("removing all data and quitting");
+                logging::close();
+                let report = storage::describe(&storage::remove_all());
+                data_removed = true;
+                *control_flow = ControlFlow::Exit;
+                // NOTE: blocks the loop, which is on its way out anyway
+                platform::show_info(
+                    i18n::t("app.name"),
+                    &i18n::tf("dialog.remove_data_done", &[("report", &report)]),
+                );
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ConfirmMoveToPortable)) => {
+                let proxy = loop_proxy.clone();
+                std::thread::spawn(move || {
+                    let text = i18n::tf(
+                        "dialog.portable_confirm",
+                        &[("path", &storage::data_dir().display())],
+                    );
+                    if platform::ask_yes_no(i18n::t("app.name"), &text) {
+                        proxy.send_event(CustomEvent::Tray(TrayCommand::MoveToPortable));
+                    }
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::MoveToPortable)) => {
+                // NOTE: a second copy would overwrite what the portable install saved since
+                if storage::is_portable() {
+                    log::info!("already portable, nothing to move");
+                    return;
+                }
+                let (text, failed) = match storage::migrate_to_portable() {
+                    Ok(dir) => {
+                        log::info!("data copied to {}, now portable", dir.display());
+                        let text = i18n::tf("dialog.portable_done", &[("path", &dir.display())]);
+                        (text, false)
+                    }
+                    Err(e) => {
+                        log::warn!("failed to move the data next to the app: {e}");
+                        (i18n::tf("dialog.portable_failed", &[("error", &e)]), true)
+                    }
+                };
+                std::thread::spawn(move || match failed {
+                    true => platform::show_error(i18n::t("app.name"), &text),
+                    false => platform::show_info(i18n::t("app.name"), &text),
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::PickTheme)) => {
+                // NOTE: the file dialog is modal, keep it off the event loop
+                let proxy = loop_proxy.clone();
+                std::thread::spawn(move || {
+                    if let Some(path) = platform::pick_file(
+                        i18n::t("dialog.ping_packs"),
+                        "*.pingpack",
+                        &storage::packs_dir(),
+                    ) {
+                        proxy.send_event(CustomEvent::Tray(TrayCommand::LoadTheme(Some(path))));
+                    }
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::PickPreview)) => {
+                // NOTE: cancelling the picker still previews the current theme and the styles
+                let proxy = loop_proxy.clone();
+                std::thread::spawn(move || {
+                    let path = platform::pick_file(
+                        i18n::t("dialog.ping_packs"),
+                        "*.pingpack",
+                        &storage::packs_dir(),
+                    );
+                    proxy.send_event(CustomEvent::Tray(TrayCommand::PreviewThemes(path)));
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::LoadTheme(path))) => {
+                let theme = match &path {
+                    Some(path) => {
+                        match theme::Theme::load_pack(&storage::resolve(path), &my_app.config.theme)
+                        {
+                            Ok(theme) => theme,
+                            Err(e) => {
+                                log::warn!("failed to load theme {}: {e}", path.display());
+                                let text = i18n::tf(
+                                    "dialog.load_theme_failed",
+                                    &[("path", &path.display()), ("error", &e)],
+                                );
+                                std::thread::spawn(move || {
+                                    platform::show_error(i18n::t("app.name"), &text)
+                                });
+                                return;
+                            }
+                        }
+                    }
+                    None => theme::Theme::builtin(),
+                };
+                let text = i18n::tf("toast.theme", &[("name", &theme.name)]);
+                loop_proxy.send_event(CustomEvent::Toast(text));
+                switch_theme(&mut my_app, theme, path, &audio, &frame_times);
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::PreviewThemes(path))) => {
+                let Some(overlay) = &overlay else {
+                    deferred.push(CustomEvent::Tray(TrayCommand::PreviewThemes(path)));
+                    request_overlay(&mut overlay_wanted, &mut next_monitor_retry);
+                    return;
+                };
+                let mut themes = vec![(my_app.theme.clone(), my_app.config.theme.path.clone())];
+                if my_app.config.theme.path.is_some() {
+                    themes.push((theme::Theme::builtin(), None));
+                }
+                let mut picked = None;
+                if let Some(path) = path {
+                    match theme::Theme::load_pack(&path, &my_app.config.theme) {
+                        Ok(theme) => {
+                            themes.push((theme, Some(path)));
+                            picked = Some(themes.len() - 1);
+                        }
+                        Err(e) => {
+                            log::warn!("failed to load theme {}: {e}", path.display());
+                            let text = i18n::tf(
+                                "dialog.load_theme_failed",
+                                &[("path", &path.display()), ("error", &e)],
+                            );
+                            std::thread::spawn(move || {
+                                platform::show_error(i18n::t("app.name"), &text)
+                            });
+                            return;
+                        }
+                    }
+                }
+                // NOTE: start on what was picked, or on what's live
+                let selected = match (picked, my_app.config.marker.style) {
+                    (Some(picked), _) => picked,
+                    (None, marker::Style::Gif) => 0,
+                    // NOTE: procedural styles follow the themes in declaration order
+                    (None, style) => themes.len() + style as usize - 1,
+                };
+                let preview = preview::Preview::new(themes, selected, my_app.config.marker.color);
+
+                let Some(dialog) = open_dialog_window(
+                    target,
+                    overlay,
+                    &mut open_dialog,
+                    &mut spare_dialog,
+                    i18n::t("dialog.preview_title"),
+                    (260.0, 320.0),
+                ) else {
+                    return;
+                };
+                dialog.enable_images();
+                open_dialog = Some((dialog, Panel::Preview(Box::new(preview))));
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ToggleZone(index))) => {
+                let Some(enabled) = zones.toggle(index) else {
+                    return;
+                };
+                if let Some(tray) = &tray {
+                    tray.check_zone(index, enabled);
+                }
+                let name = &zones.zones()[index].name;
+                log::info!("zone {name} enabled: {enabled}");
+                let key = if enabled {
+                    "toast.zone_on"
+                } else {
+                    "toast.zone_off"
+                };
+                loop_proxy.send_event(CustomEvent::Toast(i18n::tf(key, &[("name", name)])));
+                my_app.config.zones[index].enabled = enabled;
+                if let Err(e) = my_app.config.save() {
+                    log::warn!("failed to remember the zone: {e}");
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::Restrict(restriction))) => {
+                log::info!("restricting pings to {restriction:?}");
+                let allowed = layout.allowed(&restriction, startup_cursor);
+                *allowed_monitor.lock().unwrap() = allowed;
+                let index =
+                    allowed.and_then(|allowed| layout.monitors.iter().position(|m| *m == allowed));
+                let text = match index {
+                    Some(index) => i18n::tf(
+                        "toast.restrict_monitor",
+                        &[
+                            ("index", &(index + 1)),
+                            ("name", &layout.name(index + 1).unwrap_or("?")),
+                        ],
+                    ),
+                    None => i18n::t("toast.restrict_all").to_owned(),
+                };
+                loop_proxy.send_event(CustomEvent::Toast(text));
+                if let Some(tray) = &tray {
+                    tray.check_restriction(&restriction);
+                }
+                my_app.config.restrict.monitor = restriction;
+                if let Err(e) = my_app.config.save() {
+                    log::warn!("failed to save the monitor restriction: {e}");
+                }
+            }
+            Event::MainEventsCleared
+                if !my_app.trail.is_empty() && next_trail_tick <= Instant::now() =>
+            {
+                next_trail_tick = Instant::now() + trail::TICK;
+                trail::prune(&mut my_app.trail, Instant::now(), &my_app.config.trail);
+                if let Some(overlay) = &overlay {
+                    overlay.request_redraw();
+                }
+            }
+            Event::MainEventsCleared if scheduled && next_schedule_check <= Instant::now() => {
+                // NOTE: rechecked at least every minute, the clock may have been changed
+                let left = pause
+                    .until_schedule_change()
+                    .map_or(SCHEDULE_CHECK_MAX, |left| left.min(SCHEDULE_CHECK_MAX));
+                next_schedule_check = Instant::now() + left;
+                next_tooltip_refresh = Instant::now();
+            }
+            Event::MainEventsCleared if next_sweep <= Instant::now() => {
+                next_sweep = Instant::now() + sweep_interval;
+                my_app.sweep(sweep_grace);
+            }
+            Event::MainEventsCleared if next_tooltip_refresh <= Instant::now() => {
+                next_tooltip_refresh = Instant::now() + TOOLTIP_REFRESH;
+                if std::mem::replace(&mut paused, pause_status != pause::Status::Active) && !paused
+                {
+                    log::info!("pings resumed");
+                }
+                let text = tooltip(&pause, pause_status, sound_auto_muted, &bindings);
+                if let Some(tray) = &mut tray {
+                    tray.show_status(text, pause_status != pause::Status::Active);
+                }
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::ReloadConfig)) => {
+                let reloaded = match config::Config::try_load() {
+                    Ok(reloaded) => reloaded,
+                    Err(e) => {
+                        log::warn!("reloaded config is invalid, keeping the current one: {e}");
+                        let text = i18n::tf("dialog.config_invalid", &[("error", &e)]);
+                        std::thread::spawn(move || {
+                            platform::show_error(i18n::t("app.name"), &text)
+                        });
+                        return;
+                    }
+                };
+                let problems = config_check::problems(&reloaded);
+                if !problems.is_empty() {
+                    let problems = problems
+                        .iter()
+                        .map(|problem| {
+                            log::warn!("config {problem}");
+                            problem.to_string()
+                        })
+                        .collect::<Vec<_>>()
+                        .join("\n");
+                    let text = i18n::tf("dialog.config_problems", &[("problems", &problems)]);
+                    std::thread::spawn(move || platform::show_error(i18n::t("app.name"), &text));
+                }
+                let changes = live::changes(&my_app.config, &reloaded);
+                if changes.is_empty() {
+                    log::info!("config reloaded, nothing changed");
+                    return;
+                }
+                log::info!("config reloaded, changed: {}", changes.join(", "));
+                let old = my_app.apply_config(reloaded);
+                let changed = |section| live::touches(&changes, section);
+                if changed("sound.volume") {
+                    audio.send(audio::Command::SetVolume(my_app.config.sound.volume));
+                }
+                if changed("sound.device") {
+                    audio.send(audio::Command::SetDevice(
+                        my_app.config.sound.device.clone(),
+                    ));
+                }
+                if changed("remote.sound") {
+                    remote_sound.store(my_app.config.remote.sound, Ordering::Relaxed);
+                }
+                if changed("limit") {
+                    *limiter.lock().unwrap() = limiter::Limiter::new(&my_app.config.limit);
+                }
+                if changed("input.prime_key") {
+                    // NOTE: taken on once the hotkey checks out, like a recorded one
+                    let key =
+                        std::mem::replace(&mut my_app.config.input.prime_key, old.input.prime_key);
+                    loop_proxy.send_event(CustomEvent::HotkeyCaptured(hotkey::Capture::Key(key)));
+                }
+                if changed("restrict") {
+                    let restriction = my_app.config.restrict.monitor.clone();
+                    loop_proxy.send_event(CustomEvent::Tray(TrayCommand::Restrict(restriction)));
+                }
+                if changed("theme") {
+                    let path = my_app.config.theme.path.clone();
+                    loop_proxy.send_event(CustomEvent::Tray(TrayCommand::LoadTheme(path)));
+                }
+                *frame_times.lock().unwrap() = self::frame_times(&my_app.config, &my_app.theme);
+                if changed("overlay.renderer") {
+                    if let Some(overlay) = &overlay {
+                        overlay.mark_lost();
+                        overlay.request_redraw();
+                    }
+                }
+
+                let restart = changes
+                    .into_iter()
+                    .filter(|key| live::classify(key) == live::Apply::Restart)
+                    .collect::<Vec<_>>();
+                if restart.is_empty() {
+                    loop_proxy.send_event(CustomEvent::Toast(i18n::t("toast.reloaded").to_owned()));
+                    return;
+                }
+                let proxy = loop_proxy.clone();
+                std::thread::spawn(move || {
+                    let text =
+                        i18n::tf("dialog.restart_required", &[("keys", &restart.join("\n"))]);
+                    if platform::ask_yes_no(i18n::t("app.name"), &text) {
+                        proxy.send_event(CustomEvent::Tray(TrayCommand::Restart));
+                    }
+                });
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::Restart)) => {
+                log::info!("restarting to apply the config");
+                restarting = true;
+                *control_flow = ControlFlow::Exit;
+            }
+            Event::UserEvent(CustomEvent::Tray(TrayCommand::RelaunchElevated)) => {
+                let relaunched = platform::relaunch_elevated();
+                if relaunched {
+                    *control_flow = ControlFlow::Exit;
+                }
+            }
+            // NOTE: only dialogs take drops, the overlay lets the mouse through
+            Event::WindowEvent {
+                event: WindowEvent::DroppedFile(path),
+                ..
+            } => {
+                log::info!("file dropped: {}", path.display());
+                match import::import(&path, &my_app.config.theme) {
+                    Ok(import::Imported::Sound(sound, copy)) => {
+                        log::info!("ping sound set to {}", copy.display());
+                        audio.send(audio::Command::SetPing(Some(sound)));
+                        let name = copy.file_name().unwrap_or_default().to_string_lossy();
+                        let text = i18n::tf("toast.sound", &[("name", &name)]);
+                        loop_proxy.send_event(CustomEvent::Toast(text));
+                        my_app.config.sound.ping = Some(config::SoundSpec::File(copy));
+                        if let Err(e) = my_app.config.save() {
+                            log::warn!("failed to remember the ping sound: {e}");
+                        }
+                    }
+                    Ok(import::Imported::Pack(theme, copy)) => {
+                        let text = i18n::tf("toast.theme", &[("name", &theme.name)]);
+                        loop_proxy.send_event(CustomEvent::Toast(text));
+                        switch_theme(&mut my_app, theme, Some(copy), &audio, &frame_times);
+                    }
+                    Err(e) => {
+                        log::warn!("dropped file not used: {e}");
+                        let text = i18n::tf("dialog.import_failed", &[("error", &e)]);
+                        std::thread::spawn(move || {
+                            platform::show_error(i18n::t("app.name"), &text)
+                        });
+                    }
+                }
+            }
+            Event::WindowEvent { window_id, event }
+                if open_dialog
+                    .as_ref()
+                    .is_some_and(|(dialog, _)| dialog.id() == window_id) =>
+            {
+                let (dialog, _) = open_dialog.as_mut().unwrap();
+                let device = &overlay.as_ref().unwrap().device;
+                if dialog.on_event(device, &event) {
+                    let (dialog, _) = open_dialog.take().unwrap();
+                    spare_dialog = Some(dialog.into_spare());
+                    hotkey.cancel_capture();
+                }
+            }
+            Event::WindowEvent {
+                window_id,
+                event:
+                    WindowEvent::ScaleFactorChanged {
+                        scale_factor,
+                        new_inner_size,
+                    },
+            } if overlay
+                .as_ref()
+                .is_some_and(|overlay| overlay.window.id() == window_id) =>
+            {
+                log::info!("display scale changed to {scale_factor}");
+                // NOTE: the overlay is sized in physical pixels, don't let Windows
+                // rescale it; the layout check refits it if monitors changed too
+                *new_inner_size = overlay.as_ref().unwrap().window.inner_size();
+                next_layout_check = Instant::now();
+            }
+            Event::WindowEvent {
+                window_id,
+                event: WindowEvent::Resized(size),
+            } if overlay
+                .as_ref()
+                .is_some_and(|overlay| overlay.window.id() == window_id) =>
+            {
+                overlay.as_mut().unwrap().resize(size);
+            }
+            Event::WindowEvent { window_id, event }
+                if companion
+                    .as_ref()
+                    .is_some_and(|companion| companion.id() == window_id) =>
+            {
+                match event {
+                    WindowEvent::CloseRequested => {
+                        log::info!("closed from the taskbar, quitting");
+                        *control_flow = ControlFlow::Exit;
+                    }
+                    // NOTE: there's nothing to show, clicking the entry just keeps it down
+                    WindowEvent::Focused(true) => {
+                        companion.as_ref().unwrap().set_minimized(true);
+                    }
+                    _ => {}
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::CloseRequested,
+                ..
+            } => *control_flow = ControlFlow::Exit,
+            Event::LoopDestroyed => {
+                driver_control.send(DriverCommand::Shutdown).ok();
+                driver_thread.unpark();
+                let session_state = state::SessionState {
+                    // NOTE: keep restored markers that never made it on screen
+                    markers: pending_markers.take().unwrap_or_else(|| my_app.markers()),
+                    counters: stats.snapshot(),
+                };
+                if !data_removed {
+                    session_state.save();
+                }
+                // NOTE: only once the state is saved, the new instance reads it
+                if restarting {
+                    platform::relaunch();
+                }
+            }
+            Event::RedrawEventsCleared => {
+                if let Some(overlay) = &mut overlay {
+                    overlay.check_styles();
+                    overlay.sync_spots(target, &my_app);
+                    // NOTE: what's on screen stays there, only changes need a redraw then
+                    if !(my_app.power_saving && my_app.idle()) {
+                        overlay.request_redraw();
+                    }
+                }
+            }
+            Event::RedrawRequested(window_id)
+                if open_dialog
+                    .as_ref()
+                    .is_some_and(|(dialog, _)| dialog.id() == window_id) =>
+            {
+                let (dialog, panel) = open_dialog.as_mut().unwrap();
+                let overlay = overlay.as_ref().unwrap();
+                let mut close = false;
+                dialog.render(&overlay.device, &overlay.queue, |ctx| close = panel.ui(ctx));
+                if close {
+                    let (dialog, panel) = open_dialog.take().unwrap();
+                    spare_dialog = Some(dialog.into_spare());
+                    hotkey.cancel_capture();
+                    if let Panel::Note(editor) = panel {
+                        let id = editor.id;
+                        if let (Some(note), Some(animation)) =
+                            (editor.saved(), my_app.animations.get_mut(&id))
+                        {
+                            let placed = animation.position;
+                            let monitor = my_app.layout.monitor_at(placed).map(|(index, _)| index);
+                            log::info!("note on the marker at {placed:?}: {note:?}");
+                            diagnostics.record_note(placed, monitor, &note);
+                            animation.note = (!note.is_empty()).then(|| note.into());
+                        }
+                    } else if let Panel::Preview(mut preview) = panel {
+                        match preview.take_applied() {
+                            Some(preview::Choice::Theme { theme, path }) => {
+                                my_app.config.marker.style = marker::Style::Gif;
+                                switch_theme(&mut my_app, *theme, path, &audio, &frame_times);
+                            }
+                            Some(preview::Choice::Style(style)) => {
+                                log::info!("switched to marker style {style:?}");
+                                my_app.config.marker.style = style;
+                                if let Err(e) = my_app.config.save() {
+                                    log::warn!("failed to remember the marker style: {e}");
+                                }
+                            }
+                            None => {}
+                        }
+                    }
+                }
+            }
+            Event::RedrawRequested(_) => {
+                if let Some(overlay) = &mut overlay {
+                    if overlay.lost() {
+                        // NOTE: dialogs draw with the overlay's device and go down with it
+                        if open_dialog.take().is_some() {
+                            hotkey.cancel_capture();
+                        }
+                        spare_dialog = None;
+                        // NOTE: a failed restart is retried by the next redraw, which
+                        // the animations on screen keep asking for
+                        if !overlay.restart_renderer(&my_app.config.overlay) {
+                            return;
+                        }
+                    }
+                    let started = Instant::now();
+                    my_app.advance_frames(started);
+                    overlay.render(&mut my_app, &metrics);
+                    pending_acks.drawn(|id| my_app.animations.contains_key(&id));
+                    if my_app.config.quality.adaptive {
+                        governor.record(started.elapsed());
+                        my_app.degraded = governor.degraded();
+                    }
                 }
-
-                queue.submit(Some(encoder.finish()));
-                frame.present();
             }
             _ => {}
         }
@@ -389,99 +2205,988 @@ async fn run() {
 
 struct MyApp {
     offset: f32,
-    frames: Vec<egui::ImageSource<'static>>,
+    config: config::Config,
+    taskbar: taskbar::TaskbarCache,
+    theme: theme::Theme,
+    /// Curves picked per ping kind, kinds without one keep the default look.
+    easing: HashMap<protocol::PingKind, easing::Curve>,
+    /// Last monitor layout seen, for keeping anchored pings on screen.
+    layout: layout::Layout,
+    /// Drawn positions of moving pings, updated every redraw.
+    smoothed: HashMap<usize, follow::Smoothed>,
     animations: HashMap<usize, Animation>,
+    /// Every source other than this machine seen this session, for the legend.
+    peers: BTreeSet<String>,
+    /// Redraws can't keep up, extras like easing are skipped.
+    degraded: bool,
+    /// Order pings arrived in, for dimming older ones. Updates of a ping on
+    /// screen keep its place.
+    arrivals: HashMap<usize, u64>,
+    next_arrival: u64,
+    /// Collects the artwork for the sprites backend during a frame, `None`
+    /// draws it with egui.
+    sprites: Option<sprites::Batch>,
+    /// The theme's frames for drawing with egui, uploaded on the first frame
+    /// drawn and again after a theme switch or a new renderer.
+    textures: Option<textures::FrameTextures>,
+    tutorial: Option<tutorial::Tutorial>,
+    /// On battery or battery saver, see [`power`].
+    power_saving: bool,
+    /// Where the last pings ended, oldest first.
+    trail: Vec<trail::TrailPoint>,
+    /// Rings at the latest clicks, oldest first.
+    ripples: Vec<ripple::Ripple>,
+    /// Shown after the limiter refused a ping.
+    cooldown: Option<limiter::Cooldown>,
+    /// The last setting change, while it's on screen.
+    toast: Option<toast::Toast>,
 }
 
 impl MyApp {
-    fn new(offset: f32) -> Self {
-        let frames = ASSET_DIR
-            .files()
-            .map(|f| {
-                let path = f.path().to_str().unwrap();
-
-                ImageSource::Bytes {
-                    uri: ::std::borrow::Cow::Owned(format!("bytes://{path}")),
-                    bytes: egui::load::Bytes::Static(ASSET_DIR.get_file(path).unwrap().contents()),
-                }
-            })
-            .collect::<Vec<_>>();
-
+    fn new(config: config::Config, theme: theme::Theme) -> Self {
         Self {
-            offset,
-            frames,
+            easing: Self::curves(&config),
+            offset: 0.0,
+            config,
+            taskbar: taskbar::TaskbarCache::default(),
+            theme,
+            layout: layout::Layout::default(),
+            smoothed: HashMap::new(),
             animations: HashMap::new(),
+            peers: BTreeSet::new(),
+            degraded: false,
+            arrivals: HashMap::new(),
+            next_arrival: 0,
+            sprites: None,
+            textures: None,
+            tutorial: None,
+            power_saving: false,
+            trail: Vec::new(),
+            ripples: Vec::new(),
+            cooldown: None,
+            toast: None,
         }
     }
+
+    /// The easing curve picked for each ping kind in `config`.
+    fn curves(config: &config::Config) -> HashMap<protocol::PingKind, easing::Curve> {
+        config
+            .easing
+            .iter()
+            .map(|(kind, name)| {
+                let curve = easing::Curve::parse(name).unwrap_or_else(|| {
+                    log::warn!("unknown easing curve {name:?} for {kind}, using linear");
+                    easing::Curve::Linear
+                });
+                (protocol::PingKind::from(kind.clone()), curve)
+            })
+            .collect()
+    }
+
+    /// Takes a reloaded `config` on for drawing, returning the one it replaced.
+    fn apply_config(&mut self, config: config::Config) -> config::Config {
+        let old = std::mem::replace(&mut self.config, config);
+        self.easing = Self::curves(&self.config);
+        old
+    }
 }
 
 impl MyApp {
-    fn ui(&mut self, ctx: &egui::Context) {
-        for animation in self.animations.values() {
-            let current_frame = self.frames[animation.frame as usize].clone();
-            let position = Rect::from_center_size(
-                Pos2::new(
-                    animation.position.0 as f32 + self.offset,
-                    animation.position.1 as _,
-                ),
-                Vec2::new(500.0, 500.0),
+    /// Draws every ping, or with `only` just that one, shifted so the given
+    /// overlay position lands at the top left corner.
+    fn frame(
+        &mut self,
+        ctx: &egui::Context,
+        input: egui::RawInput,
+        only: Option<(usize, Vec2)>,
+    ) -> egui::FullOutput {
+        debug_assert!(input.events.is_empty() && input.hovered_files.is_empty());
+        let output = ctx.run(input, |ctx| self.ui(ctx, only));
+        debug_assert!(
+            ctx.input(|i| i.pointer.latest_pos().is_none() && !i.pointer.any_down()),
+            "pointer state leaked into the overlay"
+        );
+        output
+    }
+
+    fn ui(&mut self, ctx: &egui::Context, only: Option<(usize, Vec2)>) {
+        self.follow_targets();
+        let animations = &self.animations;
+        self.arrivals.retain(|id, _| animations.contains_key(id));
+        // NOTE: all at once, so the first ping after a quiet spell allocates nothing
+        let uploaded = self.textures.as_ref().is_some_and(|t| t.is_for(ctx));
+        if !uploaded && !self.marker_style().is_procedural() {
+            self.textures = Some(textures::FrameTextures::upload(ctx, &self.theme));
+        }
+        let mut batch = self.sprites.take();
+        if self
+            .tutorial
+            .as_ref()
+            .is_some_and(|tutorial| tutorial.finished())
+        {
+            self.tutorial = None;
+        }
+        // NOTE: the opaque overlay's windows only cover pings, the trail is left out there
+        if only.is_none() && !self.trail.is_empty() {
+            let painter = ctx.layer_painter(egui::LayerId::background());
+            trail::draw(
+                &painter,
+                &self.trail,
+                self.offset,
+                Instant::now(),
+                &self.config.trail,
+            );
+        }
+        if only.is_none() && !self.ripples.is_empty() {
+            let now = Instant::now();
+            ripple::prune(&mut self.ripples, now, &self.config.ripples);
+            let painter = ctx.layer_painter(egui::LayerId::background());
+            ripple::draw(
+                &painter,
+                &self.ripples,
+                self.offset,
+                now,
+                &self.config.ripples,
             );
+            ctx.request_repaint();
+        }
+        if let (None, Some(cooldown)) = (only, self.cooldown) {
+            let remaining = cooldown.remaining(Instant::now());
+            if remaining > 0.0 {
+                let painter = ctx.layer_painter(egui::LayerId::background());
+                let (x, y) = cooldown.position;
+                self.draw_cooldown(
+                    &painter,
+                    Pos2::new(x as f32 + self.offset, y as f32),
+                    remaining,
+                );
+                ctx.request_repaint();
+            } else {
+                self.cooldown = None;
+            }
+        }
+        if let (None, Some(toast)) = (only, &self.toast) {
+            let now = Instant::now();
+            if toast.opacity(now) > 0.0 {
+                // NOTE: a layer of its own above the pings, they're drawn as if it wasn't there
+                let layer = egui::LayerId::new(egui::Order::Foreground, egui::Id::new("toast"));
+                toast.draw(&ctx.layer_painter(layer), self.offset, now);
+                ctx.request_repaint();
+            } else {
+                self.toast = None;
+            }
+        }
+        if let Some(tutorial) = &self.tutorial {
+            let shift = match only {
+                None => Some(Vec2::ZERO),
+                Some((TUTORIAL_SPOT, shift)) => Some(shift),
+                Some(_) => None,
+            };
+            if let Some(shift) = shift {
+                egui::CentralPanel::default()
+                    .frame(egui::Frame::none().fill(egui::Color32::TRANSPARENT))
+                    .show(ctx, |ui| tutorial.draw(ui.painter(), shift));
+            }
+        }
+        for animation in self.animations.values() {
+            let shift = match only {
+                Some((id, _)) if id != animation.id => continue,
+                Some((_, shift)) => shift,
+                None => Vec2::ZERO,
+            };
+            let (dim, shrink) = self.emphasis(animation);
+            let center = self.center(animation) - shift;
+            let clip = self
+                .monitor_clip(animation)
+                .map(|clip| clip.translate(-shift));
+            let style = kind_style(&self.config, &self.theme, &animation.kind);
+            let position = layout::snap(center, style.size);
 
             egui::CentralPanel::default()
                 .frame(egui::Frame::none().fill(egui::Color32::TRANSPARENT))
                 .show(ctx, |ui| {
-                    let img = egui::Image::new(current_frame);
-                    ui.put(position, img);
+                    if animation.denied {
+                        Self::draw_denied(ui.painter(), center, animation);
+                        return;
+                    }
+                    self.draw_sequence(ui.painter(), center, animation);
+                    if animation.frame >= FRAME_COUNT {
+                        // NOTE: only the sequence badge lingers
+                        return;
+                    }
+                    self.draw_halo(ui.painter(), center, animation);
+                    self.draw_arrow(ui.painter(), center, animation, shift, dim);
+                    self.draw_count(ui.painter(), center, animation);
+                    let style = self.marker_style();
+                    let curve = self
+                        .easing
+                        .get(&animation.kind)
+                        .copied()
+                        .filter(|_| !self.degraded);
+                    if style.is_procedural() {
+                        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+                        let progress = curve.map_or(progress, |curve| curve.apply(progress));
+                        let [r, g, b, a] = self.config.marker.color;
+                        let alpha = (a as f32 * Self::fade(progress) * dim) as u8;
+                        let color = egui::Color32::from_rgba_unmultiplied(r, g, b, alpha);
+                        let size = self.artwork_size(&animation.kind) * shrink;
+                        marker::draw(ui.painter(), style, center, progress, size, color);
+                    } else {
+                        let frames = self.theme.frames_for(&animation.kind);
+                        let index = self.theme.frame_index(animation.frame);
+                        // NOTE: the artwork animates itself, a curve only shapes how it
+                        // pops in over the first third
+                        let position = match curve {
+                            Some(curve) => {
+                                let progress = animation.frame as f32 / FRAME_COUNT as f32;
+                                let scale = curve.apply((progress / 0.3).min(1.0));
+                                layout::snap(center, position.width() * scale * shrink)
+                            }
+                            None => layout::snap(center, position.width() * shrink),
+                        };
+                        match (frames.get(index), &mut batch) {
+                            (Some(_), Some(batch)) if batch.accepts(&animation.kind) => {
+                                batch.sprites.push(sprites::Sprite {
+                                    kind: animation.kind.clone(),
+                                    layer: index,
+                                    rect: position,
+                                    alpha: dim,
+                                    clip,
+                                });
+                            }
+                            (Some(current_frame), _) => {
+                                let tint = egui::Color32::WHITE.gamma_multiply(dim);
+                                let texture = self
+                                    .textures
+                                    .as_ref()
+                                    .and_then(|textures| textures.get(&animation.kind, index));
+                                let image = match texture {
+                                    Some(texture) => egui::Image::from_texture(texture),
+                                    None => egui::Image::new(current_frame.clone()),
+                                }
+                                .tint(tint);
+                                // NOTE: painted in place, `put` lays the image out inside the
+                                // panel and pushes it inward at the edges instead of cutting it
+                                let size = match texture {
+                                    Some(texture) => Some(texture.size_vec2()),
+                                    None => image
+                                        .load_for_size(ui.ctx(), position.size())
+                                        .ok()
+                                        .and_then(|poll| poll.size()),
+                                };
+                                let rect =
+                                    size.map_or(position, |size| layout::fit(size, position));
+                                let unclipped = ui.clip_rect();
+                                if let Some(clip) = clip {
+                                    ui.set_clip_rect(unclipped.intersect(clip));
+                                }
+                                image.paint_at(ui, rect);
+                                ui.set_clip_rect(unclipped);
+                            }
+                            (None, _) => {}
+                        }
+                    }
+                    self.draw_expiry_ring(ui.painter(), center, animation);
+                    self.draw_source(ui.painter(), center, animation);
+                    self.draw_zone(ui.painter(), center, animation);
+                    self.draw_note(ui.painter(), center, animation);
                 });
 
             ctx.request_repaint();
         }
+        self.sprites = batch;
+    }
+
+    /// Where `animation` points now, in desktop pixels.
+    fn position(&self, animation: &Animation) -> MousePosition {
+        match self.smoothed.get(&animation.id) {
+            Some(smoothed) => smoothed.rounded(),
+            None => animation.position,
+        }
+    }
+
+    /// The monitor `animation` points at, in overlay coordinates. Its artwork
+    /// is cut at the edges.
+    fn monitor_clip(&self, animation: &Animation) -> Option<egui::Rect> {
+        let (_, monitor) = self.layout.monitor_at(self.position(animation))?;
+        Some(egui::Rect::from_min_size(
+            Pos2::new(monitor.x as f32 + self.offset, monitor.y as f32),
+            Vec2::new(monitor.width as f32, monitor.height as f32),
+        ))
+    }
+
+    /// Where `animation` is drawn, in overlay coordinates.
+    fn center(&self, animation: &Animation) -> Pos2 {
+        let position = self.position(animation);
+        let (x, y) = match self.config.taskbar.avoid {
+            taskbar::Avoidance::Nudge => self.taskbar.avoid(position, self.config.taskbar.margin),
+            _ => position,
+        };
+        let monitor = self.layout.monitor_at((x, y)).map(|(_, monitor)| monitor);
+        let style = kind_style(&self.config, &self.theme, &animation.kind);
+        let (x, y) = style
+            .anchor
+            .place((x, y), self.artwork_size(&animation.kind), monitor);
+        let center =
+            Pos2::new(x + self.offset, y) + layout::jitter(animation.id, self.config.marker.jitter);
+        center.round()
+    }
+
+    /// Area each ping draws in, rings, badges and labels included, for the
+    /// per-ping windows of the opaque overlay.
+    fn spot_rects(&self) -> HashMap<usize, egui::Rect> {
+        // NOTE: room for the badges at the corners and the labels underneath
+        const MARGIN: f32 = 40.0;
+
+        self.animations
+            .values()
+            .map(|animation| {
+                let mut radius = self.artwork_size(&animation.kind) / 2.0;
+                if animation.denied {
+                    radius = 24.0;
+                }
+                if self.config.expiry_ring.enabled && !animation.persistent {
+                    radius = radius.max(self.config.expiry_ring.radius);
+                }
+                if animation.source.label().is_some() {
+                    radius = radius.max(self.config.remote.ring_radius);
+                }
+                if animation.backdrop == backdrop::Backdrop::Light {
+                    radius = radius.max(self.config.backdrop.halo_radius);
+                }
+                let rect = egui::Rect::from_center_size(
+                    self.center(animation),
+                    Vec2::splat(((radius + MARGIN) * 2.0).ceil()),
+                );
+                (animation.id, rect)
+            })
+            .chain(
+                self.tutorial
+                    .as_ref()
+                    .map(|tutorial| (TUTORIAL_SPOT, tutorial.area())),
+            )
+            .collect()
+    }
+
+    /// Moves pings that follow something a step closer to it. Static pings
+    /// are skipped entirely.
+    fn follow_targets(&mut self) {
+        let animations = &self.animations;
+        self.smoothed.retain(|id, _| animations.contains_key(id));
+
+        let now = Instant::now();
+        for animation in self.animations.values() {
+            let Some(target) = animation.follow else {
+                continue;
+            };
+            let start = (animation.position.0 as f32, animation.position.1 as f32);
+            // NOTE: a vanished window leaves the ping where it was last drawn
+            let smoothed = self
+                .smoothed
+                .entry(animation.id)
+                .or_insert_with(|| follow::Smoothed::new(start, now));
+            if let Some(position) = target.position() {
+                smoothed.step(position, now);
+            }
+        }
+    }
+
+    /// The configured marker style, or the lighter one while saving power.
+    fn marker_style(&self) -> marker::Style {
+        match self.power_saving {
+            true => self.config.power.marker,
+            false => self.config.marker.style,
+        }
+    }
+
+    /// Nothing on screen moves: no tutorial, and only markers at rest that
+    /// stay where they are.
+    fn idle(&self) -> bool {
+        self.tutorial.is_none()
+            && self.cooldown.is_none()
+            && self.ripples.is_empty()
+            && self.toast.is_none()
+            && self.animations.values().all(|animation| {
+                animation.persistent
+                    && animation.frame >= MARKER_FRAME
+                    && animation.follow.is_none()
+            })
+    }
+
+    /// Width of the ping artwork of `kind` in the marker style in use.
+    fn artwork_size(&self, kind: &protocol::PingKind) -> f32 {
+        if self.marker_style().is_procedural() {
+            // NOTE: themes don't apply to procedural markers, only the user's sizes do
+            self.config
+                .kinds
+                .get(kind.as_str())
+                .and_then(|style| style.size)
+                .unwrap_or(self.config.marker.size)
+        } else {
+            kind_style(&self.config, &self.theme, kind).size
+        }
+    }
+
+    /// Opacity envelope matching the artwork, which fades in and out on its own.
+    fn fade(progress: f32) -> f32 {
+        const FADE_IN: f32 = 0.15;
+        const FADE_OUT: f32 = 0.15;
+
+        (progress / FADE_IN)
+            .min((1.0 - progress) / FADE_OUT)
+            .clamp(0.0, 1.0)
+    }
+
+    /// Arrow from the ping toward its target, fading with it. Starts at the
+    /// edge of the artwork so the ping itself stays readable.
+    fn draw_arrow(
+        &self,
+        painter: &egui::Painter,
+        center: Pos2,
+        animation: &Animation,
+        shift: Vec2,
+        dim: f32,
+    ) {
+        let Some((x, y)) = animation.toward else {
+            return;
+        };
+        let tip = Pos2::new(x as f32 + self.offset, y as f32) - shift;
+        let length = (tip - center).length();
+        let start = self.artwork_size(&animation.kind) * 0.3;
+        if length <= start {
+            return;
+        }
+        let direction = (tip - center) / length;
+        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+        let arrow = &self.config.arrow;
+        let [r, g, b, a] = arrow.color;
+        let alpha = (a as f32 * Self::fade(progress) * dim) as u8;
+        let color = egui::Color32::from_rgba_unmultiplied(r, g, b, alpha);
+        let head = (arrow.thickness * 4.0).min(length - start);
+        let base = tip - direction * head;
+        let side = direction.rot90() * head * 0.5;
+        painter.line_segment(
+            [center + direction * start, base],
+            egui::Stroke::new(arrow.thickness, color),
+        );
+        painter.add(egui::Shape::convex_polygon(
+            vec![tip, base + side, base - side],
+            color,
+            egui::Stroke::NONE,
+        ));
+    }
+
+    /// Small grey "no entry" sign, deliberately quieter than a ping.
+    fn draw_denied(painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        const RADIUS: f32 = 24.0;
+
+        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+        let alpha = (160.0 * Self::fade(progress)) as u8;
+        let stroke = egui::Stroke::new(
+            4.0,
+            egui::Color32::from_gray(200).gamma_multiply(alpha as f32 / 255.0),
+        );
+        painter.circle_stroke(center, RADIUS, stroke);
+        let diagonal = Vec2::splat(RADIUS * std::f32::consts::FRAC_1_SQRT_2);
+        painter.line_segment([center - diagonal, center + diagonal], stroke);
+    }
+
+    /// Number badge at the top right corner of the artwork.
+    fn draw_sequence(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        let Some(number) = animation.sequence else {
+            return;
+        };
+        let size = self.artwork_size(&animation.kind);
+        let center = center + Vec2::new(size, -size) * 0.3;
+        Self::draw_badge(painter, center, animation, number.to_string());
+    }
+
+    /// "×5" badge at the bottom right corner for a burst merged into one ping.
+    fn draw_count(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        if animation.count < 2 {
+            return;
+        }
+        let size = self.artwork_size(&animation.kind);
+        let center = center + Vec2::new(size, size) * 0.3;
+        Self::draw_badge(painter, center, animation, format!("×{}", animation.count));
+    }
+
+    fn draw_badge(painter: &egui::Painter, center: Pos2, animation: &Animation, text: String) {
+        const RADIUS: f32 = 16.0;
+        const FADE_FRAMES: f32 = 9.0;
+
+        let fade_in = animation.frame as f32 / FADE_FRAMES;
+        let fade_out = if animation.persistent {
+            1.0
+        } else {
+            (animation.last_frame() - animation.frame) as f32 / FADE_FRAMES
+        };
+        let opacity = fade_in.min(fade_out).clamp(0.0, 1.0);
+
+        painter.circle(
+            center,
+            RADIUS,
+            egui::Color32::from_black_alpha(220).gamma_multiply(opacity),
+            egui::Stroke::new(2.0, egui::Color32::WHITE.gamma_multiply(opacity)),
+        );
+        painter.text(
+            center,
+            egui::Align2::CENTER_CENTER,
+            text,
+            egui::FontId::proportional(18.0),
+            egui::Color32::WHITE.gamma_multiply(opacity),
+        );
+    }
+
+    /// Darkens bright backdrops so the artwork stays readable.
+    fn draw_halo(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        if animation.backdrop != backdrop::Backdrop::Light {
+            return;
+        }
+
+        let halo = &self.config.backdrop;
+        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+        let [r, g, b, a] = halo.halo_color;
+        let alpha = (a as f32 * Self::fade(progress)) as u8;
+        painter.circle_filled(
+            center,
+            halo.halo_radius,
+            egui::Color32::from_rgba_unmultiplied(r, g, b, alpha),
+        );
+    }
+
+    fn draw_expiry_ring(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        let ring = &self.config.expiry_ring;
+        if !ring.enabled || animation.persistent {
+            return;
+        }
+
+        let progress = (animation.started.elapsed().as_secs_f32()
+            / (animation.frame_time * FRAME_COUNT as u32).as_secs_f32())
+        .clamp(0.0, 1.0);
+        let remaining = 1.0 - progress;
+        if remaining <= 0.0 {
+            return;
+        }
+
+        let [r, g, b, a] = ring.color;
+        let alpha = (a as f32 * Self::fade(progress)) as u8;
+        let stroke = egui::Stroke::new(
+            ring.thickness,
+            egui::Color32::from_rgba_unmultiplied(r, g, b, alpha),
+        );
+        Self::draw_arc(painter, center, ring.radius, remaining, stroke);
+    }
+
+    /// Arc of `share` of a circle, from twelve o'clock clockwise, so it
+    /// shrinks counter-clockwise as `share` drops.
+    fn draw_arc(
+        painter: &egui::Painter,
+        center: Pos2,
+        radius: f32,
+        share: f32,
+        stroke: egui::Stroke,
+    ) {
+        const SEGMENTS: f32 = 64.0;
+
+        // NOTE: egui has no arc primitive so approximate one with line segments
+        let sweep = std::f32::consts::TAU * share;
+        let steps = (SEGMENTS * share).ceil().max(1.0) as usize;
+        let points = (0..=steps)
+            .map(|step| {
+                let angle = -std::f32::consts::FRAC_PI_2 + sweep * step as f32 / steps as f32;
+                center + radius * Vec2::angled(angle)
+            })
+            .collect();
+        painter.add(egui::Shape::line(points, stroke));
+    }
+
+    /// Faint full circle with the wait left drawn over it, at the cursor
+    /// where a ping was refused.
+    fn draw_cooldown(&self, painter: &egui::Painter, center: Pos2, remaining: f32) {
+        let limit = &self.config.limit;
+        let [r, g, b, a] = limit.color;
+        let color = egui::Color32::from_rgba_unmultiplied(r, g, b, a);
+        painter.circle_stroke(
+            center,
+            limit.radius,
+            egui::Stroke::new(2.0, color.gamma_multiply(0.3)),
+        );
+        Self::draw_arc(
+            painter,
+            center,
+            limit.radius,
+            remaining,
+            egui::Stroke::new(4.0, color),
+        );
+    }
+
+    /// Colored ring and peer name around pings that weren't fired on this machine.
+    fn draw_source(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        let Some(label) = animation.source.label() else {
+            return;
+        };
+
+        let remote = &self.config.remote;
+        let color = ping_source::peer_color(label, &remote.palette);
+        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+        let color = color.gamma_multiply(Self::fade(progress));
+        painter.circle_stroke(
+            center,
+            remote.ring_radius,
+            egui::Stroke::new(remote.ring_thickness, color),
+        );
+        painter.text(
+            center + Vec2::new(0.0, remote.ring_radius + 8.0),
+            egui::Align2::CENTER_TOP,
+            label,
+            egui::FontId::proportional(18.0),
+            color,
+        );
+    }
+
+    /// Name of the zone the ping landed in, under the artwork.
+    fn draw_zone(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        let Some(zone) = &animation.zone else {
+            return;
+        };
+
+        let progress = animation.frame as f32 / FRAME_COUNT as f32;
+        let color = egui::Color32::WHITE.gamma_multiply(Self::fade(progress));
+        painter.text(
+            center + Vec2::new(0.0, self.artwork_size(&animation.kind) / 2.0 + 8.0),
+            egui::Align2::CENTER_TOP,
+            zone,
+            egui::FontId::proportional(20.0),
+            color,
+        );
+    }
+
+    /// The marker's note under the artwork (and zone name), on a dark backdrop.
+    fn draw_note(&self, painter: &egui::Painter, center: Pos2, animation: &Animation) {
+        let Some(note) = &animation.note else {
+            return;
+        };
+
+        let mut top = center.y + self.artwork_size(&animation.kind) / 2.0 + 8.0;
+        if animation.zone.is_some() {
+            top += 28.0;
+        }
+        let galley = painter.layout_no_wrap(
+            note.to_string(),
+            egui::FontId::proportional(18.0),
+            egui::Color32::WHITE,
+        );
+        let rect = egui::Rect::from_center_size(
+            Pos2::new(center.x, top + galley.size().y / 2.0),
+            galley.size(),
+        );
+        painter.rect_filled(rect.expand(6.0), 6.0, egui::Color32::from_black_alpha(180));
+        painter.galley(rect.min, galley);
+    }
+
+    /// The marker under `at`, in overlay coordinates, or else the newest one.
+    fn marker_for_note(&self, at: Pos2) -> Option<usize> {
+        let markers = || {
+            self.animations
+                .values()
+                .filter(|animation| animation.persistent && !animation.denied)
+        };
+        markers()
+            .find(|animation| {
+                self.center(animation).distance(at) <= self.artwork_size(&animation.kind) / 2.0
+            })
+            .or_else(|| markers().max_by_key(|animation| self.arrivals.get(&animation.id)))
+            .map(|animation| animation.id)
+    }
+
+    fn legend(&self) -> Vec<(String, egui::Color32)> {
+        self.peers
+            .iter()
+            .map(|peer| {
+                let color = ping_source::peer_color(peer, &self.config.remote.palette);
+                (peer.clone(), color)
+            })
+            .collect()
     }
 
     fn add_animation(&mut self, animation: Animation) {
+        if let Some(label) = animation.source.label() {
+            if !self.peers.contains(label) {
+                self.peers.insert(label.to_owned());
+            }
+        }
+        // NOTE: the driver's copy of a marker knows nothing of a note typed since
+        let mut animation = animation;
+        if let Some(existing) = self.animations.get(&animation.id) {
+            if animation.note.is_none() {
+                animation.note = existing.note.clone();
+            }
+            animation.follow = existing.follow;
+        }
+        if !self.arrivals.contains_key(&animation.id) {
+            // NOTE: only pings fired here, markers stay where they were placed
+            if !animation.persistent && !animation.denied && animation.source.label().is_none() {
+                animation.follow = self.config.marker.follow.target(animation.position);
+            }
+            self.arrivals.insert(animation.id, self.next_arrival);
+            self.next_arrival += 1;
+        }
         self.animations.insert(animation.id, animation);
     }
 
+    /// Opacity and size multipliers of `animation`, see [`config::Emphasis`].
+    /// Markers and denied pings are left alone and don't push others back.
+    // NOTE: ranked by counting newer pings rather than sorting them all, so a frame
+    // allocates nothing; there are only ever a handful on screen
+    fn emphasis(&self, animation: &Animation) -> (f32, f32) {
+        let ranked = |animation: &Animation| !animation.denied && !animation.persistent;
+        if !self.config.emphasis.enabled || !ranked(animation) {
+            return (1.0, 1.0);
+        }
+        let arrival = |animation: &Animation| (self.arrivals.get(&animation.id), animation.id);
+        let rank = self
+            .animations
+            .values()
+            .filter(|other| ranked(other) && arrival(other) > arrival(animation))
+            .count();
+        self.config.emphasis.factors(rank)
+    }
+
+    /// Brings every animation to the frame its clock is on at `now`. Offline
+    /// renders skip this and draw the frames they were given.
+    fn advance_frames(&mut self, now: Instant) {
+        for animation in self.animations.values_mut() {
+            animation.frame = animation.frame_at(now);
+        }
+    }
+
     fn remove_animation(&mut self, animation_id: usize) {
         self.animations.remove(&animation_id);
     }
+
+    /// Leaves a trail dot where the ping `animation_id` ended, if trails are on.
+    fn leave_trail(&mut self, animation_id: usize) {
+        let Some(animation) = self.animations.get(&animation_id) else {
+            return;
+        };
+        if !self.config.trail.enabled || animation.persistent || animation.denied {
+            return;
+        }
+        let point = trail::TrailPoint {
+            position: animation.position,
+            ended: Instant::now(),
+        };
+        trail::push(&mut self.trail, point, &self.config.trail);
+    }
+
+    /// Removes pings that outlived their animation by more than `grace`, e.g.
+    /// after a missed `Clear`. Each is logged so the cause can be tracked down.
+    fn sweep(&mut self, grace: Duration) {
+        self.animations.retain(|id, animation| {
+            if animation.persistent {
+                return true;
+            }
+            let age = animation.started.elapsed();
+            let lifetime = animation.frame_time * animation.last_frame() as u32;
+            if age <= lifetime + grace {
+                return true;
+            }
+            log::warn!(
+                "sweeping stuck animation {id} at frame {}, {age:?} old",
+                animation.frame
+            );
+            false
+        });
+    }
+
+    /// Drops everything but persistent markers, and the trail.
+    fn clear_pings(&mut self) {
+        self.animations.retain(|_, animation| animation.persistent);
+        self.trail.clear();
+    }
+
+    /// Drops the markers, and the trail with them.
+    fn clear_markers(&mut self) {
+        self.animations.retain(|_, animation| !animation.persistent);
+        self.trail.clear();
+    }
+
+    fn markers(&self) -> Vec<state::Marker> {
+        self.animations
+            .values()
+            .filter(|animation| animation.persistent)
+            .map(|animation| state::Marker {
+                x: animation.position.0,
+                y: animation.position.1,
+                note: animation.note.as_deref().map(str::to_owned),
+            })
+            .collect()
+    }
 }
 
-use raw_window_handle::RawWindowHandle;
-unsafe fn hide_taskbar_entry(window_handle: RawWindowHandle) {
-    use windows::Win32::Foundation::HWND;
-    use windows::Win32::UI::WindowsAndMessaging::WINDOW_EX_STYLE;
+/// Swaps in `theme`, loaded from `path`, and remembers it.
+fn switch_theme(
+    my_app: &mut MyApp,
+    theme: theme::Theme,
+    path: Option<PathBuf>,
+    audio: &audio::Audio,
+    frame_times: &Mutex<HashMap<protocol::PingKind, Duration>>,
+) {
+    log::info!("switched to theme {}", theme.name);
+    audio.send(audio::Command::Reload(theme.decode_sound()));
+    *frame_times.lock().unwrap() = self::frame_times(&my_app.config, &theme);
+    my_app.theme = theme;
+    // NOTE: dropping the old handles frees their textures
+    my_app.textures = None;
+    my_app.config.theme.path = path.as_deref().map(storage::shorten);
+    if let Err(e) = my_app.config.save() {
+        log::warn!("failed to remember the theme: {e}");
+    }
+}
 
-    let RawWindowHandle::Win32(raw_handle) = window_handle else {
-        panic!("Unsupported platform!");
+/// Display style of `kind` in `theme`, see [`config::Config::kind_style`].
+fn kind_style(
+    config: &config::Config,
+    theme: &theme::Theme,
+    kind: &protocol::PingKind,
+) -> config::ResolvedStyle {
+    let defaults = config::ResolvedStyle {
+        size: theme.size,
+        duration: ANIMATION_DURATION,
+        anchor: layout::Anchor::Center,
     };
-    let hwnd = raw_handle.hwnd;
-
-    let index = windows::Win32::UI::WindowsAndMessaging::GWL_EXSTYLE;
-    let style = WINDOW_EX_STYLE(0)
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LAYERED
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LEFT
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LTRREADING
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TOPMOST
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TRANSPARENT
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_WINDOWEDGE
-        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TOOLWINDOW;
-
-    windows::Win32::UI::WindowsAndMessaging::SetWindowLongPtrA(
-        HWND(hwnd as _),
-        index,
-        style.0 as _,
-    );
+    config.kind_style(kind, theme.style_for(kind), defaults)
+}
+
+/// Every monitor as reported in diagnostics, named as in the tray.
+fn diagnostic_monitors<T>(
+    target: &winit::event_loop::EventLoopWindowTarget<T>,
+) -> Vec<diagnostics::Monitor> {
+    let names = layout::Layout::new(target.available_monitors()).names;
+    target
+        .available_monitors()
+        .zip(names)
+        .map(|(monitor, name)| diagnostics::Monitor {
+            name,
+            x: monitor.position().x,
+            y: monitor.position().y,
+            width: monitor.size().width,
+            height: monitor.size().height,
+            scale_factor: monitor.scale_factor(),
+        })
+        .collect()
+}
+
+/// Opens a dialog window on `overlay`'s device in place of the `open` one,
+/// reusing its egui state or that of the last dialog closed. `None`, logged,
+/// when the window couldn't be made.
+fn open_dialog_window<T>(
+    target: &winit::event_loop::EventLoopWindowTarget<T>,
+    overlay: &overlay::Overlay,
+    open: &mut Option<(dialog::Dialog, Panel)>,
+    spare: &mut Option<dialog::Spare>,
+    title: &str,
+    size: (f64, f64),
+) -> Option<dialog::Dialog> {
+    let spare = match open.take() {
+        Some((dialog, _)) => Some(dialog.into_spare()),
+        None => spare.take(),
+    };
+    dialog::Dialog::new(
+        target,
+        &overlay.instance,
+        &overlay.adapter,
+        &overlay.device,
+        title,
+        size,
+        spare,
+    )
+    .map_err(|e| log::warn!("{e}, dialog not shown"))
+    .ok()
+}
+
+/// Middle of the primary monitor, in desktop pixels.
+fn primary_center<T>(target: &winit::event_loop::EventLoopWindowTarget<T>) -> MousePosition {
+    target
+        .primary_monitor()
+        .map(|monitor| {
+            let position = monitor.position();
+            let size = monitor.size();
+            (
+                position.x + size.width as i32 / 2,
+                position.y + size.height as i32 / 2,
+            )
+        })
+        .unwrap_or_default()
+}
+
+/// Time between frames of each kind, so it plays over its configured duration.
+fn frame_times(
+    config: &config::Config,
+    theme: &theme::Theme,
+) -> HashMap<protocol::PingKind, Duration> {
+    let kinds = protocol::PingKind::REGISTERED
+        .into_iter()
+        .chain(theme.kinds().cloned())
+        .chain(config.kinds.keys().cloned().map(protocol::PingKind::from));
+    kinds
+        .map(|kind| {
+            let duration = kind_style(config, theme, &kind).duration;
+            (kind, duration / FRAME_COUNT as u32)
+        })
+        .collect()
+}
+
+/// Has the event loop build the overlay as soon as it can.
+fn request_overlay(wanted: &mut bool, next_retry: &mut Option<Instant>) {
+    if !*wanted {
+        *wanted = true;
+        *next_retry = Some(Instant::now());
+    }
 }
 
-fn load_icon() -> tray_icon::Icon {
-    let (icon_rgba, icon_width, icon_height) = {
-        let image = image::load_from_memory_with_format(ICON, image::ImageFormat::Png)
-            .expect("Failed to open icon path")
-            .into_rgba8();
-        let (width, height) = image.dimensions();
-        let rgba = image.into_raw();
-        (rgba, width, height)
+/// Tray tooltip: a status line reflecting the most restrictive state (a
+/// pause suppresses pings entirely, Focus Assist only mutes their sound),
+/// then the shortcuts. Windows cuts it off past 127 characters.
+fn tooltip(
+    pause: &pause::Pause,
+    status: pause::Status,
+    sound_auto_muted: bool,
+    bindings: &keybindings::Bindings,
+) -> String {
+    let status = match status {
+        pause::Status::Paused => i18n::t("tooltip.paused").into(),
+        pause::Status::PausedUntil(until) => {
+            let minutes = until
+                .saturating_duration_since(Instant::now())
+                .as_secs()
+                .div_ceil(60);
+            i18n::tf("tooltip.paused_for", &[("minutes", &minutes)])
+        }
+        pause::Status::Scheduled => match pause.schedule_resumes_at() {
+            Some(time) => i18n::tf("tooltip.scheduled", &[("time", &time)]),
+            None => i18n::t("tooltip.paused").into(),
+        },
+        pause::Status::Active if sound_auto_muted => i18n::t("tooltip.muted").into(),
+        pause::Status::Active => i18n::t("tooltip.active").into(),
     };
-    tray_icon::Icon::from_rgba(icon_rgba, icon_width, icon_height).expect("Failed to open icon")
+    let shortcuts = bindings
+        .describe()
+        .into_iter()
+        .map(|(chord, action)| format!("{chord}: {action}"))
+        .collect::<Vec<_>>();
+    format!("{status}\n{}", shortcuts.join(", "))
+}
+
+/// The app icon's pixels, width and height.
+fn icon_rgba() -> Result<(Vec<u8>, u32, u32), error::Error> {
+    let image = image::load_from_memory_with_format(ICON, image::ImageFormat::Png)
+        .map_err(|e| error::Error::Asset {
+            path: "the embedded icon".into(),
+            reason: e.to_string(),
+        })?
+        .into_rgba8();
+    let (width, height) = image.dimensions();
+    Ok((image.into_raw(), width, height))
 }
@@ -0,0 +1,72 @@
+use crate::easing::{ease_out_back, ease_out_bounce, ease_out_cubic};
+use egui::{Color32, Painter, Pos2, Shape, Stroke, Vec2};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Style {
+    /// The question mark animation from the assets folder.
+    #[default]
+    Gif,
+    /// A ring expanding out of the spot.
+    Ring,
+    /// A crosshair that pops in over the spot.
+    Crosshair,
+    /// An arrow dropping onto the spot from above.
+    Arrow,
+}
+
+impl Style {
+    pub fn is_procedural(self) -> bool {
+        self != Style::Gif
+    }
+}
+
+/// Draws a procedural marker `size` pixels across. `progress` runs from 0 to 1
+/// over the animation; the caller bakes the fade into `color`.
+pub fn draw(
+    painter: &Painter,
+    style: Style,
+    center: Pos2,
+    progress: f32,
+    size: f32,
+    color: Color32,
+) {
+    let progress = progress.clamp(0.0, 1.0);
+    let stroke = Stroke::new(size / 40.0, color);
+
+    match style {
+        Style::Gif => {}
+        Style::Ring => {
+            let radius = size / 2.0 * ease_out_cubic(progress);
+            painter.circle_stroke(center, radius, stroke);
+            painter.circle_filled(center, size / 40.0, color);
+        }
+        Style::Crosshair => {
+            // NOTE: pop in over the first third, then hold
+            let scale = ease_out_back((progress / 0.3).min(1.0));
+            let arm = size / 2.0 * scale;
+            let gap = size / 10.0 * scale;
+            for direction in [Vec2::X, -Vec2::X, Vec2::Y, -Vec2::Y] {
+                painter.line_segment([center + direction * gap, center + direction * arm], stroke);
+            }
+            painter.circle_stroke(center, arm * 0.6, stroke);
+        }
+        Style::Arrow => {
+            // NOTE: drop in over the first 40%, the tip lands on the spot
+            let drop = (1.0 - ease_out_bounce((progress / 0.4).min(1.0))) * size / 2.0;
+            let tip = center - Vec2::new(0.0, drop);
+            let head = size / 6.0;
+            let points = vec![
+                tip,
+                tip + Vec2::new(-head, -head),
+                tip + Vec2::new(head, -head),
+            ];
+            painter.add(Shape::convex_polygon(points, color, Stroke::NONE));
+            painter.line_segment(
+                [tip - Vec2::new(0.0, head), tip - Vec2::new(0.0, size / 2.0)],
+                Stroke::new(head / 2.5, color),
+            );
+        }
+    }
+}
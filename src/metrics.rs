@@ -0,0 +1,253 @@
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Upper bounds of the frame time histogram buckets, in microseconds. The
+/// last bucket catches everything slower.
+const FRAME_BUCKETS_US: [u64; 11] = [
+    1_000, 2_000, 4_000, 8_000, 12_000, 16_000, 20_000, 25_000, 33_000, 50_000, 100_000,
+];
+
+/// Runtime counters for spotting perf regressions. Everything is a relaxed
+/// atomic so recording is cheap enough for the render and hook paths.
+pub struct Metrics {
+    frames: AtomicU64,
+    frame_time_us: AtomicU64,
+    frame_buckets: [AtomicU64; FRAME_BUCKETS_US.len() + 1],
+    pub user_events: AtomicU64,
+    pub pings_accepted: AtomicU64,
+    pub pings_rejected: AtomicU64,
+    /// Pings lost because the animation queue was full.
+    pub pings_evicted: AtomicU64,
+    /// rodio does not report underruns, so this counts sounds that failed to start.
+    pub audio_errors: AtomicU64,
+    /// Frame updates dropped because the event loop fell behind.
+    pub events_dropped: AtomicU64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            frames: AtomicU64::new(0),
+            frame_time_us: AtomicU64::new(0),
+            frame_buckets: std::array::from_fn(|_| AtomicU64::new(0)),
+            user_events: AtomicU64::new(0),
+            pings_accepted: AtomicU64::new(0),
+            pings_rejected: AtomicU64::new(0),
+            pings_evicted: AtomicU64::new(0),
+            audio_errors: AtomicU64::new(0),
+            events_dropped: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Metrics {
+    pub fn record_frame(&self, duration: Duration) {
+        let us = duration.as_micros() as u64;
+        let bucket = FRAME_BUCKETS_US
+            .iter()
+            .position(|&bound| us <= bound)
+            .unwrap_or(FRAME_BUCKETS_US.len());
+        self.frames.fetch_add(1, Ordering::Relaxed);
+        self.frame_time_us.fetch_add(us, Ordering::Relaxed);
+        self.frame_buckets[bucket].fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn bump(counter: &AtomicU64) {
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> Snapshot {
+        let load = |counter: &AtomicU64| counter.load(Ordering::Relaxed);
+        Snapshot {
+            frames: load(&self.frames),
+            frame_time_us: load(&self.frame_time_us),
+            frame_buckets: std::array::from_fn(|i| load(&self.frame_buckets[i])),
+            user_events: load(&self.user_events),
+            pings_accepted: load(&self.pings_accepted),
+            pings_rejected: load(&self.pings_rejected),
+            pings_evicted: load(&self.pings_evicted),
+            audio_errors: load(&self.audio_errors),
+            events_dropped: load(&self.events_dropped),
+        }
+    }
+
+    /// Logs a summary of the last `interval` every `interval`.
+    pub fn spawn_reporter(self: &Arc<Self>, interval: Duration) {
+        let metrics = self.clone();
+        std::thread::spawn(move || {
+            let mut last = metrics.snapshot();
+            loop {
+                std::thread::sleep(interval);
+                let current = metrics.snapshot();
+                log::info!("metrics (last {interval:?}): {}", current.since(&last));
+                last = current;
+            }
+        });
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub frames: u64,
+    pub frame_time_us: u64,
+    pub frame_buckets: [u64; FRAME_BUCKETS_US.len() + 1],
+    pub user_events: u64,
+    pub pings_accepted: u64,
+    pub pings_rejected: u64,
+    pub pings_evicted: u64,
+    pub audio_errors: u64,
+    pub events_dropped: u64,
+}
+
+impl Snapshot {
+    /// Counter deltas between `earlier` and `self`.
+    pub fn since(&self, earlier: &Snapshot) -> Snapshot {
+        Snapshot {
+            frames: self.frames - earlier.frames,
+            frame_time_us: self.frame_time_us - earlier.frame_time_us,
+            frame_buckets: std::array::from_fn(|i| {
+                self.frame_buckets[i] - earlier.frame_buckets[i]
+            }),
+            user_events: self.user_events - earlier.user_events,
+            pings_accepted: self.pings_accepted - earlier.pings_accepted,
+            pings_rejected: self.pings_rejected - earlier.pings_rejected,
+            pings_evicted: self.pings_evicted - earlier.pings_evicted,
+            audio_errors: self.audio_errors - earlier.audio_errors,
+            events_dropped: self.events_dropped - earlier.events_dropped,
+        }
+    }
+
+    pub fn average_frame_time(&self) -> Option<Duration> {
+        (self.frames > 0).then(|| Duration::from_micros(self.frame_time_us / self.frames))
+    }
+
+    /// Upper bound of the bucket holding the 95th percentile frame, `None`
+    /// without frames or when it landed in the overflow bucket.
+    pub fn p95_frame_time(&self) -> Option<Duration> {
+        let target = (self.frames * 95).div_ceil(100);
+        let mut seen = 0;
+        for (i, count) in self.frame_buckets.iter().enumerate() {
+            seen += count;
+            if seen >= target && target > 0 {
+                return FRAME_BUCKETS_US.get(i).copied().map(Duration::from_micros);
+            }
+        }
+        None
+    }
+}
+
+impl std::fmt::Display for Snapshot {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} frames", self.frames)?;
+        if let Some(average) = self.average_frame_time() {
+            write!(f, ", avg {average:?}")?;
+            match self.p95_frame_time() {
+                Some(p95) => write!(f, ", p95 <= {p95:?}")?,
+                None => write!(f, ", p95 > 100ms")?,
+            }
+        }
+        write!(
+            f,
+            ", {} events ({} dropped), pings {} accepted / {} rejected / {} evicted, {} audio errors",
+            self.user_events,
+            self.events_dropped,
+            self.pings_accepted,
+            self.pings_rejected,
+            self.pings_evicted,
+            self.audio_errors
+        )
+    }
+}
+
+/// Value of `--metrics-interval <seconds>`, if passed.
+pub fn interval_from_args() -> Option<Duration> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--metrics-interval");
+    args.next()?;
+    match args.next().and_then(|secs| secs.parse().ok()) {
+        Some(secs) if secs > 0 => Some(Duration::from_secs(secs)),
+        _ => {
+            log::warn!("--metrics-interval expects a number of seconds");
+            None
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(ms: u64) -> Duration {
+        Duration::from_millis(ms)
+    }
+
+    #[test]
+    fn frames_land_in_their_bucket() {
+        let metrics = Metrics::default();
+        metrics.record_frame(Duration::from_micros(1_000));
+        metrics.record_frame(Duration::from_micros(1_001));
+        metrics.record_frame(ms(500));
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.frame_buckets[0], 1);
+        assert_eq!(snapshot.frame_buckets[1], 1);
+        assert_eq!(snapshot.frame_buckets[FRAME_BUCKETS_US.len()], 1);
+        assert_eq!(snapshot.frames, 3);
+    }
+
+    #[test]
+    fn average_and_p95() {
+        let metrics = Metrics::default();
+        for _ in 0..95 {
+            metrics.record_frame(ms(10));
+        }
+        for _ in 0..5 {
+            metrics.record_frame(ms(30));
+        }
+        let snapshot = metrics.snapshot();
+        assert_eq!(
+            snapshot.average_frame_time(),
+            Some(Duration::from_micros(11_000))
+        );
+        assert_eq!(snapshot.p95_frame_time(), Some(ms(12)));
+
+        metrics.record_frame(ms(30));
+        assert_eq!(metrics.snapshot().p95_frame_time(), Some(ms(33)));
+    }
+
+    #[test]
+    fn no_frames_no_times() {
+        let snapshot = Metrics::default().snapshot();
+        assert_eq!(snapshot.average_frame_time(), None);
+        assert_eq!(snapshot.p95_frame_time(), None);
+        assert!(snapshot.to_string().starts_with("0 frames,"));
+    }
+
+    #[test]
+    fn p95_past_the_last_bucket_is_unbounded() {
+        let metrics = Metrics::default();
+        metrics.record_frame(ms(200));
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.p95_frame_time(), None);
+        assert!(snapshot.to_string().contains("p95 > 100ms"));
+    }
+
+    #[test]
+    fn since_is_the_delta() {
+        let metrics = Metrics::default();
+        metrics.record_frame(ms(5));
+        Metrics::bump(&metrics.pings_accepted);
+        let earlier = metrics.snapshot();
+
+        metrics.record_frame(ms(5));
+        Metrics::bump(&metrics.pings_accepted);
+        Metrics::bump(&metrics.pings_rejected);
+        let delta = metrics.snapshot().since(&earlier);
+        assert_eq!(delta.frames, 1);
+        assert_eq!(delta.frame_time_us, 5_000);
+        assert_eq!(delta.frame_buckets.iter().sum::<u64>(), 1);
+        assert_eq!(delta.pings_accepted, 1);
+        assert_eq!(delta.pings_rejected, 1);
+        assert_eq!(delta.pings_evicted, 0);
+        assert_eq!(earlier.since(&earlier), Snapshot::default());
+    }
+}
@@ -0,0 +1,71 @@
+//! Per monitor tweaks to the ping sound, so it's audible where a ping landed.
+
+use crate::audio::Layered;
+use crate::config::MonitorSound;
+use crate::layout::{Layout, MonitorRef};
+use std::collections::{BTreeMap, HashMap};
+
+pub struct Cue {
+    /// Replaces the theme's sound, `None` keeps it.
+    pub sound: Option<Layered>,
+    /// Playback speed, which shifts the pitch along with it.
+    pub speed: f32,
+    /// Output device name, `None` plays on the main output.
+    pub device: Option<String>,
+}
+
+/// The configured cues and the monitors they're on in the current layout.
+#[derive(Default)]
+pub struct Cues {
+    cues: Vec<(MonitorRef, Cue)>,
+    /// 1-based monitor index to its entry in `cues`.
+    resolved: HashMap<usize, usize>,
+}
+
+impl Cues {
+    pub fn get(&self, monitor: usize) -> Option<&Cue> {
+        let &entry = self.resolved.get(&monitor)?;
+        Some(&self.cues[entry].1)
+    }
+
+    /// Matches the cues against `layout`, on startup and whenever monitors change.
+    pub fn resolve(&mut self, layout: &Layout) {
+        self.resolved = self
+            .cues
+            .iter()
+            .enumerate()
+            .filter_map(|(entry, (monitor, _))| Some((layout.resolve(monitor)?, entry)))
+            .collect();
+    }
+}
+
+/// Decodes the configured sounds up front, keyed by monitor number or name.
+/// Broken entries are logged and skipped.
+pub fn load(monitors: &BTreeMap<String, MonitorSound>) -> Cues {
+    let cues = monitors
+        .iter()
+        .map(|(key, monitor)| {
+            let sound = match &monitor.path {
+                Some(spec) => match Layered::load(spec, &format!("monitor {key} sound")) {
+                    Ok(sound) => Some(sound),
+                    Err(e) => {
+                        log::warn!("monitor {key} sound unusable: {e}");
+                        None
+                    }
+                },
+                None => None,
+            };
+            let speed = 2f32.powf(monitor.semitones / 12.0);
+            let cue = Cue {
+                sound,
+                speed,
+                device: monitor.device.clone(),
+            };
+            (MonitorRef::parse(key), cue)
+        })
+        .collect();
+    Cues {
+        cues,
+        resolved: HashMap::new(),
+    }
+}
@@ -0,0 +1,73 @@
+//! One-line notes on persistent markers, typed into a small dialog opened by
+//! the note chord (ping key + N by default).
+
+use crate::i18n::t;
+
+/// Longest note kept, in characters.
+pub const MAX_LEN: usize = 120;
+
+/// Editing the note of one marker.
+pub struct Editor {
+    /// Animation id of the marker.
+    pub id: usize,
+    text: String,
+    saved: bool,
+    focused: bool,
+}
+
+impl Editor {
+    pub fn new(id: usize, note: Option<&str>) -> Self {
+        Self {
+            id,
+            text: note.unwrap_or_default().to_owned(),
+            saved: false,
+            focused: false,
+        }
+    }
+
+    /// Returns `true` when the user saved or cancelled.
+    pub fn ui(&mut self, ctx: &egui::Context) -> bool {
+        let mut close = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.label(t("note.prompt"));
+            let response = ui.add(
+                egui::TextEdit::singleline(&mut self.text)
+                    .char_limit(MAX_LEN)
+                    .desired_width(f32::INFINITY),
+            );
+            if !self.focused {
+                response.request_focus();
+                self.focused = true;
+            }
+            let entered = response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            ui.horizontal(|ui| {
+                if ui.button(t("note.save")).clicked() || entered {
+                    self.saved = true;
+                    close = true;
+                }
+                if ui.button(t("dialog.cancel")).clicked() {
+                    close = true;
+                }
+            });
+            if ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                close = true;
+            }
+        });
+        close
+    }
+
+    /// The note to keep if the user saved, empty when it was cleared.
+    pub fn saved(self) -> Option<String> {
+        self.saved.then(|| clean(&self.text))
+    }
+}
+
+/// `text` on one line, trimmed and cut to [`MAX_LEN`].
+pub fn clean(text: &str) -> String {
+    text.split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_LEN)
+        .collect()
+}
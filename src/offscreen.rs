@@ -0,0 +1,201 @@
+//! Drawing [`MyApp`] into a texture instead of a window and reading the
+//! pixels back, for exports and snapshots.
+
+use crate::MyApp;
+use egui_wgpu::renderer::ScreenDescriptor;
+use egui_wgpu::Renderer;
+use image::RgbaImage;
+
+/// Bytes per row of a texture copy are padded to this.
+const ROW_ALIGNMENT: u32 = wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+
+/// An off-screen render target with egui on it, read back after each frame.
+pub struct Canvas {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    context: egui::Context,
+    renderer: Renderer,
+    texture: wgpu::Texture,
+    readback: wgpu::Buffer,
+    size: [u32; 2],
+    padded_row: u32,
+}
+
+impl Canvas {
+    pub fn new(size: [u32; 2]) -> Result<Self, String> {
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Default::default(),
+        });
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: None,
+            force_fallback_adapter: false,
+        }))
+        .ok_or("no graphics adapter")?;
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                label: None,
+            },
+            None,
+        ))
+        .map_err(|e| e.to_string())?;
+
+        // NOTE: not sRGB, so egui blends in gamma space as it does on the overlay
+        let format = wgpu::TextureFormat::Rgba8Unorm;
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("export"),
+            size: wgpu::Extent3d {
+                width: size[0],
+                height: size[1],
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let padded_row = (size[0] * 4).div_ceil(ROW_ALIGNMENT) * ROW_ALIGNMENT;
+        let readback = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("export readback"),
+            size: padded_row as u64 * size[1] as u64,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+
+        let context = egui::Context::default();
+        egui_extras::install_image_loaders(&context);
+        let renderer = Renderer::new(&device, format, None, 1);
+        Ok(Self {
+            device,
+            queue,
+            context,
+            renderer,
+            texture,
+            readback,
+            size,
+            padded_row,
+        })
+    }
+
+    /// Draws `app` over a `desktop` sized overlay scaled by `scale`, and reads
+    /// the result back. Colors come out premultiplied by alpha.
+    pub fn render(
+        &mut self,
+        app: &mut MyApp,
+        desktop: egui::Vec2,
+        scale: f32,
+    ) -> Result<RgbaImage, String> {
+        let input = egui::RawInput {
+            screen_rect: Some(egui::Rect::from_min_size(egui::Pos2::ZERO, desktop)),
+            pixels_per_point: Some(scale),
+            ..Default::default()
+        };
+        let output = app.frame(&self.context, input, None);
+        let paint_jobs = self.context.tessellate(output.shapes);
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: self.size,
+            pixels_per_point: scale,
+        };
+        for (id, image_delta) in &output.textures_delta.set {
+            self.renderer
+                .update_texture(&self.device, &self.queue, *id, image_delta);
+        }
+        for id in &output.textures_delta.free {
+            self.renderer.free_texture(id);
+        }
+
+        let view = self
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        self.renderer.update_buffers(
+            &self.device,
+            &self.queue,
+            &mut encoder,
+            &paint_jobs,
+            &screen_descriptor,
+        );
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: true,
+                    },
+                })],
+                depth_stencil_attachment: None,
+            });
+            self.renderer
+                .render(&mut render_pass, &paint_jobs, &screen_descriptor);
+        }
+        encoder.copy_texture_to_buffer(
+            self.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &self.readback,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(self.padded_row),
+                    rows_per_image: None,
+                },
+            },
+            self.texture.size(),
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let slice = self.readback.slice(..);
+        let (sender, receiver) = std::sync::mpsc::channel();
+        slice.map_async(wgpu::MapMode::Read, move |result| {
+            sender.send(result).ok();
+        });
+        self.device.poll(wgpu::Maintain::Wait);
+        receiver
+            .recv()
+            .map_err(|e| e.to_string())?
+            .map_err(|e| format!("failed to read the frame back: {e}"))?;
+
+        let row = self.size[0] as usize * 4;
+        let mut pixels = Vec::with_capacity(row * self.size[1] as usize);
+        for padded in slice.get_mapped_range().chunks(self.padded_row as usize) {
+            pixels.extend_from_slice(&padded[..row]);
+        }
+        self.readback.unmap();
+        RgbaImage::from_raw(self.size[0], self.size[1], pixels)
+            .ok_or_else(|| "frame size mismatch".into())
+    }
+}
+
+/// Premultiplied `pings` drawn over `background`.
+pub fn over(pings: &RgbaImage, background: &RgbaImage) -> RgbaImage {
+    let mut image = background.clone();
+    for (pixel, ping) in image.pixels_mut().zip(pings.pixels()) {
+        let keep = 255 - ping[3] as u16;
+        for c in 0..3 {
+            pixel[c] = (ping[c] as u16 + pixel[c] as u16 * keep / 255).min(255) as u8;
+        }
+        pixel[3] = 255;
+    }
+    image
+}
+
+/// Undoes the premultiplication, e.g. for GIF or PNG colors.
+pub fn unpremultiply(image: &mut RgbaImage) {
+    for pixel in image.pixels_mut() {
+        let alpha = pixel[3] as u16;
+        for c in 0..3 {
+            // NOTE: fully transparent pixels are black already
+            pixel[c] = (pixel[c] as u16 * 255)
+                .checked_div(alpha)
+                .map_or(0, |straight| straight.min(255) as u8);
+        }
+    }
+}
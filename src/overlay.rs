@@ -0,0 +1,814 @@
+use crate::error::Error;
+use crate::sprites::SpriteRenderer;
+use crate::{config, diagnostics, metrics::Metrics, platform, MyApp};
+use egui_wgpu::renderer::ScreenDescriptor;
+use egui_wgpu::{wgpu::Dx12Compiler, Renderer};
+use raw_window_handle::HasRawWindowHandle;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use windows::Win32::Foundation::HWND;
+use winit::dpi::PhysicalSize;
+use winit::event_loop::EventLoopWindowTarget;
+use winit::window::{Window, WindowLevel};
+
+/// How often the window styles are read back to catch DWM or driver resets.
+const STYLE_CHECK_INTERVAL: Duration = Duration::from_secs(2);
+/// Wait between attempts to rebuild a lost renderer, the driver can take a
+/// while to come back after a reset.
+const RESTART_INTERVAL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Mode {
+    /// Transparent where Windows can blend it, opaque otherwise.
+    #[default]
+    Auto,
+    /// One click-through window spanning every monitor.
+    Transparent,
+    /// A small opaque window around each ping, for when the transparent
+    /// overlay shows up as a black rectangle.
+    Opaque,
+}
+
+impl Mode {
+    fn transparent(self) -> bool {
+        match self {
+            Mode::Transparent => true,
+            Mode::Opaque => false,
+            Mode::Auto => {
+                let composition = platform::composition_enabled();
+                let effects = platform::transparency_effects_enabled();
+                let remote = platform::is_remote_session();
+                let transparent = composition && effects && !remote;
+                if !transparent {
+                    log::info!(
+                        "drawing pings in opaque windows (composition: {composition}, \
+                         transparency effects: {effects}, remote session: {remote})"
+                    );
+                }
+                transparent
+            }
+        }
+    }
+}
+
+/// What draws the ping artwork.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Backend {
+    /// Everything through egui.
+    #[default]
+    Egui,
+    /// Artwork as instanced quads from [`SpriteRenderer`], egui only for the rest.
+    Sprites,
+}
+
+/// The click-through window spanning every monitor, with everything needed to draw on it.
+pub struct Overlay {
+    pub instance: wgpu::Instance,
+    // NOTE: declared before the window, the surface must go first
+    pub surface: wgpu::Surface,
+    pub surface_config: wgpu::SurfaceConfiguration,
+    pub window: Window,
+    pub adapter: wgpu::Adapter,
+    pub device: wgpu::Device,
+    pub queue: wgpu::Queue,
+    pub context: egui::Context,
+    /// What the device was set up with, for the About dialog.
+    pub graphics: diagnostics::Graphics,
+    renderer: Renderer,
+    /// Draws the artwork with the sprites backend, `None` with egui.
+    sprites: Option<SpriteRenderer>,
+    /// Distance from the leftmost monitor edge to the virtual desktop origin.
+    pub offset: f32,
+    start: Instant,
+    next_style_check: Instant,
+    /// Set once the device is gone, e.g. after a driver update or a TDR reset.
+    lost: Arc<AtomicBool>,
+    next_restart: Instant,
+    /// Per-ping windows by animation id in opaque mode, `None` when transparent.
+    /// The spanning window is then kept hidden, for the device alone.
+    spots: Option<HashMap<usize, Spot>>,
+    /// Window kept above the overlay, see [`config::Overlay::above`]. `None`
+    /// leaves the overlay topmost.
+    below: Option<HWND>,
+    /// `None` when the shell has no virtual desktops to follow.
+    desktops: Option<platform::VirtualDesktops>,
+    /// Set once the overlay had to be moved after a desktop switch.
+    following: bool,
+}
+
+/// A small opaque window around one ping, drawn with the overlay's device.
+struct Spot {
+    // NOTE: declared before the window, the surface must go first
+    surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    window: Window,
+    /// Where the window sits, in overlay coordinates.
+    rect: egui::Rect,
+}
+
+/// Everything tied to one graphics device, rebuilt as a whole when it's lost.
+struct Gpu {
+    instance: wgpu::Instance,
+    surface: wgpu::Surface,
+    surface_config: wgpu::SurfaceConfiguration,
+    adapter: wgpu::Adapter,
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    context: egui::Context,
+    renderer: Renderer,
+}
+
+impl Overlay {
+    /// Builds the overlay over the current monitors. Returns `None` while there
+    /// are none, e.g. when launched before the display initializes.
+    pub fn new<T>(
+        target: &EventLoopWindowTarget<T>,
+        config: &config::Overlay,
+    ) -> Result<Option<Self>, Error> {
+        let Some((offset, size)) = bounds(target) else {
+            return Ok(None);
+        };
+        let transparent = config.mode.transparent();
+
+        let window = winit::window::WindowBuilder::new()
+            .with_inner_size(size)
+            .with_position(winit::dpi::PhysicalPosition::new(offset, 0.0))
+            .with_transparent(transparent)
+            .with_visible(transparent)
+            .with_decorations(false)
+            // NOTE: shown with SW_SHOWNOACTIVATE, the overlay must never take focus
+            .with_active(false)
+            .build(target)?;
+
+        window.set_window_level(WindowLevel::AlwaysOnTop);
+        // NOTE: an overlay catching clicks would lock the desktop up, no going on without it
+        window
+            .set_cursor_hittest(false)
+            .map_err(|e| Error::Platform {
+                call: "set_cursor_hittest",
+                reason: e.to_string(),
+            })?;
+
+        let lost = Arc::new(AtomicBool::new(false));
+        let Gpu {
+            instance,
+            surface,
+            surface_config,
+            adapter,
+            device,
+            queue,
+            context,
+            renderer,
+        } = Gpu::new(&window, &lost)?;
+        // SAFETY: we windows
+        unsafe {
+            platform::hide_taskbar_entry(window.raw_window_handle());
+        }
+        let sprites = (config.renderer == Backend::Sprites)
+            .then(|| SpriteRenderer::new(&device, surface_config.format));
+        let graphics = diagnostics::Graphics::new(&adapter, &surface_config);
+        log::info!("overlay drawn with {graphics:?}");
+
+        Ok(Some(Self {
+            window,
+            instance,
+            surface,
+            surface_config,
+            adapter,
+            device,
+            queue,
+            context,
+            graphics,
+            renderer,
+            sprites,
+            offset: offset.abs(),
+            start: Instant::now(),
+            next_style_check: Instant::now() + STYLE_CHECK_INTERVAL,
+            below: None,
+            lost,
+            next_restart: Instant::now(),
+            spots: (!transparent).then(HashMap::new),
+            desktops: virtual_desktops(),
+            following: false,
+        }))
+    }
+
+    pub fn request_redraw(&self) {
+        // NOTE: the spanning window is hidden in opaque mode, ask a visible one
+        match self.spots.iter().flat_map(HashMap::values).next() {
+            Some(spot) => spot.window.request_redraw(),
+            None => self.window.request_redraw(),
+        }
+    }
+
+    /// Opens, moves and closes the per-ping windows of opaque mode to match the
+    /// pings in `app`. Does nothing when transparent.
+    pub fn sync_spots<T>(&mut self, target: &EventLoopWindowTarget<T>, app: &MyApp) {
+        let Some(spots) = &mut self.spots else {
+            return;
+        };
+        let rects = app.spot_rects();
+        spots.retain(|id, _| rects.contains_key(id));
+
+        for (id, rect) in rects {
+            let position = winit::dpi::PhysicalPosition::new(rect.min.x - self.offset, rect.min.y);
+            let size = PhysicalSize::new(rect.width() as u32, rect.height() as u32);
+            if let Some(spot) = spots.get_mut(&id) {
+                if spot.rect.min != rect.min {
+                    spot.window.set_outer_position(position);
+                }
+                // NOTE: also catches Windows rescaling it on a monitor with another DPI
+                if spot.window.inner_size() != size {
+                    spot.window.set_inner_size(size);
+                }
+                if (spot.surface_config.width, spot.surface_config.height)
+                    != (size.width, size.height)
+                {
+                    spot.surface_config.width = size.width;
+                    spot.surface_config.height = size.height;
+                    spot.surface.configure(&self.device, &spot.surface_config);
+                }
+                spot.rect = rect;
+                continue;
+            }
+
+            let window = match winit::window::WindowBuilder::new()
+                .with_inner_size(size)
+                .with_position(position)
+                .with_decorations(false)
+                .with_active(false)
+                .build(target)
+            {
+                Ok(window) => window,
+                Err(e) => {
+                    log::warn!("failed to open a ping window: {e}");
+                    continue;
+                }
+            };
+            window.set_window_level(WindowLevel::AlwaysOnTop);
+            window.set_cursor_hittest(false).ok();
+            // SAFETY: we windows
+            unsafe {
+                platform::make_spot_window(window.raw_window_handle());
+            }
+            let surface = match unsafe { self.instance.create_surface(&window) } {
+                Ok(surface) => surface,
+                Err(e) => {
+                    log::warn!("failed to draw into a ping window: {e}");
+                    continue;
+                }
+            };
+            let surface_config = wgpu::SurfaceConfiguration {
+                width: size.width,
+                height: size.height,
+                ..self.surface_config.clone()
+            };
+            surface.configure(&self.device, &surface_config);
+            if let Some(below) = self.below {
+                // SAFETY: we windows
+                unsafe { platform::set_overlay_order(window.raw_window_handle(), Some(below)) };
+            }
+            spots.insert(
+                id,
+                Spot {
+                    surface,
+                    surface_config,
+                    window,
+                    rect,
+                },
+            );
+        }
+    }
+
+    /// Puts the overlay just below `below`, or back on top of everything.
+    pub fn stay_below(&mut self, below: Option<HWND>) {
+        if below == self.below {
+            return;
+        }
+        match below {
+            Some(below) => log::info!("keeping the overlay below window {below:?}"),
+            None => log::info!("the overlay is topmost again"),
+        }
+        self.below = below;
+        let spots = self.spots.iter().flat_map(|spots| spots.values());
+        for window in Some(&self.window)
+            .into_iter()
+            .chain(spots.map(|spot| &spot.window))
+        {
+            // SAFETY: we windows
+            unsafe { platform::set_overlay_order(window.raw_window_handle(), below) };
+        }
+    }
+
+    pub fn lost(&self) -> bool {
+        self.lost.load(Ordering::Relaxed)
+    }
+
+    /// Has the next redraw rebuild the renderer, as if the device was lost.
+    pub fn mark_lost(&self) {
+        self.lost.store(true, Ordering::Relaxed);
+    }
+
+    /// Throws away the device and everything made with it and builds them
+    /// again on the same window, with the renderer `config` asks for now.
+    /// Animations live in `MyApp` and carry on; the
+    /// new egui context uploads its textures afresh. Returns `false` when the
+    /// driver isn't back yet, attempts are spaced by [`RESTART_INTERVAL`].
+    pub fn restart_renderer(&mut self, config: &config::Overlay) -> bool {
+        if self.next_restart > Instant::now() {
+            return false;
+        }
+        self.next_restart = Instant::now() + RESTART_INTERVAL;
+
+        log::info!("restarting the renderer");
+        let lost = Arc::new(AtomicBool::new(false));
+        let gpu = match Gpu::new(&self.window, &lost) {
+            Ok(gpu) => gpu,
+            Err(e) => {
+                log::warn!("renderer restart failed, retrying: {e}");
+                return false;
+            }
+        };
+        // NOTE: the old device's error handler keeps the old flag, a late error from
+        // it can't mark the new device lost
+        self.lost = lost;
+        // NOTE: made with the old instance, they're reopened by the next sync
+        if let Some(spots) = &mut self.spots {
+            spots.clear();
+        }
+        self.instance = gpu.instance;
+        self.surface = gpu.surface;
+        self.surface_config = gpu.surface_config;
+        self.adapter = gpu.adapter;
+        self.device = gpu.device;
+        self.queue = gpu.queue;
+        self.context = gpu.context;
+        self.renderer = gpu.renderer;
+        // NOTE: the renderer setting may have changed since, see `live`
+        self.sprites = (config.renderer == Backend::Sprites)
+            .then(|| SpriteRenderer::new(&self.device, self.surface_config.format));
+        // NOTE: the window may have been resized while the device was gone
+        self.resize(self.window.inner_size());
+        self.graphics = diagnostics::Graphics::new(&self.adapter, &self.surface_config);
+        log::info!("renderer restarted on {}", self.adapter.get_info().name);
+        true
+    }
+
+    /// Moves and resizes the window to span the current monitors, e.g. after a
+    /// resolution or scaling change. Returns `true` if anything changed.
+    pub fn fit<T>(&mut self, target: &EventLoopWindowTarget<T>) -> bool {
+        let Some((offset, size)) = bounds(target) else {
+            return false;
+        };
+        if size == self.window.inner_size() && offset.abs() == self.offset {
+            return false;
+        }
+
+        log::info!("refitting the overlay to {}x{}", size.width, size.height);
+        self.window
+            .set_outer_position(winit::dpi::PhysicalPosition::new(offset, 0.0));
+        self.window.set_inner_size(size);
+        self.resize(size);
+        self.offset = offset.abs();
+        true
+    }
+
+    /// Reconfigures the surface for the window's new size.
+    pub fn resize(&mut self, size: PhysicalSize<u32>) {
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        if size.width == self.surface_config.width && size.height == self.surface_config.height {
+            return;
+        }
+        self.surface_config.width = size.width;
+        self.surface_config.height = size.height;
+        self.surface.configure(&self.device, &self.surface_config);
+    }
+
+    /// Reapplies the click-through and tool-window styles.
+    pub fn repair(&self) {
+        // SAFETY: we windows
+        unsafe {
+            platform::hide_taskbar_entry(self.window.raw_window_handle());
+        }
+        if let Err(e) = self.window.set_cursor_hittest(false) {
+            log::warn!("failed to make the overlay click-through: {e}");
+        }
+    }
+
+    /// Repairs the window if its styles were lost since the last check.
+    pub fn check_styles(&mut self) {
+        if self.next_style_check > Instant::now() {
+            return;
+        }
+        self.next_style_check = Instant::now() + STYLE_CHECK_INTERVAL;
+
+        // SAFETY: we windows
+        let intact = unsafe { platform::overlay_styles_intact(self.window.raw_window_handle()) };
+        if !intact {
+            log::warn!("overlay window styles were reset, reapplying");
+            self.repair();
+        }
+
+        // SAFETY: we windows
+        let focused = unsafe { platform::is_foreground(self.window.raw_window_handle()) };
+        debug_assert!(!focused, "the overlay took keyboard focus");
+        if focused {
+            log::warn!("the overlay took keyboard focus");
+        }
+
+        self.follow_desktop();
+    }
+
+    /// Brings the overlay along to the virtual desktop being shown.
+    fn follow_desktop(&mut self) {
+        let Some(desktops) = &self.desktops else {
+            return;
+        };
+        let handle = self.window.raw_window_handle();
+        // SAFETY: we windows
+        match unsafe { desktops.follow(handle) } {
+            Ok(false) => {}
+            Ok(true) if self.following => {}
+            Ok(true) => {
+                log::info!(
+                    "the overlay isn't shown on every virtual desktop on this Windows build, \
+                     following desktop switches instead"
+                );
+                self.following = true;
+            }
+            Err(e) => {
+                // NOTE: the manager lives in explorer, a restarted explorer needs a new one
+                log::info!("lost the virtual desktop manager ({e}), reconnecting");
+                // SAFETY: we windows
+                self.desktops = platform::VirtualDesktops::new()
+                    .ok()
+                    .filter(|desktops| unsafe { desktops.follow(handle) }.is_ok());
+                if self.desktops.is_none() {
+                    log::warn!("virtual desktops unavailable, the overlay stays where it is");
+                }
+            }
+        }
+    }
+
+    pub fn render(&mut self, app: &mut MyApp, metrics: &Metrics) {
+        let started = Instant::now();
+        if self.lost() {
+            return;
+        }
+        // NOTE: taken out for the frame, so `draw` can borrow one alongside `self`
+        match self.spots.take() {
+            None => {
+                let input = overlay_input(self.window.inner_size(), self.start);
+                self.draw(None, app, input, None);
+            }
+            Some(spots) => {
+                for (id, spot) in &spots {
+                    let input = overlay_input(spot.window.inner_size(), self.start);
+                    let origin = spot.rect.min.to_vec2();
+                    self.draw(Some(spot), app, input, Some((*id, origin)));
+                }
+                self.spots = Some(spots);
+            }
+        }
+        metrics.record_frame(started.elapsed());
+    }
+
+    /// Runs one pass of `app` and presents it on `spot`, or on the spanning
+    /// window without one. `only` is passed on to [`MyApp::frame`].
+    fn draw(
+        &mut self,
+        spot: Option<&Spot>,
+        app: &mut MyApp,
+        input: egui::RawInput,
+        only: Option<(usize, egui::Vec2)>,
+    ) {
+        let (surface, surface_config, mut load) = match spot {
+            Some(spot) => (
+                &spot.surface,
+                &spot.surface_config,
+                wgpu::LoadOp::Clear(wgpu::Color::BLACK),
+            ),
+            None => (&self.surface, &self.surface_config, wgpu::LoadOp::Load),
+        };
+        let texture = surface.get_current_texture();
+        let frame_texture = match texture {
+            Ok(f) => f,
+            Err(e @ (wgpu::SurfaceError::Outdated | wgpu::SurfaceError::Lost)) => {
+                log::warn!("surface lost: window is probably minimized: {e}");
+                surface.configure(&self.device, surface_config);
+                return;
+            }
+            Err(wgpu::SurfaceError::Timeout) => return,
+            Err(e) => {
+                log::warn!("graphics device lost: {e}");
+                self.lost.store(true, Ordering::Relaxed);
+                return;
+            }
+        };
+
+        let view = frame_texture
+            .texture
+            .create_view(&wgpu::TextureViewDescriptor::default());
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        // NOTE: procedural markers have no frames to upload
+        if let Some(sprites) = self
+            .sprites
+            .as_mut()
+            .filter(|_| !app.marker_style().is_procedural())
+        {
+            let kinds = app.animations.values().map(|animation| &animation.kind);
+            app.sprites = Some(sprites.prepare(&self.device, &self.queue, &app.theme, kinds));
+        }
+        let output = app.frame(&self.context, input, only);
+        let sprites = app
+            .sprites
+            .take()
+            .map(|batch| batch.sprites)
+            .unwrap_or_default();
+        let paint_jobs = self.context.tessellate(output.shapes);
+        let screen_descriptor = ScreenDescriptor {
+            size_in_pixels: [surface_config.width, surface_config.height],
+            pixels_per_point: 1.0,
+        };
+
+        {
+            for (id, image_delta) in &output.textures_delta.set {
+                self.renderer
+                    .update_texture(&self.device, &self.queue, *id, image_delta);
+            }
+            for id in &output.textures_delta.free {
+                self.renderer.free_texture(id);
+            }
+
+            {
+                self.renderer.update_buffers(
+                    &self.device,
+                    &self.queue,
+                    &mut encoder,
+                    &paint_jobs,
+                    &screen_descriptor,
+                );
+            }
+        }
+
+        // NOTE: with only artwork on screen egui has nothing to draw, skip its pass
+        if !paint_jobs.is_empty() || sprites.is_empty() {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: None,
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &view,
+                    resolve_target: None,
+                    ops: wgpu::Operations { load, store: true },
+                })],
+                depth_stencil_attachment: None,
+            });
+
+            self.renderer
+                .render(&mut render_pass, &paint_jobs, &screen_descriptor);
+            load = wgpu::LoadOp::Load;
+        }
+        if let Some(renderer) = &mut self.sprites {
+            renderer.draw(
+                &self.device,
+                &self.queue,
+                &mut encoder,
+                &view,
+                load,
+                screen_descriptor.size_in_pixels,
+                &app.theme,
+                &sprites,
+            );
+        }
+
+        self.queue.submit(Some(encoder.finish()));
+        frame_texture.present();
+    }
+}
+
+/// Left edge of the virtual desktop and a size spanning every monitor, `None`
+/// while there are none.
+fn bounds<T>(target: &EventLoopWindowTarget<T>) -> Option<(f32, PhysicalSize<u32>)> {
+    let mut offset = f32::MAX;
+    let mut total_width = 0;
+    let mut total_height = 0;
+
+    for monitor in target.available_monitors() {
+        let monitor_size = monitor.size();
+        total_width += monitor_size.width;
+        total_height += monitor_size.height;
+        let monitor_position = monitor.position();
+        if (monitor_position.x as f32) < offset {
+            offset = monitor_position.x as f32;
+        }
+    }
+
+    if total_width == 0 || total_height == 0 {
+        return None;
+    }
+    Some((offset, PhysicalSize::new(total_width, total_height)))
+}
+
+/// Connects to the shell's virtual desktops (Win+Ctrl+arrows), `None` where
+/// there are none, e.g. on builds without them or with explorer not running.
+fn virtual_desktops() -> Option<platform::VirtualDesktops> {
+    match platform::VirtualDesktops::new() {
+        Ok(desktops) => {
+            log::info!("virtual desktops: showing the overlay as a tool window on all of them");
+            Some(desktops)
+        }
+        Err(e) => {
+            log::warn!("virtual desktops unavailable, the overlay stays where it is: {e}");
+            None
+        }
+    }
+}
+
+/// The overlay is output only, so its input carries just the screen and clock:
+/// no pointer or keyboard state can ever reach it.
+fn overlay_input(size: PhysicalSize<u32>, start: Instant) -> egui::RawInput {
+    egui::RawInput {
+        screen_rect: Some(egui::Rect::from_min_size(
+            egui::Pos2::ZERO,
+            egui::Vec2::new(size.width as f32, size.height as f32),
+        )),
+        pixels_per_point: Some(1.0),
+        time: Some(start.elapsed().as_secs_f64()),
+        ..Default::default()
+    }
+}
+
+/// Whether `error` means the device is unusable: it ran out of memory, or
+/// it's gone, e.g. after a driver update or a GPU reset.
+fn is_device_lost(error: &wgpu::Error) -> bool {
+    // NOTE: wgpu 0.17 has no error of its own for a lost device, it's a validation
+    // error caused by the device being lost
+    let lost = std::iter::successors(Some(error as &dyn std::error::Error), |e| e.source())
+        .any(|e| e.to_string().contains("device is lost"));
+    lost || matches!(error, wgpu::Error::OutOfMemory { .. })
+}
+
+impl Gpu {
+    fn new(window: &Window, lost: &Arc<AtomicBool>) -> Result<Self, Error> {
+        let unpicked = |reason: String| Error::Graphics {
+            adapter: "no adapter".into(),
+            reason,
+        };
+        let instance = wgpu::Instance::new(wgpu::InstanceDescriptor {
+            backends: wgpu::Backends::all(),
+            dx12_shader_compiler: Dx12Compiler::default(),
+        });
+
+        let surface =
+            unsafe { instance.create_surface(window) }.map_err(|e| unpicked(e.to_string()))?;
+
+        let adapter = pollster::block_on(instance.request_adapter(&wgpu::RequestAdapterOptions {
+            power_preference: wgpu::PowerPreference::default(),
+            compatible_surface: Some(&surface),
+            force_fallback_adapter: false,
+        }))
+        .ok_or_else(|| unpicked("no graphics adapter can draw the overlay".into()))?;
+
+        let (device, queue) = pollster::block_on(adapter.request_device(
+            &wgpu::DeviceDescriptor {
+                features: wgpu::Features::empty(),
+                limits: wgpu::Limits::default(),
+                label: None,
+            },
+            None,
+        ))
+        .map_err(|e| Error::graphics(&adapter, e))?;
+        // NOTE: wgpu panics on uncaptured errors by default; once the device is gone
+        // every call errors, so flag it for a restart instead. Anything else only
+        // spoils a frame, and restarting wouldn't fix it
+        let handler_lost = lost.clone();
+        let errors = std::sync::atomic::AtomicU32::new(0);
+        device.on_uncaptured_error(Box::new(move |e| {
+            if !is_device_lost(&e) {
+                // NOTE: a bad call tends to repeat every frame, so the log thins out
+                let count = errors.fetch_add(1, Ordering::Relaxed) + 1;
+                if count.is_power_of_two() {
+                    log::warn!("graphics error ({count} so far): {e}");
+                }
+            } else if !handler_lost.swap(true, Ordering::Relaxed) {
+                log::warn!("graphics device error, restarting the renderer: {e}");
+            }
+        }));
+
+        let size = window.inner_size();
+        let mut surface_config = surface
+            .get_default_config(&adapter, size.width, size.height)
+            .ok_or_else(|| Error::graphics(&adapter, "the overlay's surface isn't supported"))?;
+
+        surface_config.present_mode = wgpu::PresentMode::Immediate;
+        surface.configure(&device, &surface_config);
+
+        let context = egui::Context::default();
+        egui_extras::install_image_loaders(&context);
+        let renderer = Renderer::new(&device, surface_config.format, None, 1);
+
+        Ok(Self {
+            instance,
+            surface,
+            surface_config,
+            adapter,
+            device,
+            queue,
+            context,
+            renderer,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::animation_builder::AnimationBuilder;
+
+    #[test]
+    fn overlay_input_is_only_screen_and_clock() {
+        let input = overlay_input(PhysicalSize::new(1920, 1080), Instant::now());
+        assert!(input.events.is_empty());
+        assert!(input.hovered_files.is_empty() && input.dropped_files.is_empty());
+        assert_eq!(input.modifiers, egui::Modifiers::NONE);
+        assert_eq!(
+            input.screen_rect,
+            Some(egui::Rect::from_min_size(
+                egui::Pos2::ZERO,
+                egui::Vec2::new(1920.0, 1080.0)
+            ))
+        );
+    }
+
+    /// A validation error caused by `cause`, the way wgpu-core nests them.
+    fn validation(cause: &'static str) -> wgpu::Error {
+        #[derive(Debug)]
+        struct Cause(&'static str);
+
+        impl std::fmt::Display for Cause {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str(self.0)
+            }
+        }
+
+        impl std::error::Error for Cause {}
+
+        #[derive(Debug)]
+        struct Call(Cause);
+
+        impl std::fmt::Display for Call {
+            fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                f.write_str("In Device::create_buffer")
+            }
+        }
+
+        impl std::error::Error for Call {
+            fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+                Some(&self.0)
+            }
+        }
+
+        wgpu::Error::Validation {
+            source: Box::new(Call(Cause(cause))),
+            description: "Validation Error".to_owned(),
+        }
+    }
+
+    #[test]
+    fn only_lost_devices_restart_the_renderer() {
+        assert!(is_device_lost(&validation("Parent device is lost")));
+        assert!(is_device_lost(&wgpu::Error::OutOfMemory {
+            source: Box::new(std::fmt::Error),
+        }));
+        assert!(!is_device_lost(&validation(
+            "Buffer usage MAP_READ | STORAGE is not valid"
+        )));
+    }
+
+    #[test]
+    fn frames_leave_no_pointer_state() {
+        let mut app = MyApp::new(config::Config::default(), crate::theme::Theme::builtin());
+        app.add_animation(AnimationBuilder::at((100, 100)).build().animation);
+        let context = egui::Context::default();
+        let start = Instant::now();
+        for _ in 0..3 {
+            app.frame(
+                &context,
+                overlay_input(PhysicalSize::new(800, 600), start),
+                None,
+            );
+        }
+        assert!(context.input(|i| i.pointer.latest_pos().is_none() && !i.pointer.any_down()));
+        assert!(!context.wants_pointer_input());
+    }
+}
@@ -0,0 +1,231 @@
+use crate::config;
+use crate::schedule::{self, Moment};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Active,
+    /// Paused until "Resume" is picked.
+    Paused,
+    /// Paused until the deadline, then resumes on its own.
+    PausedUntil(Instant),
+    /// Outside the scheduled hours.
+    Scheduled,
+}
+
+#[derive(Debug, Default)]
+struct State {
+    manual: bool,
+    until: Option<Instant>,
+    /// "Resume" outside the scheduled hours, which holds until the next time
+    /// the schedule switches.
+    schedule_override: Option<Instant>,
+}
+
+/// Do-not-disturb switch shared by the tray and the listener.
+///
+/// Pauses stack and the most restrictive one wins: a manual pause outlasts any
+/// timed one, and a shorter timed pause never cuts a longer one short. Only
+/// "Resume" lifts all of them at once.
+///
+/// Outside the hours of [`config::Schedule`] pings pause too, checked each
+/// time the status is asked for. "Resume" overrides that until the schedule
+/// switches next.
+#[derive(Debug, Default)]
+pub struct Pause {
+    state: Mutex<State>,
+    schedule: Option<config::Schedule>,
+}
+
+impl Pause {
+    pub fn new(schedule: &config::Schedule) -> Self {
+        Self {
+            state: Default::default(),
+            schedule: schedule.enabled.then(|| schedule.clone()),
+        }
+    }
+
+    pub fn pause(&self) {
+        self.state.lock().unwrap().manual = true;
+    }
+
+    pub fn pause_for(&self, duration: Duration) {
+        let deadline = Instant::now() + duration;
+        let mut state = self.state.lock().unwrap();
+        state.until = Some(state.until.map_or(deadline, |until| until.max(deadline)));
+    }
+
+    pub fn resume(&self) {
+        self.resume_at(Instant::now(), Moment::now());
+    }
+
+    fn resume_at(&self, now: Instant, moment: Moment) {
+        let mut state = self.state.lock().unwrap();
+        *state = State::default();
+        if let Some(schedule) = &self.schedule {
+            if !schedule::within(schedule, moment) {
+                let left = schedule::until_change(schedule, moment).unwrap_or(Duration::MAX);
+                state.schedule_override = now.checked_add(left);
+                log::info!("resumed outside the scheduled hours, for {left:?}");
+            }
+        }
+    }
+
+    pub fn status(&self) -> Status {
+        self.status_at(Instant::now(), Moment::now)
+    }
+
+    /// `moment` is only read with a schedule to check.
+    fn status_at(&self, now: Instant, moment: impl FnOnce() -> Moment) -> Status {
+        let mut state = self.state.lock().unwrap();
+        if state.until.is_some_and(|until| until <= now) {
+            state.until = None;
+        }
+        if state.schedule_override.is_some_and(|until| until <= now) {
+            state.schedule_override = None;
+        }
+        let off_hours = || {
+            self.schedule
+                .as_ref()
+                .is_some_and(|schedule| !schedule::within(schedule, moment()))
+        };
+        match *state {
+            State { manual: true, .. } => Status::Paused,
+            State {
+                until: Some(until), ..
+            } => Status::PausedUntil(until),
+            State {
+                schedule_override: None,
+                ..
+            } if off_hours() => Status::Scheduled,
+            _ => Status::Active,
+        }
+    }
+
+    /// Time until the schedule switches pings on or off, `None` without one
+    /// or if it never does.
+    pub fn until_schedule_change(&self) -> Option<Duration> {
+        schedule::until_change(self.schedule.as_ref()?, Moment::now())
+    }
+
+    /// Clock time the schedule switches pings back on.
+    pub fn schedule_resumes_at(&self) -> Option<schedule::ClockTime> {
+        schedule::resumes_at(self.schedule.as_ref()?, Moment::now())
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.status() != Status::Active
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schedule::Weekday;
+
+    /// Weekdays 09:00 to 18:00.
+    fn work_hours() -> Pause {
+        Pause::new(&config::Schedule {
+            enabled: true,
+            ..Default::default()
+        })
+    }
+
+    fn at(weekday: Weekday, hours: u16, minutes: u16) -> Moment {
+        Moment {
+            weekday,
+            minute: hours * 60 + minutes,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn schedule_pauses_outside_its_hours() {
+        let pause = work_hours();
+        let now = Instant::now();
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 8, 59)),
+            Status::Scheduled
+        );
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 9, 0)),
+            Status::Active
+        );
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 17, 59)),
+            Status::Active
+        );
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 18, 0)),
+            Status::Scheduled
+        );
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Sat, 12, 0)),
+            Status::Scheduled
+        );
+    }
+
+    #[test]
+    fn disabled_schedule_never_pauses() {
+        let pause = Pause::new(&config::Schedule::default());
+        let status = pause.status_at(Instant::now(), || panic!("no schedule to check"));
+        assert_eq!(status, Status::Active);
+    }
+
+    #[test]
+    fn resume_overrides_the_schedule_until_it_switches() {
+        let pause = work_hours();
+        let now = Instant::now();
+        let evening = at(Weekday::Mon, 20, 0);
+        pause.resume_at(now, evening);
+        assert_eq!(pause.status_at(now, || evening), Status::Active);
+        // NOTE: the schedule switches on at 09:00 Tuesday, 13 hours later
+        let before = now + Duration::from_secs(13 * 3600 - 1);
+        assert_eq!(pause.status_at(before, || evening), Status::Active);
+        let after = now + Duration::from_secs(13 * 3600);
+        let tuesday_evening = at(Weekday::Tue, 18, 0);
+        assert_eq!(
+            pause.status_at(after, || tuesday_evening),
+            Status::Scheduled
+        );
+    }
+
+    #[test]
+    fn resume_within_the_hours_leaves_no_override() {
+        let pause = work_hours();
+        let now = Instant::now();
+        pause.resume_at(now, at(Weekday::Mon, 12, 0));
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 19, 0)),
+            Status::Scheduled
+        );
+    }
+
+    #[test]
+    fn manual_pause_outranks_the_schedule() {
+        let pause = work_hours();
+        let now = Instant::now();
+        pause.pause();
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Sun, 3, 0)),
+            Status::Paused
+        );
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Mon, 12, 0)),
+            Status::Paused
+        );
+    }
+
+    #[test]
+    fn pause_after_an_override_still_pauses() {
+        let pause = work_hours();
+        let now = Instant::now();
+        pause.resume_at(now, at(Weekday::Sat, 12, 0));
+        pause.pause();
+        assert_eq!(
+            pause.status_at(now, || at(Weekday::Sat, 12, 0)),
+            Status::Paused
+        );
+    }
+}
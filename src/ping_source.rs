@@ -0,0 +1,61 @@
+use crate::i18n::t;
+use std::sync::Arc;
+
+/// Where a ping came from. Only local pings are drawn as plain artwork.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash)]
+pub enum PingSource {
+    #[default]
+    Local,
+    /// Sent through the control pipe by a client that gave its name, e.g. a
+    /// relay for another machine.
+    Remote { peer: Arc<str> },
+    /// Sent through the control pipe without a name, see [`crate::ipc`].
+    Ipc,
+}
+
+impl PingSource {
+    /// Name shown next to the ping and in the legend, `None` for local pings.
+    pub fn label(&self) -> Option<&str> {
+        match self {
+            PingSource::Local => None,
+            PingSource::Remote { peer } => Some(peer),
+            PingSource::Ipc => Some("IPC"),
+        }
+    }
+}
+
+/// Picks a palette color for `peer`. Stable across runs and machines, so
+/// everyone sees the same peer in the same color.
+pub fn peer_color(peer: &str, palette: &[[u8; 4]]) -> egui::Color32 {
+    let Some(len) = std::num::NonZeroUsize::new(palette.len()) else {
+        return egui::Color32::WHITE;
+    };
+
+    // NOTE: FNV-1a, std's hasher is not guaranteed to be stable between releases
+    let hash = peer.bytes().fold(0xcbf29ce484222325u64, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(0x100000001b3)
+    });
+    let [r, g, b, a] = palette[(hash % len.get() as u64) as usize];
+    egui::Color32::from_rgba_unmultiplied(r, g, b, a)
+}
+
+/// Peer to color legend. Returns `true` when the user dismissed it.
+pub fn legend_ui(ctx: &egui::Context, peers: &[(String, egui::Color32)]) -> bool {
+    let mut close = false;
+    egui::CentralPanel::default().show(ctx, |ui| {
+        ui.heading(t("legend.heading"));
+        ui.add_space(8.0);
+        if peers.is_empty() {
+            ui.label(t("legend.empty"));
+        }
+        for (peer, color) in peers {
+            ui.horizontal(|ui| {
+                ui.colored_label(*color, "⬤");
+                ui.label(peer);
+            });
+        }
+        ui.add_space(8.0);
+        close = ui.button(t("dialog.close")).clicked();
+    });
+    close
+}
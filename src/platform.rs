@@ -0,0 +1,1249 @@
+use raw_window_handle::RawWindowHandle;
+use windows::core::{HSTRING, PCWSTR};
+use windows::Win32::Foundation::{CloseHandle, HANDLE, HWND};
+use windows::Win32::Security::{
+    GetSidSubAuthority, GetSidSubAuthorityCount, GetTokenInformation, TokenIntegrityLevel,
+    TOKEN_MANDATORY_LABEL, TOKEN_QUERY,
+};
+use windows::Win32::System::Threading::{
+    GetCurrentProcess, OpenProcess, OpenProcessToken, PROCESS_QUERY_LIMITED_INFORMATION,
+};
+use windows::Win32::UI::Shell::ShellExecuteW;
+use windows::Win32::UI::WindowsAndMessaging::{
+    GetForegroundWindow, GetWindowThreadProcessId, MessageBoxW, IDYES, MB_ICONWARNING,
+    MB_SETFOREGROUND, MB_TOPMOST, MB_YESNO, SW_SHOWNORMAL, WINDOW_EX_STYLE,
+};
+
+/// Extended styles that keep the overlay click-through, out of the taskbar and
+/// from ever taking keyboard focus.
+fn overlay_ex_style() -> WINDOW_EX_STYLE {
+    WINDOW_EX_STYLE(0)
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LAYERED
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LEFT
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_LTRREADING
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TOPMOST
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TRANSPARENT
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_WINDOWEDGE
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_TOOLWINDOW
+        | windows::Win32::UI::WindowsAndMessaging::WS_EX_NOACTIVATE
+}
+
+/// Answers clicks on hit-testable parts of the overlay without activating it.
+unsafe extern "system" fn no_activate_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: windows::Win32::Foundation::WPARAM,
+    lparam: windows::Win32::Foundation::LPARAM,
+    _subclass: usize,
+    _data: usize,
+) -> windows::Win32::Foundation::LRESULT {
+    use windows::Win32::Foundation::LRESULT;
+    use windows::Win32::UI::Shell::DefSubclassProc;
+    use windows::Win32::UI::WindowsAndMessaging::{MA_NOACTIVATE, WM_MOUSEACTIVATE};
+
+    if message == WM_MOUSEACTIVATE {
+        return LRESULT(MA_NOACTIVATE as _);
+    }
+    DefSubclassProc(hwnd, message, wparam, lparam)
+}
+
+fn hwnd(window_handle: RawWindowHandle) -> HWND {
+    let RawWindowHandle::Win32(raw_handle) = window_handle else {
+        panic!("Unsupported platform!");
+    };
+    HWND(raw_handle.hwnd as _)
+}
+
+pub unsafe fn hide_taskbar_entry(window_handle: RawWindowHandle) {
+    let index = windows::Win32::UI::WindowsAndMessaging::GWL_EXSTYLE;
+    let style = overlay_ex_style();
+
+    windows::Win32::UI::WindowsAndMessaging::SetWindowLongPtrA(
+        hwnd(window_handle),
+        index,
+        style.0 as _,
+    );
+    // NOTE: WS_EX_NOACTIVATE alone still lets a click activate a window that takes
+    // hits, which parts of the overlay may once it has interactive elements
+    let installed = windows::Win32::UI::Shell::SetWindowSubclass(
+        hwnd(window_handle),
+        Some(no_activate_proc),
+        1,
+        0,
+    );
+    if !installed.as_bool() {
+        log::warn!("failed to stop the overlay from activating on click");
+    }
+}
+
+/// Keeps the overlay topmost, or with `below` just under that window so it
+/// stays above the overlay.
+pub unsafe fn set_overlay_order(window_handle: RawWindowHandle, below: Option<HWND>) {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        SetWindowPos, HWND_TOPMOST, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+    };
+
+    let after = below.unwrap_or(HWND_TOPMOST);
+    let flags = SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE;
+    if let Err(e) = SetWindowPos(hwnd(window_handle), after, 0, 0, 0, 0, flags) {
+        log::warn!("failed to reorder the overlay: {e}");
+    }
+}
+
+/// The bottommost visible top-level window whose class or process executable
+/// is one of `names`, compared without case.
+pub fn lowest_window_matching(names: &[String]) -> Option<HWND> {
+    use windows::Win32::Foundation::{BOOL, LPARAM};
+    use windows::Win32::UI::WindowsAndMessaging::{EnumWindows, GetClassNameW, IsWindowVisible};
+
+    struct Search<'a> {
+        names: &'a [String],
+        found: Option<HWND>,
+    }
+
+    unsafe extern "system" fn visit(window: HWND, search: LPARAM) -> BOOL {
+        let search = &mut *(search.0 as *mut Search);
+        if !IsWindowVisible(window).as_bool() {
+            return true.into();
+        }
+        let mut class = [0u16; 256];
+        let length = GetClassNameW(window, &mut class);
+        let class = String::from_utf16_lossy(&class[..length.max(0) as usize]);
+        let mut pid = 0;
+        GetWindowThreadProcessId(window, Some(&mut pid));
+        let process = process_name(pid);
+        let matches = |name: &String| {
+            name.eq_ignore_ascii_case(&class)
+                || process
+                    .as_deref()
+                    .is_some_and(|process| name.eq_ignore_ascii_case(process))
+        };
+        // NOTE: enumerated top to bottom, the last match is the lowest
+        if search.names.iter().any(matches) {
+            search.found = Some(window);
+        }
+        true.into()
+    }
+
+    let mut search = Search { names, found: None };
+    unsafe { EnumWindows(Some(visit), LPARAM(&mut search as *mut Search as _)) }.ok()?;
+    search.found
+}
+
+/// File name of a process' executable, e.g. `Magnify.exe`.
+fn process_name(pid: u32) -> Option<String> {
+    use windows::core::PWSTR;
+    use windows::Win32::System::Threading::{QueryFullProcessImageNameW, PROCESS_NAME_WIN32};
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let mut path = [0u16; 1024];
+        let mut length = path.len() as u32;
+        let queried = QueryFullProcessImageNameW(
+            process,
+            PROCESS_NAME_WIN32,
+            PWSTR(path.as_mut_ptr()),
+            &mut length,
+        );
+        CloseHandle(process).ok();
+        queried.ok()?;
+        let path = String::from_utf16_lossy(&path[..length as usize]);
+        path.rsplit('\\').next().map(str::to_owned)
+    }
+}
+
+thread_local! {
+    static ON_WINDOWS_CHANGE: std::cell::RefCell<Option<Box<dyn Fn()>>> = Default::default();
+}
+
+/// Calls `on_change` whenever the foreground window changes or a top-level
+/// window is shown or hidden, through a WinEvent hook rather than polling.
+pub fn watch_windows(on_change: impl Fn() + Send + 'static) {
+    use windows::Win32::UI::Accessibility::SetWinEventHook;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        DispatchMessageW, GetMessageW, EVENT_OBJECT_HIDE, EVENT_OBJECT_SHOW,
+        EVENT_SYSTEM_FOREGROUND, MSG, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS,
+    };
+
+    // NOTE: out-of-context hooks are called on the thread that set them, from its
+    // message loop
+    std::thread::spawn(move || unsafe {
+        ON_WINDOWS_CHANGE.with(|callback| *callback.borrow_mut() = Some(Box::new(on_change)));
+        let flags = WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS;
+        let hooks = [
+            (EVENT_SYSTEM_FOREGROUND, EVENT_SYSTEM_FOREGROUND),
+            (EVENT_OBJECT_SHOW, EVENT_OBJECT_HIDE),
+        ]
+        .map(|(min, max)| SetWinEventHook(min, max, None, Some(windows_change_proc), 0, 0, flags));
+        if hooks.iter().any(|hook| hook.is_invalid()) {
+            log::warn!("failed to watch for window changes, the overlay stays topmost");
+            return;
+        }
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, None, 0, 0).as_bool() {
+            DispatchMessageW(&message);
+        }
+    });
+}
+
+unsafe extern "system" fn windows_change_proc(
+    _hook: windows::Win32::UI::Accessibility::HWINEVENTHOOK,
+    _event: u32,
+    window: HWND,
+    object: i32,
+    child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetAncestor, CHILDID_SELF, GA_ROOT, OBJID_WINDOW,
+    };
+
+    // NOTE: show and hide fire for every caret and control, only top-level windows matter
+    if object != OBJID_WINDOW.0
+        || child != CHILDID_SELF as i32
+        || GetAncestor(window, GA_ROOT) != window
+    {
+        return;
+    }
+    ON_WINDOWS_CHANGE.with(|callback| {
+        if let Some(callback) = &*callback.borrow() {
+            callback();
+        }
+    });
+}
+
+/// Whether the overlay is the foreground window, which it never should be.
+pub unsafe fn is_foreground(window_handle: RawWindowHandle) -> bool {
+    GetForegroundWindow() == hwnd(window_handle)
+}
+
+/// The shell's documented virtual desktop API. Windows has no public way to
+/// pin a window to every desktop, tool windows like the overlay usually are
+/// anyway; where they aren't, the overlay is moved after each switch.
+pub struct VirtualDesktops(windows::Win32::UI::Shell::IVirtualDesktopManager);
+
+impl VirtualDesktops {
+    /// Connects to the manager, which lives in explorer.
+    pub fn new() -> windows::core::Result<Self> {
+        use windows::Win32::System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED,
+        };
+        use windows::Win32::UI::Shell::VirtualDesktopManager;
+
+        unsafe {
+            // NOTE: winit sets OLE up on the event loop thread, this only covers being first
+            CoInitializeEx(None, COINIT_APARTMENTTHREADED).ok();
+            Ok(Self(CoCreateInstance(
+                &VirtualDesktopManager,
+                None,
+                CLSCTX_ALL,
+            )?))
+        }
+    }
+
+    /// Moves the window to the desktop being shown if it's not on it.
+    /// Returns `true` if it was moved.
+    pub unsafe fn follow(&self, window_handle: RawWindowHandle) -> windows::core::Result<bool> {
+        let window = hwnd(window_handle);
+        if self.0.IsWindowOnCurrentVirtualDesktop(window)?.as_bool() {
+            return Ok(false);
+        }
+        // NOTE: nothing tells the shown desktop, the foreground window is on it;
+        // the taskbar or desktop in front belong to none, wait for another
+        let desktop = match self.0.GetWindowDesktopId(GetForegroundWindow()) {
+            Ok(desktop) if desktop != windows::core::GUID::zeroed() => desktop,
+            _ => return Ok(false),
+        };
+        self.0.MoveWindowToDesktop(window, &desktop)?;
+        Ok(true)
+    }
+}
+
+/// Whether the styles set by `hide_taskbar_entry` are still in place. A driver
+/// reset or DWM restart can recreate the window without them.
+pub unsafe fn overlay_styles_intact(window_handle: RawWindowHandle) -> bool {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        GetWindowLongPtrA, GWL_EXSTYLE, WS_EX_APPWINDOW, WS_EX_LAYERED, WS_EX_NOACTIVATE,
+        WS_EX_TOOLWINDOW, WS_EX_TRANSPARENT,
+    };
+
+    let style = WINDOW_EX_STYLE(GetWindowLongPtrA(hwnd(window_handle), GWL_EXSTYLE) as _);
+    let required = WS_EX_LAYERED | WS_EX_TRANSPARENT | WS_EX_TOOLWINDOW | WS_EX_NOACTIVATE;
+    style.contains(required) && !style.contains(WS_EX_APPWINDOW)
+}
+
+/// Mandatory integrity level RID of a process token (e.g. 0x2000 medium, 0x3000 high).
+unsafe fn process_integrity(process: HANDLE) -> Option<u32> {
+    let mut token = HANDLE::default();
+    OpenProcessToken(process, TOKEN_QUERY, &mut token).ok()?;
+
+    let mut len = 0;
+    // NOTE: first call only reports the required buffer size
+    let _ = GetTokenInformation(token, TokenIntegrityLevel, None, 0, &mut len);
+    let mut buffer = vec![0u8; len as usize];
+    let result = GetTokenInformation(
+        token,
+        TokenIntegrityLevel,
+        Some(buffer.as_mut_ptr() as _),
+        len,
+        &mut len,
+    );
+    CloseHandle(token).ok();
+    result.ok()?;
+
+    let label = &*(buffer.as_ptr() as *const TOKEN_MANDATORY_LABEL);
+    let count = *GetSidSubAuthorityCount(label.Label.Sid);
+    Some(*GetSidSubAuthority(label.Label.Sid, count as u32 - 1))
+}
+
+pub fn own_integrity() -> Option<u32> {
+    // SAFETY: the pseudo handle from `GetCurrentProcess` needs no cleanup
+    unsafe { process_integrity(GetCurrentProcess()) }
+}
+
+pub fn is_elevated() -> bool {
+    const SECURITY_MANDATORY_HIGH_RID: u32 = 0x3000;
+    matches!(own_integrity(), Some(level) if level >= SECURITY_MANDATORY_HIGH_RID)
+}
+
+/// Integrity level of the process owning the foreground window, if it can be queried.
+pub fn foreground_integrity() -> Option<u32> {
+    unsafe {
+        let hwnd = GetForegroundWindow();
+        if hwnd.0 == 0 {
+            return None;
+        }
+        let mut pid = 0;
+        GetWindowThreadProcessId(hwnd, Some(&mut pid));
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+        let integrity = process_integrity(process);
+        CloseHandle(process).ok();
+        integrity
+    }
+}
+
+/// Asks the user a yes/no question with a topmost message box. Blocks until answered.
+pub fn ask_yes_no(caption: &str, text: &str) -> bool {
+    let caption = HSTRING::from(caption);
+    let text = HSTRING::from(text);
+    let answer = unsafe {
+        MessageBoxW(
+            HWND(0),
+            &text,
+            &caption,
+            MB_YESNO | MB_ICONWARNING | MB_TOPMOST | MB_SETFOREGROUND,
+        )
+    };
+    answer == IDYES
+}
+
+/// Starts a new elevated instance of this executable. Returns `false` if the
+/// user declined the UAC prompt or the launch failed.
+pub fn relaunch_elevated() -> bool {
+    let Ok(exe) = std::env::current_exe() else {
+        return false;
+    };
+    let exe = HSTRING::from(exe.to_string_lossy().as_ref());
+    let args = HSTRING::from(std::env::args().skip(1).collect::<Vec<_>>().join(" "));
+    // NOTE: assets are loaded relative to the working directory so keep it
+    let cwd = std::env::current_dir()
+        .map(|dir| HSTRING::from(dir.to_string_lossy().as_ref()))
+        .unwrap_or_default();
+
+    let instance = unsafe {
+        ShellExecuteW(
+            HWND(0),
+            &HSTRING::from("runas"),
+            &exe,
+            &args,
+            if cwd.is_empty() {
+                PCWSTR::null()
+            } else {
+                PCWSTR(cwd.as_ptr())
+            },
+            SW_SHOWNORMAL,
+        )
+    };
+    // NOTE: ShellExecute reports success with values greater than 32
+    instance.0 > 32
+}
+
+/// Starts a new instance of this executable with the same arguments, e.g.
+/// to pick up settings only read at startup. Returns `false` if it failed.
+pub fn relaunch() -> bool {
+    let launched = std::env::current_exe().and_then(|exe| {
+        std::process::Command::new(exe)
+            .args(std::env::args_os().skip(1))
+            .spawn()
+    });
+    match launched {
+        Ok(_) => true,
+        Err(e) => {
+            log::warn!("failed to relaunch: {e}");
+            false
+        }
+    }
+}
+
+/// Runs `command` through `cmd /C` without waiting for it or flashing a
+/// console window.
+pub fn run_hidden(command: &str) -> std::io::Result<std::process::Child> {
+    use std::os::windows::process::CommandExt;
+    use windows::Win32::System::Threading::CREATE_NO_WINDOW;
+
+    std::process::Command::new("cmd")
+        .args(["/C", command])
+        .creation_flags(CREATE_NO_WINDOW.0)
+        .spawn()
+}
+
+/// Average relative luminance (0 = black, 1 = white) of a `size`x`size` screen
+/// region centered on `center`, captured with GDI.
+pub fn average_luminance(center: (i32, i32), size: i32) -> Option<f32> {
+    use windows::Win32::Graphics::Gdi::{
+        BitBlt, CreateCompatibleBitmap, CreateCompatibleDC, DeleteDC, DeleteObject, GetDC,
+        GetDIBits, ReleaseDC, SelectObject, BITMAPINFO, BITMAPINFOHEADER, BI_RGB, DIB_RGB_COLORS,
+        SRCCOPY,
+    };
+
+    unsafe {
+        let screen = GetDC(HWND(0));
+        let memory = CreateCompatibleDC(screen);
+        let bitmap = CreateCompatibleBitmap(screen, size, size);
+        let previous = SelectObject(memory, bitmap);
+
+        // NOTE: without CAPTUREBLT layered windows, like our overlay, are left out
+        let copied = BitBlt(
+            memory,
+            0,
+            0,
+            size,
+            size,
+            screen,
+            center.0 - size / 2,
+            center.1 - size / 2,
+            SRCCOPY,
+        );
+
+        let mut info = BITMAPINFO {
+            bmiHeader: BITMAPINFOHEADER {
+                biSize: std::mem::size_of::<BITMAPINFOHEADER>() as u32,
+                biWidth: size,
+                // NOTE: negative height requests a top-down bitmap
+                biHeight: -size,
+                biPlanes: 1,
+                biBitCount: 32,
+                biCompression: BI_RGB.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let mut pixels = vec![0u8; (size * size * 4) as usize];
+        SelectObject(memory, previous);
+        let lines = GetDIBits(
+            memory,
+            bitmap,
+            0,
+            size as u32,
+            Some(pixels.as_mut_ptr() as _),
+            &mut info,
+            DIB_RGB_COLORS,
+        );
+
+        DeleteObject(bitmap);
+        DeleteDC(memory);
+        ReleaseDC(HWND(0), screen);
+
+        if copied.is_err() || lines == 0 {
+            return None;
+        }
+
+        // NOTE: pixels are BGRA
+        let total: f32 = pixels
+            .chunks_exact(4)
+            .map(|px| 0.0722 * px[0] as f32 + 0.7152 * px[1] as f32 + 0.2126 * px[2] as f32)
+            .sum();
+        Some(total / (size * size) as f32 / 255.0)
+    }
+}
+
+/// Friendly monitor names from their EDID, e.g. "DELL U2720Q", keyed by the
+/// GDI device name winit reports (`\\.\DISPLAY1`). Empty if Windows won't say.
+pub fn monitor_names() -> std::collections::HashMap<String, String> {
+    use windows::Win32::Devices::Display::{
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QueryDisplayConfig,
+        DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+        DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO,
+        DISPLAYCONFIG_SOURCE_DEVICE_NAME, DISPLAYCONFIG_TARGET_DEVICE_NAME, QDC_ONLY_ACTIVE_PATHS,
+    };
+
+    let mut names = std::collections::HashMap::new();
+    let (mut path_count, mut mode_count) = (0, 0);
+    if unsafe {
+        GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+    }
+    .is_err()
+    {
+        return names;
+    }
+    let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+    let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+    let queried = unsafe {
+        QueryDisplayConfig(
+            QDC_ONLY_ACTIVE_PATHS,
+            &mut path_count,
+            paths.as_mut_ptr(),
+            &mut mode_count,
+            modes.as_mut_ptr(),
+            None,
+        )
+    };
+    if let Err(e) = queried {
+        log::warn!("failed to query the display configuration: {e}");
+        return names;
+    }
+    paths.truncate(path_count as usize);
+
+    for path in paths {
+        let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                size: std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+                adapterId: path.sourceInfo.adapterId,
+                id: path.sourceInfo.id,
+            },
+            ..Default::default()
+        };
+        let mut target = DISPLAYCONFIG_TARGET_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+                size: std::mem::size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32,
+                adapterId: path.targetInfo.adapterId,
+                id: path.targetInfo.id,
+            },
+            ..Default::default()
+        };
+        let found = unsafe {
+            DisplayConfigGetDeviceInfo(&mut source.header) == 0
+                && DisplayConfigGetDeviceInfo(&mut target.header) == 0
+        };
+        let text = |wide: &[u16]| {
+            let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+            String::from_utf16_lossy(&wide[..len])
+        };
+        let name = text(&target.monitorFriendlyDeviceName);
+        // NOTE: internal panels and some adapters report no EDID name
+        if found && !name.is_empty() {
+            names.insert(text(&source.viewGdiDeviceName), name);
+        }
+    }
+    names
+}
+
+pub fn composition_enabled() -> bool {
+    use windows::Win32::Graphics::Dwm::DwmIsCompositionEnabled;
+
+    unsafe { DwmIsCompositionEnabled() }.is_ok_and(|enabled| enabled.as_bool())
+}
+
+/// A value of the Windows color settings, `None` when unset.
+fn personalize_setting(name: &str) -> Option<u32> {
+    use windows::Win32::System::Registry::{RegGetValueW, HKEY_CURRENT_USER, RRF_RT_REG_DWORD};
+
+    let mut value = 0u32;
+    let mut size = std::mem::size_of::<u32>() as u32;
+    unsafe {
+        RegGetValueW(
+            HKEY_CURRENT_USER,
+            &HSTRING::from(r"Software\Microsoft\Windows\CurrentVersion\Themes\Personalize"),
+            &HSTRING::from(name),
+            RRF_RT_REG_DWORD,
+            None,
+            Some(&mut value as *mut u32 as _),
+            Some(&mut size),
+        )
+    }
+    .ok()?;
+    Some(value)
+}
+
+/// Whether "Transparency effects" is on in the Windows color settings. Unset
+/// counts as on, as Windows treats it.
+pub fn transparency_effects_enabled() -> bool {
+    personalize_setting("EnableTransparency") != Some(0)
+}
+
+/// Whether the taskbar and tray are light. Unset, as before Windows 10 1903,
+/// they're dark.
+pub fn system_uses_light_theme() -> bool {
+    personalize_setting("SystemUsesLightTheme").is_some_and(|light| light != 0)
+}
+
+/// Side of a small icon such as the tray's, in pixels at the primary
+/// monitor's scale once the process is DPI aware.
+pub fn small_icon_size() -> u32 {
+    use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_CXSMICON};
+
+    match unsafe { GetSystemMetrics(SM_CXSMICON) } {
+        size if size > 0 => size as u32,
+        _ => 16,
+    }
+}
+
+thread_local! {
+    static ON_SETTING_CHANGE: std::cell::RefCell<Option<Box<dyn Fn()>>> = Default::default();
+}
+
+/// Calls `on_change` after every `WM_SETTINGCHANGE` and `WM_DISPLAYCHANGE`,
+/// e.g. when the light/dark theme or the scale changes.
+// NOTE: broadcasts skip message-only windows, so this is a hidden top-level one
+// on a thread of its own
+pub fn watch_setting_changes(on_change: impl Fn() + Send + 'static) {
+    use windows::core::w;
+    use windows::Win32::UI::WindowsAndMessaging::{
+        CreateWindowExW, DispatchMessageW, GetMessageW, RegisterClassW, MSG, WINDOW_STYLE,
+        WNDCLASSW,
+    };
+
+    std::thread::spawn(move || unsafe {
+        ON_SETTING_CHANGE.with(|callback| *callback.borrow_mut() = Some(Box::new(on_change)));
+        let class = WNDCLASSW {
+            lpfnWndProc: Some(setting_change_proc),
+            lpszClassName: w!("ScreenPingerSettingWatcher"),
+            ..Default::default()
+        };
+        if RegisterClassW(&class) == 0 {
+            log::warn!(
+                "failed to watch for setting changes: {}",
+                windows::core::Error::from_win32()
+            );
+            return;
+        }
+        let window = CreateWindowExW(
+            WINDOW_EX_STYLE(0),
+            class.lpszClassName,
+            w!(""),
+            WINDOW_STYLE(0),
+            0,
+            0,
+            0,
+            0,
+            None,
+            None,
+            None,
+            None,
+        );
+        if window.0 == 0 {
+            log::warn!(
+                "failed to watch for setting changes: {}",
+                windows::core::Error::from_win32()
+            );
+            return;
+        }
+        let mut message = MSG::default();
+        while GetMessageW(&mut message, None, 0, 0).as_bool() {
+            DispatchMessageW(&message);
+        }
+    });
+}
+
+unsafe extern "system" fn setting_change_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: windows::Win32::Foundation::WPARAM,
+    lparam: windows::Win32::Foundation::LPARAM,
+) -> windows::Win32::Foundation::LRESULT {
+    use windows::Win32::UI::WindowsAndMessaging::{
+        DefWindowProcW, WM_DISPLAYCHANGE, WM_SETTINGCHANGE,
+    };
+
+    if message == WM_SETTINGCHANGE || message == WM_DISPLAYCHANGE {
+        ON_SETTING_CHANGE.with(|callback| {
+            if let Some(callback) = &*callback.borrow() {
+                callback();
+            }
+        });
+    }
+    DefWindowProcW(hwnd, message, wparam, lparam)
+}
+
+/// Whether this is a Remote Desktop session, where the overlay tends to show
+/// up black even with composition on.
+pub fn is_remote_session() -> bool {
+    use windows::Win32::UI::WindowsAndMessaging::{GetSystemMetrics, SM_REMOTESESSION};
+
+    unsafe { GetSystemMetrics(SM_REMOTESESSION) != 0 }
+}
+
+/// Styles a per-ping window of the opaque fallback like the overlay, but fully
+/// opaque: a layered window is invisible until it's given an alpha.
+pub unsafe fn make_spot_window(window_handle: RawWindowHandle) {
+    use windows::Win32::Foundation::COLORREF;
+    use windows::Win32::UI::WindowsAndMessaging::{SetLayeredWindowAttributes, LWA_ALPHA};
+
+    hide_taskbar_entry(window_handle);
+    if let Err(e) = SetLayeredWindowAttributes(hwnd(window_handle), COLORREF(0), 255, LWA_ALPHA) {
+        log::warn!("failed to make a ping window opaque: {e}");
+    }
+}
+
+/// Whether Windows wants notifications held back right now: Focus Assist quiet
+/// hours, a busy or full-screen app, or presentation mode.
+pub fn notifications_suppressed() -> bool {
+    use windows::Win32::UI::Shell::{
+        SHQueryUserNotificationState, QUNS_BUSY, QUNS_PRESENTATION_MODE, QUNS_QUIET_TIME,
+        QUNS_RUNNING_D3D_FULL_SCREEN,
+    };
+
+    let Ok(state) = (unsafe { SHQueryUserNotificationState() }) else {
+        return false;
+    };
+    [
+        QUNS_BUSY,
+        QUNS_RUNNING_D3D_FULL_SCREEN,
+        QUNS_PRESENTATION_MODE,
+        QUNS_QUIET_TIME,
+    ]
+    .contains(&state)
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PowerStatus {
+    pub on_battery: bool,
+    /// `None` without a battery or when Windows can't tell.
+    pub battery_percent: Option<u8>,
+    /// Windows battery saver is on.
+    pub saver: bool,
+}
+
+/// Where the power comes from, `None` when Windows won't say.
+pub fn power_status() -> Option<PowerStatus> {
+    use windows::Win32::System::Power::{GetSystemPowerStatus, SYSTEM_POWER_STATUS};
+
+    let mut status = SYSTEM_POWER_STATUS::default();
+    unsafe { GetSystemPowerStatus(&mut status) }.ok()?;
+    // NOTE: 255 is "unknown" for both fields, 128 in BatteryFlag "no battery"
+    Some(PowerStatus {
+        on_battery: status.ACLineStatus == 0,
+        battery_percent: (status.BatteryFlag & 128 == 0 && status.BatteryLifePercent <= 100)
+            .then_some(status.BatteryLifePercent),
+        saver: status.SystemStatusFlag == 1,
+    })
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Edge {
+    Left,
+    Top,
+    Right,
+    Bottom,
+}
+
+/// Docked edge and `(left, top, right, bottom)` rect of the primary taskbar.
+/// `None` when it auto-hides or can't be queried.
+pub fn taskbar_position() -> Option<(Edge, (i32, i32, i32, i32))> {
+    use windows::Win32::UI::Shell::{
+        SHAppBarMessage, ABE_BOTTOM, ABE_LEFT, ABE_RIGHT, ABE_TOP, ABM_GETSTATE, ABM_GETTASKBARPOS,
+        ABS_AUTOHIDE, APPBARDATA,
+    };
+
+    let mut data = APPBARDATA {
+        cbSize: std::mem::size_of::<APPBARDATA>() as u32,
+        ..Default::default()
+    };
+    unsafe {
+        // NOTE: an auto-hidden taskbar only covers the screen while in use
+        if SHAppBarMessage(ABM_GETSTATE, &mut data) as u32 & ABS_AUTOHIDE != 0 {
+            return None;
+        }
+        if SHAppBarMessage(ABM_GETTASKBARPOS, &mut data) == 0 {
+            return None;
+        }
+    }
+
+    let edge = match data.uEdge {
+        ABE_LEFT => Edge::Left,
+        ABE_TOP => Edge::Top,
+        ABE_RIGHT => Edge::Right,
+        ABE_BOTTOM => Edge::Bottom,
+        _ => return None,
+    };
+    let rc = data.rc;
+    Some((edge, (rc.left, rc.top, rc.right, rc.bottom)))
+}
+
+/// Replaces the clipboard contents with `text`.
+pub fn set_clipboard_text(text: &str) -> windows::core::Result<()> {
+    use windows::Win32::Foundation::GlobalFree;
+    use windows::Win32::System::DataExchange::{
+        CloseClipboard, EmptyClipboard, OpenClipboard, SetClipboardData,
+    };
+    use windows::Win32::System::Memory::{GlobalAlloc, GlobalLock, GlobalUnlock, GMEM_MOVEABLE};
+    use windows::Win32::System::Ole::CF_UNICODETEXT;
+
+    let wide: Vec<u16> = text.encode_utf16().chain(std::iter::once(0)).collect();
+    unsafe {
+        OpenClipboard(HWND(0))?;
+        let result = EmptyClipboard().and_then(|()| {
+            let memory = GlobalAlloc(GMEM_MOVEABLE, wide.len() * 2)?;
+            let buffer = GlobalLock(memory) as *mut u16;
+            if buffer.is_null() {
+                let e = windows::core::Error::from_win32();
+                let _ = GlobalFree(memory);
+                return Err(e);
+            }
+            std::ptr::copy_nonoverlapping(wide.as_ptr(), buffer, wide.len());
+            // NOTE: reports an "error" once the lock count drops to zero
+            let _ = GlobalUnlock(memory);
+
+            // NOTE: the clipboard owns the memory only once this succeeds
+            match SetClipboardData(CF_UNICODETEXT.0 as u32, HANDLE(memory.0 as _)) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    let _ = GlobalFree(memory);
+                    Err(e)
+                }
+            }
+        });
+        CloseClipboard().ok();
+        result
+    }
+}
+
+/// The local day of the week and time.
+pub fn local_moment() -> crate::schedule::Moment {
+    use windows::Win32::System::SystemInformation::GetLocalTime;
+
+    let now = unsafe { GetLocalTime() };
+    crate::schedule::Moment {
+        weekday: crate::schedule::Weekday::from_index(now.wDayOfWeek),
+        minute: now.wHour * 60 + now.wMinute,
+        second: now.wSecond,
+    }
+}
+
+/// Time left until the next local midnight.
+pub fn until_local_midnight() -> std::time::Duration {
+    use windows::Win32::System::SystemInformation::GetLocalTime;
+
+    let now = unsafe { GetLocalTime() };
+    let elapsed = now.wHour as u64 * 3600 + now.wMinute as u64 * 60 + now.wSecond as u64;
+    std::time::Duration::from_secs(24 * 3600 - elapsed)
+}
+
+/// Windows display language as a code like `de-DE`.
+pub fn ui_language() -> Option<String> {
+    use windows::Win32::Globalization::{GetUserDefaultUILanguage, LCIDToLocaleName};
+
+    let mut name = [0u16; 85];
+    let length = unsafe { LCIDToLocaleName(GetUserDefaultUILanguage() as u32, Some(&mut name), 0) };
+    // NOTE: the length counts the terminating null
+    (length > 1).then(|| String::from_utf16_lossy(&name[..length as usize - 1]))
+}
+
+/// Shows a modal error box. Blocks until dismissed.
+pub fn show_error(caption: &str, text: &str) {
+    use windows::Win32::UI::WindowsAndMessaging::{MB_ICONERROR, MB_OK};
+
+    let caption = HSTRING::from(caption);
+    let text = HSTRING::from(text);
+    unsafe {
+        MessageBoxW(
+            HWND(0),
+            &text,
+            &caption,
+            MB_OK | MB_ICONERROR | MB_TOPMOST | MB_SETFOREGROUND,
+        );
+    }
+}
+
+/// Attaches to the console of the process that started this one, so
+/// output of a command line run shows there. `false` when it has none,
+/// e.g. when started from Explorer or a shortcut.
+pub fn attach_parent_console() -> bool {
+    use windows::Win32::Foundation::ERROR_ACCESS_DENIED;
+    use windows::Win32::System::Console::{AttachConsole, ATTACH_PARENT_PROCESS};
+
+    // NOTE: debug builds already have a console, attaching another is refused
+    match unsafe { AttachConsole(ATTACH_PARENT_PROCESS) } {
+        Ok(()) => true,
+        Err(e) => e.code() == ERROR_ACCESS_DENIED.to_hresult(),
+    }
+}
+
+/// Shows a modal information box. Blocks until dismissed.
+pub fn show_info(caption: &str, text: &str) {
+    use windows::Win32::UI::WindowsAndMessaging::{MB_ICONINFORMATION, MB_OK};
+
+    let caption = HSTRING::from(caption);
+    let text = HSTRING::from(text);
+    unsafe {
+        MessageBoxW(
+            HWND(0),
+            &text,
+            &caption,
+            MB_OK | MB_ICONINFORMATION | MB_TOPMOST | MB_SETFOREGROUND,
+        );
+    }
+}
+
+/// Asks for an existing file matching `pattern` with the standard open
+/// dialog, starting in `folder`. Blocks until dismissed; `None` when cancelled.
+pub fn pick_file(
+    description: &str,
+    pattern: &str,
+    folder: &std::path::Path,
+) -> Option<std::path::PathBuf> {
+    use windows::core::PWSTR;
+    use windows::Win32::UI::Controls::Dialogs::{
+        GetOpenFileNameW, OFN_FILEMUSTEXIST, OFN_PATHMUSTEXIST, OPENFILENAMEW,
+    };
+
+    // NOTE: the filter is pairs of NUL-terminated strings ending with an extra NUL
+    let filter: Vec<u16> = format!("{description}\0{pattern}\0\0")
+        .encode_utf16()
+        .collect();
+    let mut file = vec![0u16; 1024];
+    let folder = HSTRING::from(folder.to_string_lossy().as_ref());
+    let mut dialog = OPENFILENAMEW {
+        lStructSize: std::mem::size_of::<OPENFILENAMEW>() as u32,
+        lpstrFilter: PCWSTR(filter.as_ptr()),
+        lpstrInitialDir: PCWSTR(folder.as_ptr()),
+        lpstrFile: PWSTR(file.as_mut_ptr()),
+        nMaxFile: file.len() as u32,
+        Flags: OFN_FILEMUSTEXIST | OFN_PATHMUSTEXIST,
+        ..Default::default()
+    };
+    if !unsafe { GetOpenFileNameW(&mut dialog) }.as_bool() {
+        return None;
+    }
+
+    let len = file.iter().position(|&c| c == 0).unwrap_or(file.len());
+    Some(String::from_utf16_lossy(&file[..len]).into())
+}
+
+/// Virtual key no app or Windows itself reacts to, AutoHotkey's menu mask key.
+pub const MASK_KEY: u16 = 0xE8;
+
+/// Taps [`MASK_KEY`]. A held Alt or Win opens the menu bar or Start menu on
+/// release only if no other key was seen since its press, this is one.
+pub fn tap_mask_key() {
+    use windows::Win32::UI::Input::KeyboardAndMouse::{
+        SendInput, INPUT, INPUT_0, INPUT_KEYBOARD, KEYBDINPUT, KEYBD_EVENT_FLAGS, KEYEVENTF_KEYUP,
+        VIRTUAL_KEY,
+    };
+
+    let key = |flags| INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: VIRTUAL_KEY(MASK_KEY),
+                wScan: 0,
+                dwFlags: flags,
+                time: 0,
+                dwExtraInfo: 0,
+            },
+        },
+    };
+    let inputs = [key(KEYBD_EVENT_FLAGS(0)), key(KEYEVENTF_KEYUP)];
+    let sent = unsafe { SendInput(&inputs, std::mem::size_of::<INPUT>() as i32) };
+    if sent as usize != inputs.len() {
+        log::warn!(
+            "failed to mask the ping key: {}",
+            windows::core::Error::from_win32()
+        );
+    }
+}
+
+/// Top-level window under `pos` and its top-left corner.
+pub fn window_origin_at(pos: (i32, i32)) -> Option<(HWND, (i32, i32))> {
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::UI::WindowsAndMessaging::{GetAncestor, WindowFromPoint, GA_ROOT};
+
+    let window = unsafe { WindowFromPoint(POINT { x: pos.0, y: pos.1 }) };
+    if window.0 == 0 {
+        return None;
+    }
+    let window = unsafe { GetAncestor(window, GA_ROOT) };
+    Some((window, window_origin(window)?))
+}
+
+/// Top-left corner of `window`, `None` once it is gone.
+pub fn window_origin(window: HWND) -> Option<(i32, i32)> {
+    use windows::Win32::Foundation::RECT;
+    use windows::Win32::UI::WindowsAndMessaging::GetWindowRect;
+
+    let mut rect = RECT::default();
+    unsafe { GetWindowRect(window, &mut rect) }.ok()?;
+    Some((rect.left, rect.top))
+}
+
+/// UI Automation client, for [`Automation::caret`]. Bound to the thread
+/// that made it.
+pub struct Automation(windows::Win32::UI::Accessibility::IUIAutomation);
+
+impl Automation {
+    /// Sets COM up on the calling thread as an STA and connects.
+    pub fn new() -> windows::core::Result<Self> {
+        use windows::Win32::System::Com::{
+            CoCreateInstance, CoInitializeEx, CLSCTX_INPROC_SERVER, COINIT_APARTMENTTHREADED,
+        };
+        use windows::Win32::UI::Accessibility::CUIAutomation;
+
+        unsafe {
+            CoInitializeEx(None, COINIT_APARTMENTTHREADED)?;
+            Ok(Self(CoCreateInstance(
+                &CUIAutomation,
+                None,
+                CLSCTX_INPROC_SERVER,
+            )?))
+        }
+    }
+
+    /// Screen position of the text caret in the focused control, at its
+    /// left edge halfway down. `None` when the control has no text pattern.
+    pub fn caret(&self) -> Option<(i32, i32)> {
+        use windows::Win32::System::Ole::{
+            SafeArrayAccessData, SafeArrayDestroy, SafeArrayGetUBound, SafeArrayUnaccessData,
+        };
+        use windows::Win32::UI::Accessibility::{
+            IUIAutomationTextPattern2, TextUnit_Character, UIA_TextPattern2Id,
+        };
+
+        unsafe {
+            let focused = self.0.GetFocusedElement().ok()?;
+            let pattern: IUIAutomationTextPattern2 =
+                focused.GetCurrentPatternAs(UIA_TextPattern2Id).ok()?;
+            let mut active = windows::Win32::Foundation::BOOL(0);
+            let mut range = None;
+            pattern.GetCaretRange(&mut active, &mut range).ok()?;
+            let range = range?;
+            // NOTE: the empty range at the caret often has no rectangle, the character after it does
+            range.ExpandToEnclosingUnit(TextUnit_Character).ok()?;
+            let rects = range.GetBoundingRectangles().ok()?;
+            if rects.is_null() {
+                return None;
+            }
+            // NOTE: doubles, left, top, width and height for each line the range spans
+            let mut position = None;
+            let mut data = std::ptr::null_mut();
+            if SafeArrayGetUBound(rects, 1).is_ok_and(|upper| upper >= 3)
+                && SafeArrayAccessData(rects, &mut data).is_ok()
+            {
+                let rect = std::slice::from_raw_parts(data as *const f64, 4);
+                position = Some((rect[0] as i32, (rect[1] + rect[3] / 2.0) as i32));
+                SafeArrayUnaccessData(rects).ok();
+            }
+            SafeArrayDestroy(rects).ok();
+            position
+        }
+    }
+}
+
+/// Screen position of the system caret in the foreground thread, for apps
+/// without UI Automation text support. `None` when none is shown.
+pub fn system_caret() -> Option<(i32, i32)> {
+    use windows::Win32::Foundation::POINT;
+    use windows::Win32::Graphics::Gdi::ClientToScreen;
+    use windows::Win32::UI::WindowsAndMessaging::{GetGUIThreadInfo, GUITHREADINFO};
+
+    let mut info = GUITHREADINFO {
+        cbSize: std::mem::size_of::<GUITHREADINFO>() as u32,
+        ..Default::default()
+    };
+    unsafe { GetGUIThreadInfo(0, &mut info) }.ok()?;
+    if info.hwndCaret.0 == 0 {
+        return None;
+    }
+    let mut point = POINT {
+        x: info.rcCaret.left,
+        y: (info.rcCaret.top + info.rcCaret.bottom) / 2,
+    };
+    unsafe { ClientToScreen(info.hwndCaret, &mut point) }
+        .as_bool()
+        .then_some((point.x, point.y))
+}
+
+/// Names this process' audio session in the volume mixer so its volume and
+/// mute are set per app. Call on the thread that opened the output stream.
+pub fn name_audio_session(name: &str) -> windows::core::Result<()> {
+    use windows::Win32::Media::Audio::{
+        eConsole, eRender, IAudioSessionManager, IMMDeviceEnumerator, MMDeviceEnumerator,
+    };
+    use windows::Win32::System::Com::{
+        CoCreateInstance, CoInitializeEx, CLSCTX_ALL, COINIT_APARTMENTTHREADED,
+    };
+
+    unsafe {
+        // NOTE: the audio backend already initialized COM on this thread, this only
+        // makes sure of it
+        let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+        let enumerator: IMMDeviceEnumerator =
+            CoCreateInstance(&MMDeviceEnumerator, None, CLSCTX_ALL)?;
+        let device = enumerator.GetDefaultAudioEndpoint(eRender, eConsole)?;
+        let manager: IAudioSessionManager = device.Activate(CLSCTX_ALL, None)?;
+        // NOTE: the default session, which shared mode streams join
+        let session = manager.GetAudioSessionControl(None, 0)?;
+        session.SetDisplayName(&HSTRING::from(name), std::ptr::null())?;
+        // NOTE: the executable has no icon resource to point at, so the mixer keeps
+        // its generic icon
+    }
+    Ok(())
+}
+
+/// A plain tone from the system, which doesn't need an audio stream of our
+/// own. Blocks for `duration`.
+pub fn beep(frequency: u32, duration: std::time::Duration) {
+    use windows::Win32::System::Diagnostics::Debug::Beep;
+
+    if let Err(e) = unsafe { Beep(frequency, duration.as_millis() as u32) } {
+        log::info!("failed to beep: {e}");
+    }
+}
+
+/// Real Windows version like `10.0.22631`, which `GetVersionEx` lies about
+/// without a compatibility manifest.
+pub fn os_version() -> String {
+    use windows::Wdk::System::SystemServices::RtlGetVersion;
+    use windows::Win32::System::SystemInformation::OSVERSIONINFOW;
+
+    let mut info = OSVERSIONINFOW {
+        dwOSVersionInfoSize: std::mem::size_of::<OSVERSIONINFOW>() as u32,
+        ..Default::default()
+    };
+    match unsafe { RtlGetVersion(&mut info) } {
+        Ok(()) => format!(
+            "{}.{}.{}",
+            info.dwMajorVersion, info.dwMinorVersion, info.dwBuildNumber
+        ),
+        Err(e) => format!("unknown ({e})"),
+    }
+}
+
+/// Server end of a named pipe with a client connected. Flushed and closed on drop.
+pub struct PipeConnection(HANDLE);
+
+// NOTE: a pipe handle may be used from any thread
+unsafe impl Send for PipeConnection {}
+
+impl PipeConnection {
+    /// Creates a new instance of pipe `name` and blocks until a local client
+    /// connects to it. With `first`, fails if another process serves `name`
+    /// already.
+    pub fn accept(name: &str, first: bool) -> windows::core::Result<Self> {
+        use windows::Win32::Foundation::{ERROR_PIPE_CONNECTED, INVALID_HANDLE_VALUE};
+        use windows::Win32::Storage::FileSystem::{
+            FILE_FLAGS_AND_ATTRIBUTES, FILE_FLAG_FIRST_PIPE_INSTANCE, PIPE_ACCESS_DUPLEX,
+        };
+        use windows::Win32::System::Pipes::{
+            ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+            PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+        };
+
+        let handle = unsafe {
+            CreateNamedPipeW(
+                &HSTRING::from(name),
+                PIPE_ACCESS_DUPLEX
+                    | if first {
+                        FILE_FLAG_FIRST_PIPE_INSTANCE
+                    } else {
+                        FILE_FLAGS_AND_ATTRIBUTES(0)
+                    },
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                4096,
+                4096,
+                0,
+                None,
+            )
+        };
+        if handle == INVALID_HANDLE_VALUE {
+            return Err(windows::core::Error::from_win32());
+        }
+        let pipe = Self(handle);
+        match unsafe { ConnectNamedPipe(handle, None) } {
+            // NOTE: the client can connect between creating and waiting
+            Err(e) if e.code() != ERROR_PIPE_CONNECTED.to_hresult() => Err(e),
+            _ => Ok(pipe),
+        }
+    }
+
+    pub fn read(&self, buffer: &mut [u8]) -> windows::core::Result<usize> {
+        use windows::Win32::Storage::FileSystem::ReadFile;
+
+        let mut read = 0;
+        unsafe { ReadFile(self.0, Some(buffer), Some(&mut read), None)? };
+        Ok(read as usize)
+    }
+
+    pub fn write_all(&self, mut bytes: &[u8]) -> windows::core::Result<()> {
+        use windows::Win32::Storage::FileSystem::WriteFile;
+
+        while !bytes.is_empty() {
+            let mut written = 0;
+            unsafe { WriteFile(self.0, Some(bytes), Some(&mut written), None)? };
+            bytes = &bytes[written as usize..];
+        }
+        Ok(())
+    }
+}
+
+impl Drop for PipeConnection {
+    fn drop(&mut self) {
+        use windows::Win32::Storage::FileSystem::FlushFileBuffers;
+        use windows::Win32::System::Pipes::DisconnectNamedPipe;
+
+        // NOTE: flush first, disconnecting discards whatever the client hasn't read yet
+        unsafe {
+            FlushFileBuffers(self.0).ok();
+            DisconnectNamedPipe(self.0).ok();
+            CloseHandle(self.0).ok();
+        }
+    }
+}
+
+/// The Remote Desktop session this process runs in, 0 if unknown.
+pub fn session_id() -> u32 {
+    use windows::Win32::System::RemoteDesktop::ProcessIdToSessionId;
+    use windows::Win32::System::Threading::GetCurrentProcessId;
+
+    let mut session = 0;
+    if let Err(e) = unsafe { ProcessIdToSessionId(GetCurrentProcessId(), &mut session) } {
+        log::warn!("session id unknown: {e}");
+    }
+    session
+}
+
+/// A named mutex, held until dropped or the process ends.
+pub struct InstanceLock(HANDLE);
+
+impl InstanceLock {
+    /// Takes the mutex `name`, waiting up to `wait` for a holder on its way
+    /// out, e.g. the instance that relaunched this one. `None` if it's still
+    /// held after that.
+    pub fn acquire(name: &str, wait: std::time::Duration) -> windows::core::Result<Option<Self>> {
+        use windows::Win32::Foundation::{WAIT_ABANDONED, WAIT_OBJECT_0};
+        use windows::Win32::System::Threading::{CreateMutexW, WaitForSingleObject};
+
+        let handle = unsafe { CreateMutexW(None, false, &HSTRING::from(name))? };
+        let waited = unsafe { WaitForSingleObject(handle, wait.as_millis() as u32) };
+        // NOTE: abandoned means the last holder exited without letting go, it's ours now
+        if waited == WAIT_OBJECT_0 || waited == WAIT_ABANDONED {
+            return Ok(Some(Self(handle)));
+        }
+        unsafe { CloseHandle(handle).ok() };
+        Ok(None)
+    }
+}
+
+impl Drop for InstanceLock {
+    fn drop(&mut self) {
+        use windows::Win32::System::Threading::ReleaseMutex;
+
+        unsafe {
+            ReleaseMutex(self.0).ok();
+            CloseHandle(self.0).ok();
+        }
+    }
+}
@@ -0,0 +1,45 @@
+//! Saving power on battery: while it's on, markers are drawn procedurally,
+//! animations step at 30 fps, the audio device is only open around a sound
+//! and the overlay isn't redrawn while nothing moves.
+
+use crate::config::{self, PowerMode};
+use crate::platform::{self, PowerStatus};
+use std::time::Duration;
+
+/// Whether `status` calls for saving power under `config`.
+pub fn saving(status: &PowerStatus, config: &config::Power) -> bool {
+    match config.mode {
+        PowerMode::Always => true,
+        PowerMode::Never => false,
+        PowerMode::Auto => {
+            let low = status
+                .battery_percent
+                .is_none_or(|percent| percent < config.below_percent);
+            (config.battery_saver && status.saver)
+                || (config.on_battery && status.on_battery && low)
+        }
+    }
+}
+
+/// Polls the power status every `config.poll_secs` and calls `on_change` when
+/// saving starts or stops, right away for the state at startup.
+pub fn spawn_poller(config: config::Power, on_change: impl Fn(bool) + Send + 'static) {
+    let interval = Duration::from_secs(config.poll_secs.max(1));
+    std::thread::spawn(move || {
+        let mut last = false;
+        loop {
+            // NOTE: desktops without a battery report AC power, nothing to save there
+            let status = platform::power_status().unwrap_or_default();
+            let now = saving(&status, &config);
+            if now != last {
+                log::info!(
+                    "power saving {} ({status:?})",
+                    if now { "on" } else { "off" }
+                );
+                on_change(now);
+                last = now;
+            }
+            std::thread::sleep(interval);
+        }
+    });
+}
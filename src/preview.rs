@@ -0,0 +1,154 @@
+//! Plays themes and marker styles in a dialog before they go live.
+
+use crate::animation_builder::AnimationBuilder;
+use crate::i18n::{t, tf};
+use crate::marker::Style;
+use crate::protocol::PingKind;
+use crate::theme::Theme;
+use crate::{Animation, MyApp, FRAME_COUNT};
+use egui::{Color32, Pos2, Rect, Vec2};
+use std::path::PathBuf;
+use std::time::Instant;
+
+const STAGE: f32 = 220.0;
+const CHECKER: f32 = 20.0;
+
+/// Something the preview can show and apply.
+pub enum Choice {
+    /// A loaded theme, drawn with the artwork style. `path` is `None` for the
+    /// built-in one.
+    Theme {
+        theme: Box<Theme>,
+        path: Option<PathBuf>,
+    },
+    Style(Style),
+}
+
+impl Choice {
+    fn label(&self) -> String {
+        match self {
+            Choice::Theme { theme, .. } => tf("preview.theme", &[("name", &theme.name)]),
+            Choice::Style(style) => tf("preview.style", &[("style", &format!("{style:?}"))]),
+        }
+    }
+}
+
+pub struct Preview {
+    choices: Vec<Choice>,
+    selected: usize,
+    /// A ping that's never queued, stepped here with the driver's logic.
+    animation: Animation,
+    color: [u8; 4],
+    applied: Option<Choice>,
+}
+
+impl Preview {
+    /// `themes` are listed first, then every procedural style.
+    pub fn new(themes: Vec<(Theme, Option<PathBuf>)>, selected: usize, color: [u8; 4]) -> Self {
+        let choices = themes
+            .into_iter()
+            .map(|(theme, path)| Choice::Theme {
+                theme: Box::new(theme),
+                path,
+            })
+            .chain(
+                [Style::Ring, Style::Crosshair, Style::Arrow]
+                    .into_iter()
+                    .map(Choice::Style),
+            )
+            .collect::<Vec<_>>();
+        Self {
+            selected: selected.min(choices.len() - 1),
+            choices,
+            animation: AnimationBuilder::at((0, 0)).build().animation,
+            color,
+            applied: None,
+        }
+    }
+
+    /// What "Apply" was clicked on, if it was. Closes the preview.
+    pub fn take_applied(&mut self) -> Option<Choice> {
+        self.applied.take()
+    }
+
+    fn advance(&mut self) {
+        // NOTE: after a while undrawn, e.g. minimized, this lands past the end and restarts
+        self.animation.frame = self.animation.frame_at(Instant::now());
+        if self.animation.frame >= FRAME_COUNT {
+            self.restart();
+        }
+    }
+
+    fn restart(&mut self) {
+        self.animation = AnimationBuilder::at((0, 0)).build().animation;
+    }
+
+    /// Returns `true` when the user closed the dialog, applying or not.
+    pub fn ui(&mut self, ctx: &egui::Context) -> bool {
+        self.advance();
+        let mut close = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            let before = self.selected;
+            egui::ComboBox::from_id_source("preview choice")
+                .width(STAGE)
+                .selected_text(self.choices[self.selected].label())
+                .show_ui(ui, |ui| {
+                    for (i, choice) in self.choices.iter().enumerate() {
+                        ui.selectable_value(&mut self.selected, i, choice.label());
+                    }
+                });
+            if self.selected != before {
+                self.restart();
+            }
+
+            let (stage, _) = ui.allocate_exact_size(Vec2::splat(STAGE), egui::Sense::hover());
+            checkerboard(ui.painter(), stage);
+            self.draw(ui, stage);
+
+            ui.horizontal(|ui| {
+                if ui.button(t("dialog.apply")).clicked() {
+                    self.applied = Some(self.choices.remove(self.selected));
+                    close = true;
+                }
+                close |= ui.button(t("dialog.close")).clicked();
+            });
+        });
+        ctx.request_repaint();
+        close
+    }
+
+    fn draw(&self, ui: &mut egui::Ui, stage: Rect) {
+        let frame = self.animation.frame;
+        let progress = frame as f32 / FRAME_COUNT as f32;
+        match &self.choices[self.selected] {
+            Choice::Theme { theme, .. } => {
+                let frames = theme.frames_for(&PingKind::Missing);
+                if let Some(source) = frames.get(theme.frame_index(frame)) {
+                    let size = theme.size.min(STAGE);
+                    let rect = Rect::from_center_size(stage.center(), Vec2::splat(size));
+                    ui.put(rect, egui::Image::new(source.clone()));
+                }
+            }
+            Choice::Style(style) => {
+                let [r, g, b, a] = self.color;
+                let alpha = (a as f32 * MyApp::fade(progress)) as u8;
+                let color = Color32::from_rgba_unmultiplied(r, g, b, alpha);
+                let center: Pos2 = stage.center();
+                crate::marker::draw(ui.painter(), *style, center, progress, STAGE * 0.8, color);
+            }
+        }
+    }
+}
+
+/// Grey squares, so both light and dark artwork and transparency show up.
+fn checkerboard(painter: &egui::Painter, stage: Rect) {
+    let cells = (STAGE / CHECKER) as usize;
+    for row in 0..cells {
+        for column in 0..cells {
+            let shade = if (row + column) % 2 == 0 { 90 } else { 160 };
+            let min = stage.min + Vec2::new(column as f32, row as f32) * CHECKER;
+            let cell = Rect::from_min_size(min, Vec2::splat(CHECKER));
+            painter.rect_filled(cell, 0.0, Color32::from_gray(shade));
+        }
+    }
+}
@@ -0,0 +1,187 @@
+use std::time::{Duration, Instant};
+
+/// Tracks whether Alt is held to arm a ping.
+///
+/// The hook sometimes never sees Alt's release, e.g. after Alt+Tab, so priming
+/// also expires on its own and is cross-checked against the real key state.
+/// Held keys auto-repeat, which keeps a genuine long hold refreshed.
+#[derive(Debug)]
+pub struct Priming {
+    pressed_at: Option<Instant>,
+    expiry: Duration,
+}
+
+impl Priming {
+    pub fn new(expiry: Duration) -> Self {
+        Self {
+            pressed_at: None,
+            expiry,
+        }
+    }
+
+    pub fn press(&mut self, now: Instant) {
+        self.pressed_at = Some(now);
+    }
+
+    pub fn release(&mut self) {
+        self.pressed_at = None;
+    }
+
+    /// Whether the ping key is held as far as the hook knows, unlike
+    /// [`Self::check`] without cross-checking it.
+    pub fn is_primed(&self) -> bool {
+        self.pressed_at.is_some()
+    }
+
+    /// Whether a click at `now` should count as primed. `alt_down` is the
+    /// polled key state; a stale priming is dropped here.
+    pub fn check(&mut self, now: Instant, alt_down: bool) -> bool {
+        let Some(pressed_at) = self.pressed_at else {
+            return false;
+        };
+        if now.saturating_duration_since(pressed_at) > self.expiry {
+            log::info!("Alt priming expired after {:?}", self.expiry);
+            self.release();
+            return false;
+        }
+        if !alt_down {
+            log::info!("Alt release was missed by the hook, unpriming");
+            self.release();
+            return false;
+        }
+        true
+    }
+}
+
+/// Decides when the prime and unprime cues play: only on an actual change of
+/// the primed state, not on the key's auto-repeat, and at most once per
+/// [`Cues::GAP`] so a quick run of chords doesn't turn into a stream of clicks.
+#[derive(Debug, Default)]
+pub struct Cues {
+    primed: bool,
+    last_played: Option<Instant>,
+}
+
+impl Cues {
+    const GAP: Duration = Duration::from_secs(1);
+
+    /// Returns the new state when a cue for it should play.
+    pub fn update(&mut self, primed: bool, now: Instant) -> Option<bool> {
+        if primed == self.primed {
+            return None;
+        }
+        self.primed = primed;
+        if self
+            .last_played
+            .is_some_and(|last| now.saturating_duration_since(last) < Self::GAP)
+        {
+            return None;
+        }
+        self.last_played = Some(now);
+        Some(primed)
+    }
+}
+
+/// Spots the ping key tapped twice on its own, which pings without a click.
+///
+/// Any other key or a click in between, as in Alt+Tab or a primed click,
+/// cancels the pending tap.
+#[derive(Debug)]
+pub struct DoubleTap {
+    interval: Duration,
+    /// Set while the key is down and nothing else happened since.
+    pressed_at: Option<Instant>,
+    /// Press of the last clean tap, waiting for a second one.
+    last_tap: Option<Instant>,
+}
+
+impl DoubleTap {
+    pub fn new(interval: Duration) -> Self {
+        Self {
+            interval,
+            pressed_at: None,
+            last_tap: None,
+        }
+    }
+
+    pub fn press(&mut self, now: Instant) {
+        // NOTE: a held key auto-repeats its press
+        if self.pressed_at.is_none() {
+            self.pressed_at = Some(now);
+        }
+    }
+
+    /// Returns `true` when this release completes a double tap.
+    pub fn release(&mut self) -> bool {
+        let Some(pressed_at) = self.pressed_at.take() else {
+            // NOTE: interrupted, or held from before
+            self.last_tap = None;
+            return false;
+        };
+        let double = self.last_tap.is_some_and(|last_tap| {
+            pressed_at.saturating_duration_since(last_tap) <= self.interval
+        });
+        self.last_tap = (!double).then_some(pressed_at);
+        double
+    }
+
+    /// Another key or a mouse button was pressed.
+    pub fn interrupt(&mut self) {
+        self.pressed_at = None;
+        self.last_tap = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const EXPIRY: Duration = Duration::from_secs(3);
+
+    #[test]
+    fn unprimed_clicks_dont_count() {
+        let mut priming = Priming::new(EXPIRY);
+        assert!(!priming.check(Instant::now(), true));
+    }
+
+    #[test]
+    fn held_key_counts_until_released() {
+        let start = Instant::now();
+        let mut priming = Priming::new(EXPIRY);
+        priming.press(start);
+        assert!(priming.is_primed());
+        assert!(priming.check(start + Duration::from_secs(1), true));
+        priming.release();
+        assert!(!priming.check(start + Duration::from_secs(1), true));
+    }
+
+    #[test]
+    fn priming_expires() {
+        let start = Instant::now();
+        let mut priming = Priming::new(EXPIRY);
+        priming.press(start);
+        assert!(priming.check(start + EXPIRY, true));
+        assert!(!priming.check(start + EXPIRY + Duration::from_millis(1), true));
+        // NOTE: stays dropped, not just refused once
+        assert!(!priming.is_primed());
+    }
+
+    #[test]
+    fn auto_repeat_keeps_a_long_hold_primed() {
+        let start = Instant::now();
+        let mut priming = Priming::new(EXPIRY);
+        priming.press(start);
+        priming.press(start + EXPIRY);
+        assert!(priming.check(start + EXPIRY * 2 - Duration::from_millis(1), true));
+    }
+
+    #[test]
+    fn missed_release_is_caught_by_the_key_state() {
+        let start = Instant::now();
+        let mut priming = Priming::new(EXPIRY);
+        priming.press(start);
+        assert!(!priming.check(start, false));
+        assert!(!priming.is_primed());
+        assert!(!priming.check(start, true));
+    }
+}
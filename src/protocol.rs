@@ -0,0 +1,202 @@
+//! Wire format shared by network and IPC pings.
+//!
+//! Kinds travel as plain strings so a newer peer can send kinds an older one
+//! doesn't know. Those are kept as [`PingKind::Other`] and drawn with the
+//! default style instead of being dropped.
+
+use crate::ping_source::PingSource;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum PingKind {
+    /// The question mark, and what unknown kinds fall back to.
+    #[default]
+    Missing,
+    Danger,
+    OnMyWay,
+    Assist,
+    /// A kind from a newer or customized peer, kept verbatim.
+    Other(String),
+}
+
+impl PingKind {
+    pub const REGISTERED: [PingKind; 4] = [
+        PingKind::Missing,
+        PingKind::Danger,
+        PingKind::OnMyWay,
+        PingKind::Assist,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            PingKind::Missing => "missing",
+            PingKind::Danger => "danger",
+            PingKind::OnMyWay => "on_my_way",
+            PingKind::Assist => "assist",
+            PingKind::Other(kind) => kind,
+        }
+    }
+
+    pub fn is_registered(&self) -> bool {
+        !matches!(self, PingKind::Other(_))
+    }
+}
+
+impl From<String> for PingKind {
+    fn from(kind: String) -> Self {
+        PingKind::REGISTERED
+            .into_iter()
+            .find(|registered| registered.as_str() == kind)
+            .unwrap_or(PingKind::Other(kind))
+    }
+}
+
+impl From<PingKind> for String {
+    fn from(kind: PingKind) -> Self {
+        kind.as_str().to_owned()
+    }
+}
+
+/// A single ping as sent between instances.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PingMessage {
+    #[serde(default)]
+    pub kind: PingKind,
+    pub x: i32,
+    pub y: i32,
+    #[serde(default)]
+    pub persistent: bool,
+    /// Name the sender goes by, shown on the ping in its own color. Pings
+    /// without one are labelled `IPC`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    /// Echoed back once the ping is drawn, so a sender with several in
+    /// flight can tell the answers apart.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub seq: Option<u64>,
+}
+
+impl PingMessage {
+    /// Who sent this, by the name it gave.
+    pub fn source(&self) -> PingSource {
+        match self.from.as_deref().map(str::trim) {
+            Some(peer) if !peer.is_empty() => PingSource::Remote { peer: peer.into() },
+            _ => PingSource::Ipc,
+        }
+    }
+}
+
+/// Takes incoming messages in, remembering which unknown kinds were already
+/// reported so each peer is only logged about once per kind.
+#[derive(Debug, Default)]
+pub struct Receiver {
+    reported: HashSet<(String, String)>,
+}
+
+impl Receiver {
+    /// Who sent `message`. Its kind is logged the first time if this build
+    /// doesn't know it, it's still drawn in the default style.
+    pub fn receive(&mut self, message: &PingMessage) -> PingSource {
+        let source = message.source();
+        if !message.kind.is_registered() {
+            let peer = source.label().unwrap_or("local").to_owned();
+            let kind = message.kind.as_str().to_owned();
+            if self.reported.insert((peer.clone(), kind.clone())) {
+                log::info!("unknown ping kind {kind:?} from {peer}, drawing the default style");
+            }
+        }
+        source
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(kind: &str) -> PingMessage {
+        serde_json::from_str(&format!(r#"{{"kind":"{kind}","x":100,"y":200}}"#)).unwrap()
+    }
+
+    #[test]
+    fn registered_kinds_round_trip() {
+        for kind in PingKind::REGISTERED {
+            let message = PingMessage {
+                kind: kind.clone(),
+                x: -5,
+                y: 7,
+                persistent: true,
+                from: None,
+                seq: Some(3),
+            };
+            let json = serde_json::to_string(&message).unwrap();
+            assert!(json.contains(&format!(r#""kind":"{}""#, kind.as_str())));
+            assert_eq!(serde_json::from_str::<PingMessage>(&json).unwrap(), message);
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_kept_verbatim() {
+        let message = message("laser");
+        assert_eq!(message.kind, PingKind::Other("laser".to_owned()));
+        assert!(!message.kind.is_registered());
+        let json = serde_json::to_string(&message).unwrap();
+        assert_eq!(serde_json::from_str::<PingMessage>(&json).unwrap(), message);
+    }
+
+    #[test]
+    fn missing_fields_default() {
+        let message: PingMessage = serde_json::from_str(r#"{"x":1,"y":2}"#).unwrap();
+        assert_eq!(message.kind, PingKind::Missing);
+        assert!(!message.persistent);
+        assert_eq!(message.from, None);
+        assert_eq!(message.seq, None);
+        let json = serde_json::to_string(&message).unwrap();
+        assert!(!json.contains("from") && !json.contains("seq"));
+    }
+
+    #[test]
+    fn named_senders_are_peers_of_their_own() {
+        let from = |from: &str| {
+            serde_json::from_str::<PingMessage>(&format!(r#"{{"x":1,"y":2,"from":{from}}}"#))
+                .unwrap()
+                .source()
+        };
+        let laptop = from(r#""laptop""#);
+        assert_eq!(
+            laptop,
+            PingSource::Remote {
+                peer: "laptop".into()
+            }
+        );
+        assert_eq!(laptop.label(), Some("laptop"));
+        assert_eq!(from(r#"" laptop ""#), laptop);
+        assert_ne!(from(r#""desktop""#), laptop);
+        // NOTE: nameless senders share the one IPC source
+        assert_eq!(from(r#""  ""#), PingSource::Ipc);
+        assert_eq!(from("null"), PingSource::Ipc);
+        assert_eq!(PingSource::Ipc.label(), Some("IPC"));
+    }
+
+    #[test]
+    fn unknown_kinds_are_received_not_dropped() {
+        let mut receiver = Receiver::default();
+        let message = message("laser");
+        assert_eq!(receiver.receive(&message), PingSource::Ipc);
+        assert_eq!(message.kind, PingKind::Other("laser".to_owned()));
+    }
+
+    #[test]
+    fn unknown_kinds_are_reported_once_per_source() {
+        let mut receiver = Receiver::default();
+        for _ in 0..3 {
+            receiver.receive(&message("laser"));
+        }
+        receiver.receive(&message("danger"));
+        let mut from_laptop = message("laser");
+        from_laptop.from = Some("laptop".to_owned());
+        receiver.receive(&from_laptop);
+        assert_eq!(receiver.reported.len(), 2);
+    }
+}
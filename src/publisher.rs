@@ -0,0 +1,90 @@
+//! Posts every ping to a webhook as JSON, e.g. for home automation.
+//!
+//! Runs on its own thread fed by a bounded channel, so a slow or unreachable
+//! endpoint never holds up the hook: pings that don't fit are dropped.
+
+use crate::config;
+use crossbeam::channel::{self, Sender, TrySendError};
+use serde::Serialize;
+use std::time::Duration;
+
+const QUEUE: usize = 64;
+const RETRY_MIN: Duration = Duration::from_secs(1);
+const RETRY_MAX: Duration = Duration::from_secs(60);
+/// Attempts per ping before it's given up on. Stale pings aren't worth more.
+const ATTEMPTS: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PingEvent {
+    pub x: i32,
+    pub y: i32,
+    /// 1-based, `None` when off every known monitor.
+    pub monitor: Option<usize>,
+    /// Position within the monitor, 0-1 from its top-left corner.
+    pub normalized: Option<(f32, f32)>,
+    pub kind: String,
+    pub persistent: bool,
+}
+
+pub struct Publisher {
+    sender: Sender<PingEvent>,
+}
+
+impl Publisher {
+    /// Starts the publisher thread. `on_status` is called with whether the
+    /// endpoint is reachable every time that changes.
+    pub fn spawn(config: &config::Publish, on_status: impl Fn(bool) + Send + 'static) -> Self {
+        let (sender, receiver) = channel::bounded::<PingEvent>(QUEUE);
+        let url = config.url.clone();
+        let agent = ureq::AgentBuilder::new().timeout(TIMEOUT).build();
+
+        std::thread::spawn(move || {
+            let mut connected = None;
+            let mut retry = RETRY_MIN;
+            for event in receiver {
+                let url = url
+                    .replace(
+                        "{monitor}",
+                        &event
+                            .monitor
+                            .map_or("none".into(), |index| index.to_string()),
+                    )
+                    .replace("{kind}", &event.kind);
+                let body = serde_json::to_string(&event).expect("ping events are serializable");
+
+                for attempt in 1..=ATTEMPTS {
+                    let result = agent
+                        .post(&url)
+                        .set("Content-Type", "application/json")
+                        .send_string(&body);
+                    let ok = match result {
+                        Ok(_) => true,
+                        Err(e) => {
+                            log::warn!("publishing ping failed (attempt {attempt}): {e}");
+                            false
+                        }
+                    };
+                    if connected != Some(ok) {
+                        connected = Some(ok);
+                        on_status(ok);
+                    }
+                    if ok {
+                        retry = RETRY_MIN;
+                        break;
+                    }
+                    std::thread::sleep(retry);
+                    retry = (retry * 2).min(RETRY_MAX);
+                }
+            }
+        });
+
+        Self { sender }
+    }
+
+    pub fn publish(&self, event: PingEvent) {
+        if let Err(TrySendError::Full(_)) = self.sender.try_send(event) {
+            log::warn!("publisher is backed up, dropping a ping");
+        }
+    }
+}
@@ -0,0 +1,98 @@
+//! Trades smoothness for keeping up when the GPU can't redraw fast enough.
+//!
+//! Degraded, the animation driver advances two frames per step, which halves
+//! the redraws pings cost while keeping their wall-clock duration.
+
+use std::sync::atomic::{AtomicU8, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+/// Redraws must come in under this share of the budget to count as headroom,
+/// so the quality doesn't flap right at the threshold.
+const HEADROOM: f32 = 0.75;
+
+pub struct Governor {
+    budget: Duration,
+    degrade_after: u32,
+    restore_after: u32,
+    /// Redraws in a row pointing the other way than the current state.
+    streak: u32,
+    degraded: bool,
+    /// Saving power, which halves the frame rate like degrading does.
+    power_saving: bool,
+    /// Frames the driver advances per step.
+    stride: Arc<AtomicU8>,
+}
+
+impl Governor {
+    pub fn new(config: &crate::config::Quality) -> Self {
+        let budget =
+            Duration::try_from_secs_f32(config.slow_frame_ms / 1000.0).unwrap_or_else(|_| {
+                log::warn!("invalid quality.slow_frame_ms, using 16.7");
+                Duration::from_micros(16_700)
+            });
+        Self {
+            budget,
+            degrade_after: config.degrade_after_frames.max(1),
+            restore_after: config.restore_after_frames.max(1),
+            streak: 0,
+            degraded: false,
+            power_saving: false,
+            stride: Arc::new(AtomicU8::new(1)),
+        }
+    }
+
+    /// Shared with the animation driver.
+    pub fn stride(&self) -> Arc<AtomicU8> {
+        self.stride.clone()
+    }
+
+    pub fn degraded(&self) -> bool {
+        self.degraded
+    }
+
+    /// Feeds how long a redraw took, switching quality once enough redraws
+    /// in a row were too slow or had room to spare.
+    pub fn record(&mut self, redraw: Duration) {
+        let against = if self.degraded {
+            redraw.as_secs_f32() < self.budget.as_secs_f32() * HEADROOM
+        } else {
+            redraw > self.budget
+        };
+        self.streak = if against { self.streak + 1 } else { 0 };
+
+        let limit = match self.degraded {
+            true => self.restore_after,
+            false => self.degrade_after,
+        };
+        if self.streak < limit {
+            return;
+        }
+        self.streak = 0;
+        self.degraded = !self.degraded;
+        if self.degraded {
+            log::info!(
+                "{limit} redraws over {:?} in a row, skipping every other animation frame",
+                self.budget
+            );
+        } else {
+            log::info!("redraws are keeping up again, back to full quality");
+        }
+        self.apply();
+    }
+
+    /// Caps animations at 30 fps while `on`, whatever the redraw times say.
+    pub fn set_power_saving(&mut self, on: bool) {
+        self.power_saving = on;
+        self.apply();
+    }
+
+    fn apply(&self) {
+        let stride = if self.degraded || self.power_saving {
+            2
+        } else {
+            1
+        };
+        self.stride.store(stride, Ordering::Relaxed);
+    }
+}
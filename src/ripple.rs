@@ -0,0 +1,58 @@
+//! A small ring on every mouse click, for screen recordings. Drawn from a
+//! plain list, with no queue, sound or texture, so a flurry of clicks costs
+//! next to nothing and never counts against the pings.
+
+use crate::config;
+use device_query::mouse_state::MousePosition;
+use egui::{Color32, Painter, Pos2, Stroke};
+use std::time::Instant;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ripple {
+    /// Desktop pixels.
+    pub position: MousePosition,
+    pub clicked: Instant,
+}
+
+/// Adds a ripple for a click, dropping the oldest past
+/// [`config::Ripples::max`].
+pub fn push(ripples: &mut Vec<Ripple>, ripple: Ripple, config: &config::Ripples) {
+    ripples.push(ripple);
+    let excess = ripples.len().saturating_sub(config.max);
+    ripples.drain(..excess);
+}
+
+/// Drops the ripples that have played out.
+pub fn prune(ripples: &mut Vec<Ripple>, now: Instant, config: &config::Ripples) {
+    let duration = config.duration();
+    ripples.retain(|ripple| now.saturating_duration_since(ripple.clicked) < duration);
+}
+
+/// Draws each ripple as a ring growing to [`config::Ripples::radius`] while
+/// it fades, with `offset` from desktop to overlay x.
+pub fn draw(
+    painter: &Painter,
+    ripples: &[Ripple],
+    offset: f32,
+    now: Instant,
+    config: &config::Ripples,
+) {
+    let duration = config.duration().as_secs_f32().max(f32::EPSILON);
+    let [r, g, b, a] = config.color;
+    for ripple in ripples {
+        let age = now.saturating_duration_since(ripple.clicked).as_secs_f32();
+        let progress = (age / duration).clamp(0.0, 1.0);
+        if progress == 1.0 {
+            continue;
+        }
+        // NOTE: eased out, the ring jumps open and then settles
+        let grown = 1.0 - (1.0 - progress).powi(2);
+        let color = Color32::from_rgba_unmultiplied(r, g, b, (a as f32 * (1.0 - progress)) as u8);
+        let center = Pos2::new(ripple.position.0 as f32 + offset, ripple.position.1 as f32);
+        painter.circle_stroke(
+            center,
+            config.radius * (0.3 + 0.7 * grown),
+            Stroke::new(2.0, color),
+        );
+    }
+}
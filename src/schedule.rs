@@ -0,0 +1,265 @@
+//! Hours pings are on for, in local time. Outside them pings pause by
+//! themselves, see [`crate::pause`].
+
+use crate::config;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::time::Duration;
+
+const MINUTES_PER_DAY: u16 = 24 * 60;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Weekday {
+    Sun,
+    Mon,
+    Tue,
+    Wed,
+    Thu,
+    Fri,
+    Sat,
+}
+
+impl Weekday {
+    pub const ALL: [Weekday; 7] = [
+        Weekday::Sun,
+        Weekday::Mon,
+        Weekday::Tue,
+        Weekday::Wed,
+        Weekday::Thu,
+        Weekday::Fri,
+        Weekday::Sat,
+    ];
+
+    /// From 0 for Sunday, as Windows counts.
+    pub fn from_index(index: u16) -> Self {
+        Self::ALL[index as usize % 7]
+    }
+
+    fn previous(self) -> Self {
+        Self::from_index(self as u16 + 6)
+    }
+
+    fn next(self) -> Self {
+        Self::from_index(self as u16 + 1)
+    }
+}
+
+/// Time of day as `"HH:MM"`, in minutes since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClockTime(pub u16);
+
+impl ClockTime {
+    pub fn parse(text: &str) -> Option<Self> {
+        let (hours, minutes) = text.trim().split_once(':')?;
+        let (hours, minutes): (u16, u16) = (hours.parse().ok()?, minutes.parse().ok()?);
+        // NOTE: 24:00 is the end of the day, for windows running until midnight
+        match (hours, minutes) {
+            (24, 0) => Some(Self(MINUTES_PER_DAY)),
+            (0..=23, 0..=59) => Some(Self(hours * 60 + minutes)),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for ClockTime {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:02}:{:02}", self.0 / 60, self.0 % 60)
+    }
+}
+
+impl Serialize for ClockTime {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for ClockTime {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let text = String::deserialize(deserializer)?;
+        Self::parse(&text).ok_or_else(|| {
+            serde::de::Error::custom(format!("{text:?} isn't a time like \"09:30\""))
+        })
+    }
+}
+
+/// A point in the local week.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Moment {
+    pub weekday: Weekday,
+    /// Since midnight.
+    pub minute: u16,
+    pub second: u16,
+}
+
+impl Moment {
+    pub fn now() -> Self {
+        crate::platform::local_moment()
+    }
+
+    fn plus_minutes(self, minutes: u16) -> Self {
+        let total = self.minute + minutes;
+        let mut weekday = self.weekday;
+        for _ in 0..total / MINUTES_PER_DAY {
+            weekday = weekday.next();
+        }
+        Self {
+            weekday,
+            minute: total % MINUTES_PER_DAY,
+            second: 0,
+        }
+    }
+}
+
+/// Whether `schedule` has pings on at `moment`. A window ending before it
+/// starts runs overnight and belongs to the day it starts on; one ending as
+/// it starts covers the whole day.
+pub fn within(schedule: &config::Schedule, moment: Moment) -> bool {
+    let (start, end) = (schedule.start.0, schedule.end.0);
+    let on = |weekday| schedule.days.contains(&weekday);
+    if start == end {
+        on(moment.weekday)
+    } else if start < end {
+        on(moment.weekday) && (start..end).contains(&moment.minute)
+    } else {
+        (on(moment.weekday) && moment.minute >= start)
+            || (on(moment.weekday.previous()) && moment.minute < end)
+    }
+}
+
+/// Time until pings switch on or off next, `None` if they never do.
+pub fn until_change(schedule: &config::Schedule, moment: Moment) -> Option<Duration> {
+    let current = within(schedule, moment);
+    // NOTE: a week of minutes is a few thousand checks, and only run on a boundary
+    // or a tooltip refresh
+    (1..=7 * MINUTES_PER_DAY)
+        .find(|minutes| within(schedule, moment.plus_minutes(*minutes)) != current)
+        .map(|minutes| Duration::from_secs(minutes as u64 * 60 - moment.second as u64))
+}
+
+/// Clock time at which pings switch back on, for the tooltip.
+pub fn resumes_at(schedule: &config::Schedule, moment: Moment) -> Option<ClockTime> {
+    let left = until_change(schedule, moment)?;
+    let minutes = (left.as_secs() + moment.second as u64).div_ceil(60) as u16;
+    Some(ClockTime(moment.plus_minutes(minutes).minute))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn schedule(days: &[Weekday], start: &str, end: &str) -> config::Schedule {
+        config::Schedule {
+            enabled: true,
+            days: days.to_vec(),
+            start: ClockTime::parse(start).unwrap(),
+            end: ClockTime::parse(end).unwrap(),
+        }
+    }
+
+    fn at(weekday: Weekday, time: &str) -> Moment {
+        Moment {
+            weekday,
+            minute: ClockTime::parse(time).unwrap().0,
+            second: 0,
+        }
+    }
+
+    #[test]
+    fn clock_time_parses_hours_and_minutes() {
+        assert_eq!(ClockTime::parse("09:30"), Some(ClockTime(570)));
+        assert_eq!(ClockTime::parse(" 0:00 "), Some(ClockTime(0)));
+        assert_eq!(ClockTime::parse("24:00"), Some(ClockTime(MINUTES_PER_DAY)));
+        assert_eq!(ClockTime::parse("24:01"), None);
+        assert_eq!(ClockTime::parse("12:60"), None);
+        assert_eq!(ClockTime::parse("noon"), None);
+        assert_eq!(ClockTime(570).to_string(), "09:30");
+    }
+
+    #[test]
+    fn day_window_includes_start_and_excludes_end() {
+        let work = schedule(&[Weekday::Mon], "09:00", "18:00");
+        assert!(!within(&work, at(Weekday::Mon, "08:59")));
+        assert!(within(&work, at(Weekday::Mon, "09:00")));
+        assert!(within(&work, at(Weekday::Mon, "17:59")));
+        assert!(!within(&work, at(Weekday::Mon, "18:00")));
+        assert!(!within(&work, at(Weekday::Tue, "12:00")));
+    }
+
+    #[test]
+    fn overnight_window_belongs_to_the_day_it_starts() {
+        let night = schedule(&[Weekday::Fri], "22:00", "02:00");
+        assert!(!within(&night, at(Weekday::Fri, "01:00")));
+        assert!(within(&night, at(Weekday::Fri, "22:00")));
+        assert!(within(&night, at(Weekday::Sat, "01:59")));
+        assert!(!within(&night, at(Weekday::Sat, "02:00")));
+        assert!(!within(&night, at(Weekday::Sat, "22:00")));
+    }
+
+    #[test]
+    fn overnight_window_wraps_from_saturday_to_sunday() {
+        let night = schedule(&[Weekday::Sat], "23:00", "01:00");
+        assert!(within(&night, at(Weekday::Sun, "00:30")));
+        assert!(!within(&night, at(Weekday::Sun, "23:30")));
+    }
+
+    #[test]
+    fn equal_start_and_end_cover_the_whole_day() {
+        let all_day = schedule(&[Weekday::Wed], "00:00", "00:00");
+        assert!(within(&all_day, at(Weekday::Wed, "00:00")));
+        assert!(within(&all_day, at(Weekday::Wed, "23:59")));
+        assert!(!within(&all_day, at(Weekday::Thu, "00:00")));
+    }
+
+    #[test]
+    fn window_to_midnight_ends_with_the_day() {
+        let evening = schedule(&[Weekday::Mon], "18:00", "24:00");
+        assert!(within(&evening, at(Weekday::Mon, "23:59")));
+        assert!(!within(&evening, at(Weekday::Tue, "00:00")));
+    }
+
+    #[test]
+    fn until_change_counts_to_the_next_boundary() {
+        let work = schedule(&[Weekday::Mon, Weekday::Tue], "09:00", "18:00");
+        let minutes = |minutes: u64| Some(Duration::from_secs(minutes * 60));
+        assert_eq!(until_change(&work, at(Weekday::Mon, "08:00")), minutes(60));
+        assert_eq!(until_change(&work, at(Weekday::Mon, "17:59")), minutes(1));
+        assert_eq!(
+            until_change(&work, at(Weekday::Mon, "18:00")),
+            minutes(15 * 60)
+        );
+        // NOTE: Tuesday evening waits out the rest of the week for Monday
+        assert_eq!(
+            until_change(&work, at(Weekday::Tue, "18:00")),
+            minutes(5 * 24 * 60 + 15 * 60)
+        );
+    }
+
+    #[test]
+    fn until_change_leaves_out_the_current_seconds() {
+        let work = schedule(&[Weekday::Mon], "09:00", "18:00");
+        let moment = Moment {
+            second: 20,
+            ..at(Weekday::Mon, "08:59")
+        };
+        assert_eq!(until_change(&work, moment), Some(Duration::from_secs(40)));
+    }
+
+    #[test]
+    fn schedule_that_never_switches_has_no_change() {
+        let always = schedule(&Weekday::ALL, "00:00", "00:00");
+        assert_eq!(until_change(&always, at(Weekday::Mon, "12:00")), None);
+        let never = schedule(&[], "09:00", "18:00");
+        assert_eq!(until_change(&never, at(Weekday::Mon, "12:00")), None);
+        assert_eq!(resumes_at(&never, at(Weekday::Mon, "12:00")), None);
+    }
+
+    #[test]
+    fn resumes_at_gives_the_clock_time() {
+        let work = schedule(&[Weekday::Mon, Weekday::Tue], "09:00", "18:00");
+        let moment = Moment {
+            second: 30,
+            ..at(Weekday::Mon, "20:15")
+        };
+        assert_eq!(resumes_at(&work, moment), Some(ClockTime(9 * 60)));
+    }
+}
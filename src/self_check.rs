@@ -0,0 +1,129 @@
+use crate::i18n::{t, tf};
+use rodio::cpal::traits::{DeviceTrait, HostTrait};
+use std::path::Path;
+
+pub struct CheckResult {
+    pub ok: bool,
+    pub message: String,
+}
+
+impl CheckResult {
+    fn new(ok: bool, message: impl Into<String>) -> Self {
+        Self {
+            ok,
+            message: message.into(),
+        }
+    }
+}
+
+/// Results of the first-run environment check, shown in a small dialog.
+pub struct SelfCheck {
+    pub results: Vec<CheckResult>,
+}
+
+impl SelfCheck {
+    pub fn run(
+        alpha_modes: &[wgpu::CompositeAlphaMode],
+        frame_count: Option<usize>,
+        config_written: Option<&std::io::Result<()>>,
+    ) -> Self {
+        let mut results = Vec::new();
+
+        let output = rodio::cpal::default_host().default_output_device();
+        results.push(match output {
+            Some(device) => CheckResult::new(
+                true,
+                tf(
+                    "self_check.audio_output",
+                    &[(
+                        "device",
+                        &device
+                            .name()
+                            .unwrap_or_else(|_| t("self_check.unknown_device").into()),
+                    )],
+                ),
+            ),
+            None => CheckResult::new(false, t("self_check.no_audio")),
+        });
+
+        let sound = Path::new(crate::SOUND_PATH);
+        results.push(if sound.exists() {
+            CheckResult::new(true, t("self_check.sound_found"))
+        } else {
+            CheckResult::new(
+                false,
+                tf("self_check.sound_missing", &[("path", &sound.display())]),
+            )
+        });
+
+        results.push(match frame_count {
+            None => CheckResult::new(true, t("self_check.procedural")),
+            Some(0) => CheckResult::new(false, t("self_check.no_frames")),
+            Some(count) => CheckResult::new(true, tf("self_check.frames", &[("count", &count)])),
+        });
+
+        let composited = crate::platform::composition_enabled();
+        let alpha = alpha_modes
+            .iter()
+            .any(|mode| *mode != wgpu::CompositeAlphaMode::Opaque);
+        results.push(if composited {
+            CheckResult::new(true, t("self_check.composition_on"))
+        } else {
+            CheckResult::new(false, t("self_check.composition_off"))
+        });
+        if !alpha {
+            log::info!("surface only reports opaque alpha modes: {alpha_modes:?}");
+        }
+
+        match config_written {
+            Some(Ok(())) => results.push(CheckResult::new(
+                true,
+                tf(
+                    "self_check.config_written",
+                    &[("path", &crate::storage::config_path().display())],
+                ),
+            )),
+            Some(Err(e)) => results.push(CheckResult::new(
+                false,
+                tf("self_check.config_failed", &[("error", e)]),
+            )),
+            None => {}
+        }
+
+        results.push(CheckResult::new(true, t("self_check.demo")));
+
+        for result in &results {
+            log::info!(
+                "self-check {}: {}",
+                if result.ok { "ok" } else { "failed" },
+                result.message
+            );
+        }
+        Self { results }
+    }
+
+    /// Returns `true` when the user dismissed the dialog.
+    pub fn ui(&self, ctx: &egui::Context) -> bool {
+        let mut close = false;
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.heading(t("self_check.heading"));
+            ui.add_space(8.0);
+            for result in &self.results {
+                let (icon, color) = if result.ok {
+                    ("✔", egui::Color32::LIGHT_GREEN)
+                } else {
+                    ("⚠", egui::Color32::GOLD)
+                };
+                ui.horizontal_wrapped(|ui| {
+                    ui.colored_label(color, icon);
+                    ui.label(&result.message);
+                });
+            }
+            ui.add_space(8.0);
+            ui.label(t("self_check.hint"));
+            ui.add_space(8.0);
+            close = ui.button(t("dialog.close")).clicked();
+        });
+        close
+    }
+}
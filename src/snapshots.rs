@@ -0,0 +1,221 @@
+//! Rendering regression checks: fixed scenes drawn off-screen and compared
+//! against the reference PNGs in `snapshots/`. Needs a GPU, so it's a build
+//! feature run by hand rather than part of CI:
+//!
+//! `cargo run --features snapshots -- --snapshots check` (or `update` to
+//! rewrite the references after an intended change).
+
+use crate::animation_builder::AnimationBuilder;
+use crate::offscreen::{unpremultiply, Canvas};
+use crate::ping_source::PingSource;
+use crate::{config, marker, theme, Animation, MyApp, MARKER_FRAME};
+use image::RgbaImage;
+use std::path::Path;
+
+const SIZE: [u32; 2] = [800, 600];
+/// Channels may differ this much between GPUs and drivers without counting.
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Share of pixels allowed past [`CHANNEL_TOLERANCE`], for antialiasing.
+const MAX_DIFFERING: f32 = 0.005;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Compares against the references, failing past the tolerances.
+    Check,
+    /// Writes the references anew.
+    Update,
+}
+
+/// Value of `--snapshots check|update`, if passed.
+pub fn mode_from_args() -> Option<Result<Mode, String>> {
+    let mut args = std::env::args().skip_while(|arg| arg != "--snapshots");
+    args.next()?;
+    Some(match args.next().as_deref() {
+        Some("check") => Ok(Mode::Check),
+        Some("update") => Ok(Mode::Update),
+        _ => Err("--snapshots expects check or update".into()),
+    })
+}
+
+/// One fixed state of the overlay.
+struct Scene {
+    name: &'static str,
+    style: marker::Style,
+    animations: Vec<Animation>,
+}
+
+fn scenes() -> Vec<Scene> {
+    let ping = |position, frame| AnimationBuilder::at(position).start_frame(frame);
+    let artwork = vec![
+        ping((150, 150), 10).build().animation,
+        ping((450, 150), 20).sequence(Some(3), 0).build().animation,
+        ping((650, 450), 5).denied().build().animation,
+        ping((250, 420), 0)
+            .persistent(true)
+            .start_frame(MARKER_FRAME)
+            .zone(Some("Lights".into()))
+            .note(Some("Look here".into()))
+            .build()
+            .animation,
+        ping((500, 420), 25)
+            .source(PingSource::Ipc)
+            .build()
+            .animation,
+    ];
+    let procedural = |name, style| Scene {
+        name,
+        style,
+        animations: vec![
+            ping((250, 300), 10).build().animation,
+            ping((550, 300), 40).build().animation,
+        ],
+    };
+    vec![
+        Scene {
+            name: "artwork",
+            style: marker::Style::Gif,
+            animations: artwork,
+        },
+        procedural("ring", marker::Style::Ring),
+        procedural("crosshair", marker::Style::Crosshair),
+        procedural("arrow", marker::Style::Arrow),
+    ]
+}
+
+/// Draws every scene and checks or updates its reference in `dir`. `Err`
+/// lists the scenes that differ; their renders are left next to the
+/// references as `<name>.actual.png`.
+pub fn run(mode: Mode, dir: &Path) -> Result<(), String> {
+    let mut canvas = Canvas::new(SIZE)?;
+    let mut failed = Vec::new();
+    std::fs::create_dir_all(dir).map_err(|e| format!("failed to create {}: {e}", dir.display()))?;
+
+    for scene in scenes() {
+        let name = scene.name;
+        match (mode, snapshot(&mut canvas, scene, mode, dir)) {
+            (Mode::Update, Ok(())) => println!("updated {name}"),
+            (Mode::Update, Err(e)) => return Err(e),
+            (Mode::Check, Ok(())) => println!("{name}: ok"),
+            (Mode::Check, Err(reason)) => {
+                println!("{name}: {reason}");
+                failed.push(name);
+            }
+        }
+    }
+    match failed.is_empty() {
+        true => Ok(()),
+        false => Err(format!("snapshots differ: {}", failed.join(", "))),
+    }
+}
+
+/// Draws `scene` and checks or updates its reference in `dir`. A render
+/// that differs is left next to the reference as `<name>.actual.png`.
+fn snapshot(canvas: &mut Canvas, scene: Scene, mode: Mode, dir: &Path) -> Result<(), String> {
+    let mut config = config::Config::default();
+    // NOTE: the ring follows the wall clock, it would never render the same twice
+    config.expiry_ring.enabled = false;
+    config.marker.style = scene.style;
+    let mut app = MyApp::new(config, theme::load(&config::Theme::default()));
+    for (i, mut animation) in scene.animations.into_iter().enumerate() {
+        animation.id = i + 1;
+        app.add_animation(animation);
+    }
+    let desktop = egui::Vec2::new(SIZE[0] as f32, SIZE[1] as f32);
+    let mut image = canvas.render(&mut app, desktop, 1.0)?;
+    unpremultiply(&mut image);
+
+    let reference = dir.join(format!("{}.png", scene.name));
+    match mode {
+        Mode::Update => image
+            .save(&reference)
+            .map_err(|e| format!("failed to write {}: {e}", reference.display())),
+        Mode::Check => compare(&image, &reference).inspect_err(|_| {
+            image
+                .save(dir.join(format!("{}.actual.png", scene.name)))
+                .ok();
+        }),
+    }
+}
+
+fn compare(image: &RgbaImage, reference: &Path) -> Result<(), String> {
+    let expected = image::open(reference)
+        .map_err(|e| format!("no reference ({e}), run with update first"))?
+        .into_rgba8();
+    if expected.dimensions() != image.dimensions() {
+        return Err(format!(
+            "size {:?}, the reference is {:?}",
+            image.dimensions(),
+            expected.dimensions()
+        ));
+    }
+    let differing = image
+        .pixels()
+        .zip(expected.pixels())
+        .filter(|(a, b)| {
+            a.0.iter()
+                .zip(b.0.iter())
+                .any(|(a, b)| a.abs_diff(*b) > CHANNEL_TOLERANCE)
+        })
+        .count();
+    let share = differing as f32 / (SIZE[0] * SIZE[1]) as f32;
+    if share > MAX_DIFFERING {
+        return Err(format!("{differing} pixels differ ({:.2}%)", share * 100.0));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Checks the scene `name` against its reference in `snapshots/`.
+    // NOTE: ignored by default, they need a GPU; run them with
+    // `cargo test --features snapshots -- --ignored`
+    fn matches_reference(name: &str) {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        let scene = scenes()
+            .into_iter()
+            .find(|scene| scene.name == name)
+            .expect("the scene exists");
+        let mut canvas = Canvas::new(SIZE).unwrap();
+        if let Err(reason) = snapshot(&mut canvas, scene, Mode::Check, &dir) {
+            panic!("{name}: {reason}");
+        }
+    }
+
+    #[test]
+    #[ignore = "needs a GPU"]
+    fn artwork_matches_its_snapshot() {
+        matches_reference("artwork");
+    }
+
+    #[test]
+    #[ignore = "needs a GPU"]
+    fn ring_matches_its_snapshot() {
+        matches_reference("ring");
+    }
+
+    #[test]
+    #[ignore = "needs a GPU"]
+    fn crosshair_matches_its_snapshot() {
+        matches_reference("crosshair");
+    }
+
+    #[test]
+    #[ignore = "needs a GPU"]
+    fn arrow_matches_its_snapshot() {
+        matches_reference("arrow");
+    }
+
+    #[test]
+    fn every_scene_has_a_reference() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("snapshots");
+        for scene in scenes() {
+            let reference = dir.join(format!("{}.png", scene.name));
+            let image = image::open(&reference)
+                .unwrap_or_else(|e| panic!("{}: {e}", reference.display()))
+                .into_rgba8();
+            assert_eq!(image.dimensions(), (SIZE[0], SIZE[1]), "{}", scene.name);
+        }
+    }
+}
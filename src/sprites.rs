@@ -0,0 +1,464 @@
+//! A bare wgpu pipeline drawing theme artwork as textured quads, one instance
+//! per ping and one texture array per frame set, for
+//! `[overlay] renderer = "sprites"`. Rings, badges, labels and procedural
+//! markers stay with egui, whose pass is skipped when it has nothing to draw.
+
+use crate::protocol::PingKind;
+use crate::theme::Theme;
+use egui::ImageSource;
+use std::collections::{HashMap, HashSet};
+
+const SHADER: &str = r#"
+struct Screen {
+    size: vec2<f32>,
+    _padding: vec2<f32>,
+};
+
+@group(0) @binding(0) var<uniform> screen: Screen;
+@group(1) @binding(0) var frames: texture_2d_array<f32>;
+@group(1) @binding(1) var frames_sampler: sampler;
+
+struct Instance {
+    // min x, min y, width, height, in pixels
+    @location(0) rect: vec4<f32>,
+    @location(1) layer: u32,
+    @location(2) alpha: f32,
+    // min u, min v, width, height, the part of the frame shown
+    @location(3) uv: vec4<f32>,
+};
+
+struct VertexOut {
+    @builtin(position) position: vec4<f32>,
+    @location(0) uv: vec2<f32>,
+    @location(1) @interpolate(flat) layer: u32,
+    @location(2) alpha: f32,
+};
+
+@vertex
+fn vs_main(@builtin(vertex_index) vertex: u32, instance: Instance) -> VertexOut {
+    let corner = vec2<f32>(f32(vertex & 1u), f32(vertex >> 1u));
+    let pixel = instance.rect.xy + corner * instance.rect.zw;
+    var out: VertexOut;
+    out.position = vec4<f32>(
+        pixel.x / screen.size.x * 2.0 - 1.0,
+        1.0 - pixel.y / screen.size.y * 2.0,
+        0.0,
+        1.0,
+    );
+    out.uv = instance.uv.xy + corner * instance.uv.zw;
+    out.layer = instance.layer;
+    out.alpha = instance.alpha;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOut) -> @location(0) vec4<f32> {
+    let color = textureSample(frames, frames_sampler, in.uv, in.layer);
+    let alpha = color.a * in.alpha;
+    return vec4<f32>(color.rgb * alpha, alpha);
+}
+"#;
+
+/// Bytes per instance: the rect, the layer, the alpha and the texture rect.
+const INSTANCE_SIZE: u64 = 10 * 4;
+/// Frame sets kept on the GPU; older themes' sets are dropped past this.
+const MAX_SETS: usize = 16;
+
+/// One ping's artwork for this frame.
+pub struct Sprite {
+    pub kind: PingKind,
+    /// Index into the kind's frames.
+    pub layer: usize,
+    /// Where the frame is drawn, in surface pixels.
+    pub rect: egui::Rect,
+    pub alpha: f32,
+    /// Where the frame is cut off, the edges of its monitor.
+    pub clip: Option<egui::Rect>,
+}
+
+/// Sprites collected during one egui pass, drawn on top of it.
+#[derive(Default)]
+pub struct Batch {
+    /// Kinds whose frames are uploaded, the rest are drawn by egui.
+    ready: HashSet<PingKind>,
+    pub sprites: Vec<Sprite>,
+}
+
+impl Batch {
+    pub fn accepts(&self, kind: &PingKind) -> bool {
+        self.ready.contains(kind)
+    }
+}
+
+/// The frames of one kind as layers of a single texture.
+struct FrameSet {
+    bind_group: wgpu::BindGroup,
+    /// Size of the frames as decoded, before fitting into a sprite's rect.
+    size: egui::Vec2,
+    layers: usize,
+}
+
+pub struct SpriteRenderer {
+    pipeline: wgpu::RenderPipeline,
+    frames_layout: wgpu::BindGroupLayout,
+    sampler: wgpu::Sampler,
+    screen: wgpu::Buffer,
+    screen_group: wgpu::BindGroup,
+    instances: wgpu::Buffer,
+    /// Frame sets by the uri of their first frame, `None` for sets that
+    /// failed to upload and stay with egui.
+    sets: HashMap<String, Option<FrameSet>>,
+}
+
+impl SpriteRenderer {
+    pub fn new(device: &wgpu::Device, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("sprites"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+
+        let screen_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprites screen"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let frames_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("sprites frames"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                        view_dimension: wgpu::TextureViewDimension::D2Array,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
+                },
+            ],
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("sprites"),
+            bind_group_layouts: &[&screen_layout, &frames_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some("sprites"),
+            layout: Some(&pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &[wgpu::VertexBufferLayout {
+                    array_stride: INSTANCE_SIZE,
+                    step_mode: wgpu::VertexStepMode::Instance,
+                    attributes: &wgpu::vertex_attr_array![
+                        0 => Float32x4,
+                        1 => Uint32,
+                        2 => Float32,
+                        3 => Float32x4,
+                    ],
+                }],
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format,
+                    // NOTE: the same blending as egui's, so both layers mix alike
+                    blend: Some(wgpu::BlendState::PREMULTIPLIED_ALPHA_BLENDING),
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: wgpu::PrimitiveTopology::TriangleStrip,
+                ..Default::default()
+            },
+            depth_stencil: None,
+            multisample: wgpu::MultisampleState::default(),
+            multiview: None,
+        });
+
+        let screen = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("sprites screen"),
+            size: 16,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let screen_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprites screen"),
+            layout: &screen_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: screen.as_entire_binding(),
+            }],
+        });
+        let sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+            label: Some("sprites"),
+            mag_filter: wgpu::FilterMode::Linear,
+            min_filter: wgpu::FilterMode::Linear,
+            ..Default::default()
+        });
+
+        Self {
+            pipeline,
+            frames_layout,
+            sampler,
+            screen,
+            screen_group,
+            instances: instance_buffer(device, 16),
+            sets: HashMap::new(),
+        }
+    }
+
+    /// Uploads the frames of `kinds` not on the GPU yet and returns a batch
+    /// accepting those that made it.
+    pub fn prepare<'a>(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        theme: &Theme,
+        kinds: impl IntoIterator<Item = &'a PingKind>,
+    ) -> Batch {
+        let mut ready = HashSet::new();
+        for kind in kinds {
+            let frames = theme.frames_for(kind);
+            let Some(key) = frames.first().and_then(ImageSource::uri).map(str::to_owned) else {
+                continue;
+            };
+            if !self.sets.contains_key(&key) {
+                if self.sets.len() >= MAX_SETS {
+                    self.sets.clear();
+                }
+                let set = self.upload(device, queue, frames);
+                self.sets.insert(key.clone(), set);
+            }
+            if self.sets[&key].is_some() {
+                ready.insert(kind.clone());
+            }
+        }
+        Batch {
+            ready,
+            sprites: Vec::new(),
+        }
+    }
+
+    /// Records drawing `sprites` into `view`, loaded with `load`.
+    #[allow(clippy::too_many_arguments)]
+    pub fn draw(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        view: &wgpu::TextureView,
+        load: wgpu::LoadOp<wgpu::Color>,
+        size: [u32; 2],
+        theme: &Theme,
+        sprites: &[Sprite],
+    ) {
+        // NOTE: runs of sprites sharing a frame set draw with one call
+        let mut runs: Vec<(&FrameSet, std::ops::Range<u32>)> = Vec::new();
+        let mut bytes = Vec::with_capacity(sprites.len() * INSTANCE_SIZE as usize);
+        for sprite in sprites {
+            let frames = theme.frames_for(&sprite.kind);
+            let Some(Some(set)) = frames
+                .first()
+                .and_then(ImageSource::uri)
+                .and_then(|uri| self.sets.get(uri))
+            else {
+                continue;
+            };
+            if sprite.layer >= set.layers {
+                continue;
+            }
+            let rect = crate::layout::fit(set.size, sprite.rect);
+            let full = egui::Rect::from_min_size(egui::Pos2::ZERO, egui::Vec2::splat(1.0));
+            let (rect, uv) = match sprite.clip {
+                Some(clip) => match crate::layout::clip(rect, clip) {
+                    Some(clipped) => clipped,
+                    None => continue,
+                },
+                None => (rect, full),
+            };
+            for value in [rect.min.x, rect.min.y, rect.width(), rect.height()] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+            bytes.extend_from_slice(&(sprite.layer as u32).to_le_bytes());
+            bytes.extend_from_slice(&sprite.alpha.to_le_bytes());
+            for value in [uv.min.x, uv.min.y, uv.width(), uv.height()] {
+                bytes.extend_from_slice(&value.to_le_bytes());
+            }
+
+            let index = (bytes.len() as u64 / INSTANCE_SIZE - 1) as u32;
+            match runs.last_mut() {
+                Some((last, range)) if std::ptr::eq(*last, set) => range.end = index + 1,
+                _ => runs.push((set, index..index + 1)),
+            }
+        }
+        if runs.is_empty() {
+            return;
+        }
+
+        if self.instances.size() < bytes.len() as u64 {
+            self.instances = instance_buffer(device, bytes.len() as u64 / INSTANCE_SIZE);
+        }
+        queue.write_buffer(&self.instances, 0, &bytes);
+        let screen = [size[0] as f32, size[1] as f32, 0.0, 0.0];
+        queue.write_buffer(
+            &self.screen,
+            0,
+            &screen
+                .iter()
+                .flat_map(|v| v.to_le_bytes())
+                .collect::<Vec<_>>(),
+        );
+
+        let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+            label: Some("sprites"),
+            color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                view,
+                resolve_target: None,
+                ops: wgpu::Operations { load, store: true },
+            })],
+            depth_stencil_attachment: None,
+        });
+        pass.set_pipeline(&self.pipeline);
+        pass.set_bind_group(0, &self.screen_group, &[]);
+        pass.set_vertex_buffer(0, self.instances.slice(..));
+        for (set, range) in runs {
+            pass.set_bind_group(1, &set.bind_group, &[]);
+            pass.draw(0..4, range);
+        }
+    }
+
+    /// Decodes `frames` into the layers of one texture. Frames of another
+    /// size than the first are scaled to it. `None` when the set can't be a
+    /// texture, it's logged and left to egui.
+    fn upload(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        frames: &[ImageSource<'static>],
+    ) -> Option<FrameSet> {
+        let mut images = Vec::with_capacity(frames.len());
+        for frame in frames {
+            let ImageSource::Bytes { uri, bytes } = frame else {
+                log::warn!(
+                    "frame {:?} isn't in memory, drawing it with egui",
+                    frame.uri()
+                );
+                return None;
+            };
+            match image::load_from_memory(bytes) {
+                Ok(image) => images.push(image.to_rgba8()),
+                Err(e) => {
+                    log::warn!("failed to decode {uri}, drawing it with egui: {e}");
+                    return None;
+                }
+            }
+        }
+        let (width, height) = images.first()?.dimensions();
+        let limits = device.limits();
+        if images.len() > limits.max_texture_array_layers as usize
+            || width.max(height) > limits.max_texture_dimension_2d
+        {
+            log::warn!(
+                "{} frames of {width}x{height} don't fit in a texture, drawing them with egui",
+                images.len()
+            );
+            return None;
+        }
+
+        let size = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: images.len() as u32,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("sprite frames"),
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            view_formats: &[],
+        });
+        for (layer, image) in images.into_iter().enumerate() {
+            let image = if image.dimensions() == (width, height) {
+                image
+            } else {
+                image::imageops::resize(&image, width, height, image::imageops::Triangle)
+            };
+            queue.write_texture(
+                wgpu::ImageCopyTexture {
+                    texture: &texture,
+                    mip_level: 0,
+                    origin: wgpu::Origin3d {
+                        x: 0,
+                        y: 0,
+                        z: layer as u32,
+                    },
+                    aspect: wgpu::TextureAspect::All,
+                },
+                &image,
+                wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(4 * width),
+                    rows_per_image: Some(height),
+                },
+                wgpu::Extent3d {
+                    depth_or_array_layers: 1,
+                    ..size
+                },
+            );
+        }
+
+        let view = texture.create_view(&wgpu::TextureViewDescriptor {
+            dimension: Some(wgpu::TextureViewDimension::D2Array),
+            ..Default::default()
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("sprite frames"),
+            layout: &self.frames_layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::Sampler(&self.sampler),
+                },
+            ],
+        });
+        Some(FrameSet {
+            bind_group,
+            size: egui::Vec2::new(width as f32, height as f32),
+            layers: size.depth_or_array_layers as usize,
+        })
+    }
+}
+
+fn instance_buffer(device: &wgpu::Device, instances: u64) -> wgpu::Buffer {
+    device.create_buffer(&wgpu::BufferDescriptor {
+        label: Some("sprite instances"),
+        size: instances.next_power_of_two() * INSTANCE_SIZE,
+        usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    })
+}
@@ -0,0 +1,71 @@
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Session counters, bumped by the listener and persisted across restarts.
+#[derive(Default)]
+pub struct Stats {
+    pub pings: AtomicU64,
+    pub markers: AtomicU64,
+    pub dropped: AtomicU64,
+}
+
+impl Stats {
+    pub fn snapshot(&self) -> Counters {
+        Counters {
+            pings: self.pings.load(Ordering::Relaxed),
+            markers: self.markers.load(Ordering::Relaxed),
+            dropped: self.dropped.load(Ordering::Relaxed),
+        }
+    }
+
+    pub fn restore(&self, counters: &Counters) {
+        self.pings.store(counters.pings, Ordering::Relaxed);
+        self.markers.store(counters.markers, Ordering::Relaxed);
+        self.dropped.store(counters.dropped, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Counters {
+    pub pings: u64,
+    pub markers: u64,
+    pub dropped: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Marker {
+    pub x: i32,
+    pub y: i32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub note: Option<String>,
+}
+
+/// Everything that survives a restart: persistent markers and counters.
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct SessionState {
+    pub markers: Vec<Marker>,
+    pub counters: Counters,
+}
+
+impl SessionState {
+    /// Reads the state file. A missing or corrupt file yields `None`.
+    pub fn load() -> Option<Self> {
+        let path = crate::storage::state_path();
+        let contents = std::fs::read_to_string(&path).ok()?;
+        match serde_json::from_str(&contents) {
+            Ok(state) => Some(state),
+            Err(e) => {
+                log::warn!("ignoring corrupt state file {}: {e}", path.display());
+                None
+            }
+        }
+    }
+
+    pub fn save(&self) {
+        let path = crate::storage::state_path();
+        let contents = serde_json::to_string_pretty(self).expect("state is always serializable");
+        if let Err(e) = std::fs::write(&path, contents) {
+            log::warn!("failed to write state file {}: {e}", path.display());
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! Where everything the app persists lives: `%APPDATA%\screen-pinger` when
+//! installed, or a `data` folder next to the executable when portable. An
+//! instance started with `--instance <name>` keeps its own under
+//! `instances\<name>` there. Paths of persisted files are only built here.
+
+use std::path::{Path, PathBuf};
+
+const APP_DIR: &str = "screen-pinger";
+const LOG_FILE: &str = "screen-pinger.log";
+const STATE_FILE: &str = "state.json";
+const CONFIG_FILE: &str = "config.toml";
+const PACKS_DIR: &str = "packs";
+const SOUNDS_DIR: &str = "sounds";
+/// Next to the executable, makes the install portable.
+const PORTABLE_FLAG: &str = "portable.flag";
+/// Next to the executable, the data folder of a portable install.
+const PORTABLE_DIR: &str = "data";
+/// In the data folder, one folder per named instance.
+const INSTANCES_DIR: &str = "instances";
+
+/// Everything the app persists, as locale key and path, in the order it's
+/// removed by [`remove_all`]. New persisted files go here so the cleanup
+/// doesn't miss them.
+fn artifacts() -> Vec<(&'static str, PathBuf)> {
+    let dir = base_dir();
+    let mut artifacts = vec![
+        ("storage.config", dir.join(CONFIG_FILE)),
+        ("storage.state", dir.join(STATE_FILE)),
+        ("storage.log", dir.join(LOG_FILE)),
+    ];
+    // NOTE: a named instance only removes its own folder, the flag is shared
+    let flag = exe_dir()
+        .filter(|_| crate::instance::from_args().is_none())
+        .map(|exe_dir| exe_dir.join(PORTABLE_FLAG));
+    if let Some(flag) = flag {
+        if flag.exists() {
+            artifacts.push(("storage.portable_flag", flag));
+        }
+    }
+    // NOTE: last, it also takes anything left over in it, packs included
+    artifacts.push(("storage.data_dir", dir));
+    artifacts
+}
+
+fn exe_dir() -> Option<PathBuf> {
+    std::env::current_exe()
+        .ok()?
+        .parent()
+        .map(Path::to_path_buf)
+}
+
+/// Whether data is kept next to the executable: run with `--portable` or
+/// with a `portable.flag` beside it. Checked on every call, so a migration
+/// applies without a restart.
+pub fn is_portable() -> bool {
+    std::env::args().any(|arg| arg == "--portable")
+        || exe_dir().is_some_and(|dir| dir.join(PORTABLE_FLAG).exists())
+}
+
+fn installed_dir() -> PathBuf {
+    let base = std::env::var_os("APPDATA")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("."));
+    base.join(APP_DIR)
+}
+
+fn base_dir() -> PathBuf {
+    let dir = match exe_dir().filter(|_| is_portable()) {
+        Some(exe_dir) => exe_dir.join(PORTABLE_DIR),
+        None => installed_dir(),
+    };
+    match crate::instance::from_args() {
+        Some(instance) => dir
+            .join(INSTANCES_DIR)
+            .join(crate::instance::sanitize(&instance)),
+        None => dir,
+    }
+}
+
+/// Per-user directory for everything the app persists, created on demand.
+pub fn data_dir() -> PathBuf {
+    let dir = base_dir();
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+pub fn log_path() -> PathBuf {
+    data_dir().join(LOG_FILE)
+}
+
+pub fn state_path() -> PathBuf {
+    data_dir().join(STATE_FILE)
+}
+
+pub fn config_path() -> PathBuf {
+    data_dir().join(CONFIG_FILE)
+}
+
+/// Where ping packs are kept, created on demand. The pack picker opens here.
+pub fn packs_dir() -> PathBuf {
+    let dir = data_dir().join(PACKS_DIR);
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+/// Sounds dropped on the app, see [`crate::import`].
+pub fn sounds_dir() -> PathBuf {
+    let dir = data_dir().join(SOUNDS_DIR);
+    std::fs::create_dir_all(&dir).ok();
+    dir
+}
+
+/// Copies `file` into `dir` under its own name, replacing an earlier copy.
+/// Returns the copy's path, `file` itself if it's already there.
+pub fn import(file: &Path, dir: &Path) -> std::io::Result<PathBuf> {
+    let name = file
+        .file_name()
+        .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidInput, "not a file"))?;
+    let copy = dir.join(name);
+    if copy != file {
+        std::fs::copy(file, &copy)?;
+    }
+    Ok(copy)
+}
+
+/// A path from the config, relative ones taken from the data folder so a
+/// portable config works from any drive letter.
+pub fn resolve(path: &Path) -> PathBuf {
+    match path.is_relative() {
+        true => data_dir().join(path),
+        false => path.to_owned(),
+    }
+}
+
+/// The inverse of [`resolve`] for paths written to the config: relative to
+/// the data folder when portable and inside it.
+pub fn shorten(path: &Path) -> PathBuf {
+    let dir = base_dir();
+    match path.strip_prefix(&dir) {
+        Ok(relative) if is_portable() => relative.to_owned(),
+        _ => path.to_owned(),
+    }
+}
+
+/// Copies the data in `%APPDATA%` next to the executable and marks the
+/// install portable. The originals are left in place. Returns the new data
+/// folder.
+pub fn migrate_to_portable() -> std::io::Result<PathBuf> {
+    let exe_dir = exe_dir().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::NotFound, "executable folder unknown")
+    })?;
+    let target = exe_dir.join(PORTABLE_DIR);
+    let source = installed_dir();
+    if source.is_dir() {
+        copy_dir(&source, &target)?;
+    } else {
+        std::fs::create_dir_all(&target)?;
+    }
+    // NOTE: last, a failed copy leaves the install as it was
+    std::fs::write(exe_dir.join(PORTABLE_FLAG), "")?;
+    Ok(target)
+}
+
+fn copy_dir(from: &Path, to: &Path) -> std::io::Result<()> {
+    std::fs::create_dir_all(to)?;
+    for entry in std::fs::read_dir(from)? {
+        let entry = entry?;
+        let target = to.join(entry.file_name());
+        if entry.file_type()?.is_dir() {
+            copy_dir(&entry.path(), &target)?;
+        } else {
+            std::fs::copy(entry.path(), &target)?;
+        }
+    }
+    Ok(())
+}
+
+pub enum Removal {
+    Removed,
+    /// Wasn't there to begin with.
+    Missing,
+    Failed(std::io::Error),
+}
+
+/// Deletes everything the app persisted, step by step, carrying on past
+/// failures. Returns each step's locale key, path and outcome.
+pub fn remove_all() -> Vec<(&'static str, PathBuf, Removal)> {
+    artifacts()
+        .into_iter()
+        .map(|(name, path)| {
+            let removed = if path.is_dir() {
+                std::fs::remove_dir_all(&path)
+            } else {
+                std::fs::remove_file(&path)
+            };
+            let removal = match removed {
+                Ok(()) => Removal::Removed,
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => Removal::Missing,
+                Err(e) => Removal::Failed(e),
+            };
+            (name, path, removal)
+        })
+        .collect()
+}
+
+/// One line per step of [`remove_all`], for the console or a message box.
+pub fn describe(steps: &[(&'static str, PathBuf, Removal)]) -> String {
+    steps
+        .iter()
+        .map(|(name, path, removal)| {
+            let outcome = match removal {
+                Removal::Removed => crate::i18n::t("storage.removed").to_owned(),
+                Removal::Missing => crate::i18n::t("storage.missing").to_owned(),
+                Removal::Failed(e) => crate::i18n::tf("storage.failed", &[("error", e)]),
+            };
+            format!("{} ({}): {outcome}", crate::i18n::t(name), path.display())
+        })
+        .collect::<Vec<_>>()
+        .join("\n")
+}
@@ -0,0 +1,85 @@
+use crate::platform::{self, Edge};
+use serde::{Deserialize, Serialize};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a taskbar query is reused; the taskbar rarely moves.
+const CACHE_TTL: Duration = Duration::from_secs(1);
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Avoidance {
+    /// Pings may draw over (or under) the taskbar.
+    #[default]
+    Off,
+    /// Move the ping itself away from the taskbar when it is fired.
+    Clamp,
+    /// Keep the ping where it was fired but draw the artwork shifted away.
+    Nudge,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Taskbar {
+    edge: Edge,
+    left: i32,
+    top: i32,
+    right: i32,
+    bottom: i32,
+}
+
+impl Taskbar {
+    /// Shifts `center` along the taskbar's edge normal until a `margin`-sized
+    /// artwork around it no longer overlaps the taskbar.
+    pub fn avoid(&self, (x, y): (i32, i32), margin: i32) -> (i32, i32) {
+        let overlaps = x + margin > self.left
+            && x - margin < self.right
+            && y + margin > self.top
+            && y - margin < self.bottom;
+        if !overlaps {
+            return (x, y);
+        }
+
+        match self.edge {
+            Edge::Bottom => (x, self.top - margin),
+            Edge::Top => (x, self.bottom + margin),
+            Edge::Left => (self.right + margin, y),
+            Edge::Right => (self.left - margin, y),
+        }
+    }
+}
+
+/// Taskbar position with a short-lived cache so it can be read every frame.
+#[derive(Default)]
+pub struct TaskbarCache {
+    cache: Mutex<Option<(Instant, Option<Taskbar>)>>,
+}
+
+impl TaskbarCache {
+    pub fn get(&self) -> Option<Taskbar> {
+        let mut cache = self.cache.lock().unwrap();
+        match *cache {
+            Some((queried, taskbar)) if queried.elapsed() < CACHE_TTL => taskbar,
+            _ => {
+                let taskbar =
+                    platform::taskbar_position().map(|(edge, (left, top, right, bottom))| {
+                        Taskbar {
+                            edge,
+                            left,
+                            top,
+                            right,
+                            bottom,
+                        }
+                    });
+                *cache = Some((Instant::now(), taskbar));
+                taskbar
+            }
+        }
+    }
+
+    pub fn avoid(&self, position: (i32, i32), margin: i32) -> (i32, i32) {
+        match self.get() {
+            Some(taskbar) => taskbar.avoid(position, margin),
+            None => position,
+        }
+    }
+}
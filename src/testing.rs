@@ -0,0 +1,371 @@
+//! A headless core for tests: input goes through the interpreter, pings
+//! through the limiter and the animation driver, all on a [`ManualClock`].
+//! What the overlay would be told is collected instead of drawn, so nothing
+//! here needs winit, a window or a GPU.
+
+use crate::animation_builder::AnimationBuilder;
+use crate::clock::{Clock, ManualClock};
+use crate::driver::Driver;
+use crate::input::{Action, Context, InputEvent, Interpreter};
+use crate::keybindings::{Bindings, Conflict};
+use crate::limiter::Limiter;
+use crate::ping_source::PingSource;
+use crate::priming::{DoubleTap, Priming};
+use crate::protocol::PingKind;
+use crate::{click, config, Animation, CustomEvent, DriverCommand, FRAME_TIME};
+use device_query::mouse_state::MousePosition;
+use std::alloc::{GlobalAlloc, Layout, System};
+use std::cell::Cell;
+use std::collections::BTreeSet;
+use std::sync::atomic::AtomicU8;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// The system allocator, counting every allocation and reallocation by the
+/// thread making it.
+pub struct CountingAllocator;
+
+thread_local! {
+    // NOTE: per thread, so tests running alongside don't add to each other's count
+    static ALLOCATIONS: Cell<u64> = const { Cell::new(0) };
+}
+
+fn count_allocation() {
+    // NOTE: fails only while the thread is torn down
+    ALLOCATIONS
+        .try_with(|count| count.set(count.get() + 1))
+        .ok();
+}
+
+unsafe impl GlobalAlloc for CountingAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        count_allocation();
+        System.alloc(layout)
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        System.dealloc(ptr, layout)
+    }
+
+    unsafe fn realloc(&self, ptr: *mut u8, layout: Layout, new_size: usize) -> *mut u8 {
+        count_allocation();
+        System.realloc(ptr, layout, new_size)
+    }
+}
+
+#[global_allocator]
+static ALLOCATOR: CountingAllocator = CountingAllocator;
+
+/// Runs `f` and counts the allocations it made on this thread.
+pub fn allocations_during<R>(f: impl FnOnce() -> R) -> (R, u64) {
+    let before = ALLOCATIONS.with(Cell::get);
+    let result = f();
+    (result, ALLOCATIONS.with(Cell::get) - before)
+}
+
+/// Allocations for one overlay frame of `app` and its pings, drawn headless
+/// with egui. Call it twice on one `app`: the first includes uploading the
+/// frames, the second is what drawing them costs from then on.
+pub fn frame_allocations(app: &mut crate::MyApp, context: &egui::Context) -> u64 {
+    let (_, count) = allocations_during(|| {
+        app.frame(context, egui::RawInput::default(), None);
+    });
+    count
+}
+
+/// What the overlay heard, in order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Event {
+    /// Animation `id` started, or was merged into, at `frame`.
+    Animate {
+        id: usize,
+        frame: u8,
+    },
+    Clear(usize),
+    /// The limiter refused a ping, allowed again after this long.
+    Refused(Duration),
+}
+
+pub struct Harness {
+    pub clock: Arc<ManualClock>,
+    interpreter: Interpreter,
+    limiter: Option<Limiter>,
+    driver: Driver,
+    /// Built and not yet handed to the driver, like the app's queue.
+    queued: Vec<Animation>,
+    events: Vec<Event>,
+    /// Animations on screen, as the overlay would keep them.
+    showing: BTreeSet<usize>,
+}
+
+impl Harness {
+    /// A core set up from `config` as the app would, without the hook, the
+    /// overlay, sound or backdrop sampling.
+    pub fn new(config: &config::Config) -> Result<Self, Conflict> {
+        let input = &config.input;
+        let clock = Arc::new(ManualClock::new());
+        let interpreter = Interpreter::new(
+            Bindings::new(config)?,
+            Priming::new(
+                Duration::try_from_secs_f32(input.prime_expiry_secs)
+                    .unwrap_or(Duration::from_secs(3)),
+            ),
+            input
+                .double_tap
+                .then(|| DoubleTap::new(Duration::from_millis(input.double_tap_ms))),
+            click::Click::new(Duration::from_millis(input.release_timeout_ms)),
+            input.ping_at,
+            config.flick.clone(),
+            config.arrow.clone(),
+        );
+        Ok(Self {
+            driver: Driver::new(None, Arc::new(AtomicU8::new(1)), clock.clone()),
+            clock,
+            interpreter,
+            limiter: Limiter::new(&config.limit),
+            queued: Vec::new(),
+            events: Vec::new(),
+            showing: BTreeSet::new(),
+        })
+    }
+
+    /// Feeds an event as if the hook saw it, firing whatever ping it
+    /// completes. The ping key is trusted to the events, there's no poller.
+    pub fn input(&mut self, event: InputEvent) {
+        let context = Context {
+            now: self.clock.now(),
+            paused: false,
+            prime_down: &|| true,
+            flick: None,
+        };
+        for action in self.interpreter.handle(event, &context) {
+            match action {
+                Action::Released {
+                    target,
+                    kind,
+                    persistent: Some(persistent),
+                    ..
+                } => {
+                    self.ping(target, kind, persistent);
+                }
+                Action::DoubleTapped => {
+                    self.ping((0, 0), PingKind::Missing, false);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Fires a ping past the gesture, as the listener does. Returns its
+    /// animation id, `None` if the limiter refused it.
+    pub fn ping(
+        &mut self,
+        position: MousePosition,
+        kind: PingKind,
+        persistent: bool,
+    ) -> Option<usize> {
+        let now = self.clock.now();
+        if let Some(limiter) = &mut self.limiter {
+            if let Err(wait) = limiter.take(&PingSource::Local, now) {
+                self.events.push(Event::Refused(wait));
+                return None;
+            }
+        }
+        let built = AnimationBuilder::at(position)
+            .kind(kind)
+            .persistent(persistent)
+            .build_at(now);
+        let id = built.animation.id;
+        self.queued.push(built.animation);
+        self.step();
+        Some(id)
+    }
+
+    /// Moves the clock on a frame at a time, the driver stepping after each
+    /// as its thread would. A single jump past [`crate::MAX_CATCH_UP`] reads
+    /// as a stall and cancels whatever is playing.
+    pub fn advance(&mut self, by: Duration) {
+        let mut left = by;
+        loop {
+            let step = left.min(FRAME_TIME);
+            self.clock.advance(step);
+            self.step();
+            left -= step;
+            if left.is_zero() {
+                break;
+            }
+        }
+    }
+
+    /// Finishes everything queued or playing, as pausing does.
+    pub fn clear_all(&mut self) {
+        let Self {
+            driver,
+            queued,
+            events,
+            showing,
+            ..
+        } = self;
+        driver.command(DriverCommand::Drain, queued.drain(..), &mut |event| {
+            record(events, showing, event)
+        });
+    }
+
+    pub fn events(&self) -> &[Event] {
+        &self.events
+    }
+
+    pub fn is_showing(&self, id: usize) -> bool {
+        self.showing.contains(&id)
+    }
+
+    pub fn showing(&self) -> usize {
+        self.showing.len()
+    }
+
+    fn step(&mut self) {
+        let Self {
+            driver,
+            queued,
+            events,
+            showing,
+            ..
+        } = self;
+        driver.tick(queued.drain(..), &mut |event| {
+            record(events, showing, event)
+        });
+    }
+}
+
+fn record(events: &mut Vec<Event>, showing: &mut BTreeSet<usize>, event: CustomEvent) {
+    match event {
+        CustomEvent::Animate(animation) => {
+            showing.insert(animation.id);
+            events.push(Event::Animate {
+                id: animation.id,
+                frame: animation.frame,
+            });
+        }
+        CustomEvent::Clear(id) => {
+            showing.remove(&id);
+            events.push(Event::Clear(id));
+        }
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{ANIMATION_DURATION, MARKER_FRAME};
+    use rdev::{Button, Key};
+
+    fn harness(configure: impl FnOnce(&mut config::Config)) -> Harness {
+        let mut config = config::Config::default();
+        configure(&mut config);
+        Harness::new(&config).unwrap()
+    }
+
+    #[test]
+    fn ping_plays_out_and_clears() {
+        let mut harness = harness(|_| {});
+        let id = harness.ping((10, 10), PingKind::Missing, false).unwrap();
+        assert_eq!(harness.events(), [Event::Animate { id, frame: 0 }]);
+        assert!(harness.is_showing(id));
+
+        harness.advance(ANIMATION_DURATION / 2);
+        assert!(harness.is_showing(id));
+        assert_eq!(harness.events().len(), 1);
+
+        harness.advance(ANIMATION_DURATION);
+        assert_eq!(harness.events().last(), Some(&Event::Clear(id)));
+        assert_eq!(harness.showing(), 0);
+    }
+
+    #[test]
+    fn nothing_moves_until_the_clock_does() {
+        let mut harness = harness(|_| {});
+        let id = harness.ping((10, 10), PingKind::Missing, false).unwrap();
+        for _ in 0..10 {
+            harness.advance(Duration::ZERO);
+        }
+        assert_eq!(harness.events(), [Event::Animate { id, frame: 0 }]);
+    }
+
+    #[test]
+    fn markers_stay_after_their_animation() {
+        let mut harness = harness(|_| {});
+        let id = harness.ping((10, 10), PingKind::Missing, true).unwrap();
+        harness.advance(Duration::from_secs(60));
+        assert!(harness.is_showing(id));
+        assert_eq!(harness.events().len(), 1);
+    }
+
+    #[test]
+    fn primed_drag_fires_a_ping() {
+        let mut harness = harness(|_| {});
+        harness.input(InputEvent::KeyDown(Key::Alt));
+        harness.input(InputEvent::ButtonDown(Button::Left, (500, 500)));
+        harness.advance(Duration::from_millis(50));
+        assert_eq!(harness.showing(), 0);
+        harness.input(InputEvent::ButtonUp(Button::Left, (300, 500)));
+        assert_eq!(harness.showing(), 1);
+        // NOTE: an unprimed drag is left to the app under the cursor
+        harness.input(InputEvent::KeyUp(Key::Alt));
+        harness.input(InputEvent::ButtonDown(Button::Left, (500, 500)));
+        harness.input(InputEvent::ButtonUp(Button::Left, (300, 500)));
+        assert_eq!(harness.showing(), 1);
+    }
+
+    #[test]
+    fn limiter_refuses_pings_until_the_cooldown_passes() {
+        let mut harness = harness(|config| {
+            config.limit.enabled = true;
+            config.limit.burst = 2;
+            config.limit.refill_secs = 2.0;
+        });
+        assert!(harness.ping((0, 0), PingKind::Missing, false).is_some());
+        assert!(harness.ping((0, 0), PingKind::Missing, false).is_some());
+        assert_eq!(harness.ping((0, 0), PingKind::Missing, false), None);
+        assert_eq!(
+            harness.events().last(),
+            Some(&Event::Refused(Duration::from_secs(2)))
+        );
+
+        harness.advance(Duration::from_secs(1));
+        assert_eq!(harness.ping((0, 0), PingKind::Missing, false), None);
+        let Some(Event::Refused(wait)) = harness.events().last() else {
+            panic!("the ping should have been refused");
+        };
+        assert!(wait.abs_diff(Duration::from_secs(1)) < Duration::from_millis(1));
+
+        harness.advance(Duration::from_secs(1));
+        assert!(harness.ping((0, 0), PingKind::Missing, false).is_some());
+    }
+
+    #[test]
+    fn clear_all_ends_pings_and_rests_markers() {
+        let mut harness = harness(|_| {});
+        let ping = harness.ping((0, 0), PingKind::Missing, false).unwrap();
+        let marker = harness.ping((5, 5), PingKind::Missing, true).unwrap();
+        harness.advance(FRAME_TIME * 3);
+        harness.clear_all();
+
+        assert!(!harness.is_showing(ping));
+        assert!(harness.is_showing(marker));
+        let events = harness.events();
+        assert!(events.contains(&Event::Clear(ping)));
+        assert_eq!(
+            events.last(),
+            Some(&Event::Animate {
+                id: marker,
+                frame: MARKER_FRAME,
+            })
+        );
+
+        // NOTE: nothing is left playing to come back later
+        let count = harness.events().len();
+        harness.advance(Duration::from_secs(60));
+        assert_eq!(harness.events().len(), count);
+    }
+}
@@ -0,0 +1,140 @@
+//! Every frame of the theme decoded and uploaded once, as egui textures the
+//! app holds on to. Pings then draw a frame by index without going through
+//! egui's image loaders, which decode on first use and keep what they
+//! decoded for good.
+
+use crate::protocol::PingKind;
+use crate::theme::Theme;
+use egui::{ColorImage, ImageSource, TextureHandle, TextureOptions};
+use std::collections::HashMap;
+
+pub struct FrameTextures {
+    /// Textures belong to the context that uploaded them.
+    context: egui::Context,
+    sets: HashMap<PingKind, Vec<TextureHandle>>,
+}
+
+impl FrameTextures {
+    /// Uploads every frame set of `theme`. A set with a frame that can't be
+    /// decoded is left out, its pings go through the image loaders instead.
+    pub fn upload(context: &egui::Context, theme: &Theme) -> Self {
+        let mut sets = HashMap::new();
+        let (mut frames, mut bytes) = (0, 0);
+        for kind in theme.kinds() {
+            let Some(set) = upload_set(context, theme.frames_for(kind)) else {
+                log::warn!(
+                    "{} frames of theme {} left to egui",
+                    kind.as_str(),
+                    theme.name
+                );
+                continue;
+            };
+            frames += set.len();
+            bytes += set.iter().map(|texture| texture.byte_size()).sum::<usize>();
+            sets.insert(kind.clone(), set);
+        }
+        log::info!(
+            "uploaded {frames} frames of theme {}, {:.1} MB",
+            theme.name,
+            bytes as f32 / (1024.0 * 1024.0)
+        );
+        Self {
+            context: context.clone(),
+            sets,
+        }
+    }
+
+    /// Whether these textures can be drawn with `context`.
+    pub fn is_for(&self, context: &egui::Context) -> bool {
+        self.context == *context
+    }
+
+    /// Frame `index` of `kind`, from `Missing`'s set like [`Theme::frames_for`].
+    pub fn get(&self, kind: &PingKind, index: usize) -> Option<&TextureHandle> {
+        self.sets
+            .get(kind)
+            .or_else(|| self.sets.get(&PingKind::Missing))?
+            .get(index)
+    }
+}
+
+fn upload_set(
+    context: &egui::Context,
+    frames: &[ImageSource<'static>],
+) -> Option<Vec<TextureHandle>> {
+    frames
+        .iter()
+        .map(|frame| {
+            let ImageSource::Bytes { uri, bytes } = frame else {
+                return None;
+            };
+            let image = image::load_from_memory(bytes)
+                .map_err(|e| log::warn!("failed to decode {uri}: {e}"))
+                .ok()?
+                .to_rgba8();
+            let size = [image.width() as usize, image.height() as usize];
+            let image = ColorImage::from_rgba_unmultiplied(size, image.as_raw());
+            Some(context.load_texture(uri.as_ref(), image, TextureOptions::LINEAR))
+        })
+        .collect()
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::*;
+    use crate::animation_builder::AnimationBuilder;
+    use crate::testing::{allocations_during, frame_allocations};
+    use crate::{config, MyApp};
+
+    /// An app showing `pings` pings of `theme`'s artwork, all at frame 0.
+    fn app(theme: &Theme, pings: usize) -> MyApp {
+        let mut app = MyApp::new(config::Config::default(), theme.clone());
+        for i in 0..pings {
+            let mut animation = AnimationBuilder::at((100 + 60 * i as i32, 300))
+                .build()
+                .animation;
+            animation.id = i + 1;
+            app.add_animation(animation);
+        }
+        app
+    }
+
+    #[test]
+    fn looking_up_frames_allocates_nothing() {
+        let context = egui::Context::default();
+        let theme = Theme::builtin();
+        let textures = FrameTextures::upload(&context, &theme);
+        let frames = theme.frames_for(&PingKind::Missing).len();
+        let (found, allocations) = allocations_during(|| {
+            (0..frames)
+                .filter(|index| textures.get(&PingKind::Missing, *index).is_some())
+                .count()
+        });
+        assert_eq!(found, frames);
+        assert_eq!(allocations, 0);
+    }
+
+    /// Allocations for a frame of `app` once egui and the textures settled.
+    fn steady_allocations(app: &mut MyApp) -> u64 {
+        let context = egui::Context::default();
+        // NOTE: the first frame uploads every texture, the second settles egui's own state
+        frame_allocations(app, &context);
+        frame_allocations(app, &context);
+        frame_allocations(app, &context)
+    }
+
+    #[test]
+    fn drawing_artwork_allocates_nothing_per_ping() {
+        let theme = Theme::builtin();
+        for pings in [1, 40] {
+            let artwork = steady_allocations(&mut app(&theme, pings));
+            // NOTE: a denied ping gets the same panel and leaves the artwork out
+            let mut denied = app(&theme, pings);
+            for animation in denied.animations.values_mut() {
+                animation.denied = true;
+            }
+            let denied = steady_allocations(&mut denied);
+            assert_eq!(artwork, denied, "{pings} pings");
+        }
+    }
+}
@@ -0,0 +1,666 @@
+//! Ping themes: the frame sets and sound pings are drawn and played with.
+//!
+//! Custom themes ship as `.pingpack` files, zips with a `manifest.toml` at the
+//! root:
+//!
+//! ```toml
+//! name = "My theme"
+//! frame_rate = 60
+//! size = 500.0
+//! sound = "ping.ogg"   # optional, the built-in sound is used otherwise
+//!
+//! [kinds]
+//! missing = "missing"  # ping kind -> folder of frames, ordered by the number in their names
+//! danger = { folder = "danger", size = 300.0, duration_ms = 1500, anchor = "above" }
+//! ```
+
+use crate::config::{self, KindStyle};
+use crate::protocol::PingKind;
+use egui::ImageSource;
+use image::imageops::FilterType;
+use rodio::Decoder;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+
+pub use crate::audio::Sound;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+struct Manifest {
+    name: String,
+    #[serde(default = "default_frame_rate")]
+    frame_rate: u32,
+    #[serde(default = "default_size")]
+    size: f32,
+    sound: Option<String>,
+    kinds: HashMap<String, KindEntry>,
+}
+
+/// A `[kinds]` entry, either just the folder or a table with display defaults.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+enum KindEntry {
+    Folder(String),
+    Styled {
+        folder: String,
+        #[serde(flatten)]
+        style: KindStyle,
+    },
+}
+
+impl KindEntry {
+    fn folder(&self) -> &str {
+        match self {
+            KindEntry::Folder(folder) | KindEntry::Styled { folder, .. } => folder,
+        }
+    }
+}
+
+fn default_frame_rate() -> u32 {
+    60
+}
+
+fn default_size() -> f32 {
+    500.0
+}
+
+#[derive(Debug)]
+pub enum PackError {
+    Io(std::io::Error),
+    Zip(zip::result::ZipError),
+    MissingManifest,
+    InvalidManifest(toml::de::Error),
+    InvalidFrameRate(u32),
+    NoKinds,
+    NoFrames {
+        kind: String,
+        folder: String,
+    },
+    InvalidDuration {
+        kind: String,
+        duration_ms: u32,
+    },
+    MissingSound(String),
+    InvalidSound(String),
+    /// Decoded frames past `theme.memory_budget_mb`, even after shrinking.
+    OverBudget {
+        footprint_mb: f32,
+        budget_mb: u32,
+        max_frame_size: u32,
+    },
+}
+
+impl fmt::Display for PackError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PackError::Io(e) => write!(f, "could not read the pack: {e}"),
+            PackError::Zip(e) => write!(f, "not a valid zip file: {e}"),
+            PackError::MissingManifest => write!(f, "manifest.toml is missing from the zip root"),
+            PackError::InvalidManifest(e) => write!(f, "manifest.toml is invalid: {e}"),
+            PackError::InvalidFrameRate(rate) => {
+                write!(f, "frame_rate must be between 1 and 240, got {rate}")
+            }
+            PackError::NoKinds => write!(f, "manifest.toml lists no [kinds]"),
+            PackError::NoFrames { kind, folder } => {
+                write!(
+                    f,
+                    "kind {kind:?} points at folder {folder:?}, which has no frames"
+                )
+            }
+            PackError::InvalidDuration { kind, duration_ms } => {
+                let range = crate::config::DURATION_RANGE_MS;
+                write!(
+                    f,
+                    "duration_ms of kind {kind:?} must be between {} and {}, got {duration_ms}",
+                    range.start(),
+                    range.end()
+                )
+            }
+            PackError::MissingSound(name) => write!(f, "sound file {name:?} is not in the pack"),
+            PackError::InvalidSound(name) => write!(f, "sound file {name:?} could not be decoded"),
+            PackError::OverBudget {
+                footprint_mb,
+                budget_mb,
+                max_frame_size,
+            } => write!(
+                f,
+                "the frames take {footprint_mb:.0} MB decoded even at {max_frame_size}px at most, \
+                 over the {budget_mb} MB budget; use fewer or smaller frames, or raise \
+                 theme.memory_budget_mb"
+            ),
+        }
+    }
+}
+
+impl From<std::io::Error> for PackError {
+    fn from(e: std::io::Error) -> Self {
+        PackError::Io(e)
+    }
+}
+
+impl From<zip::result::ZipError> for PackError {
+    fn from(e: zip::result::ZipError) -> Self {
+        PackError::Zip(e)
+    }
+}
+
+#[derive(Clone)]
+pub struct Theme {
+    pub name: String,
+    pub frame_rate: u32,
+    /// Width and height pings are drawn at, in pixels.
+    pub size: f32,
+    /// Encoded sound, `None` when there is none to play.
+    pub sound: Option<Arc<[u8]>>,
+    /// Animation frames per ping kind. Kinds without their own set use `Missing`'s.
+    frame_sets: HashMap<PingKind, Vec<ImageSource<'static>>>,
+    /// Display defaults per ping kind from the manifest, same fallback as the frames.
+    styles: HashMap<PingKind, KindStyle>,
+}
+
+/// Makes every loaded theme's URIs unique, egui caches decoded images by URI.
+fn next_generation() -> usize {
+    static GENERATION: AtomicUsize = AtomicUsize::new(0);
+    GENERATION.fetch_add(1, Ordering::Relaxed)
+}
+
+impl Theme {
+    /// The question mark frames embedded in the executable and the sound next to it.
+    pub fn builtin() -> Self {
+        let names = crate::ASSET_DIR.files().map(|f| f.path().to_str().unwrap());
+        let frames = crate::assets::order("built-in", names)
+            .into_iter()
+            .map(|(_, path)| ImageSource::Bytes {
+                uri: ::std::borrow::Cow::Owned(format!("bytes://{path}")),
+                bytes: egui::load::Bytes::Static(
+                    crate::ASSET_DIR.get_file(path).unwrap().contents(),
+                ),
+            })
+            .collect::<Vec<_>>();
+        let decoded = decode_frames("built-in", frames, u32::MAX, Filter::Triangle);
+
+        let sound = match std::fs::read(crate::SOUND_PATH) {
+            Ok(bytes) => Some(Arc::from(bytes)),
+            Err(e) => {
+                log::warn!("ping sound {} unavailable: {e}", crate::SOUND_PATH);
+                None
+            }
+        };
+
+        Self {
+            name: "Built-in".into(),
+            frame_rate: 60,
+            size: 500.0,
+            sound,
+            frame_sets: HashMap::from([(PingKind::Missing, decoded.frames)]),
+            styles: HashMap::new(),
+        }
+    }
+
+    /// Reads and validates a whole `.pingpack`. Nothing is swapped in until
+    /// this returns, so a broken pack leaves the current theme untouched.
+    pub fn load_pack(path: &Path, limits: &config::Theme) -> Result<Self, PackError> {
+        let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+
+        let manifest = match archive.by_name("manifest.toml") {
+            Ok(mut file) => {
+                let mut contents = String::new();
+                file.read_to_string(&mut contents)?;
+                contents
+            }
+            Err(zip::result::ZipError::FileNotFound) => return Err(PackError::MissingManifest),
+            Err(e) => return Err(e.into()),
+        };
+        let manifest: Manifest = toml::from_str(&manifest).map_err(PackError::InvalidManifest)?;
+        if !(1..=240).contains(&manifest.frame_rate) {
+            return Err(PackError::InvalidFrameRate(manifest.frame_rate));
+        }
+        if manifest.kinds.is_empty() {
+            return Err(PackError::NoKinds);
+        }
+
+        let generation = next_generation();
+        let mut builtin = None;
+        let mut frame_sets = HashMap::new();
+        let mut styles = HashMap::new();
+        let (mut footprint, mut shrunk) = (0, 0);
+        for (kind, entry) in &manifest.kinds {
+            let folder = entry.folder();
+            if let KindEntry::Styled { style, .. } = entry {
+                if let Some(duration_ms) = style.duration_ms {
+                    if !crate::config::DURATION_RANGE_MS.contains(&duration_ms) {
+                        return Err(PackError::InvalidDuration {
+                            kind: kind.clone(),
+                            duration_ms,
+                        });
+                    }
+                }
+                styles.insert(PingKind::from(kind.clone()), style.clone());
+            }
+            let prefix = format!("{}/", folder.trim_end_matches('/'));
+            let names = archive
+                .file_names()
+                .filter(|name| name.starts_with(&prefix) && !name.ends_with('/'));
+            let names = crate::assets::order(kind, names)
+                .into_iter()
+                .map(|(_, name)| name.to_owned())
+                .collect::<Vec<_>>();
+            if names.is_empty() {
+                return Err(PackError::NoFrames {
+                    kind: kind.clone(),
+                    folder: folder.to_owned(),
+                });
+            }
+
+            let mut frames = Vec::with_capacity(names.len());
+            for name in names {
+                let bytes = read_entry(&mut archive, &name)?;
+                frames.push(ImageSource::Bytes {
+                    uri: format!("bytes://pack/{generation}/{name}").into(),
+                    bytes: egui::load::Bytes::Shared(bytes),
+                });
+            }
+            let total = frames.len();
+            let decoded =
+                decode_frames(kind, frames, limits.max_frame_size, limits.downscale_filter);
+            let (mut frames, dropped) = (decoded.frames, decoded.dropped);
+            footprint += decoded.footprint;
+            shrunk += decoded.shrunk;
+            if dropped * 2 > total {
+                log::warn!(
+                    "{dropped} of {total} {kind} frames are corrupt, using the built-in ones"
+                );
+                let builtin: &Theme = builtin.get_or_insert_with(Theme::builtin);
+                frames = builtin.frames_for(&PingKind::Missing).to_vec();
+            }
+            frame_sets.insert(PingKind::from(kind.clone()), frames);
+        }
+        let footprint_mb = footprint as f32 / (1024.0 * 1024.0);
+        log::info!(
+            "theme {}: frames take {footprint_mb:.1} MB decoded, {shrunk} shrunk to {}px",
+            manifest.name,
+            limits.max_frame_size
+        );
+        if footprint_mb > limits.memory_budget_mb as f32 {
+            return Err(PackError::OverBudget {
+                footprint_mb,
+                budget_mb: limits.memory_budget_mb,
+                max_frame_size: limits.max_frame_size,
+            });
+        }
+
+        let sound = match &manifest.sound {
+            Some(name) => {
+                let bytes = match read_entry(&mut archive, name) {
+                    Err(PackError::Zip(zip::result::ZipError::FileNotFound)) => {
+                        return Err(PackError::MissingSound(name.clone()))
+                    }
+                    result => result?,
+                };
+                if Decoder::new(Cursor::new(bytes.clone())).is_err() {
+                    return Err(PackError::InvalidSound(name.clone()));
+                }
+                Some(bytes)
+            }
+            None => builtin.unwrap_or_else(Theme::builtin).sound,
+        };
+
+        Ok(Self {
+            name: manifest.name,
+            frame_rate: manifest.frame_rate,
+            size: manifest.size,
+            sound,
+            frame_sets,
+            styles,
+        })
+    }
+
+    pub fn frames_for(&self, kind: &PingKind) -> &[ImageSource<'static>] {
+        self.frame_sets
+            .get(kind)
+            .or_else(|| self.frame_sets.get(&PingKind::Missing))
+            .map_or(&[], Vec::as_slice)
+    }
+
+    /// Manifest display defaults for `kind`, if it has any.
+    pub fn style_for(&self, kind: &PingKind) -> Option<&KindStyle> {
+        match self.frame_sets.contains_key(kind) {
+            true => self.styles.get(kind),
+            false => self.styles.get(&PingKind::Missing),
+        }
+    }
+
+    /// Kinds with their own frames.
+    pub fn kinds(&self) -> impl Iterator<Item = &PingKind> {
+        self.frame_sets.keys()
+    }
+
+    /// Frame of `frames` to show at driver tick `tick`; ticks run at 60 per
+    /// second whatever the theme's frame rate.
+    pub fn frame_index(&self, tick: u8) -> usize {
+        tick as usize * self.frame_rate as usize / 60
+    }
+
+    pub fn decode_sound(&self) -> Option<Sound> {
+        let bytes = self.sound.clone()?;
+        match Sound::decode(bytes) {
+            Ok(sound) => Some(sound),
+            Err(e) => {
+                log::warn!(
+                    "failed to decode the ping sound of theme {}: {e}",
+                    self.name
+                );
+                None
+            }
+        }
+    }
+}
+
+/// How frames larger than `theme.max_frame_size` are shrunk, from fastest to sharpest.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Filter {
+    Nearest,
+    Triangle,
+    CatmullRom,
+    Lanczos3,
+}
+
+impl From<Filter> for FilterType {
+    fn from(filter: Filter) -> Self {
+        match filter {
+            Filter::Nearest => FilterType::Nearest,
+            Filter::Triangle => FilterType::Triangle,
+            Filter::CatmullRom => FilterType::CatmullRom,
+            Filter::Lanczos3 => FilterType::Lanczos3,
+        }
+    }
+}
+
+struct Decoded {
+    /// The usable frames, still in order.
+    frames: Vec<ImageSource<'static>>,
+    /// Corrupt ones left out.
+    dropped: usize,
+    /// Shrunk to fit `max_size`.
+    shrunk: usize,
+    /// Bytes the frames take decoded, as textures.
+    footprint: u64,
+}
+
+/// Decodes every frame up front so a corrupt one is dropped at load time
+/// instead of flickering mid-animation, and shrinks those wider or taller
+/// than `max_size` so they don't take hundreds of MB as textures.
+fn decode_frames(
+    set: &str,
+    frames: Vec<ImageSource<'static>>,
+    max_size: u32,
+    filter: Filter,
+) -> Decoded {
+    let total = frames.len();
+    let (mut shrunk, mut footprint) = (0, 0);
+    let frames = frames
+        .into_iter()
+        .filter_map(|frame| {
+            let ImageSource::Bytes { uri, bytes } = &frame else {
+                return Some(frame);
+            };
+            let image = match image::load_from_memory(bytes) {
+                Ok(image) => image,
+                Err(e) => {
+                    log::warn!("dropping corrupt {set} frame {uri}: {e}");
+                    return None;
+                }
+            };
+            if image.width().max(image.height()) <= max_size {
+                footprint += image.width() as u64 * image.height() as u64 * 4;
+                return Some(frame);
+            }
+            // NOTE: re-encoded, egui's loaders only take the encoded bytes
+            let image = image.resize(max_size, max_size, filter.into());
+            let mut encoded = Cursor::new(Vec::new());
+            if let Err(e) = image.write_to(&mut encoded, image::ImageOutputFormat::Png) {
+                log::warn!("dropping {set} frame {uri}, it could not be shrunk: {e}");
+                return None;
+            }
+            shrunk += 1;
+            footprint += image.width() as u64 * image.height() as u64 * 4;
+            Some(ImageSource::Bytes {
+                uri: uri.clone(),
+                bytes: egui::load::Bytes::Shared(encoded.into_inner().into()),
+            })
+        })
+        .collect::<Vec<_>>();
+    Decoded {
+        dropped: total - frames.len(),
+        frames,
+        shrunk,
+        footprint,
+    }
+}
+
+fn read_entry<R: Read + std::io::Seek>(
+    archive: &mut zip::ZipArchive<R>,
+    name: &str,
+) -> Result<Arc<[u8]>, PackError> {
+    let mut file = archive.by_name(name)?;
+    let mut bytes = Vec::with_capacity(file.size() as usize);
+    file.read_to_end(&mut bytes)?;
+    Ok(bytes.into())
+}
+
+/// Loads the configured theme, falling back to the built-in one.
+pub fn load(config: &config::Theme) -> Theme {
+    let Some(path) = &config.path else {
+        return Theme::builtin();
+    };
+    match Theme::load_pack(&crate::storage::resolve(path), config) {
+        Ok(theme) => {
+            log::info!("loaded theme {} from {}", theme.name, path.display());
+            theme
+        }
+        Err(e) => {
+            let e = crate::error::Error::Asset {
+                path: path.display().to_string(),
+                reason: e.to_string(),
+            };
+            log::warn!("{e}, using the built-in theme");
+            Theme::builtin()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    static CORRUPT: &[u8] = include_bytes!("../tests/fixtures/corrupt-frame.gif");
+
+    fn png() -> Arc<[u8]> {
+        png_of(4, 4)
+    }
+
+    fn png_of(width: u32, height: u32) -> Arc<[u8]> {
+        let image = image::RgbaImage::from_pixel(width, height, image::Rgba([255, 0, 0, 255]));
+        let mut encoded = Cursor::new(Vec::new());
+        image
+            .write_to(&mut encoded, image::ImageOutputFormat::Png)
+            .unwrap();
+        encoded.into_inner().into()
+    }
+
+    fn frame(name: &str, bytes: Arc<[u8]>) -> ImageSource<'static> {
+        ImageSource::Bytes {
+            uri: format!("bytes://test/{name}").into(),
+            bytes: egui::load::Bytes::Shared(bytes),
+        }
+    }
+
+    fn uris(frames: &[ImageSource<'static>]) -> Vec<String> {
+        frames
+            .iter()
+            .map(|frame| match frame {
+                ImageSource::Bytes { uri, .. } => uri.to_string(),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    /// Writes a pack with one `missing` kind holding `frames` in order.
+    fn pack(name: &str, frames: &[&[u8]]) -> std::path::PathBuf {
+        let path = std::env::temp_dir().join(format!(
+            "screen-pinger-{name}-{}.pingpack",
+            std::process::id()
+        ));
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        let options =
+            zip::write::FileOptions::default().compression_method(zip::CompressionMethod::Stored);
+        zip.start_file("manifest.toml", options).unwrap();
+        zip.write_all(b"name = \"Test\"\n[kinds]\nmissing = \"missing\"\n")
+            .unwrap();
+        for (i, bytes) in frames.iter().enumerate() {
+            zip.start_file(format!("missing/frame_{i:02}.png"), options)
+                .unwrap();
+            zip.write_all(bytes).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn corrupt_frames_are_dropped_in_order() {
+        let frames = vec![
+            frame("0", png()),
+            frame("1", CORRUPT.into()),
+            frame("2", png()),
+            frame("3", png()),
+        ];
+        let decoded = decode_frames("test", frames, u32::MAX, Filter::Triangle);
+        assert_eq!(decoded.dropped, 1);
+        assert_eq!(
+            uris(&decoded.frames),
+            ["bytes://test/0", "bytes://test/2", "bytes://test/3"]
+        );
+        assert_eq!(decoded.footprint, 3 * 4 * 4 * 4);
+    }
+
+    #[test]
+    fn pack_with_a_few_corrupt_frames_keeps_the_rest() {
+        let png = png();
+        let path = pack("few-corrupt", &[&png, CORRUPT, &png]);
+        let theme = Theme::load_pack(&path, &config::Theme::default()).unwrap();
+        std::fs::remove_file(path).ok();
+        let frames = uris(theme.frames_for(&PingKind::Missing));
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].ends_with("missing/frame_00.png"));
+        assert!(frames[1].ends_with("missing/frame_02.png"));
+    }
+
+    #[test]
+    fn pack_with_mostly_corrupt_frames_falls_back_to_the_builtin_ones() {
+        let png = png();
+        let path = pack("mostly-corrupt", &[CORRUPT, &png, CORRUPT]);
+        let theme = Theme::load_pack(&path, &config::Theme::default()).unwrap();
+        std::fs::remove_file(path).ok();
+        assert_eq!(
+            uris(theme.frames_for(&PingKind::Missing)),
+            uris(Theme::builtin().frames_for(&PingKind::Missing))
+        );
+    }
+
+    fn dimensions(frame: &ImageSource<'static>) -> (u32, u32) {
+        let ImageSource::Bytes { bytes, .. } = frame else {
+            unreachable!()
+        };
+        let image = image::load_from_memory(bytes).unwrap();
+        (image.width(), image.height())
+    }
+
+    fn limits(max_frame_size: u32, memory_budget_mb: u32) -> config::Theme {
+        config::Theme {
+            max_frame_size,
+            memory_budget_mb,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn large_frames_shrink_to_the_max_size_keeping_their_shape() {
+        let frames = vec![
+            frame("wide", png_of(1200, 600)),
+            frame("tall", png_of(300, 1200)),
+        ];
+        let decoded = decode_frames("test", frames, 600, Filter::Triangle);
+        assert_eq!(decoded.shrunk, 2);
+        assert_eq!(decoded.dropped, 0);
+        assert_eq!(
+            uris(&decoded.frames),
+            ["bytes://test/wide", "bytes://test/tall"]
+        );
+        assert_eq!(dimensions(&decoded.frames[0]), (600, 300));
+        assert_eq!(dimensions(&decoded.frames[1]), (150, 600));
+        assert_eq!(decoded.footprint, (600 * 300 + 150 * 600) * 4);
+    }
+
+    #[test]
+    fn frames_at_the_max_size_are_left_alone() {
+        let bytes = png_of(512, 256);
+        let decoded = decode_frames(
+            "test",
+            vec![frame("0", bytes.clone())],
+            512,
+            Filter::Triangle,
+        );
+        assert_eq!(decoded.shrunk, 0);
+        let ImageSource::Bytes { bytes: kept, .. } = &decoded.frames[0] else {
+            unreachable!()
+        };
+        assert_eq!(&kept[..], &bytes[..]);
+        assert_eq!(decoded.footprint, 512 * 256 * 4);
+    }
+
+    #[test]
+    fn every_filter_shrinks() {
+        for filter in [
+            Filter::Nearest,
+            Filter::Triangle,
+            Filter::CatmullRom,
+            Filter::Lanczos3,
+        ] {
+            let decoded = decode_frames("test", vec![frame("0", png_of(64, 32))], 16, filter);
+            assert_eq!(dimensions(&decoded.frames[0]), (16, 8), "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn pack_over_budget_after_shrinking_is_refused() {
+        let large = png_of(768, 768);
+        let path = pack("over-budget", &[&large, &large]);
+        // NOTE: two 384px frames are a little over 1 MB decoded
+        let result = Theme::load_pack(&path, &limits(384, 1));
+        std::fs::remove_file(path).ok();
+        let Err(error @ PackError::OverBudget { budget_mb: 1, .. }) = result else {
+            panic!("expected an over budget error");
+        };
+        assert!(error.to_string().contains("theme.memory_budget_mb"));
+    }
+
+    #[test]
+    fn shrinking_brings_a_pack_under_budget() {
+        let large = png_of(768, 768);
+        let path = pack("shrunk-under-budget", &[&large]);
+        // NOTE: over 2 MB at full size, a quarter MB once shrunk to 256px
+        let theme = Theme::load_pack(&path, &limits(256, 1)).unwrap();
+        std::fs::remove_file(path).ok();
+        assert_eq!(
+            dimensions(&theme.frames_for(&PingKind::Missing)[0]),
+            (256, 256)
+        );
+    }
+}
@@ -0,0 +1,64 @@
+//! A short confirmation of a setting change, e.g. "Paused for 15 min", at the
+//! bottom of the cursor's monitor. A newer toast replaces the one showing, so
+//! quick changes only show the last value.
+
+use crate::layout::MonitorRect;
+use egui::{Color32, FontId, Painter, Pos2, Rect, Vec2};
+use std::time::{Duration, Instant};
+
+/// How long a toast is up, fade included.
+const SHOWN: Duration = Duration::from_millis(1500);
+const FADE: Duration = Duration::from_millis(400);
+/// Space between the toast and the bottom of the monitor, clear of the taskbar.
+const MARGIN: f32 = 96.0;
+const PADDING: Vec2 = Vec2::new(16.0, 10.0);
+
+#[derive(Debug, Clone)]
+pub struct Toast {
+    text: String,
+    /// Bottom center of the monitor, in desktop pixels.
+    anchor: Pos2,
+    shown: Instant,
+}
+
+impl Toast {
+    pub fn new(text: String, monitor: MonitorRect, now: Instant) -> Self {
+        let anchor = Pos2::new(
+            monitor.x as f32 + monitor.width as f32 / 2.0,
+            (monitor.y + monitor.height as i32) as f32,
+        );
+        Self {
+            text,
+            anchor,
+            shown: now,
+        }
+    }
+
+    /// 1 until the fade starts, then down to 0 once the toast is gone.
+    pub fn opacity(&self, now: Instant) -> f32 {
+        let left = (self.shown + SHOWN).saturating_duration_since(now);
+        (left.as_secs_f32() / FADE.as_secs_f32()).min(1.0)
+    }
+
+    /// Draws the toast, with `offset` from desktop to overlay x.
+    pub fn draw(&self, painter: &Painter, offset: f32, now: Instant) {
+        let opacity = self.opacity(now);
+        let galley = painter.layout_no_wrap(
+            self.text.clone(),
+            FontId::proportional(18.0),
+            Color32::WHITE.gamma_multiply(opacity),
+        );
+        let size = galley.size() + PADDING * 2.0;
+        let center = Pos2::new(
+            self.anchor.x + offset,
+            self.anchor.y - MARGIN - size.y / 2.0,
+        );
+        let rect = Rect::from_center_size(center, size);
+        painter.rect_filled(
+            rect,
+            8.0,
+            Color32::from_black_alpha((200.0 * opacity) as u8),
+        );
+        painter.galley(rect.min + PADDING, galley);
+    }
+}
@@ -0,0 +1,55 @@
+//! Fading dots where the last pings ended, so viewers can follow the path of
+//! a longer explanation.
+
+use crate::config;
+use device_query::mouse_state::MousePosition;
+use egui::{Color32, Painter, Pos2};
+use std::time::{Duration, Instant};
+
+/// How often dots are redrawn while fading. They change slowly, a full rate
+/// redraw would be wasted on them.
+pub const TICK: Duration = Duration::from_millis(250);
+
+#[derive(Debug, Clone, Copy)]
+pub struct TrailPoint {
+    /// Desktop pixels.
+    pub position: MousePosition,
+    /// When the ping's animation ended, the dot fades from there.
+    pub ended: Instant,
+}
+
+/// Adds a dot for a ping that just ended, dropping the oldest past
+/// [`config::Trail::max_dots`].
+pub fn push(trail: &mut Vec<TrailPoint>, point: TrailPoint, config: &config::Trail) {
+    trail.push(point);
+    let excess = trail.len().saturating_sub(config.max_dots);
+    trail.drain(..excess);
+}
+
+/// Drops the dots that have faded out.
+pub fn prune(trail: &mut Vec<TrailPoint>, now: Instant, config: &config::Trail) {
+    let fade = config.fade();
+    trail.retain(|point| now.saturating_duration_since(point.ended) < fade);
+}
+
+/// Draws the dots, fading linearly, with `offset` from desktop to overlay x.
+pub fn draw(
+    painter: &Painter,
+    trail: &[TrailPoint],
+    offset: f32,
+    now: Instant,
+    config: &config::Trail,
+) {
+    let fade = config.fade().as_secs_f32().max(f32::EPSILON);
+    let [r, g, b, a] = config.color;
+    for point in trail {
+        let age = now.saturating_duration_since(point.ended).as_secs_f32();
+        let left = (1.0 - age / fade).clamp(0.0, 1.0);
+        if left == 0.0 {
+            continue;
+        }
+        let color = Color32::from_rgba_unmultiplied(r, g, b, (a as f32 * left) as u8);
+        let center = Pos2::new(point.position.0 as f32 + offset, point.position.1 as f32);
+        painter.circle_filled(center, config.radius, color);
+    }
+}
@@ -0,0 +1,363 @@
+use crate::error::Error;
+use crate::i18n::{t, tf};
+use crate::layout::{Layout, MonitorRect, MonitorRef, Restriction};
+use crate::{config, platform, zones};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::time::Duration;
+use tray_icon::menu::{self, Menu, MenuId};
+use tray_icon::{Icon, TrayIcon, TrayIconBuilder};
+
+/// What a tray menu click asks for, handled on the event loop where the app state lives.
+#[derive(Debug, Clone)]
+pub enum TrayCommand {
+    RelaunchElevated,
+    ClearMarkers,
+    RunSelfCheck,
+    ShowTutorial,
+    CopyDiagnostics,
+    RecordHotkey,
+    ShowLegend,
+    ShowAbout,
+    /// `None` pauses until resumed by hand.
+    Pause(Option<Duration>),
+    PauseUntilTomorrow,
+    Resume,
+    RepairOverlay,
+    RestartRenderer,
+    /// Asks for a pack, then sends `LoadTheme`.
+    PickTheme,
+    /// `None` switches back to the built-in theme.
+    LoadTheme(Option<PathBuf>),
+    /// Asks for a pack, then sends `PreviewThemes`.
+    PickPreview,
+    /// Opens the preview, with the picked pack if there is one.
+    PreviewThemes(Option<PathBuf>),
+    ToggleZone(usize),
+    Restrict(Restriction),
+    /// Asks for confirmation, then sends `RemoveAllData`.
+    ConfirmRemoveAllData,
+    /// Deletes everything persisted and quits.
+    RemoveAllData,
+    /// Asks for confirmation, then sends `MoveToPortable`.
+    ConfirmMoveToPortable,
+    /// Copies the data next to the executable and switches to it.
+    MoveToPortable,
+    /// Reads the config file again and applies what changed.
+    ReloadConfig,
+    /// Starts a new instance and quits, for settings read at startup.
+    Restart,
+}
+
+/// Color of the tray icon's glyph.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IconShade {
+    /// Light on a dark taskbar, dark on a light one.
+    Auto,
+    Light,
+    Dark,
+}
+
+/// How the tray icon is drawn right now.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IconLook {
+    light: bool,
+    /// Side in pixels.
+    size: u32,
+}
+
+impl IconLook {
+    /// The look `shade` asks for with the current taskbar theme and scale.
+    pub fn current(shade: IconShade) -> Self {
+        let light = match shade {
+            IconShade::Auto => !platform::system_uses_light_theme(),
+            IconShade::Light => true,
+            IconShade::Dark => false,
+        };
+        Self {
+            light,
+            size: platform::small_icon_size(),
+        }
+    }
+
+    /// The embedded artwork resampled to the icon size, its dark glyph
+    /// inverted for the light variant.
+    fn render(self) -> Result<Icon, Error> {
+        let asset = |reason: String| Error::Asset {
+            path: "the embedded icon".into(),
+            reason,
+        };
+        let mut image = image::load_from_memory_with_format(crate::ICON, image::ImageFormat::Png)
+            .map_err(|e| asset(e.to_string()))?
+            .resize(self.size, self.size, image::imageops::FilterType::Lanczos3)
+            .into_rgba8();
+        if self.light {
+            for pixel in image.pixels_mut() {
+                for channel in &mut pixel.0[..3] {
+                    *channel = 255 - *channel;
+                }
+            }
+        }
+        let (width, height) = image.dimensions();
+        Icon::from_rgba(image.into_raw(), width, height).map_err(|e| asset(e.to_string()))
+    }
+}
+
+/// The tray icon, the command behind every menu item and the items whose
+/// state follows the app's.
+pub struct Tray {
+    icon: TrayIcon,
+    look: IconLook,
+    routes: HashMap<MenuId, TrayCommand>,
+    resume_item: menu::MenuItem,
+    #[cfg(feature = "webhook")]
+    publisher_item: menu::MenuItem,
+    restrict_menu: menu::Submenu,
+    restrict_items: Vec<(Restriction, menu::CheckMenuItem)>,
+    zone_items: Vec<menu::CheckMenuItem>,
+    tooltip: String,
+}
+
+impl Tray {
+    // NOTE: `config` only decides on the webhook status item
+    #[cfg_attr(not(feature = "webhook"), allow(unused_variables))]
+    pub fn new(
+        config: &config::Config,
+        zones: &zones::Zones,
+        look: IconLook,
+    ) -> Result<Self, Error> {
+        let tray_menu = Menu::new();
+        let mut routes = HashMap::new();
+        let mut item = |text: &'static str, command: TrayCommand| {
+            let text = t(text);
+            let item = menu::MenuItem::new(text, true, None);
+            routes.insert(item.id().clone(), command);
+            item
+        };
+
+        let relaunch_item = item("tray.relaunch", TrayCommand::RelaunchElevated);
+        if !platform::is_elevated() {
+            tray_menu.append(&relaunch_item)?;
+        }
+        let clear_markers_item = item("tray.clear_markers", TrayCommand::ClearMarkers);
+        let self_check_item = item("tray.self_check", TrayCommand::RunSelfCheck);
+        let tutorial_item = item("tray.tutorial", TrayCommand::ShowTutorial);
+        let diagnostics_item = item("tray.diagnostics", TrayCommand::CopyDiagnostics);
+        let hotkey_item = item("tray.hotkey", TrayCommand::RecordHotkey);
+        tray_menu.append_items(&[
+            &clear_markers_item,
+            &self_check_item,
+            &tutorial_item,
+            &diagnostics_item,
+            &hotkey_item,
+        ])?;
+        // NOTE: status only, shown once the publisher first reports in
+        #[cfg(feature = "webhook")]
+        let publisher_item = menu::MenuItem::new(t("tray.webhook_connecting"), false, None);
+        #[cfg(feature = "webhook")]
+        if config.publish.enabled {
+            tray_menu.append(&publisher_item)?;
+        }
+        let legend_item = item("tray.legend", TrayCommand::ShowLegend);
+        tray_menu.append(&legend_item)?;
+
+        let pause_menu = menu::Submenu::new(t("tray.pause"), true);
+        pause_menu.append_items(&[
+            &item("tray.pause_until_resumed", TrayCommand::Pause(None)),
+            &item(
+                "tray.pause_15_minutes",
+                TrayCommand::Pause(Some(Duration::from_secs(15 * 60))),
+            ),
+            &item(
+                "tray.pause_hour",
+                TrayCommand::Pause(Some(Duration::from_secs(60 * 60))),
+            ),
+            &item("tray.pause_tomorrow", TrayCommand::PauseUntilTomorrow),
+        ])?;
+        tray_menu.append(&pause_menu)?;
+        let resume_item = item("tray.resume", TrayCommand::Resume);
+        resume_item.set_enabled(false);
+        tray_menu.append(&resume_item)?;
+        tray_menu.append_items(&[
+            &item("tray.repair", TrayCommand::RepairOverlay),
+            &item("tray.restart_renderer", TrayCommand::RestartRenderer),
+            &item("tray.load_theme", TrayCommand::PickTheme),
+            &item("tray.builtin_theme", TrayCommand::LoadTheme(None)),
+            &item("tray.preview_themes", TrayCommand::PickPreview),
+            &item("tray.reload_config", TrayCommand::ReloadConfig),
+        ])?;
+
+        let remove_data_item = item("tray.remove_data", TrayCommand::ConfirmRemoveAllData);
+        let move_portable_item = (!crate::storage::is_portable())
+            .then(|| item("tray.move_portable", TrayCommand::ConfirmMoveToPortable));
+        let about_item = item("tray.about", TrayCommand::ShowAbout);
+
+        // NOTE: filled in once the monitor layout is known
+        let restrict_menu = menu::Submenu::new(t("tray.restrict"), true);
+        tray_menu.append(&restrict_menu)?;
+
+        let mut zone_items = Vec::new();
+        if !zones.zones().is_empty() {
+            let zones_menu = menu::Submenu::new(t("tray.zones"), true);
+            for (i, zone) in zones.zones().iter().enumerate() {
+                let item = menu::CheckMenuItem::new(&zone.name, true, zone.enabled, None);
+                routes.insert(item.id().clone(), TrayCommand::ToggleZone(i));
+                zones_menu.append(&item)?;
+                zone_items.push(item);
+            }
+            tray_menu.append(&zones_menu)?;
+        }
+        if let Some(move_portable_item) = &move_portable_item {
+            tray_menu.append(move_portable_item)?;
+        }
+        tray_menu.append_items(&[
+            &remove_data_item,
+            &about_item,
+            &menu::PredefinedMenuItem::quit(Some(t("tray.quit"))),
+        ])?;
+
+        let tooltip = t("tooltip.active").to_owned();
+        let icon = TrayIconBuilder::new()
+            .with_menu(Box::new(tray_menu))
+            .with_tooltip(&tooltip)
+            .with_icon(look.render()?)
+            .build()?;
+
+        Ok(Self {
+            icon,
+            look,
+            routes,
+            resume_item,
+            #[cfg(feature = "webhook")]
+            publisher_item,
+            restrict_menu,
+            restrict_items: Vec::new(),
+            zone_items,
+            tooltip,
+        })
+    }
+
+    /// Passes menu clicks on as they come, until `forward` returns `false`.
+    // NOTE: only the ids cross threads, what they mean is decided by `route`
+    pub fn spawn_forwarder(forward: impl Fn(MenuId) -> bool + Send + 'static) {
+        std::thread::spawn(move || {
+            while let Ok(event) = menu::MenuEvent::receiver().recv() {
+                if !forward(event.id) {
+                    break;
+                }
+            }
+        });
+    }
+
+    /// The command behind a clicked item, `None` for ids from items since removed.
+    pub fn route(&self, id: &MenuId) -> Option<TrayCommand> {
+        self.routes.get(id).cloned()
+    }
+
+    /// Redraws the icon if `look` differs from the current one.
+    pub fn show_look(&mut self, look: IconLook) {
+        if look == self.look {
+            return;
+        }
+        log::info!("tray icon redrawn: {look:?}");
+        let updated = look
+            .render()
+            .and_then(|icon| Ok(self.icon.set_icon(Some(icon))?));
+        if let Err(e) = updated {
+            log::warn!("failed to update the tray icon: {e}");
+        }
+        self.look = look;
+    }
+
+    /// Shows `tooltip` and enables "Resume" while `paused`.
+    pub fn show_status(&mut self, tooltip: String, paused: bool) {
+        if tooltip == self.tooltip {
+            return;
+        }
+        self.icon.set_tooltip(Some(&tooltip)).ok();
+        self.resume_item.set_enabled(paused);
+        self.tooltip = tooltip;
+    }
+
+    #[cfg(feature = "webhook")]
+    pub fn show_publisher(&self, connected: bool) {
+        self.publisher_item.set_text(t(if connected {
+            "tray.webhook_connected"
+        } else {
+            "tray.webhook_unreachable"
+        }));
+    }
+
+    /// Replaces the "Restrict pings to" entries with the monitors in `layout`.
+    /// `allowed` is the monitor `current` resolves to.
+    pub fn rebuild_restrict_menu(
+        &mut self,
+        layout: &Layout,
+        current: &Restriction,
+        allowed: Option<MonitorRect>,
+    ) {
+        for (_, item) in self.restrict_items.drain(..) {
+            self.routes.remove(item.id());
+        }
+        while self.restrict_menu.remove_at(0).is_some() {}
+
+        let mut entries = vec![
+            (
+                *current == Restriction::All,
+                Restriction::All,
+                t("tray.restrict_all").to_owned(),
+            ),
+            (
+                *current == Restriction::CursorAtStartup,
+                Restriction::CursorAtStartup,
+                t("tray.restrict_cursor").to_owned(),
+            ),
+        ];
+        // NOTE: a restriction picked by index, or on another layout, still checks its monitor
+        let by_monitor = !matches!(current, Restriction::All | Restriction::CursorAtStartup);
+        for (i, name) in layout.names.iter().enumerate() {
+            // NOTE: picked by name so it sticks to the monitor, the index is the fallback
+            let restriction = Restriction::Monitor(MonitorRef::Either {
+                id: name.clone(),
+                index: i + 1,
+            });
+            entries.push((
+                by_monitor && allowed == Some(layout.monitors[i]),
+                restriction,
+                tf(
+                    "tray.restrict_monitor",
+                    &[("index", &(i + 1)), ("name", name)],
+                ),
+            ));
+        }
+
+        for (checked, restriction, label) in entries {
+            let item = menu::CheckMenuItem::new(label, true, checked, None);
+            self.routes.insert(
+                item.id().clone(),
+                TrayCommand::Restrict(restriction.clone()),
+            );
+            if let Err(e) = self.restrict_menu.append(&item) {
+                log::warn!("failed to add a monitor to the tray menu: {e}");
+            }
+            self.restrict_items.push((restriction, item));
+        }
+    }
+
+    /// Checks the entry for `restriction` alone.
+    // NOTE: muda flips a check item on click by itself, so this also undoes
+    // unchecking the current entry
+    pub fn check_restriction(&self, restriction: &Restriction) {
+        for (item_restriction, item) in &self.restrict_items {
+            item.set_checked(item_restriction == restriction);
+        }
+    }
+
+    pub fn check_zone(&self, index: usize, enabled: bool) {
+        if let Some(item) = self.zone_items.get(index) {
+            item.set_checked(enabled);
+        }
+    }
+}
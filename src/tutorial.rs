@@ -0,0 +1,87 @@
+//! The first-run walkthrough: a bubble on the overlay explaining the ping
+//! gesture, which waits for the first real ping, congratulates and fades out.
+
+use crate::i18n::{t, tf};
+use egui::{Color32, FontId, Painter, Pos2, Rect, Vec2};
+use std::time::{Duration, Instant};
+
+/// How long the congratulation shows before fading.
+const CONGRATULATE: Duration = Duration::from_millis(2500);
+const FADE: Duration = Duration::from_millis(500);
+const WRAP_WIDTH: f32 = 440.0;
+const PADDING: f32 = 18.0;
+/// Room the bubble may take, for the per-ping windows of the opaque overlay.
+const AREA: Vec2 = Vec2::new(WRAP_WIDTH + 2.0 * PADDING, 200.0);
+
+enum Stage {
+    /// Waiting for the first ping.
+    Explain,
+    /// Pinged at this time.
+    Done(Instant),
+}
+
+pub struct Tutorial {
+    /// Middle of the bubble, in overlay coordinates.
+    center: Pos2,
+    /// Name of the ping key, as shown to the user.
+    key: String,
+    stage: Stage,
+}
+
+impl Tutorial {
+    pub fn new(center: Pos2, key: String) -> Self {
+        log::info!("showing the tutorial");
+        Self {
+            center,
+            key,
+            stage: Stage::Explain,
+        }
+    }
+
+    /// A real ping was fired, the tutorial moves on to congratulating.
+    pub fn pinged(&mut self) {
+        if let Stage::Explain = self.stage {
+            log::info!("tutorial done");
+            self.stage = Stage::Done(Instant::now());
+        }
+    }
+
+    /// Faded out, nothing left to draw.
+    pub fn finished(&self) -> bool {
+        match self.stage {
+            Stage::Explain => false,
+            Stage::Done(at) => at.elapsed() >= CONGRATULATE + FADE,
+        }
+    }
+
+    pub fn area(&self) -> Rect {
+        Rect::from_center_size(self.center, AREA)
+    }
+
+    /// Draws the bubble moved by `-shift`, on a dark backdrop so the text
+    /// reads on any background.
+    pub fn draw(&self, painter: &Painter, shift: Vec2) {
+        let (text, alpha) = match self.stage {
+            Stage::Explain => (tf("tutorial.explain", &[("key", &self.key)]), 1.0),
+            Stage::Done(at) => {
+                let fading = at.elapsed().saturating_sub(CONGRATULATE);
+                let alpha = 1.0 - fading.as_secs_f32() / FADE.as_secs_f32();
+                (t("tutorial.done").to_owned(), alpha.clamp(0.0, 1.0))
+            }
+        };
+        let galley = painter.layout(
+            text,
+            FontId::proportional(22.0),
+            Color32::WHITE.gamma_multiply(alpha),
+            WRAP_WIDTH,
+        );
+        let center = self.center - shift;
+        let text_rect = Rect::from_center_size(center, galley.size());
+        painter.rect_filled(
+            text_rect.expand(PADDING),
+            12.0,
+            Color32::from_black_alpha((200.0 * alpha) as u8),
+        );
+        painter.galley(text_rect.min, galley);
+    }
+}
@@ -0,0 +1,236 @@
+//! Named screen regions that give pings landing in them a label, a sound and
+//! a command.
+
+use crate::audio::Layered;
+use crate::config::Zone;
+use crate::layout::{Layout, MonitorRect};
+use crossbeam::channel::Sender;
+use device_query::mouse_state::MousePosition;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+/// The configured zones with their sounds decoded, shared between the hook
+/// and the tray, which toggles them.
+pub struct Zones {
+    zones: Vec<Zone>,
+    sounds: Vec<Option<Layered>>,
+    enabled: Vec<AtomicBool>,
+    /// 1-based monitor of each zone in the current layout, `None` while it's
+    /// not connected.
+    monitors: Mutex<Vec<Option<usize>>>,
+    /// Zone name and command for the thread running them, `None` when no
+    /// zone has a command.
+    commands: Option<Sender<(String, String)>>,
+}
+
+impl Zones {
+    pub fn new(zones: Vec<Zone>) -> Self {
+        let sounds = zones
+            .iter()
+            .map(|zone| {
+                let spec = zone.sound.as_ref()?;
+                match Layered::load(spec, &format!("zone {} sound", zone.name)) {
+                    Ok(sound) => Some(sound),
+                    Err(e) => {
+                        log::warn!("zone {} sound unusable: {e}", zone.name);
+                        None
+                    }
+                }
+            })
+            .collect();
+        let enabled = zones
+            .iter()
+            .map(|zone| AtomicBool::new(zone.enabled))
+            .collect();
+        // NOTE: starting a process takes long enough to stall the hook
+        let commands = zones.iter().any(|zone| zone.command.is_some()).then(|| {
+            let (sender, receiver) = crossbeam::channel::unbounded::<(String, String)>();
+            std::thread::spawn(move || {
+                for (zone, command) in receiver {
+                    match crate::platform::run_hidden(&command) {
+                        Ok(_) => log::info!("zone {zone} ran {command:?}"),
+                        Err(e) => log::warn!("zone {zone} failed to run {command:?}: {e}"),
+                    }
+                }
+            });
+            sender
+        });
+        Self {
+            monitors: Mutex::new(vec![None; zones.len()]),
+            zones,
+            sounds,
+            enabled,
+            commands,
+        }
+    }
+
+    /// Matches each zone's monitor against `layout`, on startup and whenever
+    /// monitors change.
+    pub fn resolve(&self, layout: &Layout) {
+        *self.monitors.lock().unwrap() = self
+            .zones
+            .iter()
+            .map(|zone| layout.resolve(&zone.monitor))
+            .collect();
+    }
+
+    pub fn zones(&self) -> &[Zone] {
+        &self.zones
+    }
+
+    /// Flips zone `index` on or off, returning whether it's now enabled.
+    pub fn toggle(&self, index: usize) -> Option<bool> {
+        let enabled = self.enabled.get(index)?;
+        Some(!enabled.fetch_xor(true, Ordering::Relaxed))
+    }
+
+    /// Index of the enabled zone `position` falls in, the smallest one when
+    /// zones overlap.
+    pub fn find(&self, layout: &Layout, position: MousePosition) -> Option<usize> {
+        let (monitor, rect) = layout.monitor_at(position)?;
+        let monitors = self.monitors.lock().unwrap();
+        self.zones
+            .iter()
+            .enumerate()
+            .filter(|(i, zone)| {
+                self.enabled[*i].load(Ordering::Relaxed)
+                    && monitors[*i] == Some(monitor)
+                    && contains(zone, rect, position)
+            })
+            .min_by(|(_, a), (_, b)| area(a).total_cmp(&area(b)))
+            .map(|(i, _)| i)
+    }
+
+    pub fn sound(&self, index: usize) -> Option<Layered> {
+        self.sounds.get(index)?.clone()
+    }
+
+    /// Starts the zone's command, if it has one, on the command thread.
+    pub fn run_command(&self, index: usize) {
+        let zone = &self.zones[index];
+        if let (Some(command), Some(commands)) = (&zone.command, &self.commands) {
+            commands.send((zone.name.clone(), command.clone())).ok();
+        }
+    }
+}
+
+/// Whether `position` lies in `zone`, whose rect is relative to `monitor`.
+fn contains(zone: &Zone, monitor: &MonitorRect, position: MousePosition) -> bool {
+    let [x, y, width, height] = zone.rect;
+    let nx = (position.0 - monitor.x) as f32 / monitor.width as f32;
+    let ny = (position.1 - monitor.y) as f32 / monitor.height as f32;
+    nx >= x && ny >= y && nx < x + width && ny < y + height
+}
+
+/// Normalized area; zones on the same monitor compare fine this way.
+fn area(zone: &Zone) -> f32 {
+    zone.rect[2] * zone.rect[3]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layout::MonitorRef;
+
+    fn zone(name: &str, monitor: usize, rect: [f32; 4]) -> Zone {
+        Zone {
+            name: name.to_owned(),
+            monitor: MonitorRef::Index(monitor),
+            rect,
+            sound: None,
+            command: None,
+            enabled: true,
+        }
+    }
+
+    /// A 1080p primary with a 1440p monitor to its left.
+    fn layout() -> Layout {
+        let rect = |x, width, height| MonitorRect {
+            x,
+            y: 0,
+            width,
+            height,
+        };
+        Layout {
+            monitors: vec![rect(0, 1920, 1080), rect(-2560, 2560, 1440)],
+            names: vec!["Primary".to_owned(), "Left".to_owned()],
+        }
+    }
+
+    fn zones(zones: Vec<Zone>) -> Zones {
+        let zones = Zones::new(zones);
+        zones.resolve(&layout());
+        zones
+    }
+
+    #[test]
+    fn pings_match_the_zone_they_land_in() {
+        let zones = zones(vec![
+            zone("Top left", 1, [0.0, 0.0, 0.5, 0.5]),
+            zone("Bottom right", 1, [0.5, 0.5, 0.5, 0.5]),
+        ]);
+        assert_eq!(zones.find(&layout(), (100, 100)), Some(0));
+        assert_eq!(zones.find(&layout(), (1800, 1000)), Some(1));
+    }
+
+    #[test]
+    fn zone_edges_are_half_open() {
+        let zones = zones(vec![zone("Left half", 1, [0.0, 0.0, 0.5, 1.0])]);
+        assert_eq!(zones.find(&layout(), (0, 0)), Some(0));
+        assert_eq!(zones.find(&layout(), (959, 1079)), Some(0));
+        assert_eq!(zones.find(&layout(), (960, 500)), None);
+    }
+
+    #[test]
+    fn the_smallest_overlapping_zone_wins() {
+        let zones = zones(vec![
+            zone("Screen", 1, [0.0, 0.0, 1.0, 1.0]),
+            zone("Button", 1, [0.4, 0.4, 0.1, 0.1]),
+            zone("Panel", 1, [0.3, 0.3, 0.4, 0.4]),
+        ]);
+        assert_eq!(zones.find(&layout(), (900, 500)), Some(1));
+        assert_eq!(zones.find(&layout(), (700, 400)), Some(2));
+        assert_eq!(zones.find(&layout(), (10, 10)), Some(0));
+    }
+
+    #[test]
+    fn zones_are_relative_to_their_own_monitor() {
+        let zones = zones(vec![zone("Left top", 2, [0.0, 0.0, 0.5, 0.5])]);
+        assert_eq!(zones.find(&layout(), (-2500, 100)), Some(0));
+        // NOTE: the same spot on the primary monitor isn't in it
+        assert_eq!(zones.find(&layout(), (60, 100)), None);
+    }
+
+    #[test]
+    fn zones_on_disconnected_monitors_never_match() {
+        let zones = zones(vec![zone("Gone", 3, [0.0, 0.0, 1.0, 1.0])]);
+        assert_eq!(zones.find(&layout(), (100, 100)), None);
+    }
+
+    #[test]
+    fn disabled_zones_are_skipped_until_toggled() {
+        let mut disabled = zone("Off", 1, [0.0, 0.0, 1.0, 1.0]);
+        disabled.enabled = false;
+        let zones = zones(vec![disabled]);
+        assert_eq!(zones.find(&layout(), (100, 100)), None);
+        assert_eq!(zones.toggle(0), Some(true));
+        assert_eq!(zones.find(&layout(), (100, 100)), Some(0));
+        assert_eq!(zones.toggle(1), None);
+    }
+
+    #[test]
+    fn off_every_monitor_matches_nothing() {
+        let zones = zones(vec![zone("Screen", 1, [0.0, 0.0, 1.0, 1.0])]);
+        assert_eq!(zones.find(&layout(), (5000, 5000)), None);
+    }
+
+    #[test]
+    fn the_command_thread_only_runs_with_commands() {
+        assert!(zones(vec![zone("Quiet", 1, [0.0, 0.0, 1.0, 1.0])])
+            .commands
+            .is_none());
+        let mut loud = zone("Loud", 1, [0.0, 0.0, 1.0, 1.0]);
+        loud.command = Some("echo".to_owned());
+        assert!(zones(vec![loud]).commands.is_some());
+    }
+}
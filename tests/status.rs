@@ -0,0 +1,95 @@
+//! Starts the app without a tray or overlay and asks it for its status over
+//! the control pipe, the way automation around it would.
+#![cfg(windows)]
+
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::process::{Child, Command};
+use std::time::{Duration, Instant};
+
+/// How long the app gets to open its pipe.
+const STARTUP: Duration = Duration::from_secs(10);
+
+/// The app under test, killed and cleaned up after even if the test fails.
+struct App {
+    child: Child,
+    appdata: PathBuf,
+}
+
+impl Drop for App {
+    fn drop(&mut self) {
+        self.child.kill().ok();
+        self.child.wait().ok();
+        std::fs::remove_dir_all(&self.appdata).ok();
+    }
+}
+
+/// Runs its own instance on its own pipe, with `%APPDATA%` in a temp folder
+/// so the user's data and a running instance are left alone.
+fn start(pipe: &str) -> App {
+    let instance = format!("status-test-{}", std::process::id());
+    let appdata = std::env::temp_dir().join(&instance);
+    let data = appdata
+        .join("screen-pinger")
+        .join("instances")
+        .join(&instance);
+    std::fs::create_dir_all(&data).unwrap();
+    // NOTE: an existing config skips the first-run tutorial and self-check
+    std::fs::write(
+        data.join("config.toml"),
+        format!("[ipc]\npipe = '{pipe}'\n"),
+    )
+    .unwrap();
+    let child = Command::new(env!("CARGO_BIN_EXE_screen-pinger"))
+        .args(["--no-tray", "--instance", &instance])
+        .env("APPDATA", &appdata)
+        .spawn()
+        .unwrap();
+    App { child, appdata }
+}
+
+/// Sends `request` and returns the reply line, retrying while the app starts.
+fn ask(app: &mut App, pipe: &str, request: &str) -> serde_json::Value {
+    let deadline = Instant::now() + STARTUP;
+    let mut connection = loop {
+        match std::fs::OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(pipe)
+        {
+            Ok(connection) => break connection,
+            Err(e) => {
+                if let Some(status) = app.child.try_wait().unwrap() {
+                    panic!("app exited with {status} before opening its pipe");
+                }
+                assert!(Instant::now() < deadline, "pipe never opened: {e}");
+                std::thread::sleep(Duration::from_millis(100));
+            }
+        }
+    };
+    connection
+        .write_all(format!("{request}\n").as_bytes())
+        .unwrap();
+    let mut reply = String::new();
+    BufReader::new(connection).read_line(&mut reply).unwrap();
+    serde_json::from_str(&reply).unwrap()
+}
+
+#[test]
+fn headless_app_reports_its_status() {
+    let pipe = format!(r"\\.\pipe\screen-pinger-status-test-{}", std::process::id());
+    let mut app = start(&pipe);
+
+    let status = ask(&mut app, &pipe, "status");
+    assert_eq!(status["version"], env!("CARGO_PKG_VERSION"));
+    assert!(status["uptime_secs"].is_u64());
+    assert_eq!(status["paused"], false);
+    assert_eq!(status["animations"], 0);
+    // NOTE: nothing was pinged, so no GPU device or surface was created
+    assert_eq!(status["overlay"], false);
+    assert!(status["graphics"].is_null());
+    assert!(status["monitors"].is_array());
+
+    let reply = ask(&mut app, &pipe, "quit");
+    assert!(reply["error"].as_str().unwrap().contains("unknown request"));
+}